@@ -3,6 +3,7 @@
 mod board_tests;
 pub mod moves;
 pub mod piece;
+mod see;
 
 use std::collections::HashMap;
 
@@ -11,20 +12,33 @@ use crate::board::piece::{Color, Piece, PieceType};
 use crate::game_state::ParseFenError;
 use std::str::FromStr;
 
-use self::piece::king::generate_king_moves;
-use self::piece::knight::{generate_knight_moves, precalculate_knight_moves};
-use self::piece::pawn::generate_pawn_moves;
-use self::piece::sliding_pieces::generate_sliding_moves;
+use self::piece::king::{generate_king_captures, generate_king_moves};
+use self::piece::knight::{
+    generate_knight_captures, generate_knight_moves, precalculate_knight_moves,
+};
+use self::piece::pawn::{generate_pawn_captures, generate_pawn_moves};
+use self::piece::sliding_pieces::{generate_sliding_captures, generate_sliding_moves};
 
 /// Represents the size of the chess board (number of rows and columns).
 pub const BOARD_SIZE: u8 = 8;
 
+/// The squares of the rooks of the castling rights in standard chess: h1, a1, h8 and a8.
+pub const STANDARD_CASTLING_ROOKS: [u8; 4] = [7, 0, 63, 56];
+
 /// Represents the chess board, containing squares with optional pieces.
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone)]
 pub struct Board {
     pub squares: Vec<Option<Piece>>,
     pub knight_moves_map: HashMap<u8, Vec<u8>>,
     pub castling_availability: (bool, bool, bool, bool),
+    /// The squares of the rooks each castling right is for, in the order of
+    /// `castling_availability`. These are the corners, except in Chess960.
+    pub castling_rooks: [u8; 4],
+    /// Whether castling moves are written as the king taking its own rook, as in Chess960,
+    /// instead of the king moving by two squares.
+    pub chess960: bool,
+    /// The square a pawn skipped over with a two-square move on the previous turn, if any.
+    pub en_passant_target: Option<u8>,
 }
 
 impl Board {
@@ -39,7 +53,7 @@ impl Board {
     /// # Example
     ///
     /// ```
-    /// use chess_engine::board::Board;
+    /// use ruce::board::Board;
     ///
     /// let board = Board::new_empty_board();
     /// ```
@@ -53,6 +67,9 @@ impl Board {
             squares,
             knight_moves_map: precalculate_knight_moves(),
             castling_availability: (false, false, false, false),
+            castling_rooks: STANDARD_CASTLING_ROOKS,
+            chess960: false,
+            en_passant_target: None,
         }
     }
 
@@ -61,8 +78,8 @@ impl Board {
     /// # Arguments
     ///
     /// * `square`: The index of the square to retrieve the piece from.
-    ///             It is represented as a `u8` value, where the index starts from 0 (top-left square)
-    ///             and increases sequentially from left to right and top to bottom.
+    ///   It is represented as a `u8` value, where the index starts from 0 (top-left square)
+    ///   and increases sequentially from left to right and top to bottom.
     ///
     /// # Returns
     ///
@@ -73,14 +90,15 @@ impl Board {
     /// # Examples
     ///
     /// ```
-    /// use chess_engine::board::{Board, Color, Piece, PieceType};
+    /// use ruce::board::Board;
+    /// use ruce::board::piece::{Color, Piece, PieceType};
     ///
     /// let mut board = Board::new_empty_board();
     /// let piece = Piece {
     ///     piece_type: PieceType::Pawn,
     ///     color: Color::White,
     /// };
-    /// board.set_piece(8, Some(piece)); // Set a pawn piece at square 8
+    /// board.set_piece(8, piece); // Set a pawn piece at square 8
     ///
     /// // Retrieve the piece at square 8
     /// let retrieved_piece = board.get_piece(8);
@@ -103,6 +121,128 @@ impl Board {
         self.squares[square as usize] = Some(piece);
     }
 
+    /// Removes the piece at a specific square, returning it if there was one.
+    ///
+    /// # Arguments
+    ///
+    /// * `square` - The index of the square (0 to 63) to clear.
+    pub fn remove_piece(&mut self, square: u8) -> Option<Piece> {
+        self.squares[square as usize].take()
+    }
+
+    /// Returns the castling rights of `castling_availability` as an array, in the same order.
+    pub fn castling_rights(&self) -> [bool; 4] {
+        let (white_king, white_queen, black_king, black_queen) = self.castling_availability;
+        [white_king, white_queen, black_king, black_queen]
+    }
+
+    /// Sets `castling_availability` from an array of castling rights, in the same order.
+    pub fn set_castling_rights(&mut self, rights: [bool; 4]) {
+        self.castling_availability = (rights[0], rights[1], rights[2], rights[3]);
+    }
+
+    /// Returns the square of the king of the given color, if it is on the board.
+    pub fn king_square(&self, color: Color) -> Option<u8> {
+        (0..64).find(|&square| {
+            self.get_piece(square)
+                == Some(&Piece {
+                    piece_type: PieceType::King,
+                    color,
+                })
+        })
+    }
+
+    /// Checks whether any piece of the `attacker` color attacks the given square.
+    ///
+    /// The square itself may be empty or occupied by a piece of either color. Attacks are
+    /// computed by looking outwards from the square with the movement of every piece type,
+    /// reusing the regular move generators of the sliding pieces and the knight map.
+    ///
+    /// # Arguments
+    ///
+    /// * `square` - The index of the square (0 to 63) to check.
+    /// * `attacker` - The color of the attacking side.
+    pub fn is_square_attacked(&self, square: u8, attacker: Color) -> bool {
+        let row = square / BOARD_SIZE;
+        let col = square % BOARD_SIZE;
+        let is_attacker = |target: u8, piece_types: &[PieceType]| {
+            self.get_piece(target).is_some_and(|piece| {
+                piece.color == attacker && piece_types.contains(&piece.piece_type)
+            })
+        };
+
+        // Pawns attack diagonally forward, so look one row backwards from their point of view
+        let pawn_row = match attacker {
+            Color::White => row as isize - 1,
+            Color::Black => row as isize + 1,
+        };
+        if (0..BOARD_SIZE as isize).contains(&pawn_row) {
+            for pawn_col in [col as isize - 1, col as isize + 1] {
+                if (0..BOARD_SIZE as isize).contains(&pawn_col)
+                    && is_attacker(
+                        (pawn_row * BOARD_SIZE as isize + pawn_col) as u8,
+                        &[PieceType::Pawn],
+                    )
+                {
+                    return true;
+                }
+            }
+        }
+
+        if self.knight_moves_map[&square]
+            .iter()
+            .any(|&target| is_attacker(target, &[PieceType::Knight]))
+        {
+            return true;
+        }
+
+        for row_offset in -1..=1 {
+            for col_offset in -1..=1 {
+                let target_row = row as isize + row_offset;
+                let target_col = col as isize + col_offset;
+                if (row_offset, col_offset) != (0, 0)
+                    && (0..BOARD_SIZE as isize).contains(&target_row)
+                    && (0..BOARD_SIZE as isize).contains(&target_col)
+                    && is_attacker(
+                        (target_row * BOARD_SIZE as isize + target_col) as u8,
+                        &[PieceType::King],
+                    )
+                {
+                    return true;
+                }
+            }
+        }
+
+        // A bishop (rook) placed on the square captures exactly the pieces that attack it
+        // along diagonals (lines).
+        let sliders = [
+            (PieceType::Bishop, [PieceType::Bishop, PieceType::Queen]),
+            (PieceType::Rook, [PieceType::Rook, PieceType::Queen]),
+        ];
+        for (piece_type, attackers) in sliders {
+            let probe = Piece {
+                piece_type,
+                color: attacker.opposite(),
+            };
+            if let Some(moves) = generate_sliding_moves(self, row, col, probe) {
+                if moves
+                    .iter()
+                    .any(|chess_move| is_attacker(chess_move.target_square, &attackers))
+                {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Checks whether the king of the given color is attacked.
+    pub fn is_in_check(&self, color: Color) -> bool {
+        self.king_square(color)
+            .is_some_and(|square| self.is_square_attacked(square, color.opposite()))
+    }
+
     /// Generates all possible moves for the pieces of the specified player.
     ///
     /// # Arguments
@@ -115,33 +255,90 @@ impl Board {
     pub fn generate_moves(&self, current_player: Color) -> Vec<Move> {
         let mut moves: Vec<Move> = Vec::new();
 
-        for row in 0..BOARD_SIZE {
-            for col in 0..BOARD_SIZE {
-                let square = row * BOARD_SIZE + col;
-                if let Some(piece) = self.get_piece(square) {
-                    // Only check moves of the pieces that match the current player
-                    if piece.color != current_player {
-                        continue;
-                    }
-                    let piece_moves = match piece.piece_type {
-                        PieceType::Pawn => generate_pawn_moves(self, row, col, piece.color),
-                        PieceType::Bishop => generate_sliding_moves(self, row, col, *piece),
-                        PieceType::Knight => generate_knight_moves(self, row, col),
-                        PieceType::Rook => generate_sliding_moves(self, row, col, *piece),
-                        PieceType::Queen => generate_sliding_moves(self, row, col, *piece),
-                        PieceType::King => generate_king_moves(self, row, col),
-                    };
-
-                    // Add it to the list if there is a move
-                    if let Some(valid_move) = piece_moves {
-                        moves.extend(valid_move);
-                    }
+        for square in 0..BOARD_SIZE * BOARD_SIZE {
+            // Only check moves of the pieces that match the current player
+            if self
+                .get_piece(square)
+                .is_some_and(|piece| piece.color == current_player)
+            {
+                // Add them to the list if there is a move
+                if let Some(piece_moves) = self.generate_piece_moves(square) {
+                    moves.extend(piece_moves);
                 }
             }
         }
         moves
     }
 
+    /// Generates the moves of the piece standing on a square, `None` if the square is empty
+    /// or the piece has no move.
+    fn generate_piece_moves(&self, square: u8) -> Option<Vec<Move>> {
+        let piece = self.get_piece(square)?;
+        let row = square / BOARD_SIZE;
+        let col = square % BOARD_SIZE;
+        match piece.piece_type {
+            PieceType::Pawn => generate_pawn_moves(self, row, col, piece.color),
+            PieceType::Knight => generate_knight_moves(self, row, col),
+            PieceType::King => generate_king_moves(self, row, col),
+            PieceType::Bishop | PieceType::Rook | PieceType::Queen => {
+                generate_sliding_moves(self, row, col, *piece)
+            }
+        }
+    }
+
+    /// Checks whether a move would be generated by `generate_moves` for the given player.
+    ///
+    /// Only the moves of the piece on the initial square are generated, which makes it a cheap
+    /// way to validate a move remembered from another position, such as a hash or killer move.
+    pub fn is_pseudo_legal(&self, chess_move: &Move, current_player: Color) -> bool {
+        let square = chess_move.initial_square;
+        self.get_piece(square)
+            .is_some_and(|piece| piece.color == current_player)
+            && self
+                .generate_piece_moves(square)
+                .is_some_and(|moves| moves.contains(chess_move))
+    }
+
+    /// Generates the captures and promotions of the pieces of the specified player.
+    ///
+    /// This is the subset of `generate_moves` that changes the material on the board, used by
+    /// the quiescence search. Quiet moves are never generated, instead of being generated and
+    /// filtered out.
+    ///
+    /// # Arguments
+    ///
+    /// * `current_player` - The color of the current player (either `Color::White` or `Color::Black`).
+    ///
+    /// # Returns
+    ///
+    /// A vector containing the captures, en passant captures and promotions of the current player.
+    pub fn generate_captures(&self, current_player: Color) -> Vec<Move> {
+        let mut moves: Vec<Move> = Vec::new();
+
+        for square in 0..BOARD_SIZE * BOARD_SIZE {
+            let Some(piece) = self.get_piece(square) else {
+                continue;
+            };
+            if piece.color != current_player {
+                continue;
+            }
+            let row = square / BOARD_SIZE;
+            let col = square % BOARD_SIZE;
+            let piece_moves = match piece.piece_type {
+                PieceType::Pawn => generate_pawn_captures(self, row, col, piece.color),
+                PieceType::Knight => generate_knight_captures(self, row, col),
+                PieceType::King => generate_king_captures(self, row, col),
+                PieceType::Bishop | PieceType::Rook | PieceType::Queen => {
+                    generate_sliding_captures(self, row, col, *piece)
+                }
+            };
+            if let Some(valid_move) = piece_moves {
+                moves.extend(valid_move);
+            }
+        }
+        moves
+    }
+
     /// Prints the current state of the chess board.
     ///
     /// This function will print the chess board, displaying each piece's symbol at its respective square.
@@ -150,14 +347,15 @@ impl Board {
     /// # Examples
     ///
     /// ```
-    /// use chess_engine::board::{Board, Color, Piece, PieceType};
+    /// use ruce::board::Board;
+    /// use ruce::board::piece::{Color, Piece, PieceType};
     ///
     /// let mut board = Board::new_empty_board();
     /// let piece = Piece {
     ///     piece_type: PieceType::Pawn,
     ///     color: Color::White,
     /// };
-    /// board.set_piece(8, Some(piece)); // Set a pawn piece at square 8
+    /// board.set_piece(8, piece); // Set a pawn piece at square 8
     ///
     /// board.print_board();
     /// // The output should be:
@@ -193,6 +391,63 @@ impl Board {
         println!("  +------------------------+");
         println!("    a  b  c  d  e  f  g  h ");
     }
+
+    /// Returns the piece placement field of the FEN string describing the board.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ruce::board::Board;
+    /// use std::str::FromStr;
+    ///
+    /// let placement = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR";
+    /// assert_eq!(Board::from_str(placement).unwrap().piece_placement(), placement);
+    /// ```
+    pub fn piece_placement(&self) -> String {
+        let mut placement = String::new();
+        for row in (0..BOARD_SIZE).rev() {
+            let mut empty_squares = 0;
+            for col in 0..BOARD_SIZE {
+                match self.get_piece(row * BOARD_SIZE + col) {
+                    Some(piece) => {
+                        if empty_squares > 0 {
+                            placement.push_str(&empty_squares.to_string());
+                            empty_squares = 0;
+                        }
+                        placement.push(piece.as_char());
+                    }
+                    None => empty_squares += 1,
+                }
+            }
+            if empty_squares > 0 {
+                placement.push_str(&empty_squares.to_string());
+            }
+            if row > 0 {
+                placement.push('/');
+            }
+        }
+        placement
+    }
+}
+
+/// Returns the algebraic name of a square, e.g. `e4` for square 28.
+pub fn square_name(square: u8) -> String {
+    let file = (b'a' + square % BOARD_SIZE) as char;
+    let rank = (b'1' + square / BOARD_SIZE) as char;
+    format!("{}{}", file, rank)
+}
+
+/// Parses an algebraic square name such as `e4` into its index (0 to 63).
+///
+/// Returns `None` if the string is not a valid square name.
+pub fn parse_square(name: &str) -> Option<u8> {
+    let mut chars = name.chars();
+    let file = chars.next()?;
+    let rank = chars.next()?;
+    if chars.next().is_some() || !('a'..='h').contains(&file) || !('1'..='8').contains(&rank) {
+        return None;
+    }
+    Some((rank as u8 - b'1') * BOARD_SIZE + (file as u8 - b'a'))
 }
 
 // Errors are raised if the FEN string is invalid
@@ -231,43 +486,56 @@ impl FromStr for Board {
     type Err = ParseFenError;
 
     /// Errors are raised if the FEN string is invalid
-    /// char_to_piece is responsible for raising the error
+    /// char_to_piece is responsible for raising the error on unknown pieces,
+    /// ranks that do not add up to 8 squares or a count of ranks other than 8
+    /// are reported as a malformed piece placement
     ///
     /// # Examples
     ///
     /// ```
-    /// use chess::board::Board;
+    /// use ruce::board::Board;
     /// use std::str::FromStr;
     ///
     /// let board = Board::from_str("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR").unwrap();
     /// ```
     fn from_str(piece_placement: &str) -> Result<Self, Self::Err> {
         let mut board = Board::new_empty_board();
+        let malformed = || ParseFenError::MalformedPiecePlacement(piece_placement.to_string());
 
-        let mut rank: u8 = 7;
-        let mut file: u8 = 0;
+        let ranks: Vec<&str> = piece_placement.split('/').collect();
+        if ranks.len() != BOARD_SIZE as usize {
+            return Err(malformed());
+        }
 
-        for c in piece_placement.chars() {
-            match c {
-                '0'..='8' => {
-                    let empty_squares: u8 = c.to_digit(10).unwrap() as u8;
-                    file += empty_squares;
-                }
-                '/' => {
-                    rank -= 1;
-                    file = 0;
-                }
-                'a'..='z' => {
-                    let new_piece = char_to_piece(&c.to_lowercase().to_string(), Color::Black)?;
-                    board.set_piece(rank * BOARD_SIZE + file, new_piece);
-                    file += 1;
-                }
-                'A'..='Z' => {
-                    let new_piece = char_to_piece(&c.to_lowercase().to_string(), Color::White)?;
-                    board.set_piece(rank * BOARD_SIZE + file, new_piece);
-                    file += 1;
+        for (rank, rank_field) in (0..BOARD_SIZE).rev().zip(ranks) {
+            let mut file: u8 = 0;
+            for c in rank_field.chars() {
+                match c {
+                    '0'..='9' => {
+                        let empty_squares = c.to_digit(10).unwrap() as u8;
+                        file += empty_squares;
+                        if empty_squares == 0 || file > BOARD_SIZE {
+                            return Err(malformed());
+                        }
+                    }
+                    'a'..='z' | 'A'..='Z' => {
+                        let color = if c.is_ascii_lowercase() {
+                            Color::Black
+                        } else {
+                            Color::White
+                        };
+                        let new_piece = char_to_piece(&c.to_lowercase().to_string(), color)?;
+                        if file >= BOARD_SIZE {
+                            return Err(malformed());
+                        }
+                        board.set_piece(rank * BOARD_SIZE + file, new_piece);
+                        file += 1;
+                    }
+                    _ => return Err(ParseFenError::InvalidPiecePlacement(c.to_string())),
                 }
-                _ => break,
+            }
+            if file != BOARD_SIZE {
+                return Err(malformed());
             }
         }
         Ok(board)
@@ -19,6 +19,123 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_malformed_piece_placement() {
+        for placement in [
+            "8/8/8/8/8/8/8/8/8",
+            "8/8/8/8/8/8/8",
+            "rnbqkbnrr/8/8/8/8/8/8/8",
+            "9/8/8/8/8/8/8/8",
+            "7/8/8/8/8/8/8/8",
+            "44k/8/8/8/8/8/8/8",
+        ] {
+            assert_eq!(
+                Board::from_str(placement).unwrap_err(),
+                ParseFenError::MalformedPiecePlacement(placement.to_string()),
+                "{placement}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_see_of_captures() {
+        let see = |placement: &str, chess_move: Move| {
+            Board::from_str(placement).unwrap().see(&chess_move)
+        };
+        // Undefended knight, pawn defended by a pawn
+        assert_eq!(see("4k3/8/8/3n4/8/8/8/3QK3", Move::new(3, 35)), 320);
+        assert_eq!(see("4k3/8/2p5/3p4/8/8/8/3QK3", Move::new(3, 35)), -800);
+        // The pieces behind the first attacker join the exchange
+        assert_eq!(see("4k3/3r4/8/3p4/8/8/3Q4/3RK3", Move::new(11, 35)), -300);
+        assert_eq!(see("4k3/3r4/8/3p4/8/8/3Q4/4K3", Move::new(11, 35)), -800);
+        assert_eq!(see("3rk3/8/8/3n4/2B5/8/Q7/4K3", Move::new(26, 35)), 320);
+        assert_eq!(see("3rk3/8/8/3n4/2B5/8/8/4K3", Move::new(26, 35)), -10);
+        // The king only takes back when the square is not defended anymore
+        assert_eq!(see("8/8/4k3/4p3/8/5N2/8/K7", Move::new(21, 36)), -220);
+        assert_eq!(see("8/8/4k3/4p3/8/5N2/8/K3R3", Move::new(21, 36)), 100);
+    }
+
+    #[test]
+    fn test_see_of_special_moves() {
+        // En passant, the captured pawn no longer hides the rook behind it
+        let mut board = Board::from_str("3rk3/8/8/3pP3/8/8/8/4K3").unwrap();
+        board.en_passant_target = Some(43);
+        assert_eq!(board.see(&Move::new(36, 43)), 0);
+        let mut board = Board::from_str("3rk3/8/8/3pP3/8/8/8/3RK3").unwrap();
+        board.en_passant_target = Some(43);
+        assert_eq!(board.see(&Move::new(36, 43)), 100);
+
+        // A promotion on a defended square loses the pawn
+        let board = Board::from_str("1r2k3/P7/8/8/8/8/8/4K3").unwrap();
+        let promotion = Move {
+            initial_square: 48,
+            target_square: 56,
+            promotion: Some(PieceType::Queen),
+        };
+        assert_eq!(board.see(&promotion), -100);
+        assert!(board.see_ge(&promotion, -100));
+        assert!(!board.see_ge(&promotion, 0));
+
+        // A quiet move to a square attacked by a pawn loses the piece
+        let board = Board::from_str("4k3/8/4p3/8/8/2N5/8/4K3").unwrap();
+        assert_eq!(board.see(&Move::new(18, 35)), -320);
+        assert_eq!(board.see(&Move::new(18, 33)), 0);
+    }
+
+    #[test]
+    fn test_is_pseudo_legal() {
+        let board = Board::from_str("r3k2r/8/8/8/8/8/4p3/R3K2R").unwrap();
+        for color in [Color::White, Color::Black] {
+            for chess_move in board.generate_moves(color) {
+                assert!(board.is_pseudo_legal(&chess_move, color));
+                assert!(!board.is_pseudo_legal(&chess_move, color.opposite()));
+            }
+        }
+        // Blocked, empty square and missing promotion
+        assert!(!board.is_pseudo_legal(&Move::new(0, 5), Color::White));
+        assert!(!board.is_pseudo_legal(&Move::new(20, 28), Color::White));
+        assert!(!board.is_pseudo_legal(&Move::new(12, 4), Color::Black));
+    }
+
+    #[test]
+    fn test_generate_captures_matches_generate_moves() {
+        let positions = [
+            (
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R",
+                None,
+            ),
+            ("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR", Some(45)),
+            (
+                "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1",
+                None,
+            ),
+        ];
+        for (fen, en_passant_target) in positions {
+            let mut board = Board::from_str(fen).unwrap();
+            board.en_passant_target = en_passant_target;
+            for color in [Color::White, Color::Black] {
+                let mut expected: Vec<Move> = board
+                    .generate_moves(color)
+                    .into_iter()
+                    .filter(|chess_move| {
+                        board.get_piece(chess_move.target_square).is_some()
+                            || chess_move.promotion.is_some()
+                            || Some(chess_move.target_square) == board.en_passant_target
+                                && board
+                                    .get_piece(chess_move.initial_square)
+                                    .unwrap()
+                                    .piece_type
+                                    == PieceType::Pawn
+                    })
+                    .collect();
+                let mut captures = board.generate_captures(color);
+                expected.sort_by_key(|m| m.to_string());
+                captures.sort_by_key(|m| m.to_string());
+                assert_eq!(captures, expected, "{} {:?}", fen, color);
+            }
+        }
+    }
+
     fn starting_position() -> Board {
         let mut board_hardcoded = Board::new_empty_board();
         board_hardcoded.set_piece(
@@ -1,19 +1,24 @@
 //! Module containing chess moves related logic and structures.
 
 use std::collections::HashSet;
+use std::fmt;
 use std::iter::FromIterator;
 
+use crate::board::piece::PieceType;
+use crate::board::{parse_square, square_name};
+
 /// Represents a move from an initial square to a target square on the chess board.
 ///
 /// The `Move` struct is used to represent a valid move made by a chess piece. It contains
 /// the indices of the initial square and the target square on the chess board. Each square
 /// is represented by a numeric value between 0 and 63 (inclusive), where 0 corresponds to
 /// the bottom-left square (a1) and 63 corresponds to the top-right square (h8) of the board.
+/// Pawn moves reaching the last rank also carry the piece type the pawn is promoted to.
 ///
 /// # Example
 ///
 /// ```
-/// use chess_engine::Move;
+/// use ruce::board::moves::Move;
 ///
 /// let initial_square = 8; // a2 square
 /// let target_square = 24; // a4 square
@@ -21,6 +26,7 @@ use std::iter::FromIterator;
 /// let chess_move = Move {
 ///     initial_square,
 ///     target_square,
+///     promotion: None,
 /// };
 ///
 /// assert_eq!(chess_move.initial_square, 8);
@@ -33,6 +39,64 @@ pub struct Move {
 
     /// The index of the target square where the move ends.
     pub target_square: u8,
+
+    /// The piece type a pawn is promoted to, if the move is a promotion.
+    pub promotion: Option<PieceType>,
+}
+
+impl Move {
+    /// Creates a non-promoting move between two squares.
+    pub fn new(initial_square: u8, target_square: u8) -> Self {
+        Move {
+            initial_square,
+            target_square,
+            promotion: None,
+        }
+    }
+
+    /// Parses a move written in long algebraic (UCI) notation, such as `e2e4` or `e7e8q`.
+    ///
+    /// Returns `None` if the string is not a well formed move. The move is not checked
+    /// against any position.
+    pub fn from_uci(uci: &str) -> Option<Self> {
+        if !uci.is_ascii() || (uci.len() != 4 && uci.len() != 5) {
+            return None;
+        }
+        let initial_square = parse_square(&uci[0..2])?;
+        let target_square = parse_square(&uci[2..4])?;
+        let promotion = match uci[4..].chars().next() {
+            None => None,
+            Some('q') => Some(PieceType::Queen),
+            Some('r') => Some(PieceType::Rook),
+            Some('b') => Some(PieceType::Bishop),
+            Some('n') => Some(PieceType::Knight),
+            Some(_) => return None,
+        };
+        Some(Move {
+            initial_square,
+            target_square,
+            promotion,
+        })
+    }
+}
+
+/// Formats the move in long algebraic (UCI) notation, e.g. `e2e4` or `e7e8q`.
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            square_name(self.initial_square),
+            square_name(self.target_square)
+        )?;
+        match self.promotion {
+            Some(PieceType::Queen) => write!(f, "q"),
+            Some(PieceType::Rook) => write!(f, "r"),
+            Some(PieceType::Bishop) => write!(f, "b"),
+            Some(PieceType::Knight) => write!(f, "n"),
+            _ => Ok(()),
+        }
+    }
 }
 
 // Needed to compare two list of moves with different order of moves
@@ -6,7 +6,7 @@ pub mod pawn;
 pub mod sliding_pieces;
 
 /// Represents a chess piece, containing its type and color.
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub struct Piece {
     pub piece_type: PieceType,
     pub color: Color,
@@ -32,7 +32,7 @@ impl Piece {
 }
 
 /// Represents the type of a chess piece.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum PieceType {
     Pawn,
     Bishop,
@@ -42,9 +42,46 @@ pub enum PieceType {
     King,
 }
 
+impl PieceType {
+    /// Returns the uppercase letter used for the piece type in algebraic notation.
+    pub fn as_char(&self) -> char {
+        match self {
+            PieceType::Pawn => 'P',
+            PieceType::Bishop => 'B',
+            PieceType::Knight => 'N',
+            PieceType::Rook => 'R',
+            PieceType::Queen => 'Q',
+            PieceType::King => 'K',
+        }
+    }
+
+    /// Parses the letter of a piece type, accepting both upper and lower case.
+    pub fn from_char(c: char) -> Option<PieceType> {
+        match c.to_ascii_uppercase() {
+            'P' => Some(PieceType::Pawn),
+            'B' => Some(PieceType::Bishop),
+            'N' => Some(PieceType::Knight),
+            'R' => Some(PieceType::Rook),
+            'Q' => Some(PieceType::Queen),
+            'K' => Some(PieceType::King),
+            _ => None,
+        }
+    }
+}
+
 /// Represents the color of a chess piece.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Color {
     White,
     Black,
 }
+
+impl Color {
+    /// Returns the color of the other player.
+    pub fn opposite(&self) -> Color {
+        match self {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        }
+    }
+}
@@ -1,5 +1,6 @@
 //! Module containing king related logic.
 
+use crate::board::piece::{Color, Piece, PieceType};
 use crate::board::{moves::Move, Board, BOARD_SIZE};
 
 // Define the possible offsets for king's moves
@@ -27,18 +28,19 @@ pub fn generate_king_moves(board: &Board, row: u8, col: u8) -> Option<Vec<Move>>
             let target_square = new_row * BOARD_SIZE + new_col;
 
             // Check if the target square is empty or occupied by an opponent's piece
-            if board.get_piece(target_square).map_or(true, |piece| {
-                piece.color != board.get_piece(square).unwrap().color
-            }) {
+            if board
+                .get_piece(target_square)
+                .is_none_or(|piece| piece.color != board.get_piece(square).unwrap().color)
+            {
                 moves.push(Move {
                     initial_square: square,
                     target_square,
+                    promotion: None,
                 });
             }
         }
     }
-    generate_white_castling_moves(square, board, &mut moves);
-    generate_black_castling_moves(square, board, &mut moves);
+    generate_castling_moves(square, board, &mut moves);
 
     if !moves.is_empty() {
         Some(moves)
@@ -47,56 +49,149 @@ pub fn generate_king_moves(board: &Board, row: u8, col: u8) -> Option<Vec<Move>>
     }
 }
 
-fn generate_black_castling_moves(square: u8, board: &Board, moves: &mut Vec<Move>) {
-    // checking for black castling
-    // king original position
-    if square == 60 {
-        // king side
-        if board.castling_availability.2
-            && board.get_piece(61).is_none()
-            && board.get_piece(62).is_none()
+/// Generate the captures of the king at a specific position on the board.
+///
+/// Works like `generate_king_moves`, but only keeps the target squares occupied by an
+/// opponent's piece, so castling is never generated.
+pub fn generate_king_captures(board: &Board, row: u8, col: u8) -> Option<Vec<Move>> {
+    let square: u8 = row * BOARD_SIZE + col;
+    let color = board.get_piece(square)?.color;
+    let mut moves: Vec<Move> = Vec::new();
+
+    for &(dr, dc) in &KING_MOVES {
+        let new_row = (row as isize + dr) as u8;
+        let new_col = (col as isize + dc) as u8;
+        if new_row < BOARD_SIZE && new_col < BOARD_SIZE {
+            let target_square = new_row * BOARD_SIZE + new_col;
+            if board
+                .get_piece(target_square)
+                .is_some_and(|piece| piece.color != color)
+            {
+                moves.push(Move::new(square, target_square));
+            }
+        }
+    }
+
+    if !moves.is_empty() {
+        Some(moves)
+    } else {
+        None
+    }
+}
+
+/// The squares a castling move takes the king and its rook from and to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Castling {
+    pub king_from: u8,
+    pub king_to: u8,
+    pub rook_from: u8,
+    pub rook_to: u8,
+}
+
+/// Returns the index of a castling right in `Board::castling_availability` and
+/// `Board::castling_rooks`.
+pub fn castling_index(color: Color, king_side: bool) -> usize {
+    let first = match color {
+        Color::White => 0,
+        Color::Black => 2,
+    };
+    if king_side {
+        first
+    } else {
+        first + 1
+    }
+}
+
+/// Returns the squares of the king and the rook of a castling move, or `None` when the move
+/// does not castle.
+///
+/// Castling is written as the king taking its own rook, or, outside of Chess960, as the king
+/// moving by two squares. Either way the king ends up on the g or c file and the rook next to
+/// it, on the f or d file.
+pub fn castling_move(board: &Board, chess_move: &Move) -> Option<Castling> {
+    let (from, to) = (chess_move.initial_square, chess_move.target_square);
+    let king = board
+        .get_piece(from)
+        .filter(|piece| piece.piece_type == PieceType::King)?;
+    let king_side = to > from;
+    let index = castling_index(king.color, king_side);
+    let rook_from = match board.get_piece(to) {
+        Some(piece)
+            if piece.color == king.color
+                && piece.piece_type == PieceType::Rook
+                && board.castling_rights()[index]
+                && board.castling_rooks[index] == to =>
         {
-            moves.push(Move {
-                initial_square: square,
-                target_square: 62,
-            });
+            to
         }
-        // queen side
-        if board.castling_availability.3
-            && board.get_piece(59).is_none()
-            && board.get_piece(58).is_none()
+        None if !board.chess960
+            && from.abs_diff(to) == 2
+            && board.castling_rights()[index]
+            && board.get_piece(board.castling_rooks[index])
+                == Some(&Piece {
+                    piece_type: PieceType::Rook,
+                    color: king.color,
+                }) =>
         {
-            moves.push(Move {
-                initial_square: square,
-                target_square: 58,
-            });
+            board.castling_rooks[index]
         }
-    }
+        _ => return None,
+    };
+    let back_rank = from - from % BOARD_SIZE;
+    let (king_to, rook_to) = if king_side {
+        (back_rank + 6, back_rank + 5)
+    } else {
+        (back_rank + 2, back_rank + 3)
+    };
+    Some(Castling {
+        king_from: from,
+        king_to,
+        rook_from,
+        rook_to,
+    })
 }
 
-fn generate_white_castling_moves(square: u8, board: &Board, moves: &mut Vec<Move>) {
-    // checking for white castling
-    // king original position
-    if square == 4 {
-        // king side
-        if board.castling_availability.0
-            && board.get_piece(5).is_none()
-            && board.get_piece(6).is_none()
+fn generate_castling_moves(square: u8, board: &Board, moves: &mut Vec<Move>) {
+    let color = board.get_piece(square).unwrap().color;
+    let back_rank = match color {
+        Color::White => 0,
+        Color::Black => 56,
+    };
+    // Outside of Chess960 the king castles from the e file only
+    if square / BOARD_SIZE != back_rank / BOARD_SIZE || (!board.chess960 && square != back_rank + 4)
+    {
+        return;
+    }
+    let rook = Piece {
+        piece_type: PieceType::Rook,
+        color,
+    };
+
+    for king_side in [true, false] {
+        let index = castling_index(color, king_side);
+        let rook_from = board.castling_rooks[index];
+        if !board.castling_rights()[index]
+            || (rook_from > square) != king_side
+            || board.get_piece(rook_from) != Some(&rook)
         {
-            moves.push(Move {
-                initial_square: square,
-                target_square: 6,
-            });
+            continue;
         }
-        // queen side
-        if board.castling_availability.1
-            && board.get_piece(3).is_none()
-            && board.get_piece(2).is_none()
-        {
-            moves.push(Move {
-                initial_square: square,
-                target_square: 2,
-            });
+        let (king_to, rook_to) = if king_side {
+            (back_rank + 6, back_rank + 5)
+        } else {
+            (back_rank + 2, back_rank + 3)
+        };
+        // Every square the king and the rook go over must be empty, but for themselves
+        let squares = [square, king_to, rook_from, rook_to];
+        let lowest = *squares.iter().min().unwrap();
+        let highest = *squares.iter().max().unwrap();
+        if (lowest..=highest).all(|between| {
+            between == square || between == rook_from || board.get_piece(between).is_none()
+        }) {
+            moves.push(Move::new(
+                square,
+                if board.chess960 { rook_from } else { king_to },
+            ));
         }
     }
 }
@@ -127,34 +222,42 @@ mod king_tests {
             Move {
                 initial_square: 27,
                 target_square: 18,
+                promotion: None,
             },
             Move {
                 initial_square: 27,
                 target_square: 19,
+                promotion: None,
             },
             Move {
                 initial_square: 27,
                 target_square: 20,
+                promotion: None,
             },
             Move {
                 initial_square: 27,
                 target_square: 26,
+                promotion: None,
             },
             Move {
                 initial_square: 27,
                 target_square: 28,
+                promotion: None,
             },
             Move {
                 initial_square: 27,
                 target_square: 34,
+                promotion: None,
             },
             Move {
                 initial_square: 27,
                 target_square: 35,
+                promotion: None,
             },
             Move {
                 initial_square: 27,
                 target_square: 36,
+                promotion: None,
             },
         ];
 
@@ -180,14 +283,17 @@ mod king_tests {
             Move {
                 initial_square: 0,
                 target_square: 8,
+                promotion: None,
             },
             Move {
                 initial_square: 0,
                 target_square: 9,
+                promotion: None,
             },
             Move {
                 initial_square: 0,
                 target_square: 1,
+                promotion: None,
             },
         ];
 
@@ -213,22 +319,27 @@ mod king_tests {
             Move {
                 initial_square: 23,
                 target_square: 14,
+                promotion: None,
             },
             Move {
                 initial_square: 23,
                 target_square: 15,
+                promotion: None,
             },
             Move {
                 initial_square: 23,
                 target_square: 22,
+                promotion: None,
             },
             Move {
                 initial_square: 23,
                 target_square: 30,
+                promotion: None,
             },
             Move {
                 initial_square: 23,
                 target_square: 31,
+                promotion: None,
             },
         ];
 
@@ -249,36 +360,63 @@ mod king_tests {
         board.set_piece(white_king_square, white_king);
         board.castling_availability = (true, true, false, false);
 
-        let moves = board.generate_moves(current_player);
+        board.set_piece(
+            0,
+            Piece {
+                piece_type: PieceType::Rook,
+                color: Color::White,
+            },
+        );
+        board.set_piece(
+            7,
+            Piece {
+                piece_type: PieceType::Rook,
+                color: Color::White,
+            },
+        );
+
+        // The moves of the rooks are left out
+        let moves: Vec<Move> = board
+            .generate_moves(current_player)
+            .into_iter()
+            .filter(|chess_move| chess_move.initial_square == white_king_square)
+            .collect();
 
         let expected_moves = vec![
             Move {
                 initial_square: 4,
                 target_square: 3,
+                promotion: None,
             },
             Move {
                 initial_square: 4,
                 target_square: 11,
+                promotion: None,
             },
             Move {
                 initial_square: 4,
                 target_square: 12,
+                promotion: None,
             },
             Move {
                 initial_square: 4,
                 target_square: 13,
+                promotion: None,
             },
             Move {
                 initial_square: 4,
                 target_square: 5,
+                promotion: None,
             },
             Move {
                 initial_square: 4,
                 target_square: 2,
+                promotion: None,
             },
             Move {
                 initial_square: 4,
                 target_square: 6,
+                promotion: None,
             },
         ];
 
@@ -299,32 +437,58 @@ mod king_tests {
         board.set_piece(white_king_square, white_king);
         board.castling_availability = (false, true, false, false);
 
-        let moves = board.generate_moves(current_player);
+        board.set_piece(
+            0,
+            Piece {
+                piece_type: PieceType::Rook,
+                color: Color::White,
+            },
+        );
+        board.set_piece(
+            7,
+            Piece {
+                piece_type: PieceType::Rook,
+                color: Color::White,
+            },
+        );
+
+        // The moves of the rooks are left out
+        let moves: Vec<Move> = board
+            .generate_moves(current_player)
+            .into_iter()
+            .filter(|chess_move| chess_move.initial_square == white_king_square)
+            .collect();
 
         let expected_moves = vec![
             Move {
                 initial_square: 4,
                 target_square: 3,
+                promotion: None,
             },
             Move {
                 initial_square: 4,
                 target_square: 11,
+                promotion: None,
             },
             Move {
                 initial_square: 4,
                 target_square: 12,
+                promotion: None,
             },
             Move {
                 initial_square: 4,
                 target_square: 13,
+                promotion: None,
             },
             Move {
                 initial_square: 4,
                 target_square: 5,
+                promotion: None,
             },
             Move {
                 initial_square: 4,
                 target_square: 2,
+                promotion: None,
             },
         ];
 
@@ -345,32 +509,58 @@ mod king_tests {
         board.set_piece(white_king_square, white_king);
         board.castling_availability = (true, false, false, false);
 
-        let moves = board.generate_moves(current_player);
+        board.set_piece(
+            0,
+            Piece {
+                piece_type: PieceType::Rook,
+                color: Color::White,
+            },
+        );
+        board.set_piece(
+            7,
+            Piece {
+                piece_type: PieceType::Rook,
+                color: Color::White,
+            },
+        );
+
+        // The moves of the rooks are left out
+        let moves: Vec<Move> = board
+            .generate_moves(current_player)
+            .into_iter()
+            .filter(|chess_move| chess_move.initial_square == white_king_square)
+            .collect();
 
         let expected_moves = vec![
             Move {
                 initial_square: 4,
                 target_square: 3,
+                promotion: None,
             },
             Move {
                 initial_square: 4,
                 target_square: 11,
+                promotion: None,
             },
             Move {
                 initial_square: 4,
                 target_square: 12,
+                promotion: None,
             },
             Move {
                 initial_square: 4,
                 target_square: 13,
+                promotion: None,
             },
             Move {
                 initial_square: 4,
                 target_square: 5,
+                promotion: None,
             },
             Move {
                 initial_square: 4,
                 target_square: 6,
+                promotion: None,
             },
         ];
 
@@ -391,36 +581,63 @@ mod king_tests {
         board.set_piece(white_king_square, white_king);
         board.castling_availability = (false, false, true, true);
 
-        let moves = board.generate_moves(current_player);
+        board.set_piece(
+            56,
+            Piece {
+                piece_type: PieceType::Rook,
+                color: Color::Black,
+            },
+        );
+        board.set_piece(
+            63,
+            Piece {
+                piece_type: PieceType::Rook,
+                color: Color::Black,
+            },
+        );
+
+        // The moves of the rooks are left out
+        let moves: Vec<Move> = board
+            .generate_moves(current_player)
+            .into_iter()
+            .filter(|chess_move| chess_move.initial_square == white_king_square)
+            .collect();
 
         let expected_moves = vec![
             Move {
                 initial_square: 60,
                 target_square: 59,
+                promotion: None,
             },
             Move {
                 initial_square: 60,
                 target_square: 61,
+                promotion: None,
             },
             Move {
                 initial_square: 60,
                 target_square: 51,
+                promotion: None,
             },
             Move {
                 initial_square: 60,
                 target_square: 52,
+                promotion: None,
             },
             Move {
                 initial_square: 60,
                 target_square: 53,
+                promotion: None,
             },
             Move {
                 initial_square: 60,
                 target_square: 62,
+                promotion: None,
             },
             Move {
                 initial_square: 60,
                 target_square: 58,
+                promotion: None,
             },
         ];
 
@@ -441,32 +658,58 @@ mod king_tests {
         board.set_piece(white_king_square, white_king);
         board.castling_availability = (false, false, false, true);
 
-        let moves = board.generate_moves(current_player);
+        board.set_piece(
+            56,
+            Piece {
+                piece_type: PieceType::Rook,
+                color: Color::Black,
+            },
+        );
+        board.set_piece(
+            63,
+            Piece {
+                piece_type: PieceType::Rook,
+                color: Color::Black,
+            },
+        );
+
+        // The moves of the rooks are left out
+        let moves: Vec<Move> = board
+            .generate_moves(current_player)
+            .into_iter()
+            .filter(|chess_move| chess_move.initial_square == white_king_square)
+            .collect();
 
         let expected_moves = vec![
             Move {
                 initial_square: 60,
                 target_square: 59,
+                promotion: None,
             },
             Move {
                 initial_square: 60,
                 target_square: 61,
+                promotion: None,
             },
             Move {
                 initial_square: 60,
                 target_square: 51,
+                promotion: None,
             },
             Move {
                 initial_square: 60,
                 target_square: 52,
+                promotion: None,
             },
             Move {
                 initial_square: 60,
                 target_square: 53,
+                promotion: None,
             },
             Move {
                 initial_square: 60,
                 target_square: 58,
+                promotion: None,
             },
         ];
 
@@ -487,32 +730,58 @@ mod king_tests {
         board.set_piece(white_king_square, white_king);
         board.castling_availability = (false, false, true, false);
 
-        let moves = board.generate_moves(current_player);
+        board.set_piece(
+            56,
+            Piece {
+                piece_type: PieceType::Rook,
+                color: Color::Black,
+            },
+        );
+        board.set_piece(
+            63,
+            Piece {
+                piece_type: PieceType::Rook,
+                color: Color::Black,
+            },
+        );
+
+        // The moves of the rooks are left out
+        let moves: Vec<Move> = board
+            .generate_moves(current_player)
+            .into_iter()
+            .filter(|chess_move| chess_move.initial_square == white_king_square)
+            .collect();
 
         let expected_moves = vec![
             Move {
                 initial_square: 60,
                 target_square: 59,
+                promotion: None,
             },
             Move {
                 initial_square: 60,
                 target_square: 61,
+                promotion: None,
             },
             Move {
                 initial_square: 60,
                 target_square: 51,
+                promotion: None,
             },
             Move {
                 initial_square: 60,
                 target_square: 52,
+                promotion: None,
             },
             Move {
                 initial_square: 60,
                 target_square: 53,
+                promotion: None,
             },
             Move {
                 initial_square: 60,
                 target_square: 62,
+                promotion: None,
             },
         ];
 
@@ -30,13 +30,13 @@ const KNIGHT_MOVES: [(isize, isize); 8] = [
 /// # Example
 ///
 /// ```
-/// use chess_engine::board::precalculate_knight_moves;
+/// use ruce::board::piece::knight::precalculate_knight_moves;
 ///
 /// let knight_moves_map = precalculate_knight_moves();
 ///
 /// // Get the possible knight moves for square 'a1' (index 0)
-/// let possible_moves_a1 = knight_moves_map[&0];
-/// assert_eq!(possible_moves_a1, vec![10, 17]);
+/// let possible_moves_a1 = &knight_moves_map[&0];
+/// assert_eq!(*possible_moves_a1, vec![10, 17]);
 /// ```
 pub fn precalculate_knight_moves() -> HashMap<u8, Vec<u8>> {
     let mut knight_moves_map: HashMap<u8, Vec<u8>> = HashMap::new();
@@ -90,9 +90,10 @@ pub fn precalculate_knight_moves() -> HashMap<u8, Vec<u8>> {
 /// # Example
 ///
 /// ```
-/// use chess_engine::board::{Board, generate_knight_moves};
-/// use chess_engine::board::piece::{Color, Piece, PieceType};
-/// use chess_engine::board::moves::Move;
+/// use ruce::board::Board;
+/// use ruce::board::piece::knight::generate_knight_moves;
+/// use ruce::board::piece::{Color, Piece, PieceType};
+/// use ruce::board::moves::Move;
 ///
 /// // Create an empty chess board
 /// let mut board = Board::new_empty_board();
@@ -102,21 +103,21 @@ pub fn precalculate_knight_moves() -> HashMap<u8, Vec<u8>> {
 ///     piece_type: PieceType::Knight,
 ///     color: Color::White,
 /// };
-/// board.set_piece(27, Some(knight));
+/// board.set_piece(27, knight);
 ///
 /// // Generate knight moves for the knight on 'd4'
 /// let moves = generate_knight_moves(&board, 3, 3);
 ///
-/// // The valid knight moves for 'd4' are 'b5', 'f5', 'c2', 'e2', 'b3', 'f3', 'c6', and 'e6'.
+/// // The valid knight moves for 'd4' are 'e2', 'c2', 'f3', 'b3', 'f5', 'b5', 'e6' and 'c6'.
 /// let expected_moves = vec![
-///     Move { initial_square: 27, target_square: 17 },
-///     Move { initial_square: 27, target_square: 37 },
-///     Move { initial_square: 27, target_square: 12 },
-///     Move { initial_square: 27, target_square: 42 },
-///     Move { initial_square: 27, target_square: 19 },
-///     Move { initial_square: 27, target_square: 39 },
-///     Move { initial_square: 27, target_square: 14 },
-///     Move { initial_square: 27, target_square: 44 },
+///     Move::new(27, 12),
+///     Move::new(27, 10),
+///     Move::new(27, 21),
+///     Move::new(27, 17),
+///     Move::new(27, 37),
+///     Move::new(27, 33),
+///     Move::new(27, 44),
+///     Move::new(27, 42),
 /// ];
 /// assert_eq!(moves.unwrap(), expected_moves);
 /// ```
@@ -127,13 +128,15 @@ pub fn generate_knight_moves(board: &Board, row: u8, col: u8) -> Option<Vec<Move
     if let Some(possible_moves) = board.knight_moves_map.get(&square) {
         for &target_square in possible_moves {
             // Check if the target square is empty or occupied by an opponent's piece
-            if board.get_piece(target_square).map_or(true, |piece| {
-                piece.color != board.get_piece(square).unwrap().color
-            }) {
+            if board
+                .get_piece(target_square)
+                .is_none_or(|piece| piece.color != board.get_piece(square).unwrap().color)
+            {
                 let initial_square = row * BOARD_SIZE + col;
                 moves.push(Move {
                     initial_square,
                     target_square,
+                    promotion: None,
                 });
             }
         }
@@ -146,6 +149,30 @@ pub fn generate_knight_moves(board: &Board, row: u8, col: u8) -> Option<Vec<Move
     }
 }
 
+/// Generate the captures of the knight at a specific position on the board.
+///
+/// Works like `generate_knight_moves`, but only keeps the target squares occupied by an
+/// opponent's piece.
+pub fn generate_knight_captures(board: &Board, row: u8, col: u8) -> Option<Vec<Move>> {
+    let square: u8 = row * BOARD_SIZE + col;
+    let color = board.get_piece(square)?.color;
+    let moves: Vec<Move> = board.knight_moves_map[&square]
+        .iter()
+        .filter(|&&target_square| {
+            board
+                .get_piece(target_square)
+                .is_some_and(|piece| piece.color != color)
+        })
+        .map(|&target_square| Move::new(square, target_square))
+        .collect();
+
+    if !moves.is_empty() {
+        Some(moves)
+    } else {
+        None
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::board::{
@@ -212,34 +239,42 @@ mod tests {
             Move {
                 initial_square: 27,
                 target_square: 33,
+                promotion: None,
             },
             Move {
                 initial_square: 27,
                 target_square: 42,
+                promotion: None,
             },
             Move {
                 initial_square: 27,
                 target_square: 44,
+                promotion: None,
             },
             Move {
                 initial_square: 27,
                 target_square: 37,
+                promotion: None,
             },
             Move {
                 initial_square: 27,
                 target_square: 21,
+                promotion: None,
             },
             Move {
                 initial_square: 27,
                 target_square: 12,
+                promotion: None,
             },
             Move {
                 initial_square: 27,
                 target_square: 10,
+                promotion: None,
             },
             Move {
                 initial_square: 27,
                 target_square: 17,
+                promotion: None,
             },
         ];
 
@@ -265,10 +300,12 @@ mod tests {
             Move {
                 initial_square: 0,
                 target_square: 17,
+                promotion: None,
             },
             Move {
                 initial_square: 0,
                 target_square: 10,
+                promotion: None,
             },
         ];
 
@@ -293,18 +330,22 @@ mod tests {
             Move {
                 initial_square: 23,
                 target_square: 38,
+                promotion: None,
             },
             Move {
                 initial_square: 23,
                 target_square: 29,
+                promotion: None,
             },
             Move {
                 initial_square: 23,
                 target_square: 13,
+                promotion: None,
             },
             Move {
                 initial_square: 23,
                 target_square: 6,
+                promotion: None,
             },
         ];
 
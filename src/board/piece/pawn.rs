@@ -2,7 +2,15 @@
 
 use crate::board::{moves::Move, Board, BOARD_SIZE};
 
-use super::Color;
+use super::{Color, PieceType};
+
+/// Piece types a pawn may be promoted to, strongest first.
+pub const PROMOTION_PIECES: [PieceType; 4] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+];
 
 fn one_square_move(board: &Board, initial_square: u8, col: u8, new_row: u8) -> Option<Move> {
     // Calculate the target square number
@@ -13,6 +21,7 @@ fn one_square_move(board: &Board, initial_square: u8, col: u8, new_row: u8) -> O
         return Some(Move {
             initial_square,
             target_square,
+            promotion: None,
         });
     }
     None
@@ -39,6 +48,7 @@ fn two_square_move(
             return Some(Move {
                 initial_square,
                 target_square: two_square_target,
+                promotion: None,
             });
         }
     }
@@ -62,8 +72,16 @@ fn capture_moves(
                 return Some(Move {
                     initial_square,
                     target_square: diagonal_target,
+                    promotion: None,
                 });
             }
+        } else if board.en_passant_target == Some(diagonal_target) {
+            // The square was skipped by an opponent pawn on the previous move
+            return Some(Move {
+                initial_square,
+                target_square: diagonal_target,
+                promotion: None,
+            });
         }
     }
     None
@@ -76,7 +94,8 @@ fn capture_moves(
 /// It takes into consideration the pawn's color to determine the direction of movement.
 /// The function considers one-square moves, two-square moves for the pawn's first move,
 /// and capturing moves diagonally.
-/// The function also handles special moves like en passant and promotion, but these are not yet implemented.
+/// The function also handles en passant captures, using the board's `en_passant_target`, and
+/// promotions, generating one move per possible promotion piece.
 ///
 /// The function returns an `Option<Vec<Move>>`, where `Some(moves)` contains a vector of `Move`
 /// structs representing the valid moves that the pawn can make. If no valid moves are found,
@@ -97,9 +116,10 @@ fn capture_moves(
 /// # Example
 ///
 /// ```
-/// use chess_engine::board::{Board, generate_pawn_moves};
-/// use chess_engine::board::piece::{Color, Piece, PieceType};
-/// use chess_engine::board::moves::Move;
+/// use ruce::board::Board;
+/// use ruce::board::piece::pawn::generate_pawn_moves;
+/// use ruce::board::piece::{Color, Piece, PieceType};
+/// use ruce::board::moves::Move;
 ///
 /// // Create an empty chess board
 /// let mut board = Board::new_empty_board();
@@ -109,15 +129,15 @@ fn capture_moves(
 ///     piece_type: PieceType::Pawn,
 ///     color: Color::White,
 /// };
-/// board.set_piece(11, Some(pawn));
+/// board.set_piece(11, pawn);
 ///
 /// // Generate pawn moves for the pawn on 'd2'
 /// let moves = generate_pawn_moves(&board, 1, 3, Color::White);
 ///
 /// // The valid pawn moves for 'd2' are 'd3' (one square move) and 'd4' (two square move).
 /// let expected_moves = vec![
-///     Move { initial_square: 11, target_square: 19 },
-///     Move { initial_square: 11, target_square: 27 },
+///     Move { initial_square: 11, target_square: 19, promotion: None },
+///     Move { initial_square: 11, target_square: 27, promotion: None },
 /// ];
 /// assert_eq!(moves.unwrap(), expected_moves);
 /// ```
@@ -185,10 +205,52 @@ pub fn generate_pawn_moves(board: &Board, row: u8, col: u8, color: Color) -> Opt
         moves.push(move_);
     }
 
-    // TODO: Implement en passant, for this we need to know the previous move
+    // Promotion, a move that ends either in the last row or the first row
+    // is replaced by one move per piece the pawn can become
+    if new_row == 0 || new_row == BOARD_SIZE - 1 {
+        moves = expand_promotions(moves);
+    }
+
+    if !moves.is_empty() {
+        return Some(moves);
+    }
+    None
+}
+
+/// Generate the captures and promotions of the pawn at a specific position on the board.
+///
+/// This is the subset of `generate_pawn_moves` that changes the material on the board:
+/// diagonal captures (including en passant) and moves to the last row, promoting or not.
+/// Plain pawn pushes are never generated.
+///
+/// # Returns
+///
+/// An `Option<Vec<Move>>`, where `Some(moves)` contains the captures and promotions that the
+/// pawn can make, and `None` if there are none.
+pub fn generate_pawn_captures(board: &Board, row: u8, col: u8, color: Color) -> Option<Vec<Move>> {
+    let mut moves: Vec<Move> = Vec::new();
+    let direction = match color {
+        Color::White => 1,
+        Color::Black => -1,
+    };
+    let initial_square = row * BOARD_SIZE + col;
+    let new_row = (row as isize + direction) as u8;
+    if new_row >= BOARD_SIZE {
+        return None;
+    }
+
+    for new_col in [col as isize - 1, col as isize + 1] {
+        if let Some(move_) = capture_moves(board, initial_square, row, direction, new_col, color) {
+            moves.push(move_);
+        }
+    }
 
-    // TODO: Implement promotion, hmm what is the purpose of this?
-    // this is a move that ends either in the last row or the first row
+    if new_row == 0 || new_row == BOARD_SIZE - 1 {
+        if let Some(move_) = one_square_move(board, initial_square, col, new_row) {
+            moves.push(move_);
+        }
+        moves = expand_promotions(moves);
+    }
 
     if !moves.is_empty() {
         return Some(moves);
@@ -196,6 +258,19 @@ pub fn generate_pawn_moves(board: &Board, row: u8, col: u8, color: Color) -> Opt
     None
 }
 
+/// Replaces each move by one move per piece the pawn can be promoted to.
+fn expand_promotions(moves: Vec<Move>) -> Vec<Move> {
+    moves
+        .into_iter()
+        .flat_map(|move_| {
+            PROMOTION_PIECES.iter().map(move |&piece_type| Move {
+                promotion: Some(piece_type),
+                ..move_.clone()
+            })
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -222,12 +297,14 @@ mod tests {
         assert!(moves.contains(&Move {
             initial_square: 11,
             target_square: 19,
+            promotion: None,
         }));
 
         // Check that the pawn can move two squares forward
         assert!(moves.contains(&Move {
             initial_square: 11,
             target_square: 27,
+            promotion: None,
         }));
     }
 
@@ -249,12 +326,14 @@ mod tests {
         assert!(moves.contains(&Move {
             initial_square: 51,
             target_square: 43,
+            promotion: None,
         }));
 
         // Check that the pawn can move two squares forward
         assert!(moves.contains(&Move {
             initial_square: 51,
             target_square: 35,
+            promotion: None,
         }));
     }
 
@@ -285,12 +364,14 @@ mod tests {
         assert!(moves.contains(&Move {
             initial_square: 11,
             target_square: 19,
+            promotion: None,
         }));
 
         // Check that the pawn can't move two squares forward
         assert!(!moves.contains(&Move {
             initial_square: 11,
             target_square: 27,
+            promotion: None,
         }));
     }
 
@@ -321,12 +402,14 @@ mod tests {
         assert!(moves.contains(&Move {
             initial_square: 51,
             target_square: 43,
+            promotion: None,
         }));
 
         // Check that the pawn can't move two squares forward
         assert!(!moves.contains(&Move {
             initial_square: 51,
             target_square: 35,
+            promotion: None,
         }));
     }
 
@@ -357,18 +440,21 @@ mod tests {
         assert!(moves.contains(&Move {
             initial_square: 11,
             target_square: 19,
+            promotion: None,
         }));
 
         // Check that the pawn can take the black pawn
         assert!(moves.contains(&Move {
             initial_square: 11,
             target_square: 20,
+            promotion: None,
         }));
 
         // Check that pawn can move two square forward
         assert!(moves.contains(&Move {
             initial_square: 11,
             target_square: 27,
+            promotion: None,
         }));
     }
 
@@ -399,12 +485,14 @@ mod tests {
         assert!(moves.contains(&Move {
             initial_square: 27,
             target_square: 35,
+            promotion: None,
         }));
 
         // Check that the pawn can take the black pawn
         assert!(moves.contains(&Move {
             initial_square: 27,
             target_square: 36,
+            promotion: None,
         }));
     }
 
@@ -434,18 +522,21 @@ mod tests {
         assert!(moves.contains(&Move {
             initial_square: 51,
             target_square: 43,
+            promotion: None,
         }));
 
         // Check that the pawn can take the white pawn
         assert!(moves.contains(&Move {
             initial_square: 51,
             target_square: 44,
+            promotion: None,
         }));
 
         // Check that pawn can move two square forward
         assert!(moves.contains(&Move {
             initial_square: 51,
             target_square: 35,
+            promotion: None,
         }));
     }
 
@@ -477,12 +568,14 @@ mod tests {
         assert!(moves.contains(&Move {
             initial_square: 35,
             target_square: 27,
+            promotion: None,
         }));
 
         // Check that the pawn can take the white pawn
         assert!(moves.contains(&Move {
             initial_square: 35,
             target_square: 26,
+            promotion: None,
         }));
     }
 
@@ -513,12 +606,14 @@ mod tests {
         assert!(moves.contains(&Move {
             initial_square: 24,
             target_square: 32,
+            promotion: None,
         }));
 
         // Check that the pawn can take the black pawn
         assert!(moves.contains(&Move {
             initial_square: 24,
             target_square: 33,
+            promotion: None,
         }));
     }
 
@@ -549,12 +644,14 @@ mod tests {
         assert!(moves.contains(&Move {
             initial_square: 31,
             target_square: 39,
+            promotion: None,
         }));
 
         // Check that the pawn can take the black pawn
         assert!(moves.contains(&Move {
             initial_square: 31,
             target_square: 38,
+            promotion: None,
         }));
     }
 
@@ -585,18 +682,21 @@ mod tests {
         assert!(moves.contains(&Move {
             initial_square: 48,
             target_square: 40,
+            promotion: None,
         }));
 
         // Check that the pawn can take the white pawn
         assert!(moves.contains(&Move {
             initial_square: 48,
             target_square: 41,
+            promotion: None,
         }));
 
         // Check that pawn can move two square forward
         assert!(moves.contains(&Move {
             initial_square: 48,
             target_square: 32,
+            promotion: None,
         }));
     }
 
@@ -627,18 +727,21 @@ mod tests {
         assert!(moves.contains(&Move {
             initial_square: 55,
             target_square: 47,
+            promotion: None,
         }));
 
         // Check that the pawn can take the white pawn
         assert!(moves.contains(&Move {
             initial_square: 55,
             target_square: 46,
+            promotion: None,
         }));
 
         // Check that pawn can move two square forward
         assert!(moves.contains(&Move {
             initial_square: 55,
             target_square: 39,
+            promotion: None,
         }));
     }
 
@@ -50,6 +50,65 @@ pub fn generate_sliding_moves(board: &Board, row: u8, col: u8, piece: Piece) ->
     Some(moves)
 }
 
+/// This is the public API for generating the captures of sliding pieces.
+/// Works like `generate_sliding_moves`, but only the squares where a direction is blocked by
+/// an opponent's piece are returned.
+pub fn generate_sliding_captures(
+    board: &Board,
+    row: u8,
+    col: u8,
+    piece: Piece,
+) -> Option<Vec<Move>> {
+    let directions: Vec<Direction> = match piece.piece_type {
+        PieceType::Bishop => DIAGONAL_DIRECTIONS.to_vec(),
+        PieceType::Rook => LINEAR_DIRECTIONS.to_vec(),
+        PieceType::Queen => [DIAGONAL_DIRECTIONS, LINEAR_DIRECTIONS].concat(),
+        _ => return None,
+    };
+    let square: u8 = row * BOARD_SIZE + col;
+
+    let moves: Vec<Move> = directions
+        .iter()
+        .filter_map(|dir| first_piece_in_direction(board, row, col, dir))
+        .filter(|&target_square| {
+            board
+                .get_piece(target_square)
+                .is_some_and(|target| target.color != piece.color)
+        })
+        .map(|target_square| Move::new(square, target_square))
+        .collect();
+
+    Some(moves)
+}
+
+/// Returns the square of the first piece found walking from (`row`, `col`) in a direction,
+/// or `None` if the direction reaches the edge of the board without meeting a piece.
+pub fn first_piece_in_direction(board: &Board, row: u8, col: u8, dir: &Direction) -> Option<u8> {
+    let (row_offset, col_offset, stopping_condition) = dir;
+    for i in 1..BOARD_SIZE {
+        if stopping_condition(row, col, i) {
+            return None;
+        }
+        let target_square = row_offset(row, i) * BOARD_SIZE + col_offset(col, i);
+        if board.get_piece(target_square).is_some() {
+            return Some(target_square);
+        }
+    }
+    None
+}
+
+/// Returns the squares of every piece found walking from (`row`, `col`) in a direction,
+/// nearest first. The pieces behind the first one are the ones it hides, which is what x-ray
+/// attacks go through.
+pub fn pieces_in_direction(board: &Board, row: u8, col: u8, dir: &Direction) -> Vec<u8> {
+    let (row_offset, col_offset, stopping_condition) = dir;
+    (1..BOARD_SIZE)
+        .take_while(|&i| !stopping_condition(row, col, i))
+        .map(|i| row_offset(row, i) * BOARD_SIZE + col_offset(col, i))
+        .filter(|&target_square| board.get_piece(target_square).is_some())
+        .collect()
+}
+
 /// Generate moves in the given directions.
 /// The directions are given as a tuple of functions.
 /// The first function is the row offset function, the second is the column offset function,
@@ -84,6 +143,7 @@ fn generate_moves(
                     moves.push(Move {
                         initial_square: square,
                         target_square,
+                        promotion: None,
                     });
                 }
                 // We break here because the direction is blocked
@@ -92,6 +152,7 @@ fn generate_moves(
             moves.push(Move {
                 initial_square: square,
                 target_square,
+                promotion: None,
             });
         }
     }
@@ -103,10 +164,10 @@ fn generate_moves(
 /// for sliding pieces.
 /// The first function is the row offset function, the second is the column offset function,
 /// and the third is the stopping condition function.
-type Direction = (fn(u8, u8) -> u8, fn(u8, u8) -> u8, fn(u8, u8, u8) -> bool);
+pub type Direction = (fn(u8, u8) -> u8, fn(u8, u8) -> u8, fn(u8, u8, u8) -> bool);
 
 /// The DIAGONAL_DIRECTIONS consist of the diagonal up directions and the diagonal down directions.
-static DIAGONAL_DIRECTIONS: [Direction; 4] = [
+pub static DIAGONAL_DIRECTIONS: [Direction; 4] = [
     DIAGONAL_UP_DIRECTIONS[0],
     DIAGONAL_UP_DIRECTIONS[1],
     DIAGONAL_DOWN_DIRECTIONS[0],
@@ -146,7 +207,7 @@ static DIAGONAL_DOWN_DIRECTIONS: [Direction; 2] = [
 ];
 
 /// The LINEAR_DIRECTIONS consist of the horizontal directions and the vertical directions.
-static LINEAR_DIRECTIONS: [Direction; 4] = [
+pub static LINEAR_DIRECTIONS: [Direction; 4] = [
     HORIZONTAL_DIRECTIONS[0],
     HORIZONTAL_DIRECTIONS[1],
     VERTICAL_DIRECTIONS[0],
@@ -181,7 +242,7 @@ static VERTICAL_DIRECTIONS: [Direction; 2] = [
 #[cfg(test)]
 mod linear_move_tests {
     use super::*;
-    use crate::board::{self, Board};
+    use crate::board::Board;
     use crate::board::{Color, Piece, PieceType};
 
     #[test]
@@ -0,0 +1,193 @@
+//! Module containing the Static Exchange Evaluation (SEE) of moves.
+//!
+//! The static exchange evaluation of a move is the material won or lost once every piece
+//! attacking its target square has captured there, least valuable first, each side stopping
+//! as soon as capturing again would lose material. Sliding pieces lined up behind an attacker
+//! join the exchange once the attacker in front of them has captured.
+
+use crate::board::moves::Move;
+use crate::board::piece::sliding_pieces::{
+    pieces_in_direction, DIAGONAL_DIRECTIONS, LINEAR_DIRECTIONS,
+};
+use crate::board::piece::{Color, PieceType};
+use crate::board::{Board, BOARD_SIZE};
+
+/// The value of a king, high enough that it never ends up being exchanged.
+const KING_VALUE: i32 = 20_000;
+
+impl Board {
+    /// Returns the material outcome of a move, in centipawns, for the side playing it, after
+    /// the exchange of pieces it starts on its target square.
+    ///
+    /// Quiet moves are evaluated too: a piece moving to a square where it can be taken for
+    /// free is worth minus its value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ruce::board::moves::Move;
+    /// use ruce::board::Board;
+    /// use std::str::FromStr;
+    ///
+    /// // Rxe5 Rxe5 Qxe5 wins a pawn, the queen behind the rook joins the exchange
+    /// let board = Board::from_str("1k2r3/8/8/4p3/8/8/4R3/4Q1K1").unwrap();
+    /// assert_eq!(board.see(&Move::new(12, 36)), 100);
+    /// ```
+    pub fn see(&self, chess_move: &Move) -> i32 {
+        let Some(piece) = self.get_piece(chess_move.initial_square) else {
+            return 0;
+        };
+        let target = chess_move.target_square;
+        // Castling in Chess960, the king takes its own rook
+        if self
+            .get_piece(target)
+            .is_some_and(|captured| captured.color == piece.color)
+        {
+            return 0;
+        }
+        let mut removed = 1u64 << chess_move.initial_square;
+
+        let mut captured = self
+            .get_piece(target)
+            .map_or(0, |captured| see_value(captured.piece_type));
+        if piece.piece_type == PieceType::Pawn && self.en_passant_target == Some(target) {
+            // The pawn taken en passant is next to the capturing pawn
+            let victim = chess_move.initial_square - chess_move.initial_square % BOARD_SIZE
+                + target % BOARD_SIZE;
+            removed |= 1 << victim;
+            captured = see_value(PieceType::Pawn);
+        }
+        let mut on_target = see_value(piece.piece_type);
+        if let Some(promotion) = chess_move.promotion {
+            captured += see_value(promotion) - see_value(PieceType::Pawn);
+            on_target = see_value(promotion);
+        }
+
+        let attackers = Attackers::new(self, target);
+        let mut gains = vec![captured];
+        let mut side = piece.color.opposite();
+        while let Some((square, value)) = attackers.least_valuable(self, side, removed) {
+            // The king can only take last, when no piece defends the square anymore
+            if value == KING_VALUE
+                && attackers
+                    .least_valuable(self, side.opposite(), removed | 1 << square)
+                    .is_some()
+            {
+                break;
+            }
+            gains.push(on_target - gains[gains.len() - 1]);
+            on_target = value;
+            removed |= 1 << square;
+            side = side.opposite();
+        }
+
+        // Each side chooses between stopping the exchange and going on with it
+        for i in (1..gains.len()).rev() {
+            gains[i - 1] = -(-gains[i - 1]).max(gains[i]);
+        }
+        gains[0]
+    }
+
+    /// Checks whether the static exchange evaluation of a move is at least `threshold`.
+    pub fn see_ge(&self, chess_move: &Move, threshold: i32) -> bool {
+        self.see(chess_move) >= threshold
+    }
+}
+
+/// The pieces that can take part in an exchange on a square.
+struct Attackers {
+    target: u8,
+    knights: Vec<u8>,
+    /// The pieces along each ray from the square, nearest first, and whether the ray is
+    /// diagonal.
+    rays: Vec<(bool, Vec<u8>)>,
+}
+
+impl Attackers {
+    fn new(board: &Board, target: u8) -> Self {
+        let row = target / BOARD_SIZE;
+        let col = target % BOARD_SIZE;
+        let knights = board.knight_moves_map[&target]
+            .iter()
+            .copied()
+            .filter(|&square| {
+                board
+                    .get_piece(square)
+                    .is_some_and(|piece| piece.piece_type == PieceType::Knight)
+            })
+            .collect();
+        let diagonal = DIAGONAL_DIRECTIONS.iter().map(|dir| (true, dir));
+        let linear = LINEAR_DIRECTIONS.iter().map(|dir| (false, dir));
+        let rays = diagonal
+            .chain(linear)
+            .map(|(diagonal, dir)| (diagonal, pieces_in_direction(board, row, col, dir)))
+            .collect();
+        Attackers {
+            target,
+            knights,
+            rays,
+        }
+    }
+
+    /// Returns the square and value of the least valuable piece of `side` attacking the
+    /// target, ignoring the `removed` pieces.
+    fn least_valuable(&self, board: &Board, side: Color, removed: u64) -> Option<(u8, i32)> {
+        let knights = self.knights.iter().copied();
+        // Only the first piece left on a ray attacks the target, it hides the ones behind
+        let rays = self.rays.iter().filter_map(|(diagonal, squares)| {
+            let square = squares
+                .iter()
+                .copied()
+                .find(|&square| removed & 1 << square == 0)?;
+            self.attacks_along_ray(board, square, *diagonal)
+                .then_some(square)
+        });
+        knights
+            .chain(rays)
+            .filter(|&square| removed & 1 << square == 0)
+            .filter_map(|square| {
+                let piece = board.get_piece(square)?;
+                (piece.color == side).then_some((square, see_value(piece.piece_type)))
+            })
+            .min_by_key(|&(_, value)| value)
+    }
+
+    /// Checks whether the piece on `square`, the first one on a ray from the target, attacks
+    /// the target.
+    fn attacks_along_ray(&self, board: &Board, square: u8, diagonal: bool) -> bool {
+        let Some(piece) = board.get_piece(square) else {
+            return false;
+        };
+        let rows = (square / BOARD_SIZE).abs_diff(self.target / BOARD_SIZE);
+        let cols = (square % BOARD_SIZE).abs_diff(self.target % BOARD_SIZE);
+        let adjacent = rows.max(cols) == 1;
+        match piece.piece_type {
+            PieceType::Bishop => diagonal,
+            PieceType::Rook => !diagonal,
+            PieceType::Queen => true,
+            PieceType::King => adjacent,
+            // Pawns attack diagonally forward, so they stand one row behind the target
+            PieceType::Pawn => {
+                diagonal
+                    && adjacent
+                    && match piece.color {
+                        Color::White => square < self.target,
+                        Color::Black => square > self.target,
+                    }
+            }
+            PieceType::Knight => false,
+        }
+    }
+}
+
+/// The value of a piece in an exchange.
+fn see_value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::Pawn => 100,
+        PieceType::Knight => 320,
+        PieceType::Bishop => 330,
+        PieceType::Rook => 500,
+        PieceType::Queen => 900,
+        PieceType::King => KING_VALUE,
+    }
+}
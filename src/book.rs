@@ -0,0 +1,576 @@
+//! Module containing the opening books in the Polyglot format, the most common format of
+//! opening books.
+//!
+//! A book is a file of 16-byte entries sorted by position key, each giving a move played in
+//! the position and its weight, all in big-endian:
+//!
+//! | bytes | field                                   |
+//! |-------|-----------------------------------------|
+//! | 0-7   | the Polyglot key of the position        |
+//! | 8-9   | the move                                |
+//! | 10-11 | the weight of the move                  |
+//! | 12-15 | the learning data, unused by the engine |
+//!
+//! A move has the target square in bits 0-5, the initial square in bits 6-11 and the piece
+//! type of a promotion in bits 12-14 (1 for a knight up to 4 for a queen). Castling is written
+//! as the king taking its own rook, e1h1 for white castling kingside.
+//!
+//! The Polyglot key is a Zobrist key, like the keys of `crate::zobrist`, but with the fixed
+//! random numbers of the format so that books made by other programs can be read.
+//!
+//! Books are built from games with `build::BookBuilder`.
+
+pub mod build;
+mod keys;
+
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::board::moves::Move;
+use crate::board::piece::{Color, PieceType};
+use crate::board::Board;
+use crate::game_state::GameState;
+
+/// The size of a book entry in bytes.
+const ENTRY_SIZE: usize = 16;
+
+/// An error reading a book.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum BookError {
+    #[error("Cannot read the book: {0}")]
+    Io(String),
+    #[error("Invalid book size: {0} bytes is not a whole number of entries")]
+    InvalidSize(usize),
+}
+
+/// A move of a book in a position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    /// The Polyglot key of the position.
+    pub key: u64,
+    /// The move in the Polyglot encoding.
+    pub raw_move: u16,
+    /// The weight of the move, higher for better or more often played moves.
+    pub weight: u16,
+    /// The learning data, kept as is.
+    pub learn: u32,
+}
+
+impl Entry {
+    fn from_bytes(bytes: &[u8]) -> Entry {
+        let field = |range: std::ops::Range<usize>| {
+            bytes[range]
+                .iter()
+                .fold(0u64, |value, &byte| value << 8 | byte as u64)
+        };
+        Entry {
+            key: field(0..8),
+            raw_move: field(8..10) as u16,
+            weight: field(10..12) as u16,
+            learn: field(12..16) as u32,
+        }
+    }
+
+    fn to_bytes(self) -> [u8; ENTRY_SIZE] {
+        let mut bytes = [0; ENTRY_SIZE];
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.raw_move.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.learn.to_be_bytes());
+        bytes
+    }
+}
+
+/// How the move played from a book is chosen among the moves of the position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Selection {
+    /// The move with the highest weight.
+    Best,
+    /// A random move, with a probability proportional to its weight.
+    #[default]
+    Weighted,
+    /// A random move, every move being equally likely.
+    Random,
+}
+
+impl Selection {
+    /// The selections, in the order of the UCI `BookMode` option.
+    pub const ALL: [Selection; 3] = [Selection::Best, Selection::Weighted, Selection::Random];
+}
+
+impl fmt::Display for Selection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Selection::Best => "Best",
+            Selection::Weighted => "Weighted",
+            Selection::Random => "Random",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Selection {
+    type Err = String;
+
+    /// Parses the name of a selection, ignoring case.
+    fn from_str(name: &str) -> Result<Selection, String> {
+        Selection::ALL
+            .into_iter()
+            .find(|selection| selection.to_string().eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("Unknown book selection: {}", name))
+    }
+}
+
+/// An opening book in the Polyglot format.
+#[derive(Debug, Clone, Default)]
+pub struct Book {
+    /// The entries, sorted by key.
+    entries: Vec<Entry>,
+}
+
+impl Book {
+    /// Reads a book from a file.
+    pub fn open(path: impl AsRef<Path>) -> Result<Book, BookError> {
+        let bytes = std::fs::read(path).map_err(|e| BookError::Io(e.to_string()))?;
+        Book::from_bytes(&bytes)
+    }
+
+    /// Reads a book from the bytes of a file. Entries out of order are sorted, so that they
+    /// can be searched.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Book, BookError> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err(BookError::InvalidSize(bytes.len()));
+        }
+        let mut entries: Vec<Entry> = bytes.chunks(ENTRY_SIZE).map(Entry::from_bytes).collect();
+        if !entries.is_sorted_by_key(|entry| entry.key) {
+            entries.sort_by_key(|entry| entry.key);
+        }
+        Ok(Book { entries })
+    }
+
+    /// Writes the book to a file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), BookError> {
+        std::fs::write(path, self.to_bytes()).map_err(|e| BookError::Io(e.to_string()))
+    }
+
+    /// The bytes of the book file.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.entries
+            .iter()
+            .flat_map(|entry| entry.to_bytes())
+            .collect()
+    }
+
+    /// The number of entries of the book.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The entries of a position key, found by binary search.
+    pub fn entries(&self, key: u64) -> &[Entry] {
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let end = start + self.entries[start..].partition_point(|entry| entry.key == key);
+        &self.entries[start..end]
+    }
+
+    /// The legal moves of the book in a position with their weights, in the order of the book.
+    /// Moves which are not legal, from a wrong or colliding entry, are left out.
+    pub fn moves(&self, game_state: &GameState) -> Vec<(Move, u16)> {
+        let entries = self.entries(polyglot_key(game_state));
+        if entries.is_empty() {
+            return Vec::new();
+        }
+        let legal_moves = game_state.generate_legal_moves();
+        entries
+            .iter()
+            .map(|entry| (decode_move(entry.raw_move, &game_state.board), entry.weight))
+            .filter(|(chess_move, _)| legal_moves.contains(chess_move))
+            .collect()
+    }
+
+    /// Chooses a move of the book in a position, or returns `None` when the position is not in
+    /// the book. `random` is a random number, used by the random selections.
+    ///
+    /// The weighted selection only plays moves of weight 0 when all the moves have weight 0,
+    /// and then picks one of them evenly.
+    pub fn choose(
+        &self,
+        game_state: &GameState,
+        selection: Selection,
+        random: u64,
+    ) -> Option<Move> {
+        let moves = self.moves(game_state);
+        let total: u64 = moves.iter().map(|&(_, weight)| weight as u64).sum();
+        match selection {
+            // The first of the best moves, for a stable choice
+            Selection::Best => moves
+                .iter()
+                .rev()
+                .max_by_key(|&&(_, weight)| weight)
+                .map(|(chess_move, _)| chess_move.clone()),
+            Selection::Weighted if total > 0 => {
+                let mut pick = random % total;
+                moves.into_iter().find_map(|(chess_move, weight)| {
+                    if pick < weight as u64 {
+                        Some(chess_move)
+                    } else {
+                        pick -= weight as u64;
+                        None
+                    }
+                })
+            }
+            Selection::Weighted | Selection::Random => {
+                if moves.is_empty() {
+                    return None;
+                }
+                let pick = (random % moves.len() as u64) as usize;
+                moves
+                    .into_iter()
+                    .nth(pick)
+                    .map(|(chess_move, _)| chess_move)
+            }
+        }
+    }
+}
+
+/// The index of a piece type in the Polyglot random numbers.
+fn piece_index(piece_type: PieceType) -> usize {
+    match piece_type {
+        PieceType::Pawn => 0,
+        PieceType::Knight => 1,
+        PieceType::Bishop => 2,
+        PieceType::Rook => 3,
+        PieceType::Queen => 4,
+        PieceType::King => 5,
+    }
+}
+
+/// Returns the Polyglot key of a position.
+///
+/// Unlike `crate::zobrist::position_key`, the en passant file is only part of the key when a
+/// pawn of the side to move stands next to the pawn that just moved, ready to take it.
+///
+/// # Examples
+///
+/// ```
+/// use ruce::book::polyglot_key;
+/// use ruce::game_state::GameState;
+///
+/// assert_eq!(polyglot_key(&GameState::new()), 0x463b_9618_1691_fc9c);
+/// ```
+pub fn polyglot_key(game_state: &GameState) -> u64 {
+    let board = &game_state.board;
+    let mut key = board
+        .squares
+        .iter()
+        .enumerate()
+        .filter_map(|(square, piece)| {
+            let piece = piece.as_ref()?;
+            // Black pieces come first, then white pieces of the same type
+            let kind = 2 * piece_index(piece.piece_type) + (piece.color == Color::White) as usize;
+            Some(keys::RANDOM[64 * kind + square])
+        })
+        .fold(0, |key, piece_key| key ^ piece_key);
+
+    let (white_king, white_queen, black_king, black_queen) = board.castling_availability;
+    for (i, available) in [white_king, white_queen, black_king, black_queen]
+        .into_iter()
+        .enumerate()
+    {
+        if available {
+            key ^= keys::RANDOM[keys::CASTLING + i];
+        }
+    }
+
+    if let Some(target) = board.en_passant_target {
+        if can_capture_en_passant(board, target, game_state.current_player) {
+            key ^= keys::RANDOM[keys::EN_PASSANT + (target % 8) as usize];
+        }
+    }
+
+    if game_state.current_player == Color::White {
+        key ^= keys::RANDOM[keys::TURN];
+    }
+    key
+}
+
+/// Whether a pawn of a color stands next to the pawn that moved over an en passant target.
+/// A target off the rank a pawn can be taken en passant on never lets a pawn capture.
+fn can_capture_en_passant(board: &Board, target: u8, color: Color) -> bool {
+    let rank = match color {
+        Color::White => 5,
+        Color::Black => 2,
+    };
+    if target / 8 != rank {
+        return false;
+    }
+    // The pawn that moved is in front of the target, seen from the side that moved it
+    let moved = match color {
+        Color::White => target.checked_sub(8),
+        Color::Black => target.checked_add(8),
+    };
+    let Some(moved) = moved else {
+        return false;
+    };
+    let file = target % 8;
+    [
+        (file > 0).then(|| moved.checked_sub(1)).flatten(),
+        (file < 7).then(|| moved.checked_add(1)).flatten(),
+    ]
+    .into_iter()
+    .flatten()
+    .filter_map(|square| board.get_piece(square))
+    .any(|piece| piece.piece_type == PieceType::Pawn && piece.color == color)
+}
+
+/// Decodes a Polyglot move in a position, turning castling moves, written as the king taking
+/// its rook, into moves of the king by two squares, unless the board is in Chess960 mode where
+/// castling is written the same way.
+pub fn decode_move(raw_move: u16, board: &Board) -> Move {
+    let target_square = (raw_move & 0x3f) as u8;
+    let initial_square = (raw_move >> 6 & 0x3f) as u8;
+    let promotion = match raw_move >> 12 & 0x7 {
+        1 => Some(PieceType::Knight),
+        2 => Some(PieceType::Bishop),
+        3 => Some(PieceType::Rook),
+        4 => Some(PieceType::Queen),
+        _ => None,
+    };
+    let is_king = board
+        .get_piece(initial_square)
+        .is_some_and(|piece| piece.piece_type == PieceType::King);
+    let target_square = match (initial_square, target_square) {
+        (4, 7) | (60, 63) if is_king && !board.chess960 => target_square - 1,
+        (4, 0) | (60, 56) if is_king && !board.chess960 => target_square + 2,
+        _ => target_square,
+    };
+    Move {
+        initial_square,
+        target_square,
+        promotion,
+    }
+}
+
+/// Encodes a move of a position in the Polyglot format, the inverse of `decode_move`.
+pub fn encode_move(chess_move: &Move, board: &Board) -> u16 {
+    let (from, to) = (chess_move.initial_square, chess_move.target_square);
+    let is_king = board
+        .get_piece(from)
+        .is_some_and(|piece| piece.piece_type == PieceType::King);
+    // The king takes its rook, on the corner next to its target square
+    let to = match to {
+        _ if !is_king || board.chess960 || from.abs_diff(to) != 2 => to,
+        _ if to > from => to + 1,
+        _ => to - 2,
+    };
+    let promotion = match chess_move.promotion {
+        Some(PieceType::Knight) => 1,
+        Some(PieceType::Bishop) => 2,
+        Some(PieceType::Rook) => 3,
+        Some(PieceType::Queen) => 4,
+        _ => 0,
+    };
+    promotion << 12 | (from as u16) << 6 | to as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    /// The bytes of an entry.
+    fn entry_bytes(key: u64, raw_move: u16, weight: u16) -> Vec<u8> {
+        let mut bytes = key.to_be_bytes().to_vec();
+        bytes.extend(raw_move.to_be_bytes());
+        bytes.extend(weight.to_be_bytes());
+        bytes.extend(0u32.to_be_bytes());
+        bytes
+    }
+
+    /// The Polyglot encoding of a move given in UCI notation, without promotion.
+    fn raw(uci: &str) -> u16 {
+        let chess_move = Move::from_uci(uci).unwrap();
+        (chess_move.initial_square as u16) << 6 | chess_move.target_square as u16
+    }
+
+    #[test]
+    fn test_random_numbers_are_distinct() {
+        let numbers: HashSet<u64> = keys::RANDOM.iter().copied().collect();
+        assert_eq!(numbers.len(), 781);
+    }
+
+    #[test]
+    fn test_polyglot_key() {
+        // The keys given with the specification of the format
+        let cases: [(&[&str], u64); 9] = [
+            (&[], 0x463b_9618_1691_fc9c),
+            (&["e2e4"], 0x823c_9b50_fd11_4196),
+            (&["e2e4", "d7d5"], 0x0756_b944_61c5_0fb0),
+            (&["e2e4", "d7d5", "e4e5"], 0x662f_afb9_65db_29d4),
+            (&["e2e4", "d7d5", "e4e5", "f7f5"], 0x22a4_8b5a_8e47_ff78),
+            (
+                &["e2e4", "d7d5", "e4e5", "f7f5", "e1e2"],
+                0x652a_607c_a3f2_42c1,
+            ),
+            (
+                &["e2e4", "d7d5", "e4e5", "f7f5", "e1e2", "e8f7"],
+                0x00fd_d303_c946_bdd9,
+            ),
+            (
+                &["a2a4", "b7b5", "h2h4", "b5b4", "c2c4"],
+                0x3c81_23ea_7b06_7637,
+            ),
+            (
+                &["a2a4", "b7b5", "h2h4", "b5b4", "c2c4", "b4c3", "a1a3"],
+                0x5c3f_9b82_9b27_9560,
+            ),
+        ];
+        for (moves, key) in cases {
+            let mut game_state = GameState::new();
+            for uci in moves {
+                game_state.make_move(&Move::from_uci(uci).unwrap());
+            }
+            assert_eq!(polyglot_key(&game_state), key, "after {:?}", moves);
+        }
+    }
+
+    #[test]
+    fn test_polyglot_key_ignores_invalid_en_passant_targets() {
+        for (fen, target) in [
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 1", 0),
+            ("4k3/8/8/8/8/8/8/4K3 b - - 0 1", 63),
+            ("4k3/8/8/8/8/8/P7/4K3 b - - 0 1", 8),
+        ] {
+            let mut game_state = GameState::from_str(fen).unwrap();
+            let key = polyglot_key(&game_state);
+            game_state.board.en_passant_target = Some(target);
+            assert_eq!(polyglot_key(&game_state), key, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_move_encoding() {
+        let board = Board::from_str("r3k2r/1P6/8/8/8/8/8/R3K2R").unwrap();
+        assert_eq!(
+            decode_move(raw("e1h1"), &board),
+            Move::from_uci("e1g1").unwrap()
+        );
+        assert_eq!(
+            decode_move(raw("e1a1"), &board),
+            Move::from_uci("e1c1").unwrap()
+        );
+        assert_eq!(
+            decode_move(raw("e8h8"), &board),
+            Move::from_uci("e8g8").unwrap()
+        );
+        assert_eq!(
+            decode_move(raw("e8a8"), &board),
+            Move::from_uci("e8c8").unwrap()
+        );
+        // Only the king castles
+        assert_eq!(
+            decode_move(raw("a1e1"), &board),
+            Move::from_uci("a1e1").unwrap()
+        );
+        assert_eq!(
+            decode_move(raw("b7a8") | 4 << 12, &board),
+            Move::from_uci("b7a8q").unwrap()
+        );
+        assert_eq!(
+            decode_move(raw("b7b8") | 1 << 12, &board),
+            Move::from_uci("b7b8n").unwrap()
+        );
+
+        // Encoding gives the moves back
+        for raw_move in [raw("e1h1"), raw("e8a8"), raw("a1e1"), raw("b7a8") | 3 << 12] {
+            assert_eq!(
+                encode_move(&decode_move(raw_move, &board), &board),
+                raw_move
+            );
+        }
+
+        // In Chess960 castling is already written as the king taking its rook
+        let mut board = board;
+        board.chess960 = true;
+        assert_eq!(
+            decode_move(raw("e1h1"), &board),
+            Move::from_uci("e1h1").unwrap()
+        );
+        let mut board = Board::from_str("4k3/8/8/8/8/8/8/1K1R4").unwrap();
+        board.chess960 = true;
+        let castling = Move::from_uci("b1d1").unwrap();
+        assert_eq!(encode_move(&castling, &board), raw("b1d1"));
+    }
+
+    #[test]
+    fn test_book_moves() {
+        let start = polyglot_key(&GameState::new());
+        let mut bytes = Vec::new();
+        bytes.extend(entry_bytes(start, raw("e2e4"), 6));
+        bytes.extend(entry_bytes(start, raw("d2d4"), 3));
+        bytes.extend(entry_bytes(start, raw("g1f3"), 1));
+        // Not legal in the position
+        bytes.extend(entry_bytes(start, raw("e2e5"), 100));
+        bytes.extend(entry_bytes(start + 1, raw("c2c4"), 100));
+        // Out of order, sorted when read
+        bytes.extend(entry_bytes(start - 1, raw("c2c4"), 100));
+        let book = Book::from_bytes(&bytes).unwrap();
+        assert_eq!(book.len(), 6);
+        assert_eq!(book.entries(start).len(), 4);
+        assert!(book.entries(0).is_empty());
+
+        let game_state = GameState::new();
+        let moves = book.moves(&game_state);
+        assert_eq!(
+            moves,
+            vec![
+                (Move::from_uci("e2e4").unwrap(), 6),
+                (Move::from_uci("d2d4").unwrap(), 3),
+                (Move::from_uci("g1f3").unwrap(), 1),
+            ]
+        );
+
+        let e4 = Move::from_uci("e2e4").unwrap();
+        let d4 = Move::from_uci("d2d4").unwrap();
+        let nf3 = Move::from_uci("g1f3").unwrap();
+        assert_eq!(
+            book.choose(&game_state, Selection::Best, 0),
+            Some(e4.clone())
+        );
+        // The weights split the random numbers 6, 3 and 1 ways
+        let weighted = |random| book.choose(&game_state, Selection::Weighted, random);
+        assert_eq!(weighted(0), Some(e4.clone()));
+        assert_eq!(weighted(5), Some(e4.clone()));
+        assert_eq!(weighted(6), Some(d4.clone()));
+        assert_eq!(weighted(8), Some(d4.clone()));
+        assert_eq!(weighted(9), Some(nf3.clone()));
+        assert_eq!(weighted(10), Some(e4));
+        assert_eq!(book.choose(&game_state, Selection::Random, 1), Some(d4));
+        assert_eq!(book.choose(&game_state, Selection::Random, 2), Some(nf3));
+
+        let mut after_e4 = GameState::new();
+        after_e4.make_move(&Move::from_uci("e2e4").unwrap());
+        assert_eq!(book.choose(&after_e4, Selection::Best, 0), None);
+
+        assert_eq!(
+            Book::from_bytes(&bytes[..20]).err(),
+            Some(BookError::InvalidSize(20))
+        );
+    }
+
+    #[test]
+    fn test_selection_names() {
+        for selection in Selection::ALL {
+            assert_eq!(selection.to_string().parse(), Ok(selection));
+        }
+        assert_eq!("best".parse(), Ok(Selection::Best));
+        assert!("first".parse::<Selection>().is_err());
+    }
+}
@@ -0,0 +1,323 @@
+//! Building of a book from the games of PGN files.
+//!
+//! The games are replayed up to a number of plies, and each move played is counted in the
+//! position it was played from, along with the result of the game for the side that played
+//! it. The weight of a move is its score, 2 for a win and 1 for a draw, as in the books built
+//! by Polyglot itself, so the weighted selection plays the moves which scored the most
+//! points, and the best selection the move which scored the most.
+
+use std::collections::HashMap;
+
+use crate::board::piece::Color;
+use crate::game_state::GameResult;
+use crate::pgn::PgnGame;
+
+use super::{encode_move, polyglot_key, Book, Entry};
+
+/// Which games and moves make it into a book.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildOptions {
+    /// The number of plies of each game read.
+    pub max_ply: usize,
+    /// The number of games a move must have been played in to be kept.
+    pub min_games: u32,
+    /// The rating a player must have for their moves to be counted. Players without an Elo
+    /// tag are left out when it is set.
+    pub min_elo: Option<u32>,
+}
+
+impl Default for BuildOptions {
+    fn default() -> Self {
+        BuildOptions {
+            max_ply: 30,
+            min_games: 1,
+            min_elo: None,
+        }
+    }
+}
+
+/// The games a move was played in, from the side of the player of the move.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MoveStats {
+    pub games: u32,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl MoveStats {
+    /// The score of the move, in half points.
+    pub fn score(&self) -> u64 {
+        2 * self.wins as u64 + self.draws as u64
+    }
+}
+
+/// Counts the moves of games and builds a book of them.
+///
+/// # Examples
+///
+/// ```
+/// use ruce::book::build::{BookBuilder, BuildOptions};
+/// use ruce::game_state::GameState;
+/// use ruce::pgn::PgnReader;
+///
+/// let pgn = "1. e4 e5 1-0\n\n1. d4 d5 1/2-1/2\n\n1. e4 c5 0-1\n";
+/// let mut builder = BookBuilder::new(BuildOptions::default());
+/// for game in PgnReader::new(pgn.as_bytes()) {
+///     builder.add_game(&game.unwrap());
+/// }
+/// let book = builder.build();
+/// // 1. e4 scored a win, 1. d4 a draw
+/// let moves = book.moves(&GameState::new());
+/// assert_eq!(moves.len(), 2);
+/// assert_eq!((moves[0].0.to_string().as_str(), moves[0].1), ("e2e4", 2));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct BookBuilder {
+    options: BuildOptions,
+    /// The stats of the moves, by Polyglot key of the position and Polyglot move.
+    moves: HashMap<(u64, u16), MoveStats>,
+    games: usize,
+}
+
+impl BookBuilder {
+    pub fn new(options: BuildOptions) -> Self {
+        BookBuilder {
+            options,
+            ..Default::default()
+        }
+    }
+
+    /// The number of games added so far, ignoring those without a player above the rating.
+    pub fn games(&self) -> usize {
+        self.games
+    }
+
+    /// The number of positions with a move counted so far.
+    pub fn positions(&self) -> usize {
+        let mut keys: Vec<u64> = self.moves.keys().map(|&(key, _)| key).collect();
+        keys.sort_unstable();
+        keys.dedup();
+        keys.len()
+    }
+
+    /// The stats of a move in a position, given by Polyglot key and move.
+    pub fn stats(&self, key: u64, raw_move: u16) -> Option<&MoveStats> {
+        self.moves.get(&(key, raw_move))
+    }
+
+    /// Counts the moves of a game, up to the ply of the options and for the players above the
+    /// rating of the options.
+    pub fn add_game(&mut self, game: &PgnGame) {
+        let rated = |tag: &str| match self.options.min_elo {
+            None => true,
+            Some(min_elo) => game
+                .tag(tag)
+                .and_then(|elo| elo.parse::<u32>().ok())
+                .is_some_and(|elo| elo >= min_elo),
+        };
+        let (white_rated, black_rated) = (rated("WhiteElo"), rated("BlackElo"));
+        if !white_rated && !black_rated {
+            return;
+        }
+        let Ok(mut game_state) = game.starting_position() else {
+            return;
+        };
+        self.games += 1;
+
+        for pgn_move in game.moves.iter().take(self.options.max_ply) {
+            let color = game_state.current_player;
+            let rated = match color {
+                Color::White => white_rated,
+                Color::Black => black_rated,
+            };
+            if rated {
+                let key = polyglot_key(&game_state);
+                let raw_move = encode_move(&pgn_move.chess_move, &game_state.board);
+                let stats = self.moves.entry((key, raw_move)).or_default();
+                stats.games += 1;
+                match (game.result, color) {
+                    (GameResult::WhiteWon, Color::White) | (GameResult::BlackWon, Color::Black) => {
+                        stats.wins += 1
+                    }
+                    (GameResult::WhiteWon, Color::Black) | (GameResult::BlackWon, Color::White) => {
+                        stats.losses += 1
+                    }
+                    (GameResult::Draw, _) => stats.draws += 1,
+                    // Unfinished games only count as played
+                    (GameResult::Undecided, _) => {}
+                }
+            }
+            game_state.make_move(&pgn_move.chess_move);
+        }
+    }
+
+    /// Builds the book of the moves played in at least the games of the options. The moves of
+    /// a position come best first, and their weights are scaled down when a score does not
+    /// fit the 16 bits of a weight.
+    pub fn build(&self) -> Book {
+        let mut moves: Vec<(u64, u16, u64)> = self
+            .moves
+            .iter()
+            .filter(|(_, stats)| stats.games >= self.options.min_games)
+            .map(|(&(key, raw_move), stats)| (key, raw_move, stats.score()))
+            .collect();
+        moves.sort_unstable_by(|a, b| a.0.cmp(&b.0).then(b.2.cmp(&a.2)).then(a.1.cmp(&b.1)));
+
+        let mut entries = Vec::with_capacity(moves.len());
+        for position in moves.chunk_by(|a, b| a.0 == b.0) {
+            let best = position[0].2;
+            let scale = |score: u64| {
+                if best > u16::MAX as u64 {
+                    score * u16::MAX as u64 / best
+                } else {
+                    score
+                }
+            };
+            entries.extend(position.iter().map(|&(key, raw_move, score)| Entry {
+                key,
+                raw_move,
+                weight: scale(score) as u16,
+                learn: 0,
+            }));
+        }
+        Book { entries }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::moves::Move;
+    use crate::game_state::GameState;
+    use crate::pgn::PgnReader;
+
+    const GAMES: &str = r#"[WhiteElo "2500"]
+[BlackElo "2400"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bc4 Nf6 4. O-O 1-0
+
+[WhiteElo "2300"]
+[BlackElo "2600"]
+
+1. e4 c5 2. Nf3 0-1
+
+[WhiteElo "2450"]
+[BlackElo "2450"]
+
+1. d4 d5 1/2-1/2
+
+1. e4 e5 2. Nf3 *
+"#;
+
+    fn build(options: BuildOptions) -> (BookBuilder, Book) {
+        let mut builder = BookBuilder::new(options);
+        for game in PgnReader::new(GAMES.as_bytes()) {
+            builder.add_game(&game.unwrap());
+        }
+        let book = builder.build();
+        (builder, book)
+    }
+
+    fn uci(moves: &[(Move, u16)]) -> Vec<(String, u16)> {
+        moves
+            .iter()
+            .map(|(chess_move, weight)| (chess_move.to_string(), *weight))
+            .collect()
+    }
+
+    fn after(moves: &[&str]) -> GameState {
+        let mut game_state = GameState::new();
+        for uci in moves {
+            game_state.make_move(&Move::from_uci(uci).unwrap());
+        }
+        game_state
+    }
+
+    #[test]
+    fn test_build() {
+        let (builder, book) = build(BuildOptions::default());
+        assert_eq!(builder.games(), 4);
+        let start = GameState::new();
+        let stats = builder.stats(polyglot_key(&start), 12 << 6 | 28).unwrap();
+        assert_eq!(
+            *stats,
+            MoveStats {
+                games: 3,
+                wins: 1,
+                draws: 0,
+                losses: 1,
+            }
+        );
+        assert_eq!(
+            uci(&book.moves(&start)),
+            [("e2e4".to_string(), 2), ("d2d4".to_string(), 1)]
+        );
+        assert_eq!(
+            uci(&book.moves(&after(&["e2e4"]))),
+            [("c7c5".to_string(), 2), ("e7e5".to_string(), 0)]
+        );
+        // Castling is written as the king taking the rook, and read back
+        let castling = after(&["e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "g8f6"]);
+        assert_eq!(
+            book.entries(polyglot_key(&castling))[0].raw_move,
+            4 << 6 | 7
+        );
+        assert_eq!(uci(&book.moves(&castling)), [("e1g1".to_string(), 2)]);
+
+        let bytes = book.to_bytes();
+        assert_eq!(bytes.len(), 16 * book.len());
+        assert_eq!(Book::from_bytes(&bytes).unwrap().entries, book.entries);
+    }
+
+    #[test]
+    fn test_build_options() {
+        let (builder, book) = build(BuildOptions {
+            max_ply: 1,
+            min_games: 2,
+            min_elo: None,
+        });
+        assert_eq!(builder.positions(), 1);
+        assert_eq!(
+            uci(&book.moves(&GameState::new())),
+            [("e2e4".to_string(), 2)]
+        );
+
+        // Only the moves of the players rated 2450 and above
+        let (builder, book) = build(BuildOptions {
+            min_elo: Some(2450),
+            ..Default::default()
+        });
+        assert_eq!(builder.games(), 3);
+        assert_eq!(
+            uci(&book.moves(&GameState::new())),
+            [("e2e4".to_string(), 2), ("d2d4".to_string(), 1)]
+        );
+        assert_eq!(
+            uci(&book.moves(&after(&["e2e4"]))),
+            [("c7c5".to_string(), 2)]
+        );
+    }
+
+    #[test]
+    fn test_weights_are_scaled() {
+        let mut builder = BookBuilder::new(BuildOptions::default());
+        let key = polyglot_key(&GameState::new());
+        let mut stats = |raw_move, wins| {
+            builder.moves.insert(
+                (key, raw_move),
+                MoveStats {
+                    games: wins,
+                    wins,
+                    draws: 0,
+                    losses: 0,
+                },
+            );
+        };
+        stats(12 << 6 | 28, 60_000);
+        stats(11 << 6 | 27, 30_000);
+        let book = builder.build();
+        let weights: Vec<u16> = book.entries(key).iter().map(|entry| entry.weight).collect();
+        assert_eq!(weights, [u16::MAX, u16::MAX / 2]);
+    }
+}
@@ -0,0 +1,243 @@
+//! Module containing the classification of openings by ECO (Encyclopaedia of Chess Openings)
+//! code, e.g. `B90` for the Najdorf Variation of the Sicilian Defense.
+//!
+//! The openings are defined by their moves in a table bundled with the engine. Each opening
+//! is keyed by the Polyglot key of the position its moves reach, so a game is classified by
+//! the position it reaches, whatever the order of its moves: 1. d4 Nf6 2. c4 e6 3. Nc3 Bb4
+//! and 1. c4 e6 2. Nc3 Nf6 3. d4 Bb4 are both a Nimzo-Indian Defense.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use thiserror::Error;
+
+use crate::book::polyglot_key;
+use crate::game_state::GameState;
+use crate::pgn::PgnGame;
+use crate::san::{parse_san, SanError};
+
+/// The openings bundled with the engine.
+const OPENINGS: &str = include_str!("eco/openings.tsv");
+
+/// An error in a table of openings, with the line where it was found.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum EcoError {
+    #[error("line {0}: expected an ECO code, a name and moves separated by tabs")]
+    InvalidLine(usize),
+    #[error("line {line}: move {san}: {error}")]
+    InvalidMove {
+        line: usize,
+        san: String,
+        error: SanError,
+    },
+}
+
+/// An opening of the table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Opening {
+    /// The ECO code, a letter from A to E followed by two digits.
+    pub eco: String,
+    /// The name of the opening, then of the variation after a colon.
+    pub name: String,
+    /// The moves defining the opening, in SAN with move numbers.
+    pub moves: String,
+}
+
+/// Classifies games by the openings of a table.
+#[derive(Debug, Clone)]
+pub struct EcoClassifier {
+    openings: Vec<Opening>,
+    /// The index of the opening of each position, by Polyglot key.
+    positions: HashMap<u64, usize>,
+}
+
+impl EcoClassifier {
+    /// The classifier of the openings bundled with the engine.
+    ///
+    /// The bundled table is partial: it holds the main lines of 177 of the 500 ECO codes, so
+    /// many games are only classified by an earlier, more general opening, e.g. `B90` rather
+    /// than `B96`. A complete table can be read with `from_table`.
+    pub fn bundled() -> &'static EcoClassifier {
+        static CLASSIFIER: OnceLock<EcoClassifier> = OnceLock::new();
+        CLASSIFIER
+            .get_or_init(|| EcoClassifier::from_table(OPENINGS).expect("the openings are valid"))
+    }
+
+    /// Reads a table of openings, with one opening per line: the ECO code, the name and the
+    /// moves from the starting position, separated by tabs. Empty lines and lines starting
+    /// with `#` are ignored. When openings reach the same position, the first one is kept.
+    pub fn from_table(table: &str) -> Result<EcoClassifier, EcoError> {
+        let mut openings = Vec::new();
+        let mut positions = HashMap::new();
+        for (i, line) in table.lines().enumerate() {
+            let line_number = i + 1;
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let [eco, name, moves] = line.split('\t').collect::<Vec<_>>()[..] else {
+                return Err(EcoError::InvalidLine(line_number));
+            };
+
+            let mut game_state = GameState::new();
+            // Move numbers such as `1.` or `1...` are skipped
+            for san in moves
+                .split_whitespace()
+                .filter(|token| !token.ends_with('.'))
+            {
+                let chess_move =
+                    parse_san(&game_state, san).map_err(|error| EcoError::InvalidMove {
+                        line: line_number,
+                        san: san.to_string(),
+                        error,
+                    })?;
+                game_state.make_move(&chess_move);
+            }
+            positions
+                .entry(polyglot_key(&game_state))
+                .or_insert(openings.len());
+            openings.push(Opening {
+                eco: eco.to_string(),
+                name: name.to_string(),
+                moves: moves.to_string(),
+            });
+        }
+        Ok(EcoClassifier {
+            openings,
+            positions,
+        })
+    }
+
+    /// The openings of the table, in the order of the table.
+    pub fn openings(&self) -> &[Opening] {
+        &self.openings
+    }
+
+    /// Classifies a game from its history: the opening is the one of the last position of
+    /// the game found in the table, or `None` when the game never reaches one.
+    pub fn classify(&self, game_state: &GameState) -> Option<&Opening> {
+        let mut game_state = game_state.clone();
+        loop {
+            if let Some(&index) = self.positions.get(&polyglot_key(&game_state)) {
+                return Some(&self.openings[index]);
+            }
+            game_state.unmake_move()?;
+        }
+    }
+
+    /// Classifies the main line of a PGN game.
+    pub fn classify_game(&self, game: &PgnGame) -> Option<&Opening> {
+        let mut game_state = game.starting_position().ok()?;
+        for pgn_move in &game.moves {
+            game_state.make_move(&pgn_move.chess_move);
+        }
+        self.classify(&game_state)
+    }
+
+    /// Sets the `ECO` and `Opening` tags of a PGN game to its opening, returning the opening,
+    /// or `None` when the game is left untouched.
+    pub fn tag_game(&self, game: &mut PgnGame) -> Option<&Opening> {
+        let opening = self.classify_game(game)?;
+        game.set_tag("ECO", &opening.eco);
+        game.set_tag("Opening", &opening.name);
+        Some(opening)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::moves::Move;
+    use std::str::FromStr;
+
+    fn classify(pgn: &str) -> Option<(&'static str, &'static str)> {
+        let game = PgnGame::from_str(pgn).unwrap();
+        EcoClassifier::bundled()
+            .classify_game(&game)
+            .map(|opening| (opening.eco.as_str(), opening.name.as_str()))
+    }
+
+    #[test]
+    fn test_bundled_openings() {
+        let classifier = EcoClassifier::bundled();
+        assert!(classifier.openings().len() > 200);
+        for opening in classifier.openings() {
+            let eco = opening.eco.as_bytes();
+            assert!(
+                eco.len() == 3 && (b'A'..=b'E').contains(&eco[0]),
+                "{}",
+                opening.eco
+            );
+            assert!(eco[1..].iter().all(u8::is_ascii_digit), "{}", opening.eco);
+        }
+        // Every opening has its own position
+        assert_eq!(classifier.positions.len(), classifier.openings().len());
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(
+            classify("1. e4 c5 2. Nf3 d6 3. d4 cxd4 4. Nxd4 Nf6 5. Nc3 a6 6. h3 e5 *"),
+            Some(("B90", "Sicilian Defense: Najdorf Variation"))
+        );
+        // The deepest opening
+        assert_eq!(
+            classify(
+                "1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 O-O \
+                 8. c3 d5 9. exd5 Nxd5 *"
+            ),
+            Some(("C89", "Ruy Lopez: Marshall Attack"))
+        );
+        // Transpositions, including a double step after which no pawn can take en passant
+        assert_eq!(
+            classify("1. c4 e6 2. Nc3 Nf6 3. d4 Bb4 4. Qc2 *"),
+            Some(("E32", "Nimzo-Indian Defense: Classical Variation"))
+        );
+        assert_eq!(
+            classify("1. Nf3 d5 2. d4 *"),
+            Some(("D02", "Queen's Pawn Game: Zukertort Variation"))
+        );
+        assert_eq!(classify("1. h4 e5 *"), None);
+        assert_eq!(
+            classify("[FEN \"4k3/8/8/8/8/8/8/4K3 w - - 0 1\"]\n\n1. Kd2 *"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_classify_game_state() {
+        let mut game_state = GameState::new();
+        for uci in ["d2d4", "g8f6", "c2c4", "g7g6", "b1c3", "d7d5", "c4d5"] {
+            game_state.make_move(&Move::from_uci(uci).unwrap());
+        }
+        let classifier = EcoClassifier::bundled();
+        assert_eq!(classifier.classify(&game_state).unwrap().eco, "D80");
+        // The history is left as it was
+        assert_eq!(game_state.history.len(), 7);
+    }
+
+    #[test]
+    fn test_tag_game() {
+        let mut game = PgnGame::from_str("[ECO \"?\"]\n\n1. e4 e6 2. d4 d5 3. e5 *").unwrap();
+        EcoClassifier::bundled().tag_game(&mut game);
+        assert_eq!(game.tag("ECO"), Some("C02"));
+        assert_eq!(
+            game.tag("Opening"),
+            Some("French Defense: Advance Variation")
+        );
+        assert_eq!(game.tags.len(), 2);
+    }
+
+    #[test]
+    fn test_invalid_table() {
+        assert_eq!(
+            EcoClassifier::from_table("A00\tPolish Opening").err(),
+            Some(EcoError::InvalidLine(1))
+        );
+        let error = EcoClassifier::from_table("# comment\n\nC20\tKing's Pawn\t1. e4 e5 2. Nf6")
+            .err()
+            .unwrap();
+        assert!(matches!(error, EcoError::InvalidMove { line: 3, ref san, .. } if san == "Nf6"));
+        let classifier = EcoClassifier::from_table("B00\tKing's Pawn Game\t1. e4").unwrap();
+        assert_eq!(classifier.openings()[0].moves, "1. e4");
+    }
+}
@@ -0,0 +1,411 @@
+//! Module containing EPD (Extended Position Description) related logic and structures.
+//!
+//! An EPD record is made of the first four fields of a FEN string (piece placement, active
+//! color, castling availability and en passant target) followed by a list of operations, each
+//! one an opcode with its operands and terminated by a semicolon, e.g.
+//! `bm Nf3; id "WAC.001";`. Test suites such as WAC and STS are distributed in this format.
+
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::board::moves::Move;
+use crate::game_state::{GameState, ParseFenError};
+use crate::san::{move_to_san, parse_san, SanError};
+
+/// An EPD record: a position and its operations.
+#[derive(Debug, Clone)]
+pub struct Epd {
+    /// The position described by the record. The clocks come from the `hmvc` and `fmvn`
+    /// operations when present.
+    pub game_state: GameState,
+    /// The operations of the record in order, each one an opcode and its operands.
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ParseEpdError {
+    #[error("Invalid EPD position: {0}")]
+    InvalidPosition(#[from] ParseFenError),
+    #[error("Invalid EPD opcode: {0}")]
+    InvalidOpcode(String),
+    #[error("Missing semicolon after EPD operation: {0}")]
+    MissingSemicolon(String),
+    #[error("Unterminated string operand in EPD")]
+    UnterminatedString,
+    #[error("Invalid operand for EPD opcode {0}: {1}")]
+    InvalidOperand(String, String),
+}
+
+impl Epd {
+    /// Creates a record for a position, without any operation.
+    pub fn from_game_state(game_state: &GameState) -> Self {
+        Epd {
+            game_state: game_state.clone(),
+            operations: Vec::new(),
+        }
+    }
+
+    /// Returns the operands of an opcode, if the record has it.
+    pub fn operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations
+            .iter()
+            .find(|(name, _)| name == opcode)
+            .map(|(_, operands)| operands.as_slice())
+    }
+
+    /// Sets the operands of an opcode, replacing the previous ones if the record had it.
+    pub fn set_operation(&mut self, opcode: &str, operands: Vec<String>) {
+        match self.operations.iter_mut().find(|(name, _)| name == opcode) {
+            Some((_, existing)) => *existing = operands,
+            None => self.operations.push((opcode.to_string(), operands)),
+        }
+    }
+
+    /// Removes an opcode from the record.
+    pub fn remove_operation(&mut self, opcode: &str) {
+        self.operations.retain(|(name, _)| name != opcode);
+    }
+
+    /// Returns the best moves (`bm`) of the position.
+    pub fn best_moves(&self) -> Result<Vec<Move>, SanError> {
+        self.san_moves("bm")
+    }
+
+    /// Sets the best moves (`bm`) of the position.
+    pub fn set_best_moves(&mut self, moves: &[Move]) {
+        let operands = moves
+            .iter()
+            .map(|chess_move| move_to_san(&self.game_state, chess_move))
+            .collect();
+        self.set_operation("bm", operands);
+    }
+
+    /// Returns the moves to avoid (`am`) in the position.
+    pub fn avoid_moves(&self) -> Result<Vec<Move>, SanError> {
+        self.san_moves("am")
+    }
+
+    /// Sets the moves to avoid (`am`) in the position.
+    pub fn set_avoid_moves(&mut self, moves: &[Move]) {
+        let operands = moves
+            .iter()
+            .map(|chess_move| move_to_san(&self.game_state, chess_move))
+            .collect();
+        self.set_operation("am", operands);
+    }
+
+    /// Returns the identifier (`id`) of the position.
+    pub fn id(&self) -> Option<&str> {
+        self.string_operand("id")
+    }
+
+    /// Sets the identifier (`id`) of the position.
+    pub fn set_id(&mut self, id: &str) {
+        self.set_operation("id", vec![id.to_string()]);
+    }
+
+    /// Returns the analysis count depth (`acd`), the depth searched in plies.
+    pub fn acd(&self) -> Result<Option<u32>, ParseEpdError> {
+        self.number_operand("acd")
+    }
+
+    /// Sets the analysis count depth (`acd`).
+    pub fn set_acd(&mut self, depth: u32) {
+        self.set_operation("acd", vec![depth.to_string()]);
+    }
+
+    /// Returns the centipawn evaluation (`ce`), from the point of view of the side to move.
+    pub fn ce(&self) -> Result<Option<i32>, ParseEpdError> {
+        self.number_operand("ce")
+    }
+
+    /// Sets the centipawn evaluation (`ce`).
+    pub fn set_ce(&mut self, centipawns: i32) {
+        self.set_operation("ce", vec![centipawns.to_string()]);
+    }
+
+    /// Returns the predicted variation (`pv`), each move played after the previous one.
+    pub fn pv(&self) -> Result<Vec<Move>, SanError> {
+        let mut game_state = self.game_state.clone();
+        let mut moves = Vec::new();
+        for san in self.operation("pv").unwrap_or_default() {
+            let chess_move = parse_san(&game_state, san)?;
+            game_state.make_move(&chess_move);
+            moves.push(chess_move);
+        }
+        Ok(moves)
+    }
+
+    /// Sets the predicted variation (`pv`).
+    pub fn set_pv(&mut self, moves: &[Move]) {
+        let mut game_state = self.game_state.clone();
+        let mut operands = Vec::new();
+        for chess_move in moves {
+            operands.push(move_to_san(&game_state, chess_move));
+            game_state.make_move(chess_move);
+        }
+        self.set_operation("pv", operands);
+    }
+
+    /// Returns the comment `c0` to `c9` with the given number.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `number` is greater than 9.
+    pub fn comment(&self, number: u8) -> Option<&str> {
+        assert!(number <= 9, "EPD comments go from c0 to c9");
+        self.string_operand(&format!("c{}", number))
+    }
+
+    /// Sets the comment `c0` to `c9` with the given number.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `number` is greater than 9.
+    pub fn set_comment(&mut self, number: u8, comment: &str) {
+        assert!(number <= 9, "EPD comments go from c0 to c9");
+        self.set_operation(&format!("c{}", number), vec![comment.to_string()]);
+    }
+
+    fn san_moves(&self, opcode: &str) -> Result<Vec<Move>, SanError> {
+        self.operation(opcode)
+            .unwrap_or_default()
+            .iter()
+            .map(|san| parse_san(&self.game_state, san))
+            .collect()
+    }
+
+    fn string_operand(&self, opcode: &str) -> Option<&str> {
+        self.operation(opcode)
+            .and_then(|operands| operands.first())
+            .map(String::as_str)
+    }
+
+    fn number_operand<T: FromStr>(&self, opcode: &str) -> Result<Option<T>, ParseEpdError> {
+        self.string_operand(opcode)
+            .map(|operand| {
+                operand.parse().map_err(|_| {
+                    ParseEpdError::InvalidOperand(opcode.to_string(), operand.to_string())
+                })
+            })
+            .transpose()
+    }
+}
+
+impl FromStr for Epd {
+    type Err = ParseEpdError;
+
+    /// Parses an EPD record.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ruce::board::moves::Move;
+    /// use ruce::epd::Epd;
+    /// use std::str::FromStr;
+    ///
+    /// let epd = Epd::from_str(
+    ///     "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";",
+    /// )
+    /// .unwrap();
+    /// assert_eq!(epd.id(), Some("WAC.001"));
+    /// assert_eq!(epd.best_moves(), Ok(vec![Move::new(22, 46)]));
+    /// ```
+    fn from_str(epd: &str) -> Result<Self, Self::Err> {
+        let mut rest = epd.trim();
+        let mut position = Vec::new();
+        for _ in 0..4 {
+            let (field, remainder) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            position.push(field);
+            rest = remainder.trim_start();
+        }
+
+        let mut record = Epd {
+            game_state: GameState::from_str(&position.join(" "))?,
+            operations: parse_operations(rest)?,
+        };
+        if let Some(half_move_clock) = record.number_operand("hmvc")? {
+            record.game_state.half_move_clock = half_move_clock;
+        }
+        if let Some(full_move_number) = record.number_operand("fmvn")? {
+            record.game_state.turn = full_move_number;
+        }
+        Ok(record)
+    }
+}
+
+/// Parses the operations following the position fields.
+fn parse_operations(text: &str) -> Result<Vec<(String, Vec<String>)>, ParseEpdError> {
+    let mut operations = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            return Ok(operations);
+        }
+
+        let opcode: String =
+            std::iter::from_fn(|| chars.next_if(|c| !c.is_whitespace() && *c != ';')).collect();
+        if !opcode.starts_with(|c: char| c.is_ascii_alphabetic())
+            || !opcode
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return Err(ParseEpdError::InvalidOpcode(opcode));
+        }
+
+        let mut operands = Vec::new();
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            match chars.next() {
+                Some(';') => break,
+                Some('"') => {
+                    let mut operand = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('"') => break,
+                            Some('\\') => operand.extend(chars.next()),
+                            Some(c) => operand.push(c),
+                            None => return Err(ParseEpdError::UnterminatedString),
+                        }
+                    }
+                    operands.push(operand);
+                }
+                Some(c) => {
+                    let mut operand = c.to_string();
+                    operand.extend(std::iter::from_fn(|| {
+                        chars.next_if(|c| !c.is_whitespace() && *c != ';')
+                    }));
+                    operands.push(operand);
+                }
+                None => return Err(ParseEpdError::MissingSemicolon(opcode)),
+            }
+        }
+        operations.push((opcode, operands));
+    }
+}
+
+/// Writes the record as a single EPD line.
+impl fmt::Display for Epd {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let fen = self.game_state.to_fen();
+        let position: Vec<&str> = fen.split(' ').take(4).collect();
+        write!(f, "{}", position.join(" "))?;
+
+        for (opcode, operands) in &self.operations {
+            write!(f, " {}", opcode)?;
+            for operand in operands {
+                if is_string_opcode(opcode)
+                    || operand.is_empty()
+                    || operand.contains([' ', ';', '"'])
+                {
+                    write!(
+                        f,
+                        " \"{}\"",
+                        operand.replace('\\', "\\\\").replace('"', "\\\"")
+                    )?;
+                } else {
+                    write!(f, " {}", operand)?;
+                }
+            }
+            write!(f, ";")?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks whether the operands of an opcode are always written as quoted strings.
+fn is_string_opcode(opcode: &str) -> bool {
+    matches!(
+        opcode,
+        "id" | "c0" | "c1" | "c2" | "c3" | "c4" | "c5" | "c6" | "c7" | "c8" | "c9"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::piece::PieceType;
+
+    #[test]
+    fn test_parse_operations() {
+        let epd = Epd::from_str(
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - \
+             bm Bb5 Bc4; am a3; acd 12; ce -35; pv Bb5 a6 Ba4; c0 \"Open; game\"; hmvc 2; fmvn 3;",
+        )
+        .unwrap();
+        assert_eq!(
+            epd.best_moves(),
+            Ok(vec![Move::new(5, 33), Move::new(5, 26)])
+        );
+        assert_eq!(epd.avoid_moves(), Ok(vec![Move::new(8, 16)]));
+        assert_eq!(epd.acd(), Ok(Some(12)));
+        assert_eq!(epd.ce(), Ok(Some(-35)));
+        assert_eq!(
+            epd.pv(),
+            Ok(vec![Move::new(5, 33), Move::new(48, 40), Move::new(33, 24)])
+        );
+        assert_eq!(epd.comment(0), Some("Open; game"));
+        assert_eq!(epd.comment(1), None);
+        assert_eq!(epd.id(), None);
+        assert_eq!(epd.game_state.half_move_clock, 2);
+        assert_eq!(epd.game_state.turn, 3);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            Epd::from_str("8/8/8/8/8/8/8/8 w - - bm Kf1").unwrap_err(),
+            ParseEpdError::MissingSemicolon("bm".to_string())
+        );
+        assert_eq!(
+            Epd::from_str("8/8/8/8/8/8/8/8 w - - id \"open;").unwrap_err(),
+            ParseEpdError::UnterminatedString
+        );
+        assert_eq!(
+            Epd::from_str("8/8/8/8/8/8/8/8 w - - acd deep;")
+                .unwrap()
+                .acd(),
+            Err(ParseEpdError::InvalidOperand(
+                "acd".to_string(),
+                "deep".to_string()
+            ))
+        );
+        assert_eq!(
+            Epd::from_str("8/8/8/8/8/8/8/8 x - -").unwrap_err(),
+            ParseEpdError::InvalidPosition(ParseFenError::InvalidActiveColor("x".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_write_and_read_back() {
+        let game_state = GameState::from_str("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let promotion = Move {
+            initial_square: 49,
+            target_square: 57,
+            promotion: Some(PieceType::Queen),
+        };
+        let mut epd = Epd::from_game_state(&game_state);
+        epd.set_best_moves(std::slice::from_ref(&promotion));
+        epd.set_id("promotion \"1\"");
+        epd.set_acd(5);
+        epd.set_ce(900);
+        epd.set_pv(&[promotion.clone(), Move::new(60, 52)]);
+        epd.set_comment(9, "1-0");
+        let written = epd.to_string();
+        assert_eq!(
+            written,
+            "4k3/1P6/8/8/8/8/8/4K3 w - - bm b8=Q+; id \"promotion \\\"1\\\"\"; acd 5; ce 900; \
+             pv b8=Q+ Ke7; c9 \"1-0\";"
+        );
+
+        let read = Epd::from_str(&written).unwrap();
+        assert_eq!(read.operations, epd.operations);
+        assert_eq!(read.best_moves(), Ok(vec![promotion]));
+        assert_eq!(read.id(), Some("promotion \"1\""));
+        assert_eq!(read.comment(9), Some("1-0"));
+    }
+}
@@ -0,0 +1,588 @@
+//! Module containing the static evaluation, scoring a position without searching it.
+//!
+//! Every term of the evaluation is a `Score`, a pair of middlegame and endgame values. The
+//! two values are blended according to the phase of the game, computed from the material
+//! left on the board: a position with all its pieces is scored with the middlegame values
+//! only, a position with kings and pawns only with the endgame values.
+//!
+//! The pawn structure terms are computed by the `pawns` module. An `Evaluator` caches them in
+//! a pawn hash table, the free functions compute them on every call. The mobility and king
+//! safety terms can be switched off with `EvalOptions`. `evaluate_trace` explains a score by
+//! returning every term of the evaluation separately.
+//!
+//! The weights of every term are `EvalParams`, which can be tuned and loaded from a file.
+//!
+//! With the `nnue` feature, the `nnue` module evaluates positions with a neural network
+//! instead. The search falls back to this handcrafted evaluation when no network is loaded.
+
+mod king_safety;
+mod mobility;
+#[cfg(feature = "nnue")]
+pub mod nnue;
+pub mod params;
+mod pawns;
+
+use std::fmt;
+use std::ops::{Add, AddAssign, Mul, Neg, Sub};
+
+use crate::board::piece::{Color, Piece, PieceType};
+use crate::board::Board;
+use crate::zobrist::pawn_key;
+
+use self::king_safety::king_safety;
+use self::mobility::{mobility, pawn_attacks, piece_targets};
+use self::params::EvalParams;
+use self::pawns::{evaluate_pawns, king_proximity, pawn_bitboards, PawnEntry, PawnTable};
+
+/// The number of entries of the pawn hash table of an `Evaluator`, as a power of two.
+const PAWN_TABLE_BITS: u32 = 14;
+
+/// The phase of a position with all its pieces on the board.
+pub const MAX_PHASE: i32 = 24;
+
+/// A middlegame and an endgame value, in centipawns.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Score {
+    pub mg: i32,
+    pub eg: i32,
+}
+
+impl Score {
+    pub const ZERO: Score = Score { mg: 0, eg: 0 };
+
+    pub const fn new(mg: i32, eg: i32) -> Self {
+        Score { mg, eg }
+    }
+
+    /// Blends the middlegame and endgame values, `phase` going from 0 for a bare endgame to
+    /// `MAX_PHASE` for a full board.
+    pub fn taper(self, phase: i32) -> i32 {
+        let phase = phase.clamp(0, MAX_PHASE);
+        (self.mg * phase + self.eg * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+}
+
+impl Add for Score {
+    type Output = Score;
+
+    fn add(self, other: Score) -> Score {
+        Score::new(self.mg + other.mg, self.eg + other.eg)
+    }
+}
+
+impl AddAssign for Score {
+    fn add_assign(&mut self, other: Score) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Score {
+    type Output = Score;
+
+    fn sub(self, other: Score) -> Score {
+        Score::new(self.mg - other.mg, self.eg - other.eg)
+    }
+}
+
+impl Mul<i32> for Score {
+    type Output = Score;
+
+    fn mul(self, factor: i32) -> Score {
+        Score::new(self.mg * factor, self.eg * factor)
+    }
+}
+
+impl Neg for Score {
+    type Output = Score;
+
+    fn neg(self) -> Score {
+        Score::new(-self.mg, -self.eg)
+    }
+}
+
+/// The switches of the optional terms of the evaluation, all on by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EvalOptions {
+    /// Score the number of squares the pieces can move to.
+    pub mobility: bool,
+    /// Score the pawn shield, the open files and the attackers around the kings.
+    pub king_safety: bool,
+}
+
+impl Default for EvalOptions {
+    fn default() -> Self {
+        EvalOptions {
+            mobility: true,
+            king_safety: true,
+        }
+    }
+}
+
+/// The terms of the evaluation, as reported by an `EvalTrace`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Term {
+    /// The value of the pieces.
+    Material,
+    /// The piece-square table bonuses.
+    Psqt,
+    /// The pawn structure, including the distance of the kings to the passed pawns.
+    Pawns,
+    /// The number of squares the pieces can move to.
+    Mobility,
+    /// The pawn shield, open files and attackers around the king.
+    KingSafety,
+    /// The bonus of the side to move.
+    Tempo,
+}
+
+impl Term {
+    /// Every term, in the order of the rows of the trace table.
+    pub const ALL: [Term; 6] = [
+        Term::Material,
+        Term::Psqt,
+        Term::Pawns,
+        Term::Mobility,
+        Term::KingSafety,
+        Term::Tempo,
+    ];
+
+    /// The name of the term, as printed in the trace table.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Term::Material => "Material",
+            Term::Psqt => "PSQT",
+            Term::Pawns => "Pawns",
+            Term::Mobility => "Mobility",
+            Term::KingSafety => "King safety",
+            Term::Tempo => "Tempo",
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            Term::Material => 0,
+            Term::Psqt => 1,
+            Term::Pawns => 2,
+            Term::Mobility => 3,
+            Term::KingSafety => 4,
+            Term::Tempo => 5,
+        }
+    }
+}
+
+/// The evaluation of a position term by term, explaining its score.
+///
+/// Each term is split by color, white first, each color's value being from its own point of
+/// view, and by phase with the middlegame and endgame values of a `Score`. Terms switched off
+/// in the `EvalOptions` are zero.
+///
+/// # Examples
+///
+/// ```
+/// use ruce::board::piece::Color;
+/// use ruce::board::Board;
+/// use ruce::eval::{evaluate_trace, Term};
+/// use std::str::FromStr;
+///
+/// let board = Board::from_str("4k3/8/8/8/8/8/4P3/4K3").unwrap();
+/// let trace = evaluate_trace(&board, Color::Black);
+/// let [white, black] = trace.term(Term::Material);
+/// assert!(white.eg > 0 && black.eg == 0);
+/// assert_eq!(trace.relative_score(), -trace.score);
+/// println!("{}", trace);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvalTrace {
+    terms: [[Score; 2]; 6],
+    /// The phase of the position, from 0 to `MAX_PHASE`.
+    pub phase: i32,
+    /// The side to move, who gets the tempo bonus.
+    pub side_to_move: Color,
+    /// The tapered score, from the point of view of white.
+    pub score: i32,
+}
+
+impl EvalTrace {
+    /// Returns the values of a term for white and black, each from its own point of view.
+    pub fn term(&self, term: Term) -> [Score; 2] {
+        self.terms[term.index()]
+    }
+
+    /// Returns the sum of every term, from the point of view of white, before tapering.
+    pub fn total(&self) -> Score {
+        self.terms
+            .iter()
+            .fold(Score::ZERO, |total, [white, black]| total + *white - *black)
+    }
+
+    /// Returns the tapered score from the point of view of the side to move.
+    pub fn relative_score(&self) -> i32 {
+        from_side_to_move(self.score, self.side_to_move)
+    }
+}
+
+impl fmt::Display for EvalTrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<12}|{:>14} |{:>14} |{:>14}",
+            "Term", "White", "Black", "Total"
+        )?;
+        writeln!(
+            f,
+            "{:<12}|{:>7}{:>7} |{:>7}{:>7} |{:>7}{:>7}",
+            "", "mg", "eg", "mg", "eg", "mg", "eg"
+        )?;
+        writeln!(f, "{}", "-".repeat(58))?;
+        for term in Term::ALL {
+            let [white, black] = self.term(term);
+            let total = white - black;
+            writeln!(
+                f,
+                "{:<12}|{:>7}{:>7} |{:>7}{:>7} |{:>7}{:>7}",
+                term.name(),
+                white.mg,
+                white.eg,
+                black.mg,
+                black.eg,
+                total.mg,
+                total.eg
+            )?;
+        }
+        writeln!(f, "{}", "-".repeat(58))?;
+        let total = self.total();
+        writeln!(
+            f,
+            "{:<12}|{:>14} |{:>14} |{:>7}{:>7}",
+            "Total", "", "", total.mg, total.eg
+        )?;
+        writeln!(f, "Phase: {}/{}", self.phase, MAX_PHASE)?;
+        writeln!(f, "Score: {} cp (white's point of view)", self.score)?;
+        write!(f, "Score: {} cp (side to move)", self.relative_score())
+    }
+}
+
+/// Evaluates positions, caching the pawn structure terms in a pawn hash table.
+#[derive(Debug, Clone)]
+pub struct Evaluator {
+    /// The switches of the optional terms.
+    pub options: EvalOptions,
+    params: EvalParams,
+    pawn_table: PawnTable,
+}
+
+impl Evaluator {
+    /// Creates an evaluator using the default parameters, with an empty pawn hash table.
+    pub fn new() -> Self {
+        Evaluator::with_params(EvalParams::DEFAULT)
+    }
+
+    /// Creates an evaluator using the given parameters, with an empty pawn hash table.
+    pub fn with_params(params: EvalParams) -> Self {
+        Evaluator {
+            options: EvalOptions::default(),
+            params,
+            pawn_table: PawnTable::new(PAWN_TABLE_BITS),
+        }
+    }
+
+    /// Returns the parameters of the evaluation.
+    pub fn params(&self) -> &EvalParams {
+        &self.params
+    }
+
+    /// Replaces the parameters of the evaluation, emptying the pawn hash table which holds
+    /// scores computed with the previous ones.
+    pub fn set_params(&mut self, params: EvalParams) {
+        self.params = params;
+        self.pawn_table.clear();
+    }
+
+    /// Scores the position in centipawns from the point of view of `side_to_move`.
+    pub fn evaluate(&mut self, board: &Board, side_to_move: Color) -> i32 {
+        self.trace(board, side_to_move).relative_score()
+    }
+
+    /// Evaluates the position term by term.
+    pub fn trace(&mut self, board: &Board, side_to_move: Color) -> EvalTrace {
+        let pawns = self.pawn_table.probe(board, &self.params);
+        evaluate_with(board, side_to_move, &pawns, &self.params, self.options)
+    }
+
+    /// Empties the pawn hash table.
+    pub fn clear(&mut self) {
+        self.pawn_table.clear();
+    }
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Evaluator::new()
+    }
+}
+
+/// Scores the position in centipawns from the point of view of `side_to_move`.
+///
+/// # Examples
+///
+/// ```
+/// use ruce::board::piece::Color;
+/// use ruce::board::Board;
+/// use ruce::eval::evaluate;
+/// use std::str::FromStr;
+///
+/// // White is a rook up
+/// let board = Board::from_str("4k3/8/8/8/8/8/8/R3K3").unwrap();
+/// assert!(evaluate(&board, Color::White) > 400);
+/// assert!(evaluate(&board, Color::Black) < -400);
+/// ```
+pub fn evaluate(board: &Board, side_to_move: Color) -> i32 {
+    evaluate_trace(board, side_to_move).relative_score()
+}
+
+/// Evaluates the position term by term, with the default parameters and every term switched
+/// on.
+pub fn evaluate_trace(board: &Board, side_to_move: Color) -> EvalTrace {
+    evaluate_trace_with(board, side_to_move, &EvalParams::DEFAULT)
+}
+
+/// Evaluates the position term by term with the given parameters, every term switched on.
+pub fn evaluate_trace_with(board: &Board, side_to_move: Color, params: &EvalParams) -> EvalTrace {
+    let pawns = evaluate_pawns(board, pawn_key(board), params);
+    evaluate_with(board, side_to_move, &pawns, params, EvalOptions::default())
+}
+
+fn from_side_to_move(score: i32, side_to_move: Color) -> i32 {
+    match side_to_move {
+        Color::White => score,
+        Color::Black => -score,
+    }
+}
+
+/// Evaluates the position with the given pawn structure terms.
+fn evaluate_with(
+    board: &Board,
+    side_to_move: Color,
+    pawn_entry: &PawnEntry,
+    params: &EvalParams,
+    options: EvalOptions,
+) -> EvalTrace {
+    let mut terms = [[Score::ZERO; 2]; 6];
+    let mut phase = 0;
+
+    for (square, piece) in board.squares.iter().enumerate() {
+        let Some(piece) = piece else {
+            continue;
+        };
+        let color = color_index(piece.color);
+        terms[Term::Material.index()][color] += params.material[piece_index(piece.piece_type)];
+        terms[Term::Psqt.index()][color] += psqt_score(piece, square as u8, params);
+        phase += phase_weight(piece.piece_type);
+    }
+
+    let proximity = king_proximity(board, pawn_entry.passed, params);
+    terms[Term::Pawns.index()] = [
+        pawn_entry.score[0] + proximity[0],
+        pawn_entry.score[1] + proximity[1],
+    ];
+
+    if options.mobility || options.king_safety {
+        let pieces = piece_targets(board);
+        let pawn_bitboards = pawn_bitboards(board);
+        if options.mobility {
+            let attacks = [
+                pawn_attacks(pawn_bitboards[0], Color::White),
+                pawn_attacks(pawn_bitboards[1], Color::Black),
+            ];
+            terms[Term::Mobility.index()] = mobility(&pieces, attacks, params);
+        }
+        if options.king_safety {
+            terms[Term::KingSafety.index()] = king_safety(board, &pieces, pawn_bitboards, params);
+        }
+    }
+
+    terms[Term::Tempo.index()][color_index(side_to_move)] = params.tempo;
+
+    let mut trace = EvalTrace {
+        terms,
+        phase: phase.min(MAX_PHASE),
+        side_to_move,
+        score: 0,
+    };
+    trace.score = trace.total().taper(trace.phase);
+    trace
+}
+
+/// The material value of a piece type, in centipawns, as used to order captures.
+pub fn piece_value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::Pawn => 100,
+        PieceType::Knight => 320,
+        PieceType::Bishop => 330,
+        PieceType::Rook => 500,
+        PieceType::Queen => 900,
+        PieceType::King => 0,
+    }
+}
+
+/// The index of a color in the arrays of the evaluation, white first.
+pub fn color_index(color: Color) -> usize {
+    match color {
+        Color::White => 0,
+        Color::Black => 1,
+    }
+}
+
+/// The index of a piece type in the tables of the evaluation, pawn first.
+fn piece_index(piece_type: PieceType) -> usize {
+    match piece_type {
+        PieceType::Pawn => 0,
+        PieceType::Knight => 1,
+        PieceType::Bishop => 2,
+        PieceType::Rook => 3,
+        PieceType::Queen => 4,
+        PieceType::King => 5,
+    }
+}
+
+/// The piece-square table bonus of a piece standing on `square`.
+fn psqt_score(piece: &Piece, square: u8, params: &EvalParams) -> Score {
+    // The tables are written from white's point of view with the eighth rank first, so white
+    // squares are mirrored vertically and black squares read as they are
+    let index = match piece.color {
+        Color::White => (square ^ 56) as usize,
+        Color::Black => square as usize,
+    };
+    params.psqt[piece_index(piece.piece_type)][index]
+}
+
+/// How much a piece type counts towards the phase of the game.
+fn phase_weight(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::Knight | PieceType::Bishop => 1,
+        PieceType::Rook => 2,
+        PieceType::Queen => 4,
+        PieceType::Pawn | PieceType::King => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_state::STARTING_FEN;
+    use std::str::FromStr;
+
+    fn board(fen: &str) -> Board {
+        Board::from_str(fen.split_whitespace().next().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_starting_position_is_balanced() {
+        let trace = evaluate_trace(&board(STARTING_FEN), Color::White);
+        assert_eq!(trace.phase, MAX_PHASE);
+        for term in Term::ALL.into_iter().filter(|&term| term != Term::Tempo) {
+            let [white, black] = trace.term(term);
+            assert_eq!(white, black, "{}", term.name());
+        }
+        let tempo = EvalParams::DEFAULT.tempo;
+        assert_eq!(trace.term(Term::Tempo), [tempo, Score::ZERO]);
+        assert_eq!(trace.score, tempo.mg);
+    }
+
+    #[test]
+    fn test_mirrored_positions_have_opposite_scores() {
+        let white = board("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R");
+        let black = board("rnbqk2r/pppp1ppp/5n2/2b1p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R");
+        assert_eq!(
+            evaluate(&white, Color::White),
+            evaluate(&black, Color::Black)
+        );
+    }
+
+    #[test]
+    fn test_phase_and_tapering() {
+        let pawn_ending = evaluate_trace(&board("4k3/8/8/8/8/8/4P3/4K3"), Color::White);
+        assert_eq!(pawn_ending.phase, 0);
+        assert_eq!(pawn_ending.score, pawn_ending.total().eg);
+
+        assert_eq!(Score::new(100, 200).taper(MAX_PHASE), 100);
+        assert_eq!(Score::new(100, 200).taper(MAX_PHASE / 2), 150);
+    }
+
+    #[test]
+    fn test_trace_table() {
+        let trace = evaluate_trace(&board("4k3/8/8/8/8/8/4P3/4K3"), Color::Black);
+        let table = trace.to_string();
+        for term in Term::ALL {
+            assert!(table.contains(term.name()));
+        }
+        assert!(table.contains("Phase: 0/24"));
+        assert!(table.ends_with(&format!("Score: {} cp (side to move)", -trace.score)));
+    }
+
+    #[test]
+    fn test_evaluator_matches_uncached_evaluation() {
+        let mut evaluator = Evaluator::new();
+        for placement in [
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R",
+            "8/5k2/8/2P5/1P6/8/5K2/8",
+            // Already in the pawn table
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R",
+        ] {
+            let board = board(placement);
+            assert_eq!(
+                evaluator.trace(&board, Color::White),
+                evaluate_trace(&board, Color::White)
+            );
+            assert_eq!(
+                evaluator.evaluate(&board, Color::Black),
+                evaluate(&board, Color::Black)
+            );
+        }
+    }
+
+    #[test]
+    fn test_terms_can_be_switched_off() {
+        let board = board("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R");
+        let mut evaluator = Evaluator::new();
+        let all_terms = evaluator.trace(&board, Color::White);
+        assert_ne!(all_terms.term(Term::Mobility), [Score::ZERO; 2]);
+        assert_ne!(all_terms.term(Term::KingSafety), [Score::ZERO; 2]);
+
+        evaluator.options.mobility = false;
+        let without_mobility = evaluator.trace(&board, Color::White);
+        assert_eq!(without_mobility.term(Term::Mobility), [Score::ZERO; 2]);
+        assert_eq!(
+            without_mobility.term(Term::KingSafety),
+            all_terms.term(Term::KingSafety)
+        );
+
+        evaluator.options.king_safety = false;
+        let trace = evaluator.trace(&board, Color::White);
+        assert_eq!(trace.term(Term::KingSafety), [Score::ZERO; 2]);
+        assert_eq!(trace.score, trace.total().taper(trace.phase));
+    }
+
+    #[test]
+    fn test_evaluator_parameters() {
+        let board = board("4k3/8/8/8/8/8/4P3/4K3");
+        let mut evaluator = Evaluator::new();
+        let before = evaluator.evaluate(&board, Color::White);
+
+        let mut params = EvalParams::DEFAULT;
+        params.passed[1] += Score::new(0, 100);
+        evaluator.set_params(params.clone());
+        assert_eq!(evaluator.evaluate(&board, Color::White), before + 100);
+        assert_eq!(
+            evaluator.trace(&board, Color::White),
+            evaluate_trace_with(&board, Color::White, &params)
+        );
+    }
+
+    #[test]
+    fn test_central_knight_is_better_than_knight_on_the_rim() {
+        let center = board("4k3/8/8/8/3N4/8/8/4K3");
+        let rim = board("4k3/8/8/8/N7/8/8/4K3");
+        assert!(evaluate(&center, Color::White) > evaluate(&rim, Color::White));
+    }
+}
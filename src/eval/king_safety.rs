@@ -0,0 +1,138 @@
+//! Module containing the king safety evaluation.
+//!
+//! A king is safe behind a shield of its own pawns, and in danger on open files or when
+//! several opponent pieces attack the squares around it. Only the middlegame value of these
+//! terms is set: in the endgame the king is an active piece.
+
+use crate::board::piece::{Color, PieceType};
+use crate::board::Board;
+use crate::eval::mobility::PieceTargets;
+use crate::eval::params::EvalParams;
+use crate::eval::{color_index, Score};
+
+/// The penalty of the attacks on the king zone is the square of the total attacker weight
+/// divided by this value, when there are at least two attackers.
+const ATTACK_DIVISOR: i32 = 4;
+const MAX_ATTACK_PENALTY: i32 = 500;
+
+const FILE_A: u64 = 0x0101_0101_0101_0101;
+
+/// Scores the safety of the king of each color, from its own point of view.
+/// `pawns` holds the pawns of each color as bitboards.
+pub fn king_safety(
+    board: &Board,
+    pieces: &[PieceTargets],
+    pawns: [u64; 2],
+    params: &EvalParams,
+) -> [Score; 2] {
+    let mut score = [Score::ZERO; 2];
+    for color in [Color::White, Color::Black] {
+        let us = color_index(color);
+        let Some(king) = board.king_square(color) else {
+            continue;
+        };
+        let row = king / 8;
+        let col = king % 8;
+
+        for file in col.saturating_sub(1)..=(col + 1).min(7) {
+            let file_mask = FILE_A << file;
+            for (distance, &bonus) in params.shield.iter().enumerate() {
+                let shield_row = match color {
+                    Color::White => row as i32 + 1 + distance as i32,
+                    Color::Black => row as i32 - 1 - distance as i32,
+                };
+                if (0..8).contains(&shield_row)
+                    && pawns[us] & (1 << (shield_row * 8 + file as i32)) != 0
+                {
+                    score[us] += bonus;
+                }
+            }
+            if (pawns[0] | pawns[1]) & file_mask == 0 {
+                score[us] += params.open_file;
+            } else if pawns[us] & file_mask == 0 {
+                score[us] += params.semi_open_file;
+            }
+        }
+
+        let zone = king_zone(king);
+        let mut attackers = 0;
+        let mut attack_weight = 0;
+        for piece in pieces.iter().filter(|piece| piece.piece.color != color) {
+            if piece.targets & zone == 0 {
+                continue;
+            }
+            attackers += 1;
+            attack_weight += match piece.piece.piece_type {
+                PieceType::Knight => params.king_attack_weights[0],
+                PieceType::Bishop => params.king_attack_weights[1],
+                PieceType::Rook => params.king_attack_weights[2],
+                PieceType::Queen => params.king_attack_weights[3],
+                PieceType::Pawn | PieceType::King => 0,
+            };
+        }
+        if attackers >= 2 {
+            let penalty = (attack_weight * attack_weight / ATTACK_DIVISOR).min(MAX_ATTACK_PENALTY);
+            score[us] += Score::new(-penalty, 0);
+        }
+    }
+    score
+}
+
+/// The squares around the king, and the king square itself.
+fn king_zone(king: u8) -> u64 {
+    let row = (king / 8) as i32;
+    let col = (king % 8) as i32;
+    let mut zone = 0;
+    for dr in -1..=1 {
+        for dc in -1..=1 {
+            let (r, c) = (row + dr, col + dc);
+            if (0..8).contains(&r) && (0..8).contains(&c) {
+                zone |= 1 << (r * 8 + c);
+            }
+        }
+    }
+    zone
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::mobility::piece_targets;
+    use crate::eval::pawns::pawn_bitboards;
+    use std::str::FromStr;
+
+    const PARAMS: EvalParams = EvalParams::DEFAULT;
+
+    fn safety(placement: &str) -> [Score; 2] {
+        let board = Board::from_str(placement).unwrap();
+        king_safety(
+            &board,
+            &piece_targets(&board),
+            pawn_bitboards(&board),
+            &PARAMS,
+        )
+    }
+
+    #[test]
+    fn test_pawn_shield_and_open_files() {
+        // The white king is behind its pawns, the black king has a half-open file next to it
+        let score = safety("6k1/6pp/8/8/8/8/5PPP/6K1");
+        assert_eq!(score[0], PARAMS.shield[0] * 3);
+        assert_eq!(score[1], PARAMS.shield[0] * 2 + PARAMS.semi_open_file);
+
+        let score = safety("6k1/6pp/8/8/8/8/6PP/6K1");
+        assert_eq!(score[0], PARAMS.shield[0] * 2 + PARAMS.open_file);
+    }
+
+    #[test]
+    fn test_attackers_of_the_king_zone() {
+        // The queen and the knight both attack the squares around the black king
+        let attacked = safety("6k1/5ppp/8/3Q2N1/8/8/8/6K1");
+        let one_attacker = safety("6k1/5ppp/8/3Q4/8/8/8/6K1");
+        let weight = PARAMS.king_attack_weights[3] + PARAMS.king_attack_weights[0];
+        assert_eq!(
+            attacked[1] - one_attacker[1],
+            Score::new(-weight * weight / ATTACK_DIVISOR, 0)
+        );
+    }
+}
@@ -0,0 +1,118 @@
+//! Module containing the mobility evaluation.
+//!
+//! The mobility of a piece is the number of squares it can move to, counted from the
+//! pseudo-legal moves of the regular move generators. Squares attacked by an opponent pawn
+//! are not counted, a piece going there would be lost.
+
+use crate::board::piece::knight::generate_knight_moves;
+use crate::board::piece::sliding_pieces::generate_sliding_moves;
+use crate::board::piece::{Color, Piece, PieceType};
+use crate::board::{Board, BOARD_SIZE};
+use crate::eval::params::EvalParams;
+use crate::eval::{color_index, Score};
+
+/// The typical number of squares of knights, bishops, rooks and queens. Pieces with more
+/// squares get a bonus, pieces with fewer a penalty.
+const TYPICAL_MOBILITY: [i32; 4] = [4, 7, 7, 14];
+
+/// The squares a knight, bishop, rook or queen can move to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PieceTargets {
+    pub piece: Piece,
+    /// The target squares of the pseudo-legal moves of the piece, as a bitboard.
+    pub targets: u64,
+}
+
+/// Returns the targets of every knight, bishop, rook and queen on the board.
+pub fn piece_targets(board: &Board) -> Vec<PieceTargets> {
+    let mut pieces = Vec::new();
+    for square in 0..BOARD_SIZE * BOARD_SIZE {
+        let Some(&piece) = board.get_piece(square) else {
+            continue;
+        };
+        let row = square / BOARD_SIZE;
+        let col = square % BOARD_SIZE;
+        let moves = match piece.piece_type {
+            PieceType::Knight => generate_knight_moves(board, row, col),
+            PieceType::Bishop | PieceType::Rook | PieceType::Queen => {
+                generate_sliding_moves(board, row, col, piece)
+            }
+            PieceType::Pawn | PieceType::King => continue,
+        };
+        let targets = moves
+            .unwrap_or_default()
+            .iter()
+            .fold(0, |targets, chess_move| {
+                targets | 1 << chess_move.target_square
+            });
+        pieces.push(PieceTargets { piece, targets });
+    }
+    pieces
+}
+
+/// Scores the mobility of the pieces of each color, from its own point of view.
+/// `pawn_attacks` holds the squares attacked by the pawns of each color.
+pub fn mobility(
+    pieces: &[PieceTargets],
+    pawn_attacks: [u64; 2],
+    params: &EvalParams,
+) -> [Score; 2] {
+    let mut score = [Score::ZERO; 2];
+    for piece in pieces {
+        let us = color_index(piece.piece.color);
+        let safe = piece.targets & !pawn_attacks[1 - us];
+        let index = match piece.piece.piece_type {
+            PieceType::Knight => 0,
+            PieceType::Bishop => 1,
+            PieceType::Rook => 2,
+            PieceType::Queen => 3,
+            PieceType::Pawn | PieceType::King => continue,
+        };
+        let squares = safe.count_ones() as i32 - TYPICAL_MOBILITY[index];
+        score[us] += params.mobility[index] * squares;
+    }
+    score
+}
+
+/// Returns the squares attacked by pawns of the given color.
+pub fn pawn_attacks(pawns: u64, color: Color) -> u64 {
+    const FILE_A: u64 = 0x0101_0101_0101_0101;
+    const FILE_H: u64 = FILE_A << 7;
+    match color {
+        Color::White => ((pawns << 7) & !FILE_H) | ((pawns << 9) & !FILE_A),
+        Color::Black => ((pawns >> 9) & !FILE_H) | ((pawns >> 7) & !FILE_A),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_piece_targets_and_mobility() {
+        let board = Board::from_str("4k3/8/8/8/8/8/8/N3K2R").unwrap();
+        let pieces = piece_targets(&board);
+        assert_eq!(pieces.len(), 2);
+        assert_eq!(pieces[0].targets, 1 << 10 | 1 << 17);
+        assert_eq!(pieces[1].targets.count_ones(), 9);
+
+        let params = EvalParams::DEFAULT;
+        let [knight, _, rook, _] = params.mobility;
+        let score = mobility(&pieces, [0, 0], &params);
+        assert_eq!(score[0], knight * (2 - 4) + rook * (9 - 7));
+        assert_eq!(score[1], Score::ZERO);
+
+        // Squares attacked by opponent pawns do not count
+        let score = mobility(&pieces, [0, 1 << 10], &params);
+        assert_eq!(score[0], knight * (1 - 4) + rook * (9 - 7));
+    }
+
+    #[test]
+    fn test_pawn_attacks() {
+        // Pawns on a2 and h7
+        let pawns = 1 << 8 | 1 << 55;
+        assert_eq!(pawn_attacks(pawns, Color::White), 1 << 17 | 1 << 62);
+        assert_eq!(pawn_attacks(pawns, Color::Black), 1 << 1 | 1 << 46);
+    }
+}
@@ -0,0 +1,497 @@
+//! Module containing the NNUE evaluation, a small neural network whose first layer is updated
+//! incrementally as moves are played.
+//!
+//! The network has HalfKA inputs: from the perspective of each color, one input per
+//! combination of the square of its own king, a piece and the square of that piece. The board
+//! is flipped for black, so that both perspectives see their own pieces moving up the board.
+//! The first layer turns the active inputs of each perspective into an `Accumulator` of
+//! `hidden` values, which only changes by a few weight rows when a move is played. The two
+//! accumulators, the side to move first, go through a clipped ReLU into a single output.
+//!
+//! Everything is computed with integers: the accumulators are `i16`, the output an `i64` dot
+//! product. The loops over the hidden values are plain slices iterations without branches,
+//! which the compiler turns into SIMD instructions on any CPU.
+//!
+//! A network file is little endian: the magic `RUCENNUE`, the format version and the hidden
+//! size as `u32`, then the `i16` input weights (input major), the `i16` hidden biases, the
+//! `i16` output weights (side to move half first) and the `i32` output bias.
+
+use std::path::Path;
+use std::sync::Arc;
+use thiserror::Error;
+
+use crate::board::piece::{Color, Piece, PieceType};
+use crate::board::Board;
+use crate::eval::{color_index, piece_index};
+use crate::game_state::GameState;
+use crate::search::{MAX_PLY, TB_WIN_SCORE};
+
+/// The number of inputs of each perspective: 64 king squares, 12 pieces and 64 squares.
+pub const INPUTS: usize = 64 * 12 * 64;
+
+const MAGIC: &[u8; 8] = b"RUCENNUE";
+const VERSION: u32 = 1;
+
+/// The hidden values are clipped to `0..=QA` before the output layer.
+const QA: i32 = 255;
+/// The output weights are scaled by `QB`.
+const QB: i32 = 64;
+/// The output of the network, once unscaled, is multiplied by `SCALE` to get centipawns.
+const SCALE: i32 = 400;
+/// The largest hidden size, for which the scaled output still fits in an `i32`.
+pub const MAX_HIDDEN: usize = 4096;
+/// The outputs are clamped below the mate and tablebase scores of the search.
+const MAX_OUTPUT: i32 = TB_WIN_SCORE - MAX_PLY as i32 - 1;
+
+/// An error reading a network file.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum NnueError {
+    #[error("Cannot read the network: {0}")]
+    Io(String),
+    #[error("Not a network file")]
+    InvalidMagic,
+    #[error("Unsupported network version: {0}")]
+    UnsupportedVersion(u32),
+    #[error("Unsupported hidden size: {0}, at most {MAX_HIDDEN}")]
+    UnsupportedHiddenSize(usize),
+    #[error("Invalid network size: expected {expected} bytes, found {found}")]
+    InvalidSize { expected: usize, found: usize },
+}
+
+/// The weights of a network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Network {
+    hidden: usize,
+    input_weights: Vec<i16>,
+    hidden_bias: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i32,
+}
+
+impl Network {
+    /// Reads a network from a file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Network, NnueError> {
+        let bytes = std::fs::read(path).map_err(|e| NnueError::Io(e.to_string()))?;
+        Network::from_bytes(&bytes)
+    }
+
+    /// Reads a network from the content of a network file.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Network, NnueError> {
+        if bytes.len() < 16 {
+            return Err(NnueError::InvalidMagic);
+        }
+        if &bytes[..8] != MAGIC {
+            return Err(NnueError::InvalidMagic);
+        }
+        let version = u32::from_le_bytes(bytes[8..12].try_into().unwrap());
+        if version != VERSION {
+            return Err(NnueError::UnsupportedVersion(version));
+        }
+        let hidden = u32::from_le_bytes(bytes[12..16].try_into().unwrap()) as usize;
+        if hidden > MAX_HIDDEN {
+            return Err(NnueError::UnsupportedHiddenSize(hidden));
+        }
+
+        let expected = 16 + 2 * (INPUTS * hidden + hidden + 2 * hidden) + 4;
+        if hidden == 0 || bytes.len() != expected {
+            return Err(NnueError::InvalidSize {
+                expected,
+                found: bytes.len(),
+            });
+        }
+
+        let mut values = bytes[16..expected - 4]
+            .chunks_exact(2)
+            .map(|chunk| i16::from_le_bytes([chunk[0], chunk[1]]));
+        let mut read = |count: usize| values.by_ref().take(count).collect::<Vec<i16>>();
+        let input_weights = read(INPUTS * hidden);
+        let hidden_bias = read(hidden);
+        let output_weights = read(2 * hidden);
+        let output_bias = i32::from_le_bytes(bytes[expected - 4..].try_into().unwrap());
+
+        Ok(Network {
+            hidden,
+            input_weights,
+            hidden_bias,
+            output_weights,
+            output_bias,
+        })
+    }
+
+    /// Returns the content of the network file of this network.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(self.hidden as u32).to_le_bytes());
+        for value in self
+            .input_weights
+            .iter()
+            .chain(&self.hidden_bias)
+            .chain(&self.output_weights)
+        {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.output_bias.to_le_bytes());
+        bytes
+    }
+
+    /// The number of hidden values of each perspective.
+    pub fn hidden_size(&self) -> usize {
+        self.hidden
+    }
+
+    /// Computes the accumulator of one perspective from scratch.
+    fn refresh(&self, board: &Board, perspective: Color, values: &mut [i16]) {
+        values.copy_from_slice(&self.hidden_bias);
+        let Some(king) = board.king_square(perspective) else {
+            return;
+        };
+        for (square, piece) in board.squares.iter().enumerate() {
+            if let Some(piece) = piece {
+                let input = input_index(perspective, king, piece, square as u8);
+                add_weights(values, self.weights(input));
+            }
+        }
+    }
+
+    /// The input weights of one input.
+    fn weights(&self, input: usize) -> &[i16] {
+        &self.input_weights[input * self.hidden..(input + 1) * self.hidden]
+    }
+
+    /// Computes the output of the network, in centipawns from the point of view of the side
+    /// to move.
+    fn output(&self, accumulator: &Accumulator, side_to_move: Color) -> i32 {
+        let us = color_index(side_to_move);
+        let (own_weights, their_weights) = self.output_weights.split_at(self.hidden);
+        let sum = clipped_dot(&accumulator.values[us], own_weights)
+            + clipped_dot(&accumulator.values[1 - us], their_weights)
+            + self.output_bias as i64;
+        let output = sum * SCALE as i64 / (QA * QB) as i64;
+        output.clamp(-MAX_OUTPUT as i64, MAX_OUTPUT as i64) as i32
+    }
+}
+
+/// The hidden values of both perspectives, white first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Accumulator {
+    values: [Vec<i16>; 2],
+}
+
+/// Evaluates positions with a network, keeping one accumulator per move played so that
+/// unmaking a move only drops the last one.
+///
+/// `reset` must be called with the root position, then `make_move` after every move played on
+/// it and `unmake_move` after every move taken back.
+#[derive(Debug, Clone)]
+pub struct NnueState {
+    network: Arc<Network>,
+    stack: Vec<Accumulator>,
+    /// The number of accumulators in use, the ones above are kept to reuse their memory.
+    len: usize,
+}
+
+impl NnueState {
+    /// Creates a state evaluating with the network. It must be `reset` before evaluating.
+    pub fn new(network: Arc<Network>) -> Self {
+        NnueState {
+            network,
+            stack: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// Returns the network of the state.
+    pub fn network(&self) -> &Arc<Network> {
+        &self.network
+    }
+
+    /// Computes the accumulators of the board from scratch, forgetting every move played.
+    pub fn reset(&mut self, board: &Board) {
+        self.len = 0;
+        let accumulator = self.push();
+        for color in [Color::White, Color::Black] {
+            let values = &mut self.stack[accumulator].values[color_index(color)];
+            self.network.refresh(board, color, values);
+        }
+    }
+
+    /// Updates the accumulators with the last move of `game_state`, which has just been played.
+    ///
+    /// Only the inputs of the moved, captured and castling pieces change, except for the
+    /// perspective of a king that moved, which is computed from scratch.
+    pub fn make_move(&mut self, game_state: &GameState) {
+        let Some(played) = game_state.history.last() else {
+            return;
+        };
+        let board = &game_state.board;
+        let from = played.played_move.initial_square;
+        // A castling king does not always land on the target square of its move
+        let to = played
+            .castling
+            .map_or(played.played_move.target_square, |castling| {
+                castling.king_to
+            });
+        let moved = *board
+            .get_piece(to)
+            .expect("the moved piece is on the target square");
+        let piece = Piece {
+            piece_type: if played.played_move.promotion.is_some() {
+                PieceType::Pawn
+            } else {
+                moved.piece_type
+            },
+            color: moved.color,
+        };
+
+        let mut removed = vec![(piece, from)];
+        let mut added = vec![(moved, to)];
+        if let Some(captured) = played.captured {
+            let en_passant =
+                piece.piece_type == PieceType::Pawn && played.en_passant_target == Some(to);
+            let square = if en_passant {
+                // The captured pawn is on the row the pawn came from
+                from - from % 8 + to % 8
+            } else {
+                to
+            };
+            removed.push((captured, square));
+        }
+        if let Some(castling) = played.castling {
+            let rook = Piece {
+                piece_type: PieceType::Rook,
+                color: piece.color,
+            };
+            removed.push((rook, castling.rook_from));
+            added.push((rook, castling.rook_to));
+        }
+
+        let previous = self.len - 1;
+        let current = self.push();
+        let (done, todo) = self.stack.split_at_mut(current);
+        let (previous, current) = (&done[previous], &mut todo[0]);
+        for color in [Color::White, Color::Black] {
+            let values = &mut current.values[color_index(color)];
+            let Some(king) = board.king_square(color) else {
+                values.copy_from_slice(&self.network.hidden_bias);
+                continue;
+            };
+            if piece.piece_type == PieceType::King && piece.color == color {
+                self.network.refresh(board, color, values);
+                continue;
+            }
+            values.copy_from_slice(&previous.values[color_index(color)]);
+            for (piece, square) in &removed {
+                let input = input_index(color, king, piece, *square);
+                sub_weights(values, self.network.weights(input));
+            }
+            for (piece, square) in &added {
+                let input = input_index(color, king, piece, *square);
+                add_weights(values, self.network.weights(input));
+            }
+        }
+    }
+
+    /// Drops the accumulators of the last move played.
+    pub fn unmake_move(&mut self) {
+        if self.len > 1 {
+            self.len -= 1;
+        }
+    }
+
+    /// Returns the accumulators of the current position.
+    pub fn accumulator(&self) -> &Accumulator {
+        &self.stack[self.len - 1]
+    }
+
+    /// Scores the current position from the point of view of the side to move.
+    pub fn evaluate(&self, side_to_move: Color) -> i32 {
+        self.network.output(self.accumulator(), side_to_move)
+    }
+
+    /// Makes room for one more accumulator, returning its index.
+    fn push(&mut self) -> usize {
+        if self.len == self.stack.len() {
+            let values = vec![0; self.network.hidden];
+            self.stack.push(Accumulator {
+                values: [values.clone(), values],
+            });
+        }
+        self.len += 1;
+        self.len - 1
+    }
+}
+
+/// The index of the input of a piece on a square, from the perspective of `color` whose king is
+/// on `king`.
+fn input_index(perspective: Color, king: u8, piece: &Piece, square: u8) -> usize {
+    let flip = match perspective {
+        Color::White => 0,
+        Color::Black => 56,
+    };
+    let piece = piece_index(piece.piece_type) + if piece.color == perspective { 0 } else { 6 };
+    ((((king ^ flip) as usize) * 12 + piece) * 64) + (square ^ flip) as usize
+}
+
+fn add_weights(values: &mut [i16], weights: &[i16]) {
+    for (value, weight) in values.iter_mut().zip(weights) {
+        *value = value.wrapping_add(*weight);
+    }
+}
+
+fn sub_weights(values: &mut [i16], weights: &[i16]) {
+    for (value, weight) in values.iter_mut().zip(weights) {
+        *value = value.wrapping_sub(*weight);
+    }
+}
+
+/// The dot product of the hidden values, clipped to `0..=QA`, with the output weights.
+fn clipped_dot(values: &[i16], weights: &[i16]) -> i64 {
+    values
+        .iter()
+        .zip(weights)
+        .map(|(&value, &weight)| ((value as i32).clamp(0, QA) * weight as i32) as i64)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::moves::Move;
+    use std::str::FromStr;
+
+    /// A network with small pseudo-random weights.
+    fn network(hidden: usize) -> Network {
+        let mut state: u32 = 0x1234_5678;
+        let mut next = |range: i32| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            ((state % (2 * range as u32 + 1)) as i32 - range) as i16
+        };
+        Network {
+            hidden,
+            input_weights: (0..INPUTS * hidden).map(|_| next(16)).collect(),
+            hidden_bias: (0..hidden).map(|_| next(64) + 64).collect(),
+            output_weights: (0..2 * hidden).map(|_| next(32)).collect(),
+            output_bias: 0,
+        }
+    }
+
+    #[test]
+    fn test_network_file_round_trip() {
+        let network = network(4);
+        let bytes = network.to_bytes();
+        assert_eq!(Network::from_bytes(&bytes), Ok(network));
+
+        assert_eq!(
+            Network::from_bytes(b"NOTANNUE\x01\0\0\0\x04\0\0\0"),
+            Err(NnueError::InvalidMagic)
+        );
+        let mut old = bytes.clone();
+        old[8] = 0;
+        assert_eq!(
+            Network::from_bytes(&old),
+            Err(NnueError::UnsupportedVersion(0))
+        );
+        assert!(matches!(
+            Network::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(NnueError::InvalidSize { .. })
+        ));
+        let mut too_large = bytes[..16].to_vec();
+        too_large[12..16].copy_from_slice(&(MAX_HIDDEN as u32 + 1).to_le_bytes());
+        assert_eq!(
+            Network::from_bytes(&too_large),
+            Err(NnueError::UnsupportedHiddenSize(MAX_HIDDEN + 1))
+        );
+    }
+
+    #[test]
+    fn test_incremental_updates_match_refresh() {
+        let network = Arc::new(network(8));
+        // Castling both ways, en passant, a promotion with capture and king moves
+        let mut game_state =
+            GameState::from_str("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1").unwrap();
+        let moves = [
+            Move::new(36, 43),
+            Move::new(60, 62),
+            Move::new(4, 2),
+            Move::new(61, 5),
+            Move {
+                initial_square: 49,
+                target_square: 56,
+                promotion: Some(PieceType::Queen),
+            },
+            Move::new(62, 54),
+            Move::new(3, 5),
+        ];
+        let mut state = NnueState::new(network.clone());
+        state.reset(&game_state.board);
+        let mut accumulators = vec![state.accumulator().clone()];
+        for chess_move in &moves {
+            assert!(game_state.make_legal_move(chess_move), "{}", chess_move);
+            state.make_move(&game_state);
+
+            let mut expected = NnueState::new(network.clone());
+            expected.reset(&game_state.board);
+            assert_eq!(
+                state.accumulator(),
+                expected.accumulator(),
+                "{}",
+                chess_move
+            );
+            accumulators.push(expected.accumulator().clone());
+        }
+        for expected in accumulators.iter().rev().skip(1) {
+            game_state.unmake_move();
+            state.unmake_move();
+            assert_eq!(state.accumulator(), expected);
+        }
+
+        // Castling in Chess960, the king taking its own rook
+        let mut game_state = GameState::from_str("r3k2r/8/8/8/8/8/8/RK5R w HAkq - 0 1").unwrap();
+        state.reset(&game_state.board);
+        for chess_move in [Move::new(1, 7), Move::new(60, 56)] {
+            assert!(game_state.make_legal_move(&chess_move), "{}", chess_move);
+            state.make_move(&game_state);
+
+            let mut expected = NnueState::new(network.clone());
+            expected.reset(&game_state.board);
+            assert_eq!(
+                state.accumulator(),
+                expected.accumulator(),
+                "{}",
+                chess_move
+            );
+        }
+    }
+
+    #[test]
+    fn test_large_outputs_do_not_overflow() {
+        // Every hidden value clipped at its maximum, with the largest weights
+        let network = Network {
+            hidden: MAX_HIDDEN,
+            input_weights: Vec::new(),
+            hidden_bias: Vec::new(),
+            output_weights: [vec![i16::MAX; MAX_HIDDEN], vec![i16::MIN; MAX_HIDDEN]].concat(),
+            output_bias: i32::MAX,
+        };
+        let accumulator = Accumulator {
+            values: [vec![i16::MAX; MAX_HIDDEN], vec![0; MAX_HIDDEN]],
+        };
+        assert_eq!(network.output(&accumulator, Color::White), MAX_OUTPUT);
+        assert_eq!(network.output(&accumulator, Color::Black), -MAX_OUTPUT);
+    }
+
+    #[test]
+    fn test_mirrored_positions_have_the_same_score() {
+        let network = Arc::new(network(8));
+        let mut white = NnueState::new(network.clone());
+        white.reset(&Board::from_str("4k3/ppp5/8/8/8/8/3Q4/4K3").unwrap());
+        let mut black = NnueState::new(network);
+        black.reset(&Board::from_str("4k3/3q4/8/8/8/8/PPP5/4K3").unwrap());
+        assert_eq!(white.evaluate(Color::White), black.evaluate(Color::Black));
+        assert_ne!(white.evaluate(Color::White), white.evaluate(Color::Black));
+    }
+}
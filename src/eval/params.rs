@@ -0,0 +1,518 @@
+//! Module containing the parameters of the evaluation.
+//!
+//! Every weight of the handcrafted evaluation lives in an `EvalParams`, so that the weights
+//! can be tuned and loaded from a file instead of being compiled in. The parameters are
+//! written one per line as `<name> <value>`, e.g. `material.knight.mg 337`, the format read
+//! by `EvalParams::from_str` and written by its `Display` implementation.
+
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::board::square_name;
+use crate::eval::Score;
+
+/// The names of the piece types in parameter names, in the order of the tables.
+const PIECE_NAMES: [&str; 6] = ["pawn", "knight", "bishop", "rook", "queen", "king"];
+
+/// The names of the pieces with a mobility term, in the order of `EvalParams::mobility`.
+const MOBILITY_NAMES: [&str; 4] = ["knight", "bishop", "rook", "queen"];
+
+/// An error raised while parsing evaluation parameters.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ParseParamsError {
+    #[error("Unknown parameter: {0}")]
+    UnknownParameter(String),
+    #[error("Invalid value for parameter {0}: {1}")]
+    InvalidValue(String, String),
+}
+
+/// The weights of the handcrafted evaluation, in centipawns.
+///
+/// The tables indexed by piece type are in the order pawn, knight, bishop, rook, queen, king.
+/// The piece-square tables are written from white's point of view with the eighth rank
+/// first, as they would be read on a diagram.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvalParams {
+    /// The value of each piece type.
+    pub material: [Score; 6],
+    /// The bonus of each piece type on each square.
+    pub psqt: [[Score; 64]; 6],
+    /// The penalty of each extra pawn on a file.
+    pub doubled: Score,
+    /// The penalty of a pawn without pawns of its color on the adjacent files.
+    pub isolated: Score,
+    /// The penalty of a pawn that cannot be defended by pawns and cannot advance safely.
+    pub backward: Score,
+    /// The bonus of a pawn defended by or side by side with another pawn, by relative rank.
+    pub connected: [Score; 8],
+    /// The bonus of a passed pawn, by relative rank.
+    pub passed: [Score; 8],
+    /// Per square of distance between its king and the square in front of a passed pawn.
+    pub passer_own_king_distance: Score,
+    /// Per square of distance between the opponent king and the square in front of a passed
+    /// pawn.
+    pub passer_enemy_king_distance: Score,
+    /// The bonus per square of mobility of knights, bishops, rooks and queens.
+    pub mobility: [Score; 4],
+    /// The bonus of a pawn in front of its king, one and two rows ahead.
+    pub shield: [Score; 2],
+    /// The penalty of a file next to the king without a pawn of its color.
+    pub semi_open_file: Score,
+    /// The penalty of a file next to the king without any pawn.
+    pub open_file: Score,
+    /// The weight of knights, bishops, rooks and queens attacking the king zone.
+    pub king_attack_weights: [i32; 4],
+    /// The bonus of the side to move.
+    pub tempo: Score,
+}
+
+impl EvalParams {
+    /// The handwritten parameters, used when no others are loaded.
+    pub const DEFAULT: EvalParams = EvalParams {
+        material: combine(MG_VALUES, EG_VALUES),
+        psqt: [
+            combine(MG_PSQT[0], EG_PSQT[0]),
+            combine(MG_PSQT[1], EG_PSQT[1]),
+            combine(MG_PSQT[2], EG_PSQT[2]),
+            combine(MG_PSQT[3], EG_PSQT[3]),
+            combine(MG_PSQT[4], EG_PSQT[4]),
+            combine(MG_PSQT[5], EG_PSQT[5]),
+        ],
+        doubled: Score::new(-10, -20),
+        isolated: Score::new(-10, -15),
+        backward: Score::new(-8, -10),
+        connected: [
+            Score::new(0, 0),
+            Score::new(5, 2),
+            Score::new(7, 4),
+            Score::new(10, 7),
+            Score::new(15, 12),
+            Score::new(25, 20),
+            Score::new(40, 30),
+            Score::new(0, 0),
+        ],
+        passed: [
+            Score::new(0, 0),
+            Score::new(5, 10),
+            Score::new(10, 20),
+            Score::new(15, 35),
+            Score::new(25, 60),
+            Score::new(45, 100),
+            Score::new(70, 150),
+            Score::new(0, 0),
+        ],
+        passer_own_king_distance: Score::new(0, -2),
+        passer_enemy_king_distance: Score::new(0, 5),
+        mobility: [
+            Score::new(4, 4),
+            Score::new(5, 5),
+            Score::new(2, 4),
+            Score::new(1, 2),
+        ],
+        shield: [Score::new(12, 0), Score::new(6, 0)],
+        semi_open_file: Score::new(-12, 0),
+        open_file: Score::new(-25, 0),
+        king_attack_weights: [2, 2, 3, 5],
+        tempo: Score::new(20, 10),
+    };
+
+    /// Calls `f` with the name and a mutable reference of every parameter, always in the same
+    /// order. The name is only built when `f` calls the closure it is given.
+    pub fn visit_mut(&mut self, f: &mut dyn FnMut(&dyn Fn() -> String, &mut i32)) {
+        for (piece, score) in self.material.iter_mut().enumerate() {
+            visit_score(score, &|| format!("material.{}", PIECE_NAMES[piece]), f);
+        }
+        for (piece, table) in self.psqt.iter_mut().enumerate() {
+            for (index, score) in table.iter_mut().enumerate() {
+                let name = || {
+                    let square = square_name(index as u8 ^ 56);
+                    format!("psqt.{}.{}", PIECE_NAMES[piece], square)
+                };
+                visit_score(score, &name, f);
+            }
+        }
+        visit_score(&mut self.doubled, &|| "pawns.doubled".to_string(), f);
+        visit_score(&mut self.isolated, &|| "pawns.isolated".to_string(), f);
+        visit_score(&mut self.backward, &|| "pawns.backward".to_string(), f);
+        for (rank, score) in self.connected.iter_mut().enumerate() {
+            visit_score(score, &|| format!("pawns.connected.{}", rank + 1), f);
+        }
+        for (rank, score) in self.passed.iter_mut().enumerate() {
+            visit_score(score, &|| format!("pawns.passed.{}", rank + 1), f);
+        }
+        visit_score(
+            &mut self.passer_own_king_distance,
+            &|| "pawns.passer_own_king_distance".to_string(),
+            f,
+        );
+        visit_score(
+            &mut self.passer_enemy_king_distance,
+            &|| "pawns.passer_enemy_king_distance".to_string(),
+            f,
+        );
+        for (piece, score) in self.mobility.iter_mut().enumerate() {
+            visit_score(score, &|| format!("mobility.{}", MOBILITY_NAMES[piece]), f);
+        }
+        for (distance, score) in self.shield.iter_mut().enumerate() {
+            visit_score(score, &|| format!("king_safety.shield.{}", distance + 1), f);
+        }
+        visit_score(
+            &mut self.semi_open_file,
+            &|| "king_safety.semi_open_file".to_string(),
+            f,
+        );
+        visit_score(
+            &mut self.open_file,
+            &|| "king_safety.open_file".to_string(),
+            f,
+        );
+        for (piece, weight) in self.king_attack_weights.iter_mut().enumerate() {
+            f(
+                &|| format!("king_safety.attack_weight.{}", MOBILITY_NAMES[piece]),
+                weight,
+            );
+        }
+        visit_score(&mut self.tempo, &|| "tempo".to_string(), f);
+    }
+
+    /// Returns the value of every parameter, in the order of `visit_mut`.
+    pub fn values(&self) -> Vec<i32> {
+        let mut values = Vec::new();
+        self.clone().visit_mut(&mut |_, value| values.push(*value));
+        values
+    }
+
+    /// Sets every parameter from values in the order of `visit_mut`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are fewer values than parameters.
+    pub fn set_values(&mut self, values: &[i32]) {
+        let mut values = values.iter();
+        self.visit_mut(&mut |_, value| *value = *values.next().expect("missing value"));
+    }
+
+    /// Returns the name of every parameter, in the order of `visit_mut`.
+    pub fn names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        self.clone().visit_mut(&mut |name, _| names.push(name()));
+        names
+    }
+
+    /// Writes the parameters as Rust source, a constant that can replace
+    /// `EvalParams::DEFAULT`.
+    pub fn to_rust_source(&self) -> String {
+        let mut source = String::from("pub const TUNED: EvalParams = EvalParams {\n");
+        source.push_str(&format!("    material: {},\n", scores(&self.material)));
+        source.push_str("    psqt: [\n");
+        for table in &self.psqt {
+            source.push_str("        [\n");
+            for row in table.chunks(8) {
+                let row: Vec<String> = row.iter().map(score).collect();
+                source.push_str(&format!("            {},\n", row.join(", ")));
+            }
+            source.push_str("        ],\n");
+        }
+        source.push_str("    ],\n");
+        for (name, value) in [
+            ("doubled", score(&self.doubled)),
+            ("isolated", score(&self.isolated)),
+            ("backward", score(&self.backward)),
+            ("connected", scores(&self.connected)),
+            ("passed", scores(&self.passed)),
+            (
+                "passer_own_king_distance",
+                score(&self.passer_own_king_distance),
+            ),
+            (
+                "passer_enemy_king_distance",
+                score(&self.passer_enemy_king_distance),
+            ),
+            ("mobility", scores(&self.mobility)),
+            ("shield", scores(&self.shield)),
+            ("semi_open_file", score(&self.semi_open_file)),
+            ("open_file", score(&self.open_file)),
+            (
+                "king_attack_weights",
+                format!("{:?}", self.king_attack_weights),
+            ),
+            ("tempo", score(&self.tempo)),
+        ] {
+            source.push_str(&format!("    {}: {},\n", name, value));
+        }
+        source.push_str("};\n");
+        source
+    }
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        EvalParams::DEFAULT
+    }
+}
+
+impl fmt::Display for EvalParams {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names = self.names();
+        for (name, value) in names.iter().zip(self.values()) {
+            writeln!(f, "{} {}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for EvalParams {
+    type Err = ParseParamsError;
+
+    /// Parses parameters written one per line as `<name> <value>`. Empty lines and lines
+    /// starting with `#` are skipped, and missing parameters keep their default value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ruce::eval::params::EvalParams;
+    /// use std::str::FromStr;
+    ///
+    /// let params = EvalParams::from_str("# Cheaper knights\nmaterial.knight.mg 300\n").unwrap();
+    /// assert_eq!(params.material[1].mg, 300);
+    /// assert_eq!(params.material[1].eg, EvalParams::DEFAULT.material[1].eg);
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut params = EvalParams::DEFAULT;
+        let names = params.names();
+        let mut values = params.values();
+
+        for line in s.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let index = names
+                .iter()
+                .position(|known| known == name)
+                .ok_or_else(|| ParseParamsError::UnknownParameter(name.to_string()))?;
+            values[index] = value.trim().parse().map_err(|_| {
+                ParseParamsError::InvalidValue(name.to_string(), value.trim().to_string())
+            })?;
+        }
+        params.set_values(&values);
+        Ok(params)
+    }
+}
+
+fn visit_score(
+    score: &mut Score,
+    name: &dyn Fn() -> String,
+    f: &mut dyn FnMut(&dyn Fn() -> String, &mut i32),
+) {
+    f(&|| format!("{}.mg", name()), &mut score.mg);
+    f(&|| format!("{}.eg", name()), &mut score.eg);
+}
+
+fn score(score: &Score) -> String {
+    format!("Score::new({}, {})", score.mg, score.eg)
+}
+
+fn scores(scores: &[Score]) -> String {
+    let scores: Vec<String> = scores.iter().map(score).collect();
+    format!("[{}]", scores.join(", "))
+}
+
+const fn combine<const N: usize>(mg: [i32; N], eg: [i32; N]) -> [Score; N] {
+    let mut scores = [Score::ZERO; N];
+    let mut i = 0;
+    while i < N {
+        scores[i] = Score::new(mg[i], eg[i]);
+        i += 1;
+    }
+    scores
+}
+
+const MG_VALUES: [i32; 6] = [82, 337, 365, 477, 1025, 0];
+const EG_VALUES: [i32; 6] = [94, 281, 297, 512, 936, 0];
+
+#[rustfmt::skip]
+const MG_PSQT: [[i32; 64]; 6] = [
+    // Pawn
+    [
+          0,   0,   0,   0,   0,   0,   0,   0,
+         50,  50,  50,  50,  50,  50,  50,  50,
+         10,  10,  20,  30,  30,  20,  10,  10,
+          5,   5,  10,  25,  25,  10,   5,   5,
+          0,   0,   0,  20,  20,   0,   0,   0,
+          5,  -5, -10,   0,   0, -10,  -5,   5,
+          5,  10,  10, -20, -20,  10,  10,   5,
+          0,   0,   0,   0,   0,   0,   0,   0,
+    ],
+    // Knight
+    [
+        -50, -40, -30, -30, -30, -30, -40, -50,
+        -40, -20,   0,   0,   0,   0, -20, -40,
+        -30,   0,  10,  15,  15,  10,   0, -30,
+        -30,   5,  15,  20,  20,  15,   5, -30,
+        -30,   0,  15,  20,  20,  15,   0, -30,
+        -30,   5,  10,  15,  15,  10,   5, -30,
+        -40, -20,   0,   5,   5,   0, -20, -40,
+        -50, -40, -30, -30, -30, -30, -40, -50,
+    ],
+    // Bishop
+    [
+        -20, -10, -10, -10, -10, -10, -10, -20,
+        -10,   0,   0,   0,   0,   0,   0, -10,
+        -10,   0,   5,  10,  10,   5,   0, -10,
+        -10,   5,   5,  10,  10,   5,   5, -10,
+        -10,   0,  10,  10,  10,  10,   0, -10,
+        -10,  10,  10,  10,  10,  10,  10, -10,
+        -10,   5,   0,   0,   0,   0,   5, -10,
+        -20, -10, -10, -10, -10, -10, -10, -20,
+    ],
+    // Rook
+    [
+          0,   0,   0,   0,   0,   0,   0,   0,
+          5,  10,  10,  10,  10,  10,  10,   5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+          0,   0,   0,   5,   5,   0,   0,   0,
+    ],
+    // Queen
+    [
+        -20, -10, -10,  -5,  -5, -10, -10, -20,
+        -10,   0,   0,   0,   0,   0,   0, -10,
+        -10,   0,   5,   5,   5,   5,   0, -10,
+         -5,   0,   5,   5,   5,   5,   0,  -5,
+          0,   0,   5,   5,   5,   5,   0,  -5,
+        -10,   5,   5,   5,   5,   5,   0, -10,
+        -10,   0,   5,   0,   0,   0,   0, -10,
+        -20, -10, -10,  -5,  -5, -10, -10, -20,
+    ],
+    // King
+    [
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -20, -30, -30, -40, -40, -30, -30, -20,
+        -10, -20, -20, -20, -20, -20, -20, -10,
+         20,  20,   0,   0,   0,   0,  20,  20,
+         20,  30,  10,   0,   0,  10,  30,  20,
+    ],
+];
+
+#[rustfmt::skip]
+const EG_PSQT: [[i32; 64]; 6] = [
+    // Pawn
+    [
+          0,   0,   0,   0,   0,   0,   0,   0,
+         90,  90,  90,  90,  90,  90,  90,  90,
+         60,  60,  60,  60,  60,  60,  60,  60,
+         35,  35,  35,  35,  35,  35,  35,  35,
+         20,  20,  20,  20,  20,  20,  20,  20,
+         10,  10,  10,  10,  10,  10,  10,  10,
+          5,   5,   5,   5,   5,   5,   5,   5,
+          0,   0,   0,   0,   0,   0,   0,   0,
+    ],
+    // Knight
+    [
+        -50, -40, -30, -30, -30, -30, -40, -50,
+        -40, -20,   0,   0,   0,   0, -20, -40,
+        -30,   0,  10,  15,  15,  10,   0, -30,
+        -30,   5,  15,  20,  20,  15,   5, -30,
+        -30,   0,  15,  20,  20,  15,   0, -30,
+        -30,   5,  10,  15,  15,  10,   5, -30,
+        -40, -20,   0,   5,   5,   0, -20, -40,
+        -50, -40, -30, -30, -30, -30, -40, -50,
+    ],
+    // Bishop
+    [
+        -20, -10, -10, -10, -10, -10, -10, -20,
+        -10,   0,   0,   0,   0,   0,   0, -10,
+        -10,   0,   5,  10,  10,   5,   0, -10,
+        -10,   5,   5,  10,  10,   5,   5, -10,
+        -10,   0,  10,  10,  10,  10,   0, -10,
+        -10,  10,  10,  10,  10,  10,  10, -10,
+        -10,   5,   0,   0,   0,   0,   5, -10,
+        -20, -10, -10, -10, -10, -10, -10, -20,
+    ],
+    // Rook
+    [
+          5,   5,   5,   5,   5,   5,   5,   5,
+         10,  10,  10,  10,  10,  10,  10,  10,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0,
+    ],
+    // Queen
+    [
+        -20, -10, -10,  -5,  -5, -10, -10, -20,
+        -10,   0,   5,   5,   5,   5,   0, -10,
+        -10,   5,  10,  10,  10,  10,   5, -10,
+         -5,   5,  10,  15,  15,  10,   5,  -5,
+         -5,   5,  10,  15,  15,  10,   5,  -5,
+        -10,   5,  10,  10,  10,  10,   5, -10,
+        -10,   0,   5,   5,   5,   5,   0, -10,
+        -20, -10, -10,  -5,  -5, -10, -10, -20,
+    ],
+    // King
+    [
+        -50, -40, -30, -20, -20, -30, -40, -50,
+        -30, -20, -10,   0,   0, -10, -20, -30,
+        -30, -10,  20,  30,  30,  20, -10, -30,
+        -30, -10,  30,  40,  40,  30, -10, -30,
+        -30, -10,  30,  40,  40,  30, -10, -30,
+        -30, -10,  20,  30,  30,  20, -10, -30,
+        -30, -30,   0,   0,   0,   0, -30, -30,
+        -50, -30, -30, -30, -30, -30, -30, -50,
+    ],
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_and_values_line_up() {
+        let params = EvalParams::DEFAULT;
+        let names = params.names();
+        assert_eq!(names.len(), params.values().len());
+        assert_eq!(names[0], "material.pawn.mg");
+        assert!(names.contains(&"psqt.knight.e4.eg".to_string()));
+        assert!(names.contains(&"king_safety.attack_weight.queen".to_string()));
+        let distinct: std::collections::HashSet<&String> = names.iter().collect();
+        assert_eq!(distinct.len(), names.len());
+    }
+
+    #[test]
+    fn test_write_and_parse_round_trip() {
+        let mut params = EvalParams::DEFAULT;
+        params.psqt[1][36].mg = 42;
+        params.tempo = Score::new(15, 5);
+        let parsed = EvalParams::from_str(&params.to_string()).unwrap();
+        assert_eq!(parsed, params);
+
+        assert_eq!(
+            EvalParams::from_str("tempo.mg 1\nmystery 3").unwrap_err(),
+            ParseParamsError::UnknownParameter("mystery".to_string())
+        );
+        assert_eq!(
+            EvalParams::from_str("tempo.mg ten").unwrap_err(),
+            ParseParamsError::InvalidValue("tempo.mg".to_string(), "ten".to_string())
+        );
+    }
+
+    #[test]
+    fn test_rust_source() {
+        let source = EvalParams::DEFAULT.to_rust_source();
+        assert!(source.starts_with("pub const TUNED: EvalParams = EvalParams {\n"));
+        assert!(source.contains("    tempo: Score::new(20, 10),\n"));
+        assert!(source.contains("    king_attack_weights: [2, 2, 3, 5],\n"));
+        assert!(source.ends_with("};\n"));
+    }
+}
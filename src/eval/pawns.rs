@@ -0,0 +1,278 @@
+//! Module containing the evaluation of the pawn structure.
+//!
+//! The pawn structure changes rarely during a search, so its terms are cached in a
+//! `PawnTable` keyed by the pawn-only Zobrist key of the position. Only the terms depending
+//! on the pawns alone are cached: the distance of the kings to the passed pawns is computed
+//! on every evaluation from the passed pawns stored in the entry.
+
+use crate::board::piece::{Color, PieceType};
+use crate::board::Board;
+use crate::eval::params::EvalParams;
+use crate::eval::{color_index, Score};
+use crate::zobrist::pawn_key;
+
+const FILE_A: u64 = 0x0101_0101_0101_0101;
+
+/// The pawn structure terms of a position, as cached in the pawn hash table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PawnEntry {
+    key: u64,
+    /// The pawn structure score of each color, from its own point of view.
+    pub score: [Score; 2],
+    /// The squares of the passed pawns of each color.
+    pub passed: [u64; 2],
+}
+
+/// A hash table of pawn structure evaluations, indexed by pawn key.
+#[derive(Debug, Clone)]
+pub struct PawnTable {
+    entries: Vec<Option<PawnEntry>>,
+}
+
+impl PawnTable {
+    /// Creates a table of `2^bits` entries.
+    pub fn new(bits: u32) -> Self {
+        PawnTable {
+            entries: vec![None; 1 << bits],
+        }
+    }
+
+    /// Returns the pawn structure terms of the board, computing and storing them if they are
+    /// not in the table yet. The table must be cleared when `params` change.
+    pub fn probe(&mut self, board: &Board, params: &EvalParams) -> PawnEntry {
+        let key = pawn_key(board);
+        let index = (key as usize) & (self.entries.len() - 1);
+        match self.entries[index] {
+            Some(entry) if entry.key == key => entry,
+            _ => {
+                let entry = evaluate_pawns(board, key, params);
+                self.entries[index] = Some(entry);
+                entry
+            }
+        }
+    }
+
+    /// Removes every entry.
+    pub fn clear(&mut self) {
+        self.entries.fill(None);
+    }
+}
+
+/// Computes the pawn structure terms of the board, whose pawn key is `key`.
+pub fn evaluate_pawns(board: &Board, key: u64, params: &EvalParams) -> PawnEntry {
+    let pawns = pawn_bitboards(board);
+    let mut entry = PawnEntry {
+        key,
+        score: [Score::ZERO; 2],
+        passed: [0; 2],
+    };
+
+    for color in [Color::White, Color::Black] {
+        let us = color_index(color);
+        let own = pawns[us];
+        let enemy = pawns[1 - us];
+        let mut score = Score::ZERO;
+
+        for file in 0..8 {
+            let count = (own & (FILE_A << file)).count_ones() as i32;
+            if count > 1 {
+                score += params.doubled * (count - 1);
+            }
+        }
+
+        let mut remaining = own;
+        while remaining != 0 {
+            let square = remaining.trailing_zeros() as u8;
+            remaining &= remaining - 1;
+            let row = square / 8;
+            let col = square % 8;
+            let relative_row = relative_row(row, color) as usize;
+            let neighbours = adjacent_files(col);
+
+            if own & neighbours == 0 {
+                score += params.isolated;
+            }
+
+            let phalanx = own & neighbours & rank_mask(row);
+            let supported = own & neighbours & behind_rank_mask(row, color);
+            if phalanx != 0 || supported != 0 {
+                score += params.connected[relative_row];
+            }
+
+            if enemy & (neighbours | (FILE_A << col)) & ahead_mask(row, color) == 0 {
+                entry.passed[us] |= 1 << square;
+                score += params.passed[relative_row];
+            } else if is_backward(own, enemy, row, col, color) {
+                score += params.backward;
+            }
+        }
+        entry.score[us] = score;
+    }
+    entry
+}
+
+/// Scores the distance of both kings to the passed pawns, from the point of view of each
+/// color: a passed pawn is worth more when its king escorts it and the opponent king is far.
+pub fn king_proximity(board: &Board, passed: [u64; 2], params: &EvalParams) -> [Score; 2] {
+    let mut score = [Score::ZERO; 2];
+    for color in [Color::White, Color::Black] {
+        let us = color_index(color);
+        let (Some(own_king), Some(enemy_king)) = (
+            board.king_square(color),
+            board.king_square(color.opposite()),
+        ) else {
+            continue;
+        };
+        let mut remaining = passed[us];
+        while remaining != 0 {
+            let square = remaining.trailing_zeros() as u8;
+            remaining &= remaining - 1;
+            let relative_row = relative_row(square / 8, color);
+            let weight = relative_row as i32 - 2;
+            if weight <= 0 || relative_row == 7 {
+                continue;
+            }
+            let stop = match color {
+                Color::White => square + 8,
+                Color::Black => square - 8,
+            };
+            score[us] += params.passer_own_king_distance * (distance(own_king, stop) * weight);
+            score[us] += params.passer_enemy_king_distance * (distance(enemy_king, stop) * weight);
+        }
+    }
+    score
+}
+
+/// A pawn is backward when the pawns of its color on the adjacent files are all in front of
+/// it, so that none of them can defend it, and the square in front of it is attacked by an
+/// opponent pawn. Isolated pawns are never backward.
+fn is_backward(own: u64, enemy: u64, row: u8, col: u8, color: Color) -> bool {
+    let neighbours = adjacent_files(col);
+    let supporters = own & neighbours & !ahead_mask(row, color);
+    if own & neighbours == 0 || supporters != 0 {
+        return false;
+    }
+    let stop_row = match color {
+        Color::White => row + 1,
+        Color::Black => row.wrapping_sub(1),
+    };
+    let attacker_row = match color {
+        Color::White => stop_row + 1,
+        Color::Black => stop_row.wrapping_sub(1),
+    };
+    attacker_row < 8 && enemy & neighbours & rank_mask(attacker_row) != 0
+}
+
+/// Returns the pawns of each color as bitboards, white first.
+pub fn pawn_bitboards(board: &Board) -> [u64; 2] {
+    let mut pawns = [0; 2];
+    for (square, piece) in board.squares.iter().enumerate() {
+        if let Some(piece) = piece.filter(|piece| piece.piece_type == PieceType::Pawn) {
+            pawns[color_index(piece.color)] |= 1 << square;
+        }
+    }
+    pawns
+}
+
+/// The row of a square as seen by `color`, 0 being its first rank.
+fn relative_row(row: u8, color: Color) -> u8 {
+    match color {
+        Color::White => row,
+        Color::Black => 7 - row,
+    }
+}
+
+/// The Chebyshev distance between two squares, the number of king moves from one to the other.
+fn distance(a: u8, b: u8) -> i32 {
+    let rows = (a / 8).abs_diff(b / 8);
+    let cols = (a % 8).abs_diff(b % 8);
+    rows.max(cols) as i32
+}
+
+fn adjacent_files(col: u8) -> u64 {
+    let mut mask = 0;
+    if col > 0 {
+        mask |= FILE_A << (col - 1);
+    }
+    if col < 7 {
+        mask |= FILE_A << (col + 1);
+    }
+    mask
+}
+
+fn rank_mask(row: u8) -> u64 {
+    0xff << (8 * row)
+}
+
+/// The squares on the rows in front of `row`, from the point of view of `color`.
+fn ahead_mask(row: u8, color: Color) -> u64 {
+    match color {
+        Color::White if row >= 7 => 0,
+        Color::White => !0 << (8 * (row + 1)),
+        Color::Black => (1 << (8 * row)) - 1,
+    }
+}
+
+/// The squares on the row just behind `row`, from the point of view of `color`.
+fn behind_rank_mask(row: u8, color: Color) -> u64 {
+    match color {
+        Color::White if row > 0 => rank_mask(row - 1),
+        Color::Black if row < 7 => rank_mask(row + 1),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARAMS: EvalParams = EvalParams::DEFAULT;
+    use std::str::FromStr;
+
+    fn pawns(placement: &str) -> PawnEntry {
+        let board = Board::from_str(placement).unwrap();
+        evaluate_pawns(&board, pawn_key(&board), &PARAMS)
+    }
+
+    #[test]
+    fn test_doubled_and_isolated_pawns() {
+        // White has doubled isolated pawns on the e file, black a healthy pair
+        let entry = pawns("4k3/8/8/3pp3/8/4P3/4P3/4K3");
+        assert_eq!(entry.score[0], PARAMS.doubled + PARAMS.isolated * 2);
+        assert!(entry.score[1].eg > 0);
+    }
+
+    #[test]
+    fn test_passed_pawns() {
+        let entry = pawns("4k3/8/1P6/8/8/6p1/7P/4K3");
+        assert_eq!(entry.passed[0], 1 << 41);
+        assert_eq!(entry.passed[1], 0);
+        assert_eq!(entry.score[0], PARAMS.passed[5] + PARAMS.isolated * 2);
+    }
+
+    #[test]
+    fn test_backward_pawn() {
+        // The d3 pawn cannot be defended by the pawns on c4 and e4, and d4 is attacked by c5
+        let entry = pawns("4k3/8/8/2p1p3/2P1P3/3P4/8/4K3");
+        assert_eq!(entry.score[0], PARAMS.backward + PARAMS.connected[3] * 2);
+    }
+
+    #[test]
+    fn test_king_proximity_favours_escorted_passers() {
+        let board = Board::from_str("8/8/4KP2/8/8/8/8/k7").unwrap();
+        let entry = evaluate_pawns(&board, pawn_key(&board), &PARAMS);
+        let score = king_proximity(&board, entry.passed, &PARAMS);
+        assert!(score[0].eg > 0);
+        assert_eq!(score[1], Score::ZERO);
+    }
+
+    #[test]
+    fn test_pawn_table_returns_cached_entries() {
+        let board = Board::from_str("4k3/pp6/8/8/8/8/5PPP/4K3").unwrap();
+        let mut table = PawnTable::new(4);
+        let entry = table.probe(&board, &PARAMS);
+        assert_eq!(entry, evaluate_pawns(&board, pawn_key(&board), &PARAMS));
+        assert_eq!(table.probe(&board, &PARAMS), entry);
+        assert_eq!(table.entries.iter().flatten().count(), 1);
+    }
+}
@@ -2,10 +2,31 @@ use std::str::FromStr;
 use thiserror::Error;
 
 use crate::board::moves::Move;
-use crate::board::piece::Color;
-use crate::board::Board;
+use crate::board::piece::king::{castling_index, castling_move, Castling};
+use crate::board::piece::{Color, Piece, PieceType};
+use crate::board::{parse_square, square_name, Board, BOARD_SIZE, STANDARD_CASTLING_ROOKS};
+use crate::zobrist::{castling_key, en_passant_key, piece_key, position_key, side_key};
+
+/// FEN string of the standard starting position.
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// The empty squares the two knights of a Chess960 starting position stand on, among the five
+/// left once the bishops and the queen are placed.
+const CHESS960_KNIGHTS: [(usize, usize); 10] = [
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 2),
+    (1, 3),
+    (1, 4),
+    (2, 3),
+    (2, 4),
+    (3, 4),
+];
 
 /// Represents the state of a chess game, including the chess board, the current player, and the turn number.
+#[derive(Clone, Debug)]
 pub struct GameState {
     /// The chess board containing the arrangement of pieces.
     pub board: Board,
@@ -15,9 +36,101 @@ pub struct GameState {
     pub turn: u32,
     /// The current result of the game
     pub game_result: GameResult,
+    /// The number of half moves since the last capture or pawn move.
+    pub half_move_clock: u32,
+    /// The moves played on this state so far, most recent last.
+    pub history: Vec<PlayedMove>,
+    /// The Zobrist key of the position, as given by `crate::zobrist::position_key`, updated
+    /// move by move. It has to be set again when the board is changed directly.
+    pub key: u64,
+}
+
+/// A move that has been played on a `GameState`, together with everything needed to take it back.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlayedMove {
+    /// The move that was played.
+    pub played_move: Move,
+    /// The piece captured by the move, if any.
+    pub captured: Option<Piece>,
+    /// The squares of the king and the rook, if the move castles.
+    pub castling: Option<Castling>,
+    /// The castling availability before the move.
+    pub castling_availability: (bool, bool, bool, bool),
+    /// The en passant target square before the move.
+    pub en_passant_target: Option<u8>,
+    /// The half move clock before the move.
+    pub half_move_clock: u32,
+    /// The Zobrist key of the position before the move.
+    pub key: u64,
 }
 
 impl GameState {
+    /// Creates a game state with the standard starting position.
+    pub fn new() -> Self {
+        GameState::from_str(STARTING_FEN).expect("the starting FEN is valid")
+    }
+
+    /// Creates a game state with one of the 960 starting positions of Chess960, given by its
+    /// index from 0 to 959 in the standard numbering, or `None` for other indexes. The
+    /// position 518 is the standard starting position.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ruce::game_state::GameState;
+    ///
+    /// let game_state = GameState::new_chess960(0).unwrap();
+    /// assert_eq!(
+    ///     game_state.to_fen(),
+    ///     "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1"
+    /// );
+    /// assert!(game_state.board.chess960);
+    /// ```
+    pub fn new_chess960(index: u16) -> Option<Self> {
+        if index >= 960 {
+            return None;
+        }
+        let mut back_rank = [None; BOARD_SIZE as usize];
+        let mut index = index as usize;
+        // The bishops on a light then a dark square
+        back_rank[2 * (index % 4) + 1] = Some(PieceType::Bishop);
+        index /= 4;
+        back_rank[2 * (index % 4)] = Some(PieceType::Bishop);
+        index /= 4;
+
+        // The other pieces go on the empty squares left, in order
+        let mut place = |piece_type: PieceType, nth: usize| {
+            let file = (0..back_rank.len())
+                .filter(|&file| back_rank[file].is_none())
+                .nth(nth)
+                .expect("there is an empty square left");
+            back_rank[file] = Some(piece_type);
+        };
+        place(PieceType::Queen, index % 6);
+        index /= 6;
+        let (first, second) = CHESS960_KNIGHTS[index];
+        // The second knight is placed once the first one took its square
+        place(PieceType::Knight, first);
+        place(PieceType::Knight, second - 1);
+        for piece_type in [PieceType::Rook, PieceType::King, PieceType::Rook] {
+            place(piece_type, 0);
+        }
+
+        let pieces: String = back_rank
+            .iter()
+            .flatten()
+            .map(|piece_type| piece_type.as_char().to_ascii_lowercase())
+            .collect();
+        let fen = format!(
+            "{}/pppppppp/8/8/8/8/PPPPPPPP/{} w KQkq - 0 1",
+            pieces,
+            pieces.to_ascii_uppercase()
+        );
+        let mut game_state = GameState::from_str(&fen).expect("the Chess960 FEN is valid");
+        game_state.board.chess960 = true;
+        Some(game_state)
+    }
+
     /// Generates all possible moves for the pieces of the current player.
     ///
     /// This method calls the `generate_moves` method of the `Board` to calculate all possible moves
@@ -30,10 +143,10 @@ impl GameState {
     /// # Examples
     ///
     /// ```
-    /// use chess_engine::board::piece::Color;
-    /// use chess_engine::game_state::GameState;
+    /// use ruce::game_state::GameState;
+    /// use std::str::FromStr;
     ///
-    /// // Assume `game_state` is initialized with the chess board and the current player.
+    /// let game_state = GameState::from_str("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
     /// let moves = game_state.generate_moves();
     /// // Now `moves` contains all valid moves for the current player's pieces on the board.
     /// ```
@@ -41,26 +154,366 @@ impl GameState {
         // Delegates the move generation to the `Board` struct's `generate_moves` method.
         self.board.generate_moves(self.current_player)
     }
+
+    /// Generates the moves of the current player that do not leave their own king in check.
+    ///
+    /// Castling is additionally rejected when the king is in check or passes through an
+    /// attacked square.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ruce::game_state::GameState;
+    ///
+    /// let game_state = GameState::new();
+    /// assert_eq!(game_state.generate_legal_moves().len(), 20);
+    /// ```
+    pub fn generate_legal_moves(&self) -> Vec<Move> {
+        let mut scratch = GameState {
+            board: self.board.clone(),
+            history: Vec::new(),
+            ..*self
+        };
+        self.generate_moves()
+            .into_iter()
+            .filter(|chess_move| scratch.is_legal(chess_move))
+            .collect()
+    }
+
+    /// Checks whether a pseudo-legal move of the current player is legal.
+    fn is_legal(&mut self, chess_move: &Move) -> bool {
+        let legal = self.make_legal_move(chess_move);
+        if legal {
+            self.unmake_move();
+        }
+        legal
+    }
+
+    /// Plays a pseudo-legal move of the current player if it is legal.
+    ///
+    /// This is cheaper than checking the move against `generate_legal_moves` first, and is
+    /// meant for the search, which plays most of the moves it generates anyway.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the move was played, `false` if it was illegal, in which case the state is
+    /// left unchanged.
+    pub fn make_legal_move(&mut self, chess_move: &Move) -> bool {
+        let color = self.current_player;
+        if let Some(castling) = castling_move(&self.board, chess_move) {
+            // The king's target square is checked once it is there
+            let (from, to) = (castling.king_from, castling.king_to);
+            let mut passed_squares = from.min(to) + 1..from.max(to);
+            if self.board.is_in_check(color)
+                || passed_squares
+                    .any(|square| self.board.is_square_attacked(square, color.opposite()))
+            {
+                return false;
+            }
+        }
+        self.make_move(chess_move);
+        if self.board.is_in_check(color) {
+            self.unmake_move();
+            return false;
+        }
+        true
+    }
+
+    /// Checks whether a move of the current player is a castling move.
+    pub fn is_castling(&self, chess_move: &Move) -> bool {
+        castling_move(&self.board, chess_move).is_some()
+    }
+
+    /// Checks whether the current player is in check.
+    pub fn is_in_check(&self) -> bool {
+        self.board.is_in_check(self.current_player)
+    }
+
+    /// Checks whether the current player has been checkmated.
+    pub fn is_checkmate(&self) -> bool {
+        self.is_in_check() && self.generate_legal_moves().is_empty()
+    }
+
+    /// Checks whether the current player has no legal moves while not being in check.
+    pub fn is_stalemate(&self) -> bool {
+        !self.is_in_check() && self.generate_legal_moves().is_empty()
+    }
+
+    /// Plays a move for the current player.
+    ///
+    /// The move is expected to be at least pseudo-legal, as returned by `generate_moves`.
+    /// Captures (including en passant), castling, promotions, castling availability, the en
+    /// passant target, the clocks and the current player are all updated, and the move is
+    /// recorded in `history` so it can be taken back with `unmake_move`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no piece on the initial square of the move.
+    pub fn make_move(&mut self, chess_move: &Move) {
+        let from = chess_move.initial_square;
+        let to = chess_move.target_square;
+        let castling = castling_move(&self.board, chess_move);
+        let piece = self
+            .board
+            .remove_piece(from)
+            .expect("a move must start from an occupied square");
+
+        let played = PlayedMove {
+            played_move: chess_move.clone(),
+            captured: None,
+            castling,
+            castling_availability: self.board.castling_availability,
+            en_passant_target: self.board.en_passant_target,
+            half_move_clock: self.half_move_clock,
+            key: self.key,
+        };
+        // The castling rights and the en passant target are xored in again once updated
+        let mut key = self.key
+            ^ side_key()
+            ^ piece_key(&piece, from)
+            ^ castling_key(self.board.castling_availability)
+            ^ en_passant_key(self.board.en_passant_target);
+
+        let captured = if let Some(castling) = castling {
+            // The rook is moved before the king, which may take its square
+            if let Some(rook) = self.board.remove_piece(castling.rook_from) {
+                self.board.set_piece(castling.rook_to, rook);
+                key ^= piece_key(&rook, castling.rook_from) ^ piece_key(&rook, castling.rook_to);
+            }
+            None
+        } else if piece.piece_type == PieceType::Pawn
+            && self.board.en_passant_target == Some(to)
+            && self.board.get_piece(to).is_none()
+        {
+            // En passant, the captured pawn is behind the target square
+            let victim = en_passant_victim(from, to);
+            let captured = self.board.remove_piece(victim);
+            if let Some(captured) = &captured {
+                key ^= piece_key(captured, victim);
+            }
+            captured
+        } else {
+            let captured = self.board.remove_piece(to);
+            if let Some(captured) = &captured {
+                key ^= piece_key(captured, to);
+            }
+            captured
+        };
+
+        let placed = Piece {
+            piece_type: chess_move.promotion.unwrap_or(piece.piece_type),
+            color: piece.color,
+        };
+        let placed_square = castling.map_or(to, |castling| castling.king_to);
+        self.board.set_piece(placed_square, placed);
+        key ^= piece_key(&placed, placed_square);
+
+        update_castling_availability(&mut self.board, piece, from, to);
+
+        self.board.en_passant_target =
+            if piece.piece_type == PieceType::Pawn && from.abs_diff(to) == 2 * BOARD_SIZE {
+                Some((from + to) / 2)
+            } else {
+                None
+            };
+        self.key = key
+            ^ castling_key(self.board.castling_availability)
+            ^ en_passant_key(self.board.en_passant_target);
+
+        if piece.piece_type == PieceType::Pawn || captured.is_some() {
+            self.half_move_clock = 0;
+        } else {
+            self.half_move_clock += 1;
+        }
+        if self.current_player == Color::Black {
+            self.turn += 1;
+        }
+        self.current_player = self.current_player.opposite();
+
+        self.history.push(PlayedMove { captured, ..played });
+    }
+
+    /// Takes back the last move played with `make_move`.
+    ///
+    /// # Returns
+    ///
+    /// The move that was taken back, or `None` if no move has been played.
+    pub fn unmake_move(&mut self) -> Option<Move> {
+        let played = self.history.pop()?;
+        let from = played.played_move.initial_square;
+        let to = played.played_move.target_square;
+
+        self.current_player = self.current_player.opposite();
+        if self.current_player == Color::Black {
+            self.turn -= 1;
+        }
+        self.half_move_clock = played.half_move_clock;
+        self.key = played.key;
+        self.board.castling_availability = played.castling_availability;
+        self.board.en_passant_target = played.en_passant_target;
+
+        if let Some(castling) = played.castling {
+            let king = self
+                .board
+                .remove_piece(castling.king_to)
+                .expect("the king is on its castling square");
+            let rook = self.board.remove_piece(castling.rook_to);
+            self.board.set_piece(from, king);
+            if let Some(rook) = rook {
+                self.board.set_piece(castling.rook_from, rook);
+            }
+            return Some(played.played_move);
+        }
+
+        let mut piece = self
+            .board
+            .remove_piece(to)
+            .expect("the moved piece is on the target square");
+        if played.played_move.promotion.is_some() {
+            piece.piece_type = PieceType::Pawn;
+        }
+        self.board.set_piece(from, piece);
+
+        if let Some(captured) = played.captured {
+            let capture_square =
+                if piece.piece_type == PieceType::Pawn && played.en_passant_target == Some(to) {
+                    en_passant_victim(from, to)
+                } else {
+                    to
+                };
+            self.board.set_piece(capture_square, captured);
+        }
+
+        Some(played.played_move)
+    }
+
+    /// Passes the turn to the opponent without moving, as the null move pruning of the search
+    /// does. The null move is not recorded in `history`.
+    ///
+    /// # Returns
+    ///
+    /// The en passant target square before the null move, to be given back to
+    /// `unmake_null_move`.
+    pub fn make_null_move(&mut self) -> Option<u8> {
+        if self.current_player == Color::Black {
+            self.turn += 1;
+        }
+        self.current_player = self.current_player.opposite();
+        let en_passant_target = self.board.en_passant_target.take();
+        self.key ^= side_key() ^ en_passant_key(en_passant_target);
+        en_passant_target
+    }
+
+    /// Takes back a null move played with `make_null_move`.
+    pub fn unmake_null_move(&mut self, en_passant_target: Option<u8>) {
+        self.current_player = self.current_player.opposite();
+        if self.current_player == Color::Black {
+            self.turn -= 1;
+        }
+        self.board.en_passant_target = en_passant_target;
+        self.key ^= side_key() ^ en_passant_key(en_passant_target);
+    }
+
+    /// Returns the FEN string describing the current position.
+    ///
+    /// Castling rights are written as in X-FEN: `KQkq` for the outermost rooks, the only ones in
+    /// standard chess, and the file of the rook otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ruce::game_state::{GameState, STARTING_FEN};
+    ///
+    /// assert_eq!(GameState::new().to_fen(), STARTING_FEN);
+    /// ```
+    pub fn to_fen(&self) -> String {
+        self.fen(false)
+    }
+
+    /// Returns the Shredder-FEN string describing the current position, where castling rights
+    /// are always written as the files of the rooks, `HAha` in the starting position.
+    pub fn to_shredder_fen(&self) -> String {
+        self.fen(true)
+    }
+
+    fn fen(&self, shredder: bool) -> String {
+        let active_color = match self.current_player {
+            Color::White => "w",
+            Color::Black => "b",
+        };
+        let en_passant_target = self
+            .board
+            .en_passant_target
+            .map_or("-".to_string(), square_name);
+        format!(
+            "{} {} {} {} {} {}",
+            self.board.piece_placement(),
+            active_color,
+            castling_availability_to_str(&self.board, shredder),
+            en_passant_target,
+            self.half_move_clock,
+            self.turn
+        )
+    }
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        GameState::new()
+    }
+}
+
+/// Returns the square of the pawn captured by an en passant move.
+fn en_passant_victim(from: u8, to: u8) -> u8 {
+    // Same row as the capturing pawn, same column as the target square
+    (from / BOARD_SIZE) * BOARD_SIZE + to % BOARD_SIZE
+}
+
+/// Removes the castling availability lost by a piece moving from a square to another: all of
+/// it for a king, and the right of a rook moving or taken.
+fn update_castling_availability(board: &mut Board, piece: Piece, from: u8, to: u8) {
+    let mut rights = board.castling_rights();
+    for (index, right) in rights.iter_mut().enumerate() {
+        let rook = board.castling_rooks[index];
+        // The rights of white come first
+        let king_moved =
+            piece.piece_type == PieceType::King && (index < 2) == (piece.color == Color::White);
+        if king_moved || rook == from || rook == to {
+            *right = false;
+        }
+    }
+    board.set_castling_rights(rights);
 }
 
 #[derive(Error, Debug, PartialEq, Eq)]
 pub enum ParseFenError {
     #[error("Invalid character(s) in FEN! First invalid character: {0}")]
     InvalidPiecePlacement(String),
+    #[error("Malformed piece placement in FEN: {0}")]
+    MalformedPiecePlacement(String),
+    #[error("Invalid FEN: missing {0}")]
+    MissingField(String),
+    #[error("Invalid active color in FEN: {0}")]
+    InvalidActiveColor(String),
+    #[error("Invalid castling availability in FEN: {0}")]
+    InvalidCastlingAvailability(String),
+    #[error("Invalid en passant target in FEN: {0}")]
+    InvalidEnPassantTarget(String),
+    #[error("Invalid move counter in FEN: {0}")]
+    InvalidMoveCounter(String),
 }
 
 impl FromStr for GameState {
     type Err = ParseFenError;
 
     /// The FEN string is parsed into a GameState struct
-    /// The FEN string is a space-separated string of 6 fields
-    /// currently only board is raising errors if FEN string is invalid
-    /// TODO: add error handling for other fields
-    /// TODO: Error type should be properly defined
+    /// The FEN string is a space-separated string of 6 fields.
+    /// The half move clock and the full move number may be omitted, in which case they
+    /// default to 0 and 1.
     ///
     /// # Examples
     /// ```
-    /// use chess_engine::game_state::GameState;
+    /// use ruce::game_state::GameState;
     /// use std::str::FromStr;
     ///
     /// let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
@@ -68,47 +521,67 @@ impl FromStr for GameState {
     /// ```
     fn from_str(fen: &str) -> Result<Self, Self::Err> {
         let mut iter = fen.split_whitespace();
+        let mut next_field = |name: &str| {
+            iter.next()
+                .ok_or_else(|| ParseFenError::MissingField(name.to_string()))
+        };
 
         // Parse the FEN string
-        let piece_placement = iter.next().expect("Invalid FEN: missing piece placement");
-        let active_color = iter.next().expect("Invalid FEN: missing active color");
-        let castling_availability = iter
-            .next()
-            .expect("Invalid FEN: missing castling availability");
-        let _en_passant_target = iter.next().expect("Invalid FEN: missing en passant target");
-        let _half_move_clock = iter.next().expect("Invalid FEN: missing half-move clock");
-        let full_move_number = iter.next().expect("Invalid FEN: missing full move number");
+        let piece_placement = next_field("piece placement")?;
+        let active_color = next_field("active color")?;
+        let castling_availability = next_field("castling availability")?;
+        let en_passant_target = next_field("en passant target")?;
+        let half_move_clock = next_field("half-move clock").unwrap_or("0");
+        let full_move_number = next_field("full move number").unwrap_or("1");
 
         let mut game_state: GameState = GameState {
             board: Board::from_str(piece_placement)?,
             current_player: Color::White,
-            turn: full_move_number.parse().unwrap(),
+            turn: 1,
             game_result: GameResult::Undecided,
+            half_move_clock: 0,
+            history: Vec::new(),
+            key: 0,
         };
 
         // Parse the active color
         match active_color {
             "b" => game_state.current_player = Color::Black,
             "w" => game_state.current_player = Color::White,
-            _ => panic!("Invalid active color in FEN string: {}", active_color),
+            _ => return Err(ParseFenError::InvalidActiveColor(active_color.to_string())),
         }
 
-        // Parse current turn
+        // Parse the clocks
+        game_state.half_move_clock = half_move_clock
+            .parse()
+            .map_err(|_| ParseFenError::InvalidMoveCounter(half_move_clock.to_string()))?;
         game_state.turn = full_move_number
             .parse()
-            .expect("Invalid FEN: invalid full move number");
+            .map_err(|_| ParseFenError::InvalidMoveCounter(full_move_number.to_string()))?;
 
-        // castling availabilty
-        game_state.board.castling_availability = parse_castling_availablity(castling_availability);
+        parse_castling_availability(castling_availability, &mut game_state.board)?;
 
-        // TODO missing other rules:
-        // en passant
-        // half move clock
+        // en passant, behind a pawn of the opponent that just moved two squares
+        if en_passant_target != "-" {
+            let rank = match game_state.current_player {
+                Color::White => 5,
+                Color::Black => 2,
+            };
+            let square = parse_square(en_passant_target)
+                .filter(|square| square / BOARD_SIZE == rank)
+                .ok_or_else(|| {
+                    ParseFenError::InvalidEnPassantTarget(en_passant_target.to_string())
+                })?;
+            game_state.board.en_passant_target = Some(square);
+        }
 
+        game_state.key = position_key(&game_state.board, game_state.current_player);
         Ok(game_state)
     }
 }
 
+/// The result of a chess game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum GameResult {
     WhiteWon,
     BlackWon,
@@ -116,53 +589,419 @@ pub enum GameResult {
     Undecided,
 }
 
-/// Parses a castling availability string and returns a tuple representing castling availability.
-///
-/// The castling availability string is a sequence of characters representing the availability of
-/// castling for both white and black players. The characters used are:
-/// - 'K': King-side castling for white.
-/// - 'Q': Queen-side castling for white.
-/// - 'k': King-side castling for black.
-/// - 'q': Queen-side castling for black.
-///
-/// # Arguments
+/// Formats the castling availability of a board as the castling field of a FEN string.
 ///
-/// * `castling_availabilyt_str` - The castling availability string to parse.
-///
-/// # Returns
-///
-/// A tuple with four elements representing the castling availability:
-/// * Element 0: Availability of King-side castling for white.
-/// * Element 1: Availability of Queen-side castling for white.
-/// * Element 2: Availability of King-side castling for black.
-/// * Element 3: Availability of Queen-side castling for black.
-///
-/// # Examples
+/// A right is written with its letter, uppercase for white, when the rook is the outermost one
+/// on its side of the king, and with the file of the rook otherwise, or always in
+/// Shredder-FEN.
+fn castling_availability_to_str(board: &Board, shredder: bool) -> String {
+    let castling: String = board
+        .castling_rights()
+        .iter()
+        .enumerate()
+        .filter(|(_, available)| **available)
+        .map(|(index, _)| {
+            let rook_square = board.castling_rooks[index];
+            let file = rook_square % BOARD_SIZE;
+            let back_rank = rook_square - file;
+            let color = if index < 2 {
+                Color::White
+            } else {
+                Color::Black
+            };
+            let king_side = index % 2 == 0;
+            let outer_files = if king_side {
+                file + 1..BOARD_SIZE
+            } else {
+                0..file
+            };
+            let outermost = !outer_files
+                .into_iter()
+                .any(|outer| is_rook(board, back_rank + outer, color));
+            let c = match (shredder || !outermost, king_side) {
+                (true, _) => (b'a' + file) as char,
+                (false, true) => 'k',
+                (false, false) => 'q',
+            };
+            match color {
+                Color::White => c.to_ascii_uppercase(),
+                Color::Black => c,
+            }
+        })
+        .collect();
+    if castling.is_empty() {
+        "-".to_string()
+    } else {
+        castling
+    }
+}
+
+/// Parses the castling field of a FEN string into the castling availability and rooks of a
+/// board whose pieces are already placed.
 ///
-/// ```
-/// let castling_str = "KQkq";
-/// let castling_availability = parse_castling_availablity(castling_str);
-/// assert_eq!(castling_availability, (true, true, true, true));
-/// ```
+/// Standard FEN, Shredder-FEN and X-FEN are all accepted:
+/// - 'K' and 'Q' (or 'k' and 'q' for black) are the outermost rooks on the king and queen side
+///   of the king, or the corners when there are none.
+/// - 'A' to 'H' (or 'a' to 'h' for black) are the rooks on these files.
+/// - '-' is no castling at all.
 ///
-/// ```
-/// let castling_str = "Kq";
-/// let castling_availability = parse_castling_availablity(castling_str);
-/// assert_eq!(castling_availability, (true, false, false, true));
-/// ```
-fn parse_castling_availablity(castling_availabilyt_str: &str) -> (bool, bool, bool, bool) {
-    let mut castling_availablity = (false, false, false, false);
-    if castling_availabilyt_str.contains('K') {
-        castling_availablity.0 = true;
-    }
-    if castling_availabilyt_str.contains('Q') {
-        castling_availablity.1 = true;
-    }
-    if castling_availabilyt_str.contains('k') {
-        castling_availablity.2 = true;
-    }
-    if castling_availabilyt_str.contains('q') {
-        castling_availablity.3 = true;
-    }
-    castling_availablity
+/// The board is set to Chess960 when a right is for a king or a rook away from its standard
+/// square.
+fn parse_castling_availability(castling: &str, board: &mut Board) -> Result<(), ParseFenError> {
+    let mut rights = [false; 4];
+    let mut rooks = STANDARD_CASTLING_ROOKS;
+    let mut king_files = [None; 4];
+    let letters = if castling == "-" { "" } else { castling };
+    for c in letters.chars() {
+        let color = if c.is_ascii_uppercase() {
+            Color::White
+        } else {
+            Color::Black
+        };
+        let back_rank = match color {
+            Color::White => 0,
+            Color::Black => 56,
+        };
+        let king_file = board
+            .king_square(color)
+            .filter(|square| square / BOARD_SIZE == back_rank / BOARD_SIZE)
+            .map(|square| square % BOARD_SIZE);
+        // Without a king on the back rank, the rooks are looked for on both sides of the e file
+        let king_side_files = king_file.map_or(5, |file| file + 1)..BOARD_SIZE;
+        let queen_side_files = 0..king_file.unwrap_or(4);
+        let (king_side, rook_file) = match c.to_ascii_lowercase() {
+            'k' => (
+                true,
+                king_side_files
+                    .rev()
+                    .find(|&file| is_rook(board, back_rank + file, color))
+                    .unwrap_or(BOARD_SIZE - 1),
+            ),
+            'q' => (
+                false,
+                queen_side_files
+                    .into_iter()
+                    .find(|&file| is_rook(board, back_rank + file, color))
+                    .unwrap_or(0),
+            ),
+            file @ 'a'..='h' => {
+                let file = file as u8 - b'a';
+                (file >= king_side_files.start, file)
+            }
+            _ => {
+                return Err(ParseFenError::InvalidCastlingAvailability(
+                    castling.to_string(),
+                ))
+            }
+        };
+        let index = castling_index(color, king_side);
+        // A right without its rook could never be used, it is dropped
+        rights[index] = is_rook(board, back_rank + rook_file, color);
+        rooks[index] = back_rank + rook_file;
+        king_files[index] = king_file;
+    }
+
+    board.set_castling_rights(rights);
+    board.castling_rooks = rooks;
+    board.chess960 = (0..4).any(|index| {
+        rights[index]
+            && (rooks[index] != STANDARD_CASTLING_ROOKS[index]
+                || king_files[index].is_some_and(|file| file != 4))
+    });
+    Ok(())
+}
+
+/// Checks whether there is a rook of a color on a square.
+fn is_rook(board: &Board, square: u8, color: Color) -> bool {
+    board.get_piece(square)
+        == Some(&Piece {
+            piece_type: PieceType::Rook,
+            color,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Counts the leaf nodes of the legal move tree, see https://www.chessprogramming.org/Perft
+    fn perft(game_state: &mut GameState, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let mut nodes = 0;
+        for chess_move in game_state.generate_legal_moves() {
+            game_state.make_move(&chess_move);
+            nodes += perft(game_state, depth - 1);
+            game_state.unmake_move();
+        }
+        nodes
+    }
+
+    #[test]
+    fn test_perft_starting_position() {
+        let mut game_state = GameState::new();
+        assert_eq!(perft(&mut game_state, 1), 20);
+        assert_eq!(perft(&mut game_state, 2), 400);
+        assert_eq!(perft(&mut game_state, 3), 8902);
+        assert_eq!(game_state.to_fen(), STARTING_FEN);
+    }
+
+    #[test]
+    fn test_perft_castling_and_promotions() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut game_state = GameState::from_str(fen).unwrap();
+        assert_eq!(perft(&mut game_state, 1), 48);
+        assert_eq!(perft(&mut game_state, 2), 2039);
+        assert_eq!(game_state.to_fen(), fen);
+    }
+
+    #[test]
+    fn test_perft_en_passant() {
+        let fen = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+        let mut game_state = GameState::from_str(fen).unwrap();
+        assert_eq!(perft(&mut game_state, 3), 2812);
+    }
+
+    #[test]
+    fn test_perft_promotions() {
+        let fen = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+        let mut game_state = GameState::from_str(fen).unwrap();
+        assert_eq!(perft(&mut game_state, 2), 264);
+        assert_eq!(perft(&mut game_state, 3), 9467);
+    }
+
+    #[test]
+    fn test_perft_chess960() {
+        let cases: [(&str, [u64; 3]); 3] = [
+            (
+                "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+                [21, 528, 12189],
+            ),
+            (
+                "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+                [21, 807, 18002],
+            ),
+            (
+                "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+                [20, 479, 10471],
+            ),
+        ];
+        for (fen, nodes) in cases {
+            let mut game_state = GameState::from_str(fen).unwrap();
+            assert!(game_state.board.chess960);
+            for (depth, nodes) in nodes.into_iter().enumerate() {
+                assert_eq!(perft(&mut game_state, depth as u32 + 1), nodes, "{}", fen);
+            }
+            assert_eq!(game_state.to_shredder_fen(), fen);
+        }
+    }
+
+    #[test]
+    fn test_chess960_castling() {
+        let fen = "4k3/8/8/8/8/8/8/RK5R w KQ - 0 1";
+        let mut game_state = GameState::from_str(fen).unwrap();
+        assert!(game_state.board.chess960);
+        assert_eq!(
+            game_state.to_shredder_fen(),
+            "4k3/8/8/8/8/8/8/RK5R w HA - 0 1"
+        );
+        let moves = game_state.generate_legal_moves();
+        assert!(moves.contains(&Move::new(1, 0)) && moves.contains(&Move::new(1, 7)));
+
+        game_state.make_move(&Move::new(1, 0));
+        assert_eq!(game_state.to_fen(), "4k3/8/8/8/8/8/8/2KR3R b - - 1 1");
+        game_state.unmake_move();
+        game_state.make_move(&Move::new(1, 7));
+        assert_eq!(game_state.to_fen(), "4k3/8/8/8/8/8/8/R4RK1 b - - 1 1");
+        game_state.unmake_move();
+        assert_eq!(game_state.to_fen(), fen);
+
+        // f1 is attacked: the king cannot pass it, but the rook can go there while the king
+        // stays on g1
+        let mut game_state = GameState::from_str("5rk1/8/8/8/8/8/8/R5KR w HA - 0 1").unwrap();
+        let moves = game_state.generate_legal_moves();
+        assert!(!moves.contains(&Move::new(6, 0)));
+        assert!(moves.contains(&Move::new(6, 7)));
+        game_state.make_move(&Move::new(6, 7));
+        assert_eq!(game_state.to_fen(), "5rk1/8/8/8/8/8/8/R4RK1 b - - 1 1");
+    }
+
+    #[test]
+    fn test_castling_fields() {
+        // The right of the inner rook is written with its file
+        for fen in [
+            "4k3/8/8/8/8/8/8/RR2K3 w B - 0 1",
+            "rr2k3/8/8/8/8/8/8/4K3 w b - 0 1",
+            "r3k2r/8/8/8/8/8/8/R3K2R w Kq - 0 1",
+        ] {
+            assert_eq!(GameState::from_str(fen).unwrap().to_fen(), fen);
+        }
+        let game_state = GameState::from_str("1r2k1r1/8/8/8/8/8/8/4K3 w gb - 0 1").unwrap();
+        assert_eq!(game_state.to_fen(), "1r2k1r1/8/8/8/8/8/8/4K3 w kq - 0 1");
+        let game_state = GameState::from_str("4k3/8/8/8/8/8/8/RR2K3 w Q - 0 1").unwrap();
+        assert_eq!(game_state.board.castling_rooks[1], 0);
+        assert!(!game_state.board.chess960);
+        let game_state = GameState::from_str(&STARTING_FEN.replace("KQkq", "HAha")).unwrap();
+        assert_eq!(game_state.to_fen(), STARTING_FEN);
+        assert!(!game_state.board.chess960);
+        assert_eq!(
+            GameState::from_str("4k3/8/8/8/8/8/8/4K3 w KX - 0 1").unwrap_err(),
+            ParseFenError::InvalidCastlingAvailability("KX".to_string())
+        );
+    }
+
+    #[test]
+    fn test_castling_needs_its_rook() {
+        // The right of a missing rook is dropped, a knight does not stand in for it
+        let mut game_state = GameState::from_str("4k3/8/8/8/8/8/8/4K2n w K - 0 1").unwrap();
+        assert_eq!(game_state.to_fen(), "4k3/8/8/8/8/8/8/4K2n w - - 0 1");
+        let castling = Move::new(4, 6);
+        assert!(!game_state.generate_legal_moves().contains(&castling));
+
+        // Nor when the right is set on the board directly
+        game_state
+            .board
+            .set_castling_rights([true, false, false, false]);
+        assert!(!game_state.generate_legal_moves().contains(&castling));
+        assert_eq!(castling_move(&game_state.board, &castling), None);
+    }
+
+    #[test]
+    fn test_new_chess960() {
+        let standard = GameState::new_chess960(518).unwrap();
+        assert_eq!(standard.to_fen(), STARTING_FEN);
+        assert_eq!(
+            GameState::new_chess960(959).unwrap().to_shredder_fen(),
+            "rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/RKRNNQBB w CAca - 0 1"
+        );
+        assert!(GameState::new_chess960(960).is_none());
+
+        let mut back_ranks = std::collections::HashSet::new();
+        for index in 0..960 {
+            let game_state = GameState::new_chess960(index).unwrap();
+            let back_rank: Vec<PieceType> = (0..8)
+                .map(|square| game_state.board.get_piece(square).unwrap().piece_type)
+                .collect();
+            let files = |piece_type| {
+                (0..8)
+                    .filter(|&file| back_rank[file] == piece_type)
+                    .collect::<Vec<_>>()
+            };
+            let (bishops, rooks, king) = (
+                files(PieceType::Bishop),
+                files(PieceType::Rook),
+                files(PieceType::King)[0],
+            );
+            assert_ne!(bishops[0] % 2, bishops[1] % 2);
+            assert!(rooks[0] < king && king < rooks[1]);
+            back_ranks.insert(back_rank);
+        }
+        assert_eq!(back_ranks.len(), 960);
+    }
+
+    #[test]
+    fn test_make_move_updates_fen() {
+        let mut game_state = GameState::new();
+        game_state.make_move(&Move::new(12, 28));
+        assert_eq!(
+            game_state.to_fen(),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+        );
+        game_state.make_move(&Move::new(62, 45));
+        assert_eq!(
+            game_state.to_fen(),
+            "rnbqkb1r/pppppppp/5n2/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 1 2"
+        );
+        assert_eq!(game_state.unmake_move(), Some(Move::new(62, 45)));
+        assert_eq!(game_state.unmake_move(), Some(Move::new(12, 28)));
+        assert_eq!(game_state.unmake_move(), None);
+        assert_eq!(game_state.to_fen(), STARTING_FEN);
+    }
+
+    #[test]
+    fn test_null_move() {
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+        let mut game_state = GameState::from_str(fen).unwrap();
+        let en_passant_target = game_state.make_null_move();
+        assert_eq!(
+            game_state.to_fen(),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2"
+        );
+        game_state.unmake_null_move(en_passant_target);
+        assert_eq!(game_state.to_fen(), fen);
+    }
+
+    // Checks the incremental key against the one computed from scratch over the move tree
+    fn check_keys(game_state: &mut GameState, depth: u32) {
+        assert_eq!(
+            game_state.key,
+            position_key(&game_state.board, game_state.current_player),
+            "{}",
+            game_state.to_fen()
+        );
+        if depth == 0 {
+            return;
+        }
+        let en_passant_target = game_state.make_null_move();
+        check_keys(game_state, 0);
+        game_state.unmake_null_move(en_passant_target);
+        for chess_move in game_state.generate_legal_moves() {
+            game_state.make_move(&chess_move);
+            check_keys(game_state, depth - 1);
+            game_state.unmake_move();
+        }
+    }
+
+    #[test]
+    fn test_incremental_key() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+        ] {
+            let mut game_state = GameState::from_str(fen).unwrap();
+            let key = game_state.key;
+            check_keys(&mut game_state, 3);
+            assert_eq!(game_state.key, key);
+        }
+    }
+
+    #[test]
+    fn test_checkmate_and_stalemate() {
+        let mate = GameState::from_str("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(mate.is_checkmate());
+        assert!(!mate.is_stalemate());
+        let stalemate = GameState::from_str("7k/8/6QK/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(stalemate.is_stalemate());
+        assert!(!stalemate.is_checkmate());
+    }
+
+    #[test]
+    fn test_invalid_fen_fields() {
+        assert_eq!(
+            GameState::from_str("8/8/8/8/8/8/8/8 x - - 0 1").unwrap_err(),
+            ParseFenError::InvalidActiveColor("x".to_string())
+        );
+        assert_eq!(
+            GameState::from_str("8/8/8/8/8/8/8/8 w").unwrap_err(),
+            ParseFenError::MissingField("castling availability".to_string())
+        );
+        // The en passant target is on the sixth rank for white, the third for black
+        for (fen, target) in [
+            ("4k3/8/8/8/8/8/8/4K3 w - a1 0 1", "a1"),
+            ("4k3/8/8/8/8/8/8/4K3 b - h8 0 1", "h8"),
+            ("4k3/8/8/8/4P3/8/8/4K3 w - e3 0 1", "e3"),
+            ("4k3/8/8/3p4/8/8/8/4K3 b - d6 0 1", "d6"),
+            ("4k3/8/8/8/8/8/8/4K3 w - e9 0 1", "e9"),
+        ] {
+            assert_eq!(
+                GameState::from_str(fen).unwrap_err(),
+                ParseFenError::InvalidEnPassantTarget(target.to_string())
+            );
+        }
+        let game_state = GameState::from_str("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1").unwrap();
+        assert_eq!(game_state.board.en_passant_target, Some(20));
+    }
 }
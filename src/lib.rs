@@ -0,0 +1,17 @@
+//! ruce, a Rust chess engine.
+
+pub mod board;
+pub mod book;
+pub mod eco;
+pub mod epd;
+pub mod eval;
+pub mod game_state;
+pub mod pgn;
+pub mod san;
+pub mod search;
+#[cfg(feature = "syzygy")]
+pub mod syzygy;
+pub mod tablebase;
+pub mod tune;
+pub mod uci;
+pub mod zobrist;
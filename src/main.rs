@@ -1,18 +1,342 @@
-use game_state::GameState;
+use ruce::book::build::{BookBuilder, BuildOptions};
+use ruce::book::Book;
+use ruce::eco::EcoClassifier;
+use ruce::eval::evaluate_trace;
+use ruce::eval::params::EvalParams;
+use ruce::game_state::{GameState, STARTING_FEN};
+use ruce::pgn::PgnReader;
+use ruce::san::move_to_san;
+use ruce::search::mate::find_mate;
+use ruce::search::Searcher;
+use ruce::tablebase::{Material, Tablebase};
+use ruce::tune::{load_positions, Tuner};
+use ruce::uci::{info_line, parse_go, UciEngine};
+use std::fs::{self, File};
+use std::io::BufReader;
 use std::str::FromStr;
 
-mod board;
-mod game_state;
+const USAGE: &str = "Usage:
+  ruce [uci]                     run the UCI protocol on standard input and output
+  ruce board [<fen>]             print the board and its number of moves
+  ruce eval [<fen>]              print the static evaluation of a position, term by term
+  ruce search <fen> [<limits>]   search a position, limits as in UCI go, e.g. depth 6
+  ruce mate <n> <fen>            prove or refute a forced mate in n moves
+  ruce tbgen <material> [-o <file>]
+                                 generate the distance-to-mate table of an ending, e.g. KQvKR
+  ruce tbprobe <file> <fen>      probe a position in a generated table
+  ruce book probe <file> [<fen>] list the moves of a Polyglot book in a position
+  ruce book build <pgn> [-o <file>] [--max-ply <n>] [--min-games <n>] [--min-elo <n>]
+                                 build a Polyglot book from the first moves of games
+  ruce eco <pgn> [-o <file>]     print the ECO code of each game, or write the games tagged
+                                 with their ECO code and opening to a PGN file
+  ruce tune <positions> [--params <file>] [--iterations <n>] [--rust] [-o <file>]
+                                 tune the evaluation on positions labeled with results";
 
 fn main() {
-    let game_state =
-        GameState::from_str("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
-    if let Err(e) = game_state {
-        println!("Error: {}", e);
-        return;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match args.first() {
+        None | Some(&"uci") => UciEngine::new().run(std::io::stdin().lock()),
+        Some(&"board") => print_board(args.get(1).copied().unwrap_or(STARTING_FEN)),
+        Some(&"eval") => eval(args.get(1).copied().unwrap_or(STARTING_FEN)),
+        Some(&"search") if args.len() > 1 => search(args[1], &args[2..]),
+        Some(&"mate") if args.len() > 2 => mate(args[1], args[2]),
+        Some(&"tbgen") if args.len() > 1 => tbgen(args[1], &args[2..]),
+        Some(&"tbprobe") if args.len() > 2 => tbprobe(args[1], args[2]),
+        Some(&"book") => match &args[1..] {
+            ["probe", path] => book_probe(path, STARTING_FEN),
+            ["probe", path, fen] => book_probe(path, fen),
+            ["build", pgn, options @ ..] => book_build(pgn, options),
+            _ => println!("{}", USAGE),
+        },
+        Some(&"eco") => match &args[1..] {
+            [pgn] => eco(pgn, None),
+            [pgn, "-o", output] => eco(pgn, Some(output)),
+            _ => println!("{}", USAGE),
+        },
+        Some(&"tune") if args.len() > 1 => tune(args[1], &args[2..]),
+        _ => println!("{}", USAGE),
     }
-    let game_state = game_state.unwrap();
+}
+
+fn parse_fen(fen: &str) -> Option<GameState> {
+    match GameState::from_str(fen) {
+        Ok(game_state) => Some(game_state),
+        Err(e) => {
+            println!("Error: {}", e);
+            None
+        }
+    }
+}
+
+fn print_board(fen: &str) {
+    let Some(game_state) = parse_fen(fen) else {
+        return;
+    };
     game_state.board.print_board();
     let moves = game_state.generate_moves();
     println!("There are {} moves in this position.", moves.len());
 }
+
+fn eval(fen: &str) {
+    let Some(game_state) = parse_fen(fen) else {
+        return;
+    };
+    let trace = evaluate_trace(&game_state.board, game_state.current_player);
+    println!("{}", trace);
+}
+
+fn search(fen: &str, limits: &[&str]) {
+    let Some(mut game_state) = parse_fen(fen) else {
+        return;
+    };
+    let mut limits = parse_go(limits);
+    if limits == Default::default() {
+        limits.depth = Some(6);
+    }
+    let result =
+        Searcher::new(limits).search(&mut game_state, |result| println!("{}", info_line(result)));
+    match result.best_move {
+        Some(best_move) => println!("bestmove {}", best_move),
+        None => println!("bestmove 0000"),
+    }
+}
+
+fn mate(moves: &str, fen: &str) {
+    let Ok(moves) = moves.parse::<u32>() else {
+        return println!("Error: invalid number of moves: {}", moves);
+    };
+    let Some(mut game_state) = parse_fen(fen) else {
+        return;
+    };
+    let Some(mate) = find_mate(&mut game_state, moves) else {
+        return println!("No mate in {}", moves);
+    };
+
+    let mut line = Vec::new();
+    for chess_move in mate.main_line() {
+        line.push(move_to_san(&game_state, &chess_move));
+        game_state.make_move(&chess_move);
+    }
+    println!("Mate in {}: {}", mate.moves(), line.join(" "));
+    print!("{}", mate);
+}
+
+fn tbgen(material: &str, options: &[&str]) {
+    let material = match Material::from_str(material) {
+        Ok(material) => material,
+        Err(e) => return println!("Error: {}", e),
+    };
+    let output = match options {
+        [] => format!("{}.dtm", material),
+        ["-o", path] => path.to_string(),
+        _ => return println!("{}", USAGE),
+    };
+    let table = Tablebase::generate(&material, |material| println!("Generating {}", material));
+    let table = match table {
+        Ok(table) => table,
+        Err(e) => return println!("Error: {}", e),
+    };
+    print!("{}", table.summary());
+    match table.save(&output) {
+        Ok(()) => println!("Saved {}", output),
+        Err(e) => println!("Error: {}: {}", output, e),
+    }
+}
+
+fn tbprobe(path: &str, fen: &str) {
+    let table = match Tablebase::load(path) {
+        Ok(table) => table,
+        Err(e) => return println!("Error: {}: {}", path, e),
+    };
+    let Some(game_state) = parse_fen(fen) else {
+        return;
+    };
+    match table.probe(&game_state) {
+        Some(dtm) => println!("{}", dtm),
+        None => println!("Not in the {} table", table.material()),
+    }
+}
+
+fn book_probe(path: &str, fen: &str) {
+    let book = match Book::open(path) {
+        Ok(book) => book,
+        Err(e) => return println!("Error: {}: {}", path, e),
+    };
+    let Some(game_state) = parse_fen(fen) else {
+        return;
+    };
+    let moves = book.moves(&game_state);
+    if moves.is_empty() {
+        return println!("Not in the book");
+    }
+    let total: u32 = moves.iter().map(|&(_, weight)| weight as u32).sum();
+    for (chess_move, weight) in moves {
+        let share = weight as f64 * 100.0 / total.max(1) as f64;
+        println!(
+            "{:<8} weight {:>5} ({:.1}%)",
+            move_to_san(&game_state, &chess_move),
+            weight,
+            share
+        );
+    }
+}
+
+fn book_build(pgn_path: &str, options: &[&str]) {
+    let mut build_options = BuildOptions::default();
+    let mut output = "book.bin";
+
+    let mut options = options.iter();
+    while let Some(&option) = options.next() {
+        let Some(&value) = options.next() else {
+            return println!("{}", USAGE);
+        };
+        match option {
+            "-o" => output = value,
+            "--max-ply" | "--min-games" | "--min-elo" => {
+                let Ok(number) = value.parse::<u32>() else {
+                    return println!("Error: invalid {} value: {}", option, value);
+                };
+                match option {
+                    "--max-ply" => build_options.max_ply = number as usize,
+                    "--min-games" => build_options.min_games = number,
+                    _ => build_options.min_elo = Some(number),
+                }
+            }
+            _ => return println!("{}", USAGE),
+        }
+    }
+
+    let file = match File::open(pgn_path) {
+        Ok(file) => file,
+        Err(e) => return println!("Error: {}: {}", pgn_path, e),
+    };
+    let mut builder = BookBuilder::new(build_options);
+    let mut invalid = 0;
+    for game in PgnReader::new(BufReader::new(file)) {
+        match game {
+            Ok(game) => builder.add_game(&game),
+            Err(e) => {
+                println!("Skipping game: {}", e);
+                invalid += 1;
+            }
+        }
+    }
+    let book = builder.build();
+    println!(
+        "Read {} games ({} invalid) with {} positions, kept {} moves",
+        builder.games(),
+        invalid,
+        builder.positions(),
+        book.len()
+    );
+    match book.save(output) {
+        Ok(()) => println!("Saved {}", output),
+        Err(e) => println!("Error: {}: {}", output, e),
+    }
+}
+
+fn eco(pgn_path: &str, output: Option<&str>) {
+    let file = match File::open(pgn_path) {
+        Ok(file) => file,
+        Err(e) => return println!("Error: {}: {}", pgn_path, e),
+    };
+    let classifier = EcoClassifier::bundled();
+    let mut tagged = String::new();
+    for (i, game) in PgnReader::new(BufReader::new(file)).enumerate() {
+        let mut game = match game {
+            Ok(game) => game,
+            Err(e) => {
+                println!("Game {}: {}", i + 1, e);
+                continue;
+            }
+        };
+        let Some(path) = output else {
+            match classifier.classify_game(&game) {
+                Some(opening) => println!("Game {}: {} {}", i + 1, opening.eco, opening.name),
+                None => println!("Game {}: unclassified", i + 1),
+            }
+            continue;
+        };
+        classifier.tag_game(&mut game);
+        match game.to_pgn() {
+            Ok(pgn) => tagged.push_str(&pgn),
+            Err(e) => return println!("Error: {}: {}", path, e),
+        }
+        tagged.push('\n');
+    }
+    if let Some(path) = output {
+        if let Err(e) = fs::write(path, tagged) {
+            println!("Error: {}: {}", path, e);
+        }
+    }
+}
+
+fn tune(positions_path: &str, options: &[&str]) {
+    let mut params = EvalParams::DEFAULT;
+    let mut iterations = 100;
+    let mut rust = false;
+    let mut output = None;
+
+    let mut options = options.iter();
+    while let Some(&option) = options.next() {
+        match option {
+            "--rust" => rust = true,
+            "--params" | "--iterations" | "-o" => {
+                let Some(&value) = options.next() else {
+                    return println!("{}", USAGE);
+                };
+                match option {
+                    "--params" => match load_params(value) {
+                        Ok(loaded) => params = loaded,
+                        Err(e) => return println!("Error: {}: {}", value, e),
+                    },
+                    "--iterations" => match value.parse() {
+                        Ok(value) => iterations = value,
+                        Err(_) => {
+                            return println!("Error: invalid number of iterations: {}", value)
+                        }
+                    },
+                    _ => output = Some(value),
+                }
+            }
+            _ => return println!("{}", USAGE),
+        }
+    }
+
+    let positions = match File::open(positions_path) {
+        Ok(file) => load_positions(BufReader::new(file), &params),
+        Err(e) => return println!("Error: {}: {}", positions_path, e),
+    };
+    let positions = match positions {
+        Ok(positions) => positions,
+        Err(e) => return println!("Error: {}", e),
+    };
+    println!("Loaded {} positions", positions.len());
+
+    let mut tuner = Tuner::new(positions, params);
+    println!("K = {:.4}, error = {:.6}", tuner.k(), tuner.error());
+    tuner.tune(iterations, |iteration, error| {
+        println!("Iteration {}: error = {:.6}", iteration, error)
+    });
+
+    let tuned = if rust {
+        tuner.params().to_rust_source()
+    } else {
+        tuner.params().to_string()
+    };
+    match output {
+        Some(path) => {
+            if let Err(e) = fs::write(path, tuned) {
+                println!("Error: {}: {}", path, e);
+            }
+        }
+        None => print!("{}", tuned),
+    }
+}
+
+fn load_params(path: &str) -> Result<EvalParams, String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    EvalParams::from_str(&text).map_err(|e| e.to_string())
+}
@@ -0,0 +1,161 @@
+//! Module containing PGN (Portable Game Notation) related logic and structures.
+//!
+//! A PGN game is made of a tag pair section, e.g. `[Event "Casual game"]`, followed by the
+//! movetext: moves in Standard Algebraic Notation, interleaved with move numbers, comments,
+//! Numeric Annotation Glyphs (NAGs), recursive variations and terminated by the game result.
+//! Games are read with `PgnReader` and written back with `PgnGame::to_pgn`.
+
+pub mod reader;
+pub mod writer;
+
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::board::moves::Move;
+use crate::game_state::{GameResult, GameState, ParseFenError};
+use crate::san::SanError;
+
+pub use reader::PgnReader;
+
+/// A game read from, or to be written to, a PGN file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgnGame {
+    /// The tag pairs of the game, in the order they appear in the file.
+    pub tags: Vec<(String, String)>,
+    /// The moves of the main line.
+    pub moves: Vec<PgnMove>,
+    /// The result terminating the movetext.
+    pub result: GameResult,
+}
+
+/// A move of a PGN game, together with its annotations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgnMove {
+    /// The move played.
+    pub chess_move: Move,
+    /// The Numeric Annotation Glyphs of the move, e.g. 1 for `!` or 2 for `?`.
+    pub nags: Vec<u8>,
+    /// The comments written before the move. Only used on the first move of a line.
+    pub comments_before: Vec<String>,
+    /// The comments written after the move.
+    pub comments: Vec<String>,
+    /// The alternatives to this move, each one a line starting in the position before it.
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+impl PgnMove {
+    /// Creates a move without any annotation.
+    pub fn new(chess_move: Move) -> Self {
+        PgnMove {
+            chess_move,
+            nags: Vec::new(),
+            comments_before: Vec::new(),
+            comments: Vec::new(),
+            variations: Vec::new(),
+        }
+    }
+}
+
+impl PgnGame {
+    /// Returns the value of the tag with the given name, if the game has it.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag_name, _)| tag_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Sets the value of a tag, adding the tag after the others when the game does not have it.
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag_name, _)| tag_name == name) {
+            Some((_, tag_value)) => *tag_value = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// Returns the position the game starts from, taken from the `FEN` tag when present.
+    pub fn starting_position(&self) -> Result<GameState, ParseFenError> {
+        match self.tag("FEN") {
+            Some(fen) => GameState::from_str(fen),
+            None => Ok(GameState::new()),
+        }
+    }
+
+    /// Returns the positions of the main line: the starting position followed by the
+    /// position after each move.
+    pub fn positions(&self) -> Result<Vec<GameState>, ParseFenError> {
+        let mut game_state = self.starting_position()?;
+        let mut positions = vec![game_state.clone()];
+        for pgn_move in &self.moves {
+            game_state.make_move(&pgn_move.chess_move);
+            positions.push(game_state.clone());
+        }
+        Ok(positions)
+    }
+}
+
+impl FromStr for PgnGame {
+    type Err = PgnError;
+
+    /// Parses the first game of a PGN text.
+    fn from_str(pgn: &str) -> Result<Self, Self::Err> {
+        PgnReader::new(pgn.as_bytes())
+            .next()
+            .unwrap_or(Err(PgnError {
+                line: 1,
+                kind: PgnErrorKind::NoGame,
+            }))
+    }
+}
+
+/// An error found while reading a PGN game, with the line where it was found.
+#[derive(Error, Debug, PartialEq, Eq)]
+#[error("line {line}: {kind}")]
+pub struct PgnError {
+    /// The line number, starting from 1.
+    pub line: usize,
+    /// What went wrong.
+    pub kind: PgnErrorKind,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PgnErrorKind {
+    #[error("Invalid tag pair: {0}")]
+    InvalidTag(String),
+    #[error("Invalid FEN tag: {0}")]
+    InvalidFen(#[from] ParseFenError),
+    #[error("Move {0}: {1}")]
+    InvalidMove(String, SanError),
+    #[error("Unexpected token: {0}")]
+    UnexpectedToken(String),
+    #[error("Unterminated variation")]
+    UnterminatedVariation,
+    #[error("Unterminated comment")]
+    UnterminatedComment,
+    #[error("No game found")]
+    NoGame,
+    #[error("Read error: {0}")]
+    Io(String),
+}
+
+/// Converts a PGN result token into a `GameResult`.
+pub fn parse_result(result: &str) -> Option<GameResult> {
+    match result {
+        "1-0" => Some(GameResult::WhiteWon),
+        "0-1" => Some(GameResult::BlackWon),
+        "1/2-1/2" => Some(GameResult::Draw),
+        "*" => Some(GameResult::Undecided),
+        _ => None,
+    }
+}
+
+/// Returns the PGN result token of a `GameResult`.
+pub fn result_to_str(result: GameResult) -> &'static str {
+    match result {
+        GameResult::WhiteWon => "1-0",
+        GameResult::BlackWon => "0-1",
+        GameResult::Draw => "1/2-1/2",
+        GameResult::Undecided => "*",
+    }
+}
@@ -0,0 +1,536 @@
+//! Module containing the streaming PGN reader.
+
+use std::io::BufRead;
+
+use crate::game_state::{GameResult, GameState};
+use crate::pgn::{parse_result, PgnError, PgnErrorKind, PgnGame, PgnMove};
+use crate::san::parse_san;
+
+/// Reads the games of a PGN source one at a time.
+///
+/// The reader is an iterator yielding one `Result` per game, so an invalid game is reported
+/// with the line of the error and reading continues with the next game. Only an error of the
+/// underlying reader ends the iteration.
+///
+/// # Examples
+///
+/// ```
+/// use ruce::pgn::PgnReader;
+///
+/// let pgn = "[Event \"Example\"]\n\n1. e4 e5 2. Nf3 1-0\n\n1. e4 e9 0-1\n";
+/// let games: Vec<_> = PgnReader::new(pgn.as_bytes()).collect();
+/// assert_eq!(games[0].as_ref().unwrap().moves.len(), 3);
+/// assert_eq!(games[1].as_ref().unwrap_err().line, 5);
+/// ```
+pub struct PgnReader<R: BufRead> {
+    reader: R,
+    line_number: usize,
+    /// A line read ahead that belongs to the next game.
+    pending_line: Option<String>,
+    finished: bool,
+}
+
+/// A lexical element of the movetext.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// A move in SAN, without its annotation suffix.
+    San(String),
+    /// A move number indication such as `12.` or `12...`.
+    MoveNumber,
+    Nag(u8),
+    Comment(String),
+    OpenVariation,
+    CloseVariation,
+    Result(GameResult),
+}
+
+/// The raw content of a game, split from the source but not yet replayed.
+struct RawGame {
+    tags: Vec<(String, String)>,
+    /// The first error found while splitting the game, if any.
+    error: Option<PgnError>,
+    /// The movetext tokens with their line number.
+    tokens: Vec<(Token, usize)>,
+}
+
+impl<R: BufRead> PgnReader<R> {
+    /// Creates a reader over a PGN source.
+    pub fn new(reader: R) -> Self {
+        PgnReader {
+            reader,
+            line_number: 0,
+            pending_line: None,
+            finished: false,
+        }
+    }
+
+    fn read_line(&mut self) -> Result<Option<String>, PgnError> {
+        if let Some(line) = self.pending_line.take() {
+            return Ok(Some(line));
+        }
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => Ok(None),
+            Ok(_) => {
+                self.line_number += 1;
+                Ok(Some(line))
+            }
+            Err(e) => Err(PgnError {
+                line: self.line_number + 1,
+                kind: PgnErrorKind::Io(e.to_string()),
+            }),
+        }
+    }
+
+    /// Reads the lines of the next game. Returns `None` when the source has no more games.
+    fn read_raw_game(&mut self) -> Result<Option<RawGame>, PgnError> {
+        let mut game = RawGame {
+            tags: Vec::new(),
+            error: None,
+            tokens: Vec::new(),
+        };
+        let mut tokenizer = Tokenizer::default();
+        let mut started = false;
+        let mut in_movetext = false;
+
+        while let Some(line) = self.read_line()? {
+            let trimmed = line.trim();
+            // Escape mechanism, the line is ignored
+            if line.starts_with('%') {
+                continue;
+            }
+            if trimmed.is_empty() && !tokenizer.in_comment() {
+                continue;
+            }
+
+            if trimmed.starts_with('[') && !tokenizer.in_comment() {
+                if in_movetext {
+                    // Tag section of the next game, the current game has no result
+                    self.pending_line = Some(line);
+                    break;
+                }
+                started = true;
+                match parse_tag(trimmed) {
+                    Some(tag) => game.tags.push(tag),
+                    None => {
+                        game.error.get_or_insert(PgnError {
+                            line: self.line_number,
+                            kind: PgnErrorKind::InvalidTag(trimmed.to_string()),
+                        });
+                    }
+                }
+                continue;
+            }
+
+            started = true;
+            in_movetext = true;
+            tokenizer.tokenize_line(&line, self.line_number, &mut game.tokens);
+            if tokenizer.result_found {
+                break;
+            }
+        }
+
+        if !started {
+            return Ok(None);
+        }
+        if tokenizer.in_comment() {
+            game.error.get_or_insert(PgnError {
+                line: self.line_number,
+                kind: PgnErrorKind::UnterminatedComment,
+            });
+        }
+        Ok(Some(game))
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<PgnGame, PgnError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        match self.read_raw_game() {
+            Ok(Some(raw_game)) => Some(replay_game(raw_game)),
+            Ok(None) => {
+                self.finished = true;
+                None
+            }
+            Err(error) => {
+                self.finished = true;
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+/// Parses a tag pair line such as `[Event "F/S Return Match"]`.
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?.trim();
+    let (name, value) = inner.split_once(char::is_whitespace)?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            unescaped.push(chars.next()?);
+        } else {
+            unescaped.push(c);
+        }
+    }
+    Some((name.to_string(), unescaped))
+}
+
+/// Splits movetext lines into tokens, keeping track of brace comments spanning several lines.
+#[derive(Default)]
+struct Tokenizer {
+    /// The content of the brace comment being read, with the line it started on.
+    comment: Option<(String, usize)>,
+    variation_depth: usize,
+    /// Set once the result terminating the game has been read.
+    result_found: bool,
+}
+
+impl Tokenizer {
+    fn in_comment(&self) -> bool {
+        self.comment.is_some()
+    }
+
+    fn tokenize_line(&mut self, line: &str, line_number: usize, tokens: &mut Vec<(Token, usize)>) {
+        let mut rest = line;
+        loop {
+            if let Some((mut comment, start_line)) = self.comment.take() {
+                match rest.find('}') {
+                    Some(end) => {
+                        comment.push_str(&rest[..end]);
+                        tokens.push((Token::Comment(normalize_comment(&comment)), start_line));
+                        rest = &rest[end + 1..];
+                    }
+                    None => {
+                        comment.push_str(rest);
+                        self.comment = Some((comment, start_line));
+                        return;
+                    }
+                }
+            }
+
+            rest = rest.trim_start();
+            let Some(c) = rest.chars().next() else {
+                return;
+            };
+            match c {
+                '{' => {
+                    self.comment = Some((String::new(), line_number));
+                    rest = &rest[1..];
+                }
+                ';' => {
+                    tokens.push((Token::Comment(rest[1..].trim().to_string()), line_number));
+                    return;
+                }
+                '(' => {
+                    self.variation_depth += 1;
+                    tokens.push((Token::OpenVariation, line_number));
+                    rest = &rest[1..];
+                }
+                ')' => {
+                    self.variation_depth = self.variation_depth.saturating_sub(1);
+                    tokens.push((Token::CloseVariation, line_number));
+                    rest = &rest[1..];
+                }
+                '$' => {
+                    let digits = rest[1..]
+                        .find(|c: char| !c.is_ascii_digit())
+                        .map_or(rest.len(), |end| end + 1);
+                    match rest[1..digits].parse() {
+                        Ok(nag) => tokens.push((Token::Nag(nag), line_number)),
+                        Err(_) => {
+                            tokens.push((Token::San(rest[..digits].to_string()), line_number))
+                        }
+                    }
+                    rest = &rest[digits..];
+                }
+                _ => {
+                    let end = rest
+                        .find(|c: char| c.is_whitespace() || "{}();$".contains(c))
+                        .unwrap_or(rest.len());
+                    self.push_symbol(&rest[..end], line_number, tokens);
+                    rest = &rest[end..];
+                    if self.result_found {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    fn push_symbol(&mut self, symbol: &str, line_number: usize, tokens: &mut Vec<(Token, usize)>) {
+        if let Some(result) = parse_result(symbol) {
+            tokens.push((Token::Result(result), line_number));
+            self.result_found = self.variation_depth == 0;
+            return;
+        }
+
+        // Move number indication, possibly glued to the move as in `1.e4`
+        let mut symbol = symbol;
+        if symbol.starts_with(|c: char| c.is_ascii_digit()) && !symbol.starts_with("0-0") {
+            let after_digits = symbol.trim_start_matches(|c: char| c.is_ascii_digit());
+            let after_dots = after_digits.trim_start_matches('.');
+            if after_digits.is_empty() || after_dots.len() < after_digits.len() {
+                tokens.push((Token::MoveNumber, line_number));
+                symbol = after_dots;
+            }
+        }
+        // Black moves are sometimes written as `...e5`
+        symbol = symbol.trim_start_matches('.');
+        if symbol.is_empty() {
+            return;
+        }
+
+        let san = symbol.trim_end_matches(['!', '?']);
+        tokens.push((Token::San(san.to_string()), line_number));
+        let nag = match &symbol[san.len()..] {
+            "" => return,
+            "!" => 1,
+            "?" => 2,
+            "!!" => 3,
+            "??" => 4,
+            "!?" => 5,
+            "?!" => 6,
+            suffix => {
+                tokens.push((Token::San(suffix.to_string()), line_number));
+                return;
+            }
+        };
+        tokens.push((Token::Nag(nag), line_number));
+    }
+}
+
+/// Collapses the whitespace of a comment spanning several lines.
+fn normalize_comment(comment: &str) -> String {
+    comment.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Replays the movetext of a game through a `GameState`, checking every move.
+fn replay_game(raw_game: RawGame) -> Result<PgnGame, PgnError> {
+    if let Some(error) = raw_game.error {
+        return Err(error);
+    }
+    let mut game = PgnGame {
+        tags: raw_game.tags,
+        moves: Vec::new(),
+        result: GameResult::Undecided,
+    };
+    let first_line = raw_game.tokens.first().map_or(1, |(_, line)| *line);
+    let mut game_state = game.starting_position().map_err(|e| PgnError {
+        line: first_line,
+        kind: PgnErrorKind::InvalidFen(e),
+    })?;
+
+    let mut tokens = raw_game.tokens.into_iter();
+    let (moves, end) = parse_line(&mut tokens, &mut game_state)?;
+    game.moves = moves;
+    match end {
+        LineEnd::Result(result) => game.result = result,
+        LineEnd::EndOfGame => {}
+        LineEnd::CloseVariation(line) => {
+            return Err(PgnError {
+                line,
+                kind: PgnErrorKind::UnexpectedToken(")".to_string()),
+            })
+        }
+    }
+    Ok(game)
+}
+
+/// What terminated a line of moves.
+enum LineEnd {
+    Result(GameResult),
+    CloseVariation(usize),
+    EndOfGame,
+}
+
+/// Parses a line of moves, the main line or a variation, playing the moves on `game_state`.
+///
+/// Variations are parsed recursively from the position before the move they are an
+/// alternative to, and taken back once parsed.
+fn parse_line(
+    tokens: &mut impl Iterator<Item = (Token, usize)>,
+    game_state: &mut GameState,
+) -> Result<(Vec<PgnMove>, LineEnd), PgnError> {
+    let mut moves: Vec<PgnMove> = Vec::new();
+    let mut comments_before = Vec::new();
+
+    while let Some((token, line)) = tokens.next() {
+        match token {
+            Token::MoveNumber => {}
+            Token::San(san) => {
+                let chess_move = parse_san(game_state, &san).map_err(|e| PgnError {
+                    line,
+                    kind: PgnErrorKind::InvalidMove(san.clone(), e),
+                })?;
+                game_state.make_move(&chess_move);
+                let mut pgn_move = PgnMove::new(chess_move);
+                pgn_move.comments_before = std::mem::take(&mut comments_before);
+                moves.push(pgn_move);
+            }
+            Token::Nag(nag) => match moves.last_mut() {
+                Some(last) => last.nags.push(nag),
+                None => {
+                    return Err(PgnError {
+                        line,
+                        kind: PgnErrorKind::UnexpectedToken(format!("${}", nag)),
+                    })
+                }
+            },
+            Token::Comment(comment) => match moves.last_mut() {
+                Some(last) => last.comments.push(comment),
+                None => comments_before.push(comment),
+            },
+            Token::OpenVariation => {
+                let Some(last) = moves.last_mut() else {
+                    return Err(PgnError {
+                        line,
+                        kind: PgnErrorKind::UnexpectedToken("(".to_string()),
+                    });
+                };
+                game_state.unmake_move();
+                let (variation, end) = parse_line(tokens, game_state)?;
+                for _ in 0..variation.len() {
+                    game_state.unmake_move();
+                }
+                if !matches!(end, LineEnd::CloseVariation(_)) {
+                    return Err(PgnError {
+                        line,
+                        kind: PgnErrorKind::UnterminatedVariation,
+                    });
+                }
+                game_state.make_move(&last.chess_move);
+                last.variations.push(variation);
+            }
+            Token::CloseVariation => return Ok((moves, LineEnd::CloseVariation(line))),
+            Token::Result(result) => return Ok((moves, LineEnd::Result(result))),
+        }
+    }
+    Ok((moves, LineEnd::EndOfGame))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::moves::Move;
+    use crate::game_state::ParseFenError;
+    use crate::san::SanError;
+    use std::str::FromStr;
+
+    const IMMORTAL_GAME: &str = r#"[Event "London"]
+[Site "London ENG"]
+[Date "1851.06.21"]
+[Round "?"]
+[White "Adolf Anderssen"]
+[Black "Lionel Adalbert Bagration Felix Kieseritzky"]
+[Result "1-0"]
+
+1.e4 e5 2.f4 exf4 3.Bc4 Qh4+ 4.Kf1 b5 5.Bxb5 Nf6 6.Nf3 Qh6 7.d3 Nh5 8.Nh4 Qg5
+9.Nf5 c6 10.g4 Nf6 11.Rg1 cxb5 12.h4 Qg6 13.h5 Qg5 14.Qf3 Ng8 15.Bxf4 Qf6
+16.Nc3 Bc5 17.Nd5 Qxb2 18.Bd6 Bxg1 19.e5 Qxa1+ 20.Ke2 Na6 21.Nxg7+ Kd8
+22.Qf6+ Nxf6 23.Be7# 1-0
+"#;
+
+    #[test]
+    fn test_read_full_game() {
+        let game = PgnGame::from_str(IMMORTAL_GAME).unwrap();
+        assert_eq!(game.tags.len(), 7);
+        assert_eq!(game.tag("White"), Some("Adolf Anderssen"));
+        assert_eq!(game.result, GameResult::WhiteWon);
+        assert_eq!(game.moves.len(), 45);
+
+        let positions = game.positions().unwrap();
+        assert_eq!(positions.len(), 46);
+        let final_position = positions.last().unwrap();
+        assert!(final_position.is_checkmate());
+        assert_eq!(
+            final_position.to_fen(),
+            "r1bk3r/p2pBpNp/n4n2/1p1NP2P/6P1/3P4/P1P1K3/q5b1 b - - 1 23"
+        );
+    }
+
+    #[test]
+    fn test_comments_nags_and_variations() {
+        let pgn =
+            "{Opening} 1. e4 $1 {Best by test} e5 (1... c5!? ; Sicilian\n 2. Nf3 (2. c3) d6) \
+                   2. Nf3 {A long\ncomment} *";
+        let game = PgnGame::from_str(pgn).unwrap();
+        assert_eq!(game.result, GameResult::Undecided);
+        assert_eq!(game.moves.len(), 3);
+
+        let e4 = &game.moves[0];
+        assert_eq!(e4.comments_before, vec!["Opening".to_string()]);
+        assert_eq!(e4.nags, vec![1]);
+        assert_eq!(e4.comments, vec!["Best by test".to_string()]);
+
+        let e5 = &game.moves[1];
+        assert_eq!(e5.variations.len(), 1);
+        let sicilian = &e5.variations[0];
+        assert_eq!(sicilian.len(), 3);
+        assert_eq!(sicilian[0].chess_move, Move::new(50, 34));
+        assert_eq!(sicilian[0].nags, vec![5]);
+        assert_eq!(sicilian[0].comments, vec!["Sicilian".to_string()]);
+        assert_eq!(sicilian[1].variations[0][0].chess_move, Move::new(10, 18));
+
+        assert_eq!(game.moves[2].comments, vec!["A long comment".to_string()]);
+    }
+
+    #[test]
+    fn test_errors_are_reported_per_game() {
+        let pgn = "[Event \"First\"]\n\n1. e4 e5 1-0\n\n[Event \"Second\"]\n\n1. e4 e5 2. Qh5 Kd9 0-1\n\n\
+                   [Event \"Third\"]\n[Bad tag]\n\n1. d4 *\n\n1. d4 d5 (1... Nf6 2. c4 *\n\n[Event \"Fifth\"]\n1. c4 *\n";
+        let games: Vec<_> = PgnReader::new(pgn.as_bytes()).collect();
+        assert_eq!(games.len(), 5);
+        assert!(games[0].is_ok());
+        assert_eq!(
+            games[1].as_ref().unwrap_err(),
+            &PgnError {
+                line: 7,
+                kind: PgnErrorKind::InvalidMove(
+                    "Kd9".to_string(),
+                    SanError::InvalidSan("Kd9".to_string())
+                ),
+            }
+        );
+        assert_eq!(games[2].as_ref().unwrap_err().line, 10);
+        assert_eq!(
+            games[3].as_ref().unwrap_err().kind,
+            PgnErrorKind::UnterminatedVariation
+        );
+        assert_eq!(games[4].as_ref().unwrap().tag("Event"), Some("Fifth"));
+    }
+
+    #[test]
+    fn test_malformed_fen_tag_is_reported_per_game() {
+        let pgn = "[SetUp \"1\"]\n[FEN \"8/8/8/8/8/8/8/8/8 w - - 0 1\"]\n\n1. Kd2 *\n\n\
+                   [Event \"Next\"]\n\n1. e4 e5 1-0\n";
+        let games: Vec<_> = PgnReader::new(pgn.as_bytes()).collect();
+        assert_eq!(games.len(), 2);
+        assert_eq!(
+            games[0].as_ref().unwrap_err().kind,
+            PgnErrorKind::InvalidFen(ParseFenError::MalformedPiecePlacement(
+                "8/8/8/8/8/8/8/8/8".to_string()
+            ))
+        );
+        assert_eq!(games[1].as_ref().unwrap().tag("Event"), Some("Next"));
+    }
+
+    #[test]
+    fn test_fen_tag_and_escaped_tags() {
+        let pgn = "[White \"Alice \\\"The Rook\\\"\"]\n[SetUp \"1\"]\n\
+                   [FEN \"4k3/8/8/8/8/8/8/4K2R w K - 0 1\"]\n\n1. O-O Kd7 2. Rd1+ 1/2-1/2";
+        let game = PgnGame::from_str(pgn).unwrap();
+        assert_eq!(game.tag("White"), Some("Alice \"The Rook\""));
+        assert_eq!(game.result, GameResult::Draw);
+        assert_eq!(game.moves[0].chess_move, Move::new(4, 6));
+    }
+}
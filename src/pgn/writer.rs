@@ -0,0 +1,241 @@
+//! Module containing the PGN writer.
+
+use crate::board::piece::Color;
+use crate::game_state::{GameState, ParseFenError, STARTING_FEN};
+use crate::pgn::{result_to_str, PgnGame, PgnMove};
+use crate::san::move_to_san;
+
+/// The maximum length of a movetext line.
+const MAX_LINE_LENGTH: usize = 80;
+
+/// The tags every PGN game must have, in the order they must be written.
+pub const SEVEN_TAG_ROSTER: [&str; 7] =
+    ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+impl PgnGame {
+    /// Creates a game from the moves played on a `GameState`.
+    ///
+    /// The Seven Tag Roster is filled with unknown values, and the `SetUp` and `FEN` tags are
+    /// added when the game did not start from the standard starting position.
+    pub fn from_game_state(game_state: &GameState) -> Self {
+        let mut start = game_state.clone();
+        while start.unmake_move().is_some() {}
+
+        let mut tags: Vec<(String, String)> = SEVEN_TAG_ROSTER
+            .iter()
+            .map(|name| (name.to_string(), default_tag_value(name).to_string()))
+            .collect();
+        let fen = start.to_fen();
+        if fen != STARTING_FEN {
+            tags.push(("SetUp".to_string(), "1".to_string()));
+            tags.push(("FEN".to_string(), fen));
+        }
+
+        PgnGame {
+            tags,
+            moves: game_state
+                .history
+                .iter()
+                .map(|played| PgnMove::new(played.played_move.clone()))
+                .collect(),
+            result: game_state.game_result,
+        }
+    }
+
+    /// Writes the game in PGN export format.
+    ///
+    /// The Seven Tag Roster comes first, in its standard order and with unknown values for
+    /// missing tags, followed by the other tags. `SetUp` is added when there is a `FEN` tag.
+    /// The movetext uses SAN, and is wrapped at 80 columns.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `FEN` tag of the game is not a valid FEN string.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ruce::pgn::PgnGame;
+    /// use std::str::FromStr;
+    ///
+    /// let game = PgnGame::from_str("1. e4 {King's pawn} e5 (1... c5) 2. Nf3 $1 *").unwrap();
+    /// let pgn = game.to_pgn().unwrap();
+    /// assert!(pgn.ends_with("1. e4 {King's pawn} 1... e5 (1... c5) 2. Nf3 $1 *\n"));
+    /// assert_eq!(PgnGame::from_str(&pgn).unwrap().moves, game.moves);
+    /// ```
+    pub fn to_pgn(&self) -> Result<String, ParseFenError> {
+        let mut game_state = self.starting_position()?;
+        let mut pgn = String::new();
+
+        for name in SEVEN_TAG_ROSTER {
+            let value = match name {
+                "Result" => result_to_str(self.result),
+                _ => self.tag(name).unwrap_or(default_tag_value(name)),
+            };
+            push_tag(&mut pgn, name, value);
+        }
+        if self.tag("FEN").is_some() && self.tag("SetUp").is_none() {
+            push_tag(&mut pgn, "SetUp", "1");
+        }
+        for (name, value) in &self.tags {
+            if !SEVEN_TAG_ROSTER.contains(&name.as_str()) {
+                push_tag(&mut pgn, name, value);
+            }
+        }
+        pgn.push('\n');
+
+        let mut tokens = Vec::new();
+        movetext_tokens(&self.moves, &mut game_state, &mut tokens);
+        tokens.push(result_to_str(self.result).to_string());
+
+        let mut line_length = 0;
+        for token in tokens {
+            if line_length > 0 && line_length + 1 + token.len() > MAX_LINE_LENGTH {
+                pgn.push('\n');
+                line_length = 0;
+            } else if line_length > 0 {
+                pgn.push(' ');
+                line_length += 1;
+            }
+            line_length += token.len();
+            pgn.push_str(&token);
+        }
+        pgn.push('\n');
+        Ok(pgn)
+    }
+}
+
+/// Returns the value used for a missing tag of the Seven Tag Roster.
+fn default_tag_value(name: &str) -> &'static str {
+    match name {
+        "Date" => "????.??.??",
+        "Result" => "*",
+        _ => "?",
+    }
+}
+
+fn push_tag(pgn: &mut String, name: &str, value: &str) {
+    let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+    pgn.push_str(&format!("[{} \"{}\"]\n", name, value));
+}
+
+/// Appends the tokens of a line of moves, playing them on `game_state` and taking them back.
+///
+/// Tokens are the units the movetext may be wrapped between. The parentheses of a variation
+/// are glued to the first and last token of the variation.
+fn movetext_tokens(moves: &[PgnMove], game_state: &mut GameState, tokens: &mut Vec<String>) {
+    // A black move needs its own number at the start of a line or after an interruption
+    let mut needs_number = true;
+
+    for pgn_move in moves {
+        for comment in &pgn_move.comments_before {
+            push_comment(comment, tokens);
+            needs_number = true;
+        }
+
+        match game_state.current_player {
+            Color::White => tokens.push(format!("{}.", game_state.turn)),
+            Color::Black if needs_number => tokens.push(format!("{}...", game_state.turn)),
+            Color::Black => {}
+        }
+        tokens.push(move_to_san(game_state, &pgn_move.chess_move));
+        needs_number = false;
+
+        for nag in &pgn_move.nags {
+            tokens.push(format!("${}", nag));
+        }
+        for comment in &pgn_move.comments {
+            push_comment(comment, tokens);
+            needs_number = true;
+        }
+
+        for variation in &pgn_move.variations {
+            let start = tokens.len();
+            movetext_tokens(variation, game_state, tokens);
+            if tokens.len() > start {
+                tokens[start].insert(0, '(');
+                if let Some(last) = tokens.last_mut() {
+                    last.push(')');
+                }
+            }
+            needs_number = true;
+        }
+
+        game_state.make_move(&pgn_move.chess_move);
+    }
+
+    for _ in moves {
+        game_state.unmake_move();
+    }
+}
+
+/// Appends a brace comment, one token per word so that long comments can be wrapped.
+fn push_comment(comment: &str, tokens: &mut Vec<String>) {
+    // A closing brace would end the comment early
+    let comment = comment.replace('}', ")");
+    let mut words: Vec<String> = comment.split_whitespace().map(str::to_string).collect();
+    if words.is_empty() {
+        words.push(String::new());
+    }
+    words[0].insert(0, '{');
+    if let Some(last) = words.last_mut() {
+        last.push('}');
+    }
+    tokens.extend(words);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::moves::Move;
+    use crate::game_state::GameResult;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_seven_tag_roster_order_and_defaults() {
+        let game = PgnGame {
+            tags: vec![
+                ("Annotator".to_string(), "ruce".to_string()),
+                ("White".to_string(), "Alice \"A\"".to_string()),
+            ],
+            moves: vec![PgnMove::new(Move::new(12, 28))],
+            result: GameResult::WhiteWon,
+        };
+        assert_eq!(
+            game.to_pgn().unwrap(),
+            "[Event \"?\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n\
+             [White \"Alice \\\"A\\\"\"]\n[Black \"?\"]\n[Result \"1-0\"]\n[Annotator \"ruce\"]\n\
+             \n1. e4 1-0\n"
+        );
+    }
+
+    #[test]
+    fn test_from_game_state_adds_fen_for_non_standard_start() {
+        let fen = "4k3/8/8/8/8/8/8/4K2R b K - 3 40";
+        let mut game_state = GameState::from_str(fen).unwrap();
+        game_state.make_move(&Move::new(60, 52));
+        game_state.make_move(&Move::new(4, 6));
+        let game = PgnGame::from_game_state(&game_state);
+        assert_eq!(game.tag("SetUp"), Some("1"));
+        assert_eq!(game.tag("FEN"), Some(fen));
+
+        let pgn = game.to_pgn().unwrap();
+        assert!(pgn.ends_with("\n40... Ke7 41. O-O *\n"));
+        assert_eq!(PgnGame::from_str(&pgn).unwrap(), game);
+    }
+
+    #[test]
+    fn test_round_trip_with_annotations_and_wrapping() {
+        let pgn = "[Event \"Round trip\"]\n[Site \"?\"]\n[Date \"2023.08.01\"]\n[Round \"1\"]\n\
+                   [White \"A\"]\n[Black \"B\"]\n[Result \"1/2-1/2\"]\n\n\
+                   {Start} 1. e4 e5 2. Nf3 $1 Nc6 (2... d6 {Philidor} 3. d4 (3. Bc4 Be7) 3... exd4) \
+                   3. Bb5 {The Spanish game, one of the oldest and most deeply analysed openings \
+                   there is} a6 4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3 O-O 9. h3 Nb8 \
+                   10. d4 Nbd7 1/2-1/2";
+        let game = PgnGame::from_str(pgn).unwrap();
+        let written = game.to_pgn().unwrap();
+        assert!(written.lines().all(|line| line.len() <= MAX_LINE_LENGTH));
+        assert!(written.contains("(2... d6 {Philidor} 3. d4 (3. Bc4 Be7) 3... exd4)"));
+        assert_eq!(PgnGame::from_str(&written).unwrap(), game);
+    }
+}
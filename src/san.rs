@@ -0,0 +1,278 @@
+//! Module containing Standard Algebraic Notation (SAN) related logic.
+//!
+//! SAN is the move notation used by PGN and EPD, e.g. `e4`, `Nbd7`, `exd6`, `O-O` or `e8=Q+`.
+
+use thiserror::Error;
+
+use crate::board::moves::Move;
+use crate::board::piece::PieceType;
+use crate::board::{parse_square, square_name, BOARD_SIZE};
+use crate::game_state::GameState;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum SanError {
+    #[error("Invalid SAN move: {0}")]
+    InvalidSan(String),
+    #[error("Illegal move in this position: {0}")]
+    IllegalMove(String),
+    #[error("Ambiguous move in this position: {0}")]
+    AmbiguousMove(String),
+}
+
+/// Parses a move in Standard Algebraic Notation into one of the legal moves of the position.
+///
+/// Check, checkmate and annotation suffixes (`+`, `#`, `!`, `?`) are ignored, castling may be
+/// written with letters or zeros, and the `=` before a promotion piece is optional.
+///
+/// # Examples
+///
+/// ```
+/// use ruce::board::moves::Move;
+/// use ruce::game_state::GameState;
+/// use ruce::san::parse_san;
+///
+/// let game_state = GameState::new();
+/// assert_eq!(parse_san(&game_state, "Nf3"), Ok(Move::new(6, 21)));
+/// ```
+pub fn parse_san(game_state: &GameState, san: &str) -> Result<Move, SanError> {
+    let invalid = || SanError::InvalidSan(san.to_string());
+    let text = san.trim_end_matches(['+', '#', '!', '?']);
+    let legal_moves = game_state.generate_legal_moves();
+
+    if matches!(text, "O-O" | "0-0" | "O-O-O" | "0-0-0") {
+        let king_side = text.len() == 3;
+        return legal_moves
+            .into_iter()
+            .find(|chess_move| {
+                game_state.is_castling(chess_move)
+                    && (chess_move.target_square > chess_move.initial_square) == king_side
+            })
+            .ok_or_else(|| SanError::IllegalMove(san.to_string()));
+    }
+
+    if !text.is_ascii() {
+        return Err(invalid());
+    }
+
+    // Piece letter
+    let (piece_type, mut rest) = match text.chars().next() {
+        Some(c) if c.is_ascii_uppercase() => {
+            (PieceType::from_char(c).ok_or_else(invalid)?, &text[1..])
+        }
+        Some(_) => (PieceType::Pawn, text),
+        None => return Err(invalid()),
+    };
+
+    // Promotion suffix
+    let mut promotion = None;
+    if let Some(c) = rest.chars().last().filter(|c| c.is_ascii_alphabetic()) {
+        if c.is_ascii_uppercase() || (c != 'b' && rest.len() > 2) {
+            promotion = Some(PieceType::from_char(c).ok_or_else(invalid)?);
+            rest = rest[..rest.len() - 1].trim_end_matches('=');
+        }
+    }
+
+    // Target square, preceded by the optional disambiguation and capture sign
+    if rest.len() < 2 {
+        return Err(invalid());
+    }
+    let target_square = parse_square(&rest[rest.len() - 2..]).ok_or_else(invalid)?;
+    let mut from_file = None;
+    let mut from_rank = None;
+    for c in rest[..rest.len() - 2].chars() {
+        match c {
+            'a'..='h' => from_file = Some(c as u8 - b'a'),
+            '1'..='8' => from_rank = Some(c as u8 - b'1'),
+            'x' | ':' | '-' => {}
+            _ => return Err(invalid()),
+        }
+    }
+
+    let mut candidates = legal_moves.into_iter().filter(|chess_move| {
+        chess_move.target_square == target_square
+            && chess_move.promotion == promotion
+            && !game_state.is_castling(chess_move)
+            && game_state
+                .board
+                .get_piece(chess_move.initial_square)
+                .is_some_and(|piece| piece.piece_type == piece_type)
+            && from_file.is_none_or(|file| chess_move.initial_square % BOARD_SIZE == file)
+            && from_rank.is_none_or(|rank| chess_move.initial_square / BOARD_SIZE == rank)
+    });
+
+    match (candidates.next(), candidates.next()) {
+        (Some(chess_move), None) => Ok(chess_move),
+        (Some(_), Some(_)) => Err(SanError::AmbiguousMove(san.to_string())),
+        (None, _) => Err(SanError::IllegalMove(san.to_string())),
+    }
+}
+
+/// Formats a legal move of the position in Standard Algebraic Notation.
+///
+/// The move is disambiguated by file, rank or both as needed, and suffixed with `+` or `#`
+/// when it gives check or checkmate.
+///
+/// # Examples
+///
+/// ```
+/// use ruce::board::moves::Move;
+/// use ruce::game_state::GameState;
+/// use ruce::san::move_to_san;
+///
+/// let game_state = GameState::new();
+/// assert_eq!(move_to_san(&game_state, &Move::new(12, 28)), "e4");
+/// ```
+pub fn move_to_san(game_state: &GameState, chess_move: &Move) -> String {
+    let mut san = String::new();
+    let from = chess_move.initial_square;
+    let to = chess_move.target_square;
+    let piece_type = game_state
+        .board
+        .get_piece(from)
+        .map_or(PieceType::Pawn, |piece| piece.piece_type);
+
+    if game_state.is_castling(chess_move) {
+        san.push_str(if to > from { "O-O" } else { "O-O-O" });
+    } else {
+        let is_capture = game_state.board.get_piece(to).is_some()
+            || (piece_type == PieceType::Pawn && from % BOARD_SIZE != to % BOARD_SIZE);
+
+        if piece_type == PieceType::Pawn {
+            if is_capture {
+                san.push((b'a' + from % BOARD_SIZE) as char);
+            }
+        } else {
+            san.push(piece_type.as_char());
+            let others: Vec<Move> = game_state
+                .generate_legal_moves()
+                .into_iter()
+                .filter(|other| {
+                    other.target_square == to
+                        && other.initial_square != from
+                        && game_state
+                            .board
+                            .get_piece(other.initial_square)
+                            .is_some_and(|piece| piece.piece_type == piece_type)
+                })
+                .collect();
+            if !others.is_empty() {
+                let name = square_name(from);
+                let same_file = others
+                    .iter()
+                    .any(|other| other.initial_square % BOARD_SIZE == from % BOARD_SIZE);
+                let same_rank = others
+                    .iter()
+                    .any(|other| other.initial_square / BOARD_SIZE == from / BOARD_SIZE);
+                if !same_file {
+                    san.push_str(&name[..1]);
+                } else if !same_rank {
+                    san.push_str(&name[1..]);
+                } else {
+                    san.push_str(&name);
+                }
+            }
+        }
+
+        if is_capture {
+            san.push('x');
+        }
+        san.push_str(&square_name(to));
+        if let Some(promotion) = chess_move.promotion {
+            san.push('=');
+            san.push(promotion.as_char());
+        }
+    }
+
+    let mut after = GameState {
+        board: game_state.board.clone(),
+        history: Vec::new(),
+        ..*game_state
+    };
+    after.make_move(chess_move);
+    if after.is_checkmate() {
+        san.push('#');
+    } else if after.is_in_check() {
+        san.push('+');
+    }
+    san
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_parse_san_pawn_and_piece_moves() {
+        let game_state = GameState::new();
+        assert_eq!(parse_san(&game_state, "e4"), Ok(Move::new(12, 28)));
+        assert_eq!(parse_san(&game_state, "Nc3"), Ok(Move::new(1, 18)));
+        assert_eq!(parse_san(&game_state, "Ng1-f3"), Ok(Move::new(6, 21)));
+        assert_eq!(
+            parse_san(&game_state, "e5"),
+            Err(SanError::IllegalMove("e5".to_string()))
+        );
+        assert_eq!(
+            parse_san(&game_state, "Zf3"),
+            Err(SanError::InvalidSan("Zf3".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_san_disambiguation() {
+        let game_state = GameState::from_str("4k3/8/8/8/8/8/4K3/R6R w - - 0 1").unwrap();
+        assert_eq!(
+            parse_san(&game_state, "Rd1"),
+            Err(SanError::AmbiguousMove("Rd1".to_string()))
+        );
+        assert_eq!(parse_san(&game_state, "Rad1"), Ok(Move::new(0, 3)));
+        assert_eq!(parse_san(&game_state, "Rhd1"), Ok(Move::new(7, 3)));
+    }
+
+    #[test]
+    fn test_parse_san_castling_and_promotion() {
+        let game_state = GameState::from_str("r3k3/1P6/8/8/8/8/8/R3K2R w KQq - 0 1").unwrap();
+        assert_eq!(parse_san(&game_state, "O-O"), Ok(Move::new(4, 6)));
+        assert_eq!(parse_san(&game_state, "0-0-0"), Ok(Move::new(4, 2)));
+        let promotion = Move {
+            initial_square: 49,
+            target_square: 57,
+            promotion: Some(PieceType::Knight),
+        };
+        assert_eq!(parse_san(&game_state, "b8=N"), Ok(promotion.clone()));
+        assert_eq!(parse_san(&game_state, "b8N"), Ok(promotion));
+        assert_eq!(
+            parse_san(&game_state, "bxa8=Q+"),
+            Ok(Move {
+                initial_square: 49,
+                target_square: 56,
+                promotion: Some(PieceType::Queen),
+            })
+        );
+    }
+
+    #[test]
+    fn test_move_to_san() {
+        let game_state = GameState::from_str("r3k3/1P6/8/8/8/8/8/R3K2R w KQq - 0 1").unwrap();
+        assert_eq!(move_to_san(&game_state, &Move::new(4, 6)), "O-O");
+        assert_eq!(move_to_san(&game_state, &Move::new(0, 3)), "Rd1");
+        assert_eq!(move_to_san(&game_state, &Move::new(7, 5)), "Rf1");
+        assert_eq!(
+            move_to_san(
+                &game_state,
+                &Move {
+                    initial_square: 49,
+                    target_square: 56,
+                    promotion: Some(PieceType::Queen),
+                }
+            ),
+            "bxa8=Q+"
+        );
+        let rooks = GameState::from_str("R7/8/5k2/8/8/8/4K3/R6R w - - 0 1").unwrap();
+        assert_eq!(move_to_san(&rooks, &Move::new(0, 3)), "Rad1");
+        assert_eq!(move_to_san(&rooks, &Move::new(0, 32)), "R1a5");
+        assert_eq!(move_to_san(&rooks, &Move::new(56, 32)), "R8a5");
+        let mate = GameState::from_str("7k/8/6K1/8/8/8/8/Q7 w - - 0 1").unwrap();
+        assert_eq!(move_to_san(&mate, &Move::new(0, 56)), "Qa8#");
+    }
+}
@@ -0,0 +1,1391 @@
+//! Module containing the search, finding the best move of a position.
+//!
+//! The search is a negamax alpha-beta search driven by iterative deepening: the position is
+//! searched to depth 1, 2, 3... until one of the `SearchLimits` is reached, and the result of
+//! the last completed iteration is returned. The principal variation of each iteration is
+//! searched first in the next one.
+//!
+//! Positions repeating one of the game or of the search since the last capture or pawn move are
+//! scored as draws, as are the positions where the 50-move rule applies.
+//!
+//! The search is a principal variation search: once the first move of a position is searched,
+//! the others only need to be proved worse, which a null window search does cheaply. The tree
+//! is further cut with null move pruning, late move reductions, futility and reverse futility
+//! pruning, positions in check are extended, and each iteration starts with an aspiration
+//! window around the previous score. All of them can be switched off with `SearchOptions`.
+//!
+//! At the leaves, a quiescence search keeps playing captures and promotions until the position
+//! is quiet, so that the evaluation is not taken in the middle of an exchange.
+//!
+//! The results of the positions searched are stored in a `TranspositionTable`, which cuts the
+//! search of positions reached again by transposition and provides the move to try first.
+//! The other moves are ordered by a `MovePicker`.
+//!
+//! When the limits give the clock of the side to move, a `TimeManager` decides how long the
+//! move is searched.
+//!
+//! With the `syzygy` feature and tablebases set, only the root moves keeping the result of the
+//! tablebases are searched, and the positions of the tablebases reached by the search are
+//! scored by them.
+//!
+//! With MultiPV, each iteration searches the root several times, every search excluding the
+//! root moves of the lines found before it, and reports the lines ranked by score.
+//!
+//! The search can run on several threads with Lazy SMP: helper threads search the same root
+//! with their own move ordering heuristics, half of them one ply deeper, and share what they
+//! find through the lock-free transposition table. The result is the one of the main thread,
+//! which the others only speed up. With a single thread the search is deterministic.
+
+pub mod mate;
+pub mod move_picker;
+pub mod time;
+pub mod tt;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::board::moves::Move;
+use crate::board::piece::{Color, PieceType};
+use crate::board::Board;
+#[cfg(feature = "nnue")]
+use crate::eval::nnue::{Network, NnueState};
+use crate::eval::{piece_value, Evaluator};
+use crate::game_state::GameState;
+#[cfg(feature = "syzygy")]
+use crate::syzygy::{Tablebases, Wdl};
+
+use self::move_picker::{is_quiet, Heuristics, MovePicker};
+use self::time::TimeManager;
+use self::tt::{Bound, TranspositionTable};
+
+/// The score of a checkmate at the root. A mate found `n` plies from the root is scored
+/// `MATE_SCORE - n` for the winning side.
+pub const MATE_SCORE: i32 = 32_000;
+
+/// Scores above this value (or below its negation) are mate scores.
+pub const MATE_THRESHOLD: i32 = MATE_SCORE - MAX_PLY as i32;
+
+/// The score of a position the tablebases show won, below the mate scores. A win found `n`
+/// plies from the root is scored `TB_WIN_SCORE - n`.
+pub const TB_WIN_SCORE: i32 = MATE_THRESHOLD - MAX_PLY as i32;
+
+/// The maximum depth of the search, in plies.
+pub const MAX_PLY: usize = 128;
+
+/// The largest number of search threads.
+pub const MAX_THREADS: usize = 256;
+
+/// The largest number of lines of a MultiPV search.
+pub const MAX_MULTI_PV: usize = 256;
+
+/// How many nodes are searched between two checks of the clock and the stop flag.
+const CHECK_INTERVAL: u64 = 2048;
+
+/// The half width of the first aspiration window, doubled on every failure.
+const ASPIRATION_WINDOW: i32 = 25;
+/// Aspiration windows are used from this depth on.
+const ASPIRATION_DEPTH: u32 = 4;
+
+/// Null move pruning is tried from this depth on, and reduces the depth by `NULL_MOVE_REDUCTION`
+/// plus one ply every `NULL_MOVE_DEPTH_DIVISOR` plies.
+const NULL_MOVE_DEPTH: u32 = 3;
+const NULL_MOVE_REDUCTION: u32 = 3;
+const NULL_MOVE_DEPTH_DIVISOR: u32 = 6;
+
+/// Reverse futility pruning is done up to this depth, with this margin per ply.
+const REVERSE_FUTILITY_DEPTH: u32 = 6;
+const REVERSE_FUTILITY_MARGIN: i32 = 90;
+
+/// The futility margins of depths 1, 2 and 3.
+const FUTILITY_MARGINS: [i32; 3] = [150, 300, 450];
+
+/// Late move reductions start with the quiet moves after this many moves, from this depth on.
+const LMR_MOVES: u32 = 3;
+const LMR_DEPTH: u32 = 3;
+
+/// The conditions stopping the search. Several limits can be combined, the first one reached
+/// stops the search. Without any limit the search runs until it reaches `MAX_PLY`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchLimits {
+    /// The maximum depth to search, in plies.
+    pub depth: Option<u32>,
+    /// The maximum number of nodes to search.
+    pub nodes: Option<u64>,
+    /// The time to search for.
+    pub movetime: Option<Duration>,
+    /// The time left on the clocks of white and black.
+    pub wtime: Option<Duration>,
+    pub btime: Option<Duration>,
+    /// The increments of white and black, added to their clocks after each move.
+    pub winc: Option<Duration>,
+    pub binc: Option<Duration>,
+    /// The number of moves until the next time control, sudden death when `None`.
+    pub movestogo: Option<u32>,
+    /// The time lost on each move outside of the search, e.g. by the GUI or the network, kept
+    /// aside from the clock.
+    pub move_overhead: Duration,
+    /// Search until stopped, ignoring the other limits.
+    pub infinite: bool,
+}
+
+/// The switches changing how the search works, independently of its limits.
+///
+/// Every pruning, reduction and extension of the search can be switched off, to measure what
+/// it brings. They are all on by default.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchOptions {
+    /// Also search the quiet moves giving check at the first ply of the quiescence search.
+    pub quiescence_checks: bool,
+    /// Search the moves after the first one with a null window, and search them again with
+    /// the full window only when they turn out better than the first one.
+    pub principal_variation_search: bool,
+    /// Let the opponent move twice, and prune the position if it still cannot reach beta.
+    /// Never done when the side to move only has pawns, where zugzwang is common.
+    pub null_move_pruning: bool,
+    /// Search the quiet moves ordered last with a reduced depth.
+    pub late_move_reductions: bool,
+    /// Skip the quiet moves near the leaves when the static evaluation is far below alpha.
+    pub futility_pruning: bool,
+    /// Prune the positions near the leaves whose static evaluation is far above beta.
+    pub reverse_futility_pruning: bool,
+    /// Search the positions in check one ply deeper.
+    pub check_extensions: bool,
+    /// Search each iteration with a narrow window around the score of the previous one.
+    pub aspiration_windows: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            quiescence_checks: false,
+            principal_variation_search: true,
+            null_move_pruning: true,
+            late_move_reductions: true,
+            futility_pruning: true,
+            reverse_futility_pruning: true,
+            check_extensions: true,
+            aspiration_windows: true,
+        }
+    }
+}
+
+/// The outcome of a search, or of one iteration of it, for one line of the search.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchResult {
+    /// The best move found, `None` if the position has no legal move.
+    pub best_move: Option<Move>,
+    /// The score of the position in centipawns, from the point of view of the side to move.
+    pub score: i32,
+    /// The principal variation, starting with the best move.
+    pub pv: Vec<Move>,
+    /// The depth of the last completed iteration.
+    pub depth: u32,
+    /// The number of nodes searched.
+    pub nodes: u64,
+    /// The time spent searching.
+    pub time: Duration,
+    /// The rank of the line among the lines of a MultiPV search, starting at 1.
+    pub multipv: usize,
+}
+
+impl SearchResult {
+    /// Formats the score for the UCI protocol, `cp <centipawns>` or `mate <moves>`.
+    pub fn uci_score(&self) -> String {
+        if self.score.abs() > MATE_THRESHOLD {
+            let plies = MATE_SCORE - self.score.abs();
+            let moves = (plies + 1) / 2;
+            format!("mate {}", if self.score > 0 { moves } else { -moves })
+        } else {
+            format!("cp {}", self.score)
+        }
+    }
+}
+
+/// Searches a position with alpha-beta negamax and iterative deepening.
+pub struct Searcher {
+    /// The switches of the search, which can be changed between two searches.
+    pub options: SearchOptions,
+    limits: SearchLimits,
+    /// The number of threads searching, the main one included.
+    threads: usize,
+    /// The number of lines searched by the main thread.
+    multi_pv: usize,
+    /// The root moves searched: the legal moves, less the ones losing the result of the
+    /// tablebases.
+    root_moves: Vec<Move>,
+    /// The root moves of the lines already found in the current iteration, skipped by the
+    /// search of the next line.
+    excluded_root_moves: Vec<Move>,
+    evaluator: Evaluator,
+    tt: Arc<TranspositionTable>,
+    heuristics: Heuristics,
+    /// The network evaluation, used instead of `evaluator` when a network is set.
+    #[cfg(feature = "nnue")]
+    nnue: Option<NnueState>,
+    /// The tablebases probed at the root and in the search.
+    #[cfg(feature = "syzygy")]
+    tablebases: Option<Arc<Tablebases>>,
+    stop: Arc<AtomicBool>,
+    start: Instant,
+    /// The time budget of the move, when the limits give a clock.
+    time: Option<TimeManager>,
+    nodes: u64,
+    stopped: bool,
+}
+
+impl Searcher {
+    /// Creates a searcher for the given limits.
+    pub fn new(limits: SearchLimits) -> Self {
+        Searcher::with_stop_flag(limits, Arc::new(AtomicBool::new(false)))
+    }
+
+    /// Creates a searcher that can also be stopped from another thread by setting `stop`.
+    pub fn with_stop_flag(limits: SearchLimits, stop: Arc<AtomicBool>) -> Self {
+        Searcher {
+            options: SearchOptions::default(),
+            limits,
+            threads: 1,
+            multi_pv: 1,
+            root_moves: Vec::new(),
+            excluded_root_moves: Vec::new(),
+            evaluator: Evaluator::new(),
+            tt: Arc::new(TranspositionTable::default()),
+            heuristics: Heuristics::new(),
+            #[cfg(feature = "nnue")]
+            nnue: None,
+            #[cfg(feature = "syzygy")]
+            tablebases: None,
+            stop,
+            start: Instant::now(),
+            time: None,
+            nodes: 0,
+            stopped: false,
+        }
+    }
+
+    /// Replaces the transposition table, which can be shared with other searchers.
+    pub fn set_transposition_table(&mut self, tt: Arc<TranspositionTable>) {
+        self.tt = tt;
+    }
+
+    /// Sets the number of threads searching, between 1 and `MAX_THREADS`.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.clamp(1, MAX_THREADS);
+    }
+
+    /// Sets the number of lines searched, between 1 and `MAX_MULTI_PV`.
+    pub fn set_multi_pv(&mut self, lines: usize) {
+        self.multi_pv = lines.clamp(1, MAX_MULTI_PV);
+    }
+
+    /// Sets the network evaluating the positions, or goes back to the handcrafted evaluation
+    /// with `None`.
+    #[cfg(feature = "nnue")]
+    pub fn set_network(&mut self, network: Option<Arc<Network>>) {
+        self.nnue = network.map(NnueState::new);
+    }
+
+    /// Sets the tablebases probed by the search, or stops probing them with `None`.
+    #[cfg(feature = "syzygy")]
+    pub fn set_tablebases(&mut self, tablebases: Option<Arc<Tablebases>>) {
+        self.tablebases = tablebases;
+    }
+
+    /// Searches the position, calling `report` with every line of every completed iteration of
+    /// the main thread, and returns the best line.
+    ///
+    /// The game state is used to play the moves of the search and is left unchanged. The
+    /// nodes of the result are the ones of every thread.
+    pub fn search(
+        &mut self,
+        game_state: &mut GameState,
+        report: impl FnMut(&SearchResult),
+    ) -> SearchResult {
+        self.search_lines(game_state, report).swap_remove(0)
+    }
+
+    /// Searches the position like `search`, but returns every line of the last completed
+    /// iteration, best first. There are fewer lines than set with `set_multi_pv` when the
+    /// position has fewer legal moves, and a single one without a move when it has none.
+    pub fn search_lines(
+        &mut self,
+        game_state: &mut GameState,
+        report: impl FnMut(&SearchResult),
+    ) -> Vec<SearchResult> {
+        self.tt.new_search();
+        if self.threads == 1 {
+            return self.iterative_deepening(game_state, 0, report);
+        }
+
+        let helpers_stop = Arc::new(AtomicBool::new(false));
+        thread::scope(|scope| {
+            let helpers: Vec<_> = (1..self.threads)
+                .map(|id| {
+                    let mut helper = self.helper(helpers_stop.clone());
+                    let mut game_state = game_state.clone();
+                    scope.spawn(move || {
+                        helper.iterative_deepening(&mut game_state, id, |_| {});
+                        helper.nodes
+                    })
+                })
+                .collect();
+            let mut lines = self.iterative_deepening(game_state, 0, report);
+            helpers_stop.store(true, Ordering::Relaxed);
+            let helper_nodes: u64 = helpers
+                .into_iter()
+                .map(|helper| helper.join().unwrap_or(0))
+                .sum();
+            for line in &mut lines {
+                line.nodes += helper_nodes;
+            }
+            lines
+        })
+    }
+
+    /// Creates a helper thread searcher, sharing the transposition table and searching until
+    /// `stop` is set or the depth limit is reached.
+    fn helper(&self, stop: Arc<AtomicBool>) -> Searcher {
+        let limits = SearchLimits {
+            depth: self.limits.depth,
+            infinite: self.limits.infinite,
+            ..SearchLimits::default()
+        };
+        let mut helper = Searcher::with_stop_flag(limits, stop);
+        helper.options = self.options.clone();
+        helper.tt = self.tt.clone();
+        #[cfg(feature = "nnue")]
+        helper.set_network(self.nnue.as_ref().map(|nnue| nnue.network().clone()));
+        #[cfg(feature = "syzygy")]
+        helper.set_tablebases(self.tablebases.clone());
+        helper
+    }
+
+    /// Searches the position with iterative deepening on the thread `thread_id`, 0 being the
+    /// main thread, and returns the lines of the last completed iteration. The odd helper
+    /// threads search every iteration one ply deeper, and only search one line.
+    fn iterative_deepening(
+        &mut self,
+        game_state: &mut GameState,
+        thread_id: usize,
+        mut report: impl FnMut(&SearchResult),
+    ) -> Vec<SearchResult> {
+        self.start = Instant::now();
+        self.time = TimeManager::new(&self.limits, game_state.current_player);
+        self.nodes = 0;
+        self.stopped = false;
+        self.heuristics.new_search();
+        #[cfg(feature = "nnue")]
+        if let Some(nnue) = &mut self.nnue {
+            nnue.reset(&game_state.board);
+        }
+
+        let max_depth = match (self.limits.infinite, self.limits.depth) {
+            (false, Some(depth)) => depth.clamp(1, MAX_PLY as u32),
+            _ => MAX_PLY as u32,
+        };
+
+        let mut lines = vec![SearchResult {
+            multipv: 1,
+            ..SearchResult::default()
+        }];
+        self.root_moves = game_state.generate_legal_moves();
+        #[cfg(feature = "syzygy")]
+        if let Some(tablebases) = &self.tablebases {
+            if let Some(moves) = tablebases.root_moves(game_state) {
+                self.root_moves = moves;
+            }
+        }
+        let legal_moves = self.root_moves.len();
+        // A forced move is played at once, its search only gives a score to report
+        let forced_move = self.time.is_some() && legal_moves == 1;
+        let multi_pv = match thread_id {
+            0 => self.multi_pv.min(legal_moves).max(1),
+            _ => 1,
+        };
+
+        for depth in 1..=max_depth {
+            let depth = (depth + thread_id as u32 % 2).min(max_depth);
+            if depth == lines[0].depth {
+                break;
+            }
+            let iteration = self.search_root_lines(game_state, depth, multi_pv, &lines);
+            // An interrupted iteration is only trusted if nothing has been completed yet
+            if self.stopped && depth > 1 {
+                break;
+            }
+            lines = iteration;
+            lines.iter().for_each(&mut report);
+            let best = &lines[0];
+            if self.stopped || self.stop.load(Ordering::Relaxed) || best.best_move.is_none() {
+                break;
+            }
+            if let Some(time) = &mut self.time {
+                time.update(best.best_move.as_ref(), best.score);
+                if forced_move || !time.should_continue(self.start.elapsed()) {
+                    break;
+                }
+            }
+        }
+
+        for line in &mut lines {
+            line.nodes = self.nodes;
+            line.time = self.start.elapsed();
+        }
+        lines
+    }
+
+    /// Searches the `multi_pv` best lines of the root to the given depth, each line excluding
+    /// the root moves of the lines found before it, and returns them ranked by score.
+    ///
+    /// Each line is searched first along the best line of `previous` whose root move is not
+    /// excluded yet. An interrupted search only gives a line when it is the first one.
+    fn search_root_lines(
+        &mut self,
+        game_state: &mut GameState,
+        depth: u32,
+        multi_pv: usize,
+        previous: &[SearchResult],
+    ) -> Vec<SearchResult> {
+        let mut lines = Vec::new();
+        let empty = SearchResult::default();
+        self.excluded_root_moves.clear();
+        while lines.len() < multi_pv {
+            let previous_line = previous
+                .iter()
+                .find(|line| {
+                    line.best_move
+                        .as_ref()
+                        .is_none_or(|chess_move| !self.excluded_root_moves.contains(chess_move))
+                })
+                .unwrap_or(&empty);
+            let (score, pv) = self.aspiration_search(game_state, depth, previous_line);
+            if self.stopped && !lines.is_empty() {
+                break;
+            }
+            let best_move = pv.first().cloned();
+            lines.push(SearchResult {
+                best_move: best_move.clone(),
+                score,
+                pv,
+                depth,
+                nodes: self.nodes,
+                time: self.start.elapsed(),
+                multipv: 0,
+            });
+            match best_move {
+                Some(best_move) if !self.stopped => self.excluded_root_moves.push(best_move),
+                _ => break,
+            }
+        }
+        self.excluded_root_moves.clear();
+
+        lines.sort_by_key(|line| -line.score);
+        for (index, line) in lines.iter_mut().enumerate() {
+            line.multipv = index + 1;
+        }
+        lines
+    }
+
+    /// Searches the root to the given depth, returning its score and principal variation.
+    ///
+    /// With aspiration windows, the search starts with a narrow window around the score of
+    /// the previous iteration, which is widened on the side it fails until the score falls
+    /// inside.
+    fn aspiration_search(
+        &mut self,
+        game_state: &mut GameState,
+        depth: u32,
+        previous: &SearchResult,
+    ) -> (i32, Vec<Move>) {
+        let use_window = self.options.aspiration_windows
+            && depth >= ASPIRATION_DEPTH
+            && previous.score.abs() < MATE_THRESHOLD;
+        let mut delta = ASPIRATION_WINDOW;
+        let (mut alpha, mut beta) = if use_window {
+            (previous.score - delta, previous.score + delta)
+        } else {
+            (-MATE_SCORE, MATE_SCORE)
+        };
+        loop {
+            let mut pv = previous.pv.clone();
+            let score = self.negamax(game_state, depth, 0, alpha, beta, &mut pv, true);
+            if self.stopped {
+                return (score, pv);
+            }
+            delta *= 2;
+            if score <= alpha && alpha > -MATE_SCORE {
+                alpha = (alpha - delta).max(-MATE_SCORE);
+            } else if score >= beta && beta < MATE_SCORE {
+                beta = (beta + delta).min(MATE_SCORE);
+            } else {
+                return (score, pv);
+            }
+        }
+    }
+
+    /// Checks the limits, setting `stopped` once one of them is reached.
+    fn check_limits(&mut self) {
+        if let (false, Some(nodes)) = (self.limits.infinite, self.limits.nodes) {
+            if self.nodes >= nodes {
+                self.stopped = true;
+            }
+        }
+        if self.nodes.is_multiple_of(CHECK_INTERVAL) {
+            if self.stop.load(Ordering::Relaxed) {
+                self.stopped = true;
+            }
+            if let (false, Some(movetime)) = (self.limits.infinite, self.limits.movetime) {
+                if self.start.elapsed() >= movetime {
+                    self.stopped = true;
+                }
+            }
+            if let Some(time) = &self.time {
+                if self.start.elapsed() >= time.hard_limit() {
+                    self.stopped = true;
+                }
+            }
+        }
+    }
+
+    /// Searches the position to the given depth, returning its score from the point of view of
+    /// the side to move. `pv` holds the principal variation found so far when called, its first
+    /// move is searched first, and is replaced by the new principal variation, which is left
+    /// empty when no move raises alpha. `null_move_allowed` is `false` right after a null move,
+    /// so that two null moves are never played in a row.
+    #[allow(clippy::too_many_arguments)]
+    fn negamax(
+        &mut self,
+        game_state: &mut GameState,
+        depth: u32,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+        pv: &mut Vec<Move>,
+        null_move_allowed: bool,
+    ) -> i32 {
+        self.nodes += 1;
+        self.check_limits();
+        if self.stopped && ply > 0 {
+            return 0;
+        }
+
+        if ply > 0 && (game_state.half_move_clock >= 100 || is_repetition(game_state)) {
+            return 0;
+        }
+        let in_check = game_state.is_in_check();
+        let depth = if in_check && self.options.check_extensions {
+            depth + 1
+        } else {
+            depth
+        };
+        if depth == 0 || ply >= MAX_PLY {
+            pv.clear();
+            return self.quiescence(game_state, ply, 0, alpha, beta);
+        }
+
+        let key = game_state.key;
+        let entry = self.tt.probe(key, ply);
+        if let Some(entry) = &entry {
+            // The nodes of the previous principal variation are searched again, to keep it whole
+            let cutoff = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => entry.score >= beta,
+                Bound::Upper => entry.score <= alpha,
+            };
+            if ply > 0 && pv.is_empty() && entry.depth >= depth && cutoff {
+                return entry.score;
+            }
+        }
+
+        #[cfg(feature = "syzygy")]
+        if let Some(score) = self.probe_tablebases(game_state, ply) {
+            // The result of the tablebases holds at any depth, it is not probed again
+            self.tt
+                .store(key, MAX_PLY as u32, Bound::Exact, score, None, ply);
+            pv.clear();
+            return score;
+        }
+
+        let pv_node = beta - alpha > 1;
+        let color = game_state.current_player;
+        // The static evaluation is only needed by the pruning, which is only done far from the
+        // principal variation and from mate scores
+        let prune = ply > 0 && !pv_node && !in_check && beta.abs() < MATE_THRESHOLD;
+        let static_eval = prune.then(|| self.evaluate(game_state));
+
+        if let Some(static_eval) = static_eval {
+            if self.options.reverse_futility_pruning
+                && depth <= REVERSE_FUTILITY_DEPTH
+                && static_eval - REVERSE_FUTILITY_MARGIN * depth as i32 >= beta
+            {
+                return static_eval;
+            }
+        }
+
+        if static_eval.is_some_and(|static_eval| static_eval >= beta)
+            && self.options.null_move_pruning
+            && null_move_allowed
+            && depth >= NULL_MOVE_DEPTH
+            && has_pieces(&game_state.board, color)
+        {
+            let reduction = NULL_MOVE_REDUCTION + depth / NULL_MOVE_DEPTH_DIVISOR;
+            let en_passant_target = game_state.make_null_move();
+            let mut null_pv = Vec::new();
+            let score = -self.negamax(
+                game_state,
+                depth.saturating_sub(1 + reduction),
+                ply + 1,
+                -beta,
+                -beta + 1,
+                &mut null_pv,
+                false,
+            );
+            game_state.unmake_null_move(en_passant_target);
+            if self.stopped {
+                return 0;
+            }
+            if score >= beta {
+                // A mate found after passing is not a real mate
+                return if score >= MATE_THRESHOLD { beta } else { score };
+            }
+        }
+
+        let futile = self.options.futility_pruning
+            && depth as usize <= FUTILITY_MARGINS.len()
+            && static_eval.is_some_and(|static_eval| {
+                static_eval + FUTILITY_MARGINS[depth as usize - 1] <= alpha
+            });
+
+        let hash_move = pv
+            .first()
+            .cloned()
+            .or_else(|| entry.and_then(|entry| entry.best_move));
+        let previous_move = game_state
+            .history
+            .last()
+            .map(|played| played.played_move.clone());
+        let mut picker = MovePicker::new(
+            hash_move,
+            self.heuristics.killers(ply),
+            self.heuristics.counter_move(previous_move.as_ref()),
+        );
+        let previous_pv = std::mem::take(pv);
+
+        let original_alpha = alpha;
+        let mut best_score = -MATE_SCORE;
+        let mut best_move = None;
+        let mut legal_moves = 0;
+        let mut quiets_tried = Vec::new();
+        let mut child_pv = Vec::new();
+        while let Some(chess_move) = picker.next(&game_state.board, color, &self.heuristics) {
+            if ply == 0
+                && (self.excluded_root_moves.contains(&chess_move)
+                    || !self.root_moves.contains(&chess_move))
+            {
+                continue;
+            }
+            let quiet = is_quiet(&game_state.board, &chess_move);
+            if !self.make_move(game_state, &chess_move) {
+                continue;
+            }
+            legal_moves += 1;
+            let gives_check = game_state.is_in_check();
+
+            if futile && quiet && !gives_check && legal_moves > 1 {
+                self.unmake_move(game_state);
+                continue;
+            }
+
+            child_pv.clear();
+            if legal_moves == 1 && previous_pv.first() == Some(&chess_move) {
+                child_pv.extend(previous_pv.iter().skip(1).cloned());
+            }
+            let new_depth = depth - 1;
+            let score = if legal_moves == 1 {
+                -self.negamax(
+                    game_state,
+                    new_depth,
+                    ply + 1,
+                    -beta,
+                    -alpha,
+                    &mut child_pv,
+                    true,
+                )
+            } else {
+                let reduce = self.options.late_move_reductions
+                    && depth >= LMR_DEPTH
+                    && legal_moves > LMR_MOVES
+                    && quiet
+                    && !in_check
+                    && !gives_check;
+                let reduction = if reduce {
+                    late_move_reduction(depth, legal_moves, pv_node).min(new_depth - 1)
+                } else {
+                    0
+                };
+                let (search_alpha, search_beta) = if self.options.principal_variation_search {
+                    (-alpha - 1, -alpha)
+                } else {
+                    (-beta, -alpha)
+                };
+                let mut score = -self.negamax(
+                    game_state,
+                    new_depth - reduction,
+                    ply + 1,
+                    search_alpha,
+                    search_beta,
+                    &mut child_pv,
+                    true,
+                );
+                if score > alpha && reduction > 0 {
+                    child_pv.clear();
+                    score = -self.negamax(
+                        game_state,
+                        new_depth,
+                        ply + 1,
+                        search_alpha,
+                        search_beta,
+                        &mut child_pv,
+                        true,
+                    );
+                }
+                if score > alpha && score < beta && search_beta - search_alpha == 1 {
+                    child_pv.clear();
+                    score = -self.negamax(
+                        game_state,
+                        new_depth,
+                        ply + 1,
+                        -beta,
+                        -alpha,
+                        &mut child_pv,
+                        true,
+                    );
+                }
+                score
+            };
+            self.unmake_move(game_state);
+
+            if self.stopped && (ply > 0 || legal_moves > 1) {
+                break;
+            }
+            if score > best_score {
+                best_score = score;
+                best_move = Some(chess_move.clone());
+                if score > alpha {
+                    alpha = score;
+                    pv.clear();
+                    pv.push(chess_move.clone());
+                    pv.extend(child_pv.iter().cloned());
+                }
+                if alpha >= beta {
+                    if quiet {
+                        self.heuristics.update(
+                            color,
+                            &chess_move,
+                            &quiets_tried,
+                            previous_move.as_ref(),
+                            depth,
+                            ply,
+                        );
+                    }
+                    break;
+                }
+            }
+            if quiet {
+                quiets_tried.push(chess_move);
+            }
+        }
+
+        if legal_moves == 0 {
+            pv.clear();
+            return if in_check {
+                -MATE_SCORE + ply as i32
+            } else {
+                0
+            };
+        }
+        // The best move of a root missing some of its moves is not the best move of the position
+        if !self.stopped && (ply > 0 || self.excluded_root_moves.is_empty()) {
+            let bound = if best_score >= beta {
+                Bound::Lower
+            } else if best_score > original_alpha {
+                Bound::Exact
+            } else {
+                Bound::Upper
+            };
+            self.tt
+                .store(key, depth, bound, best_score, best_move.as_ref(), ply);
+        }
+        best_score
+    }
+
+    /// Probes the WDL tablebases below the root, returning the score of the position when it
+    /// is in the tablebases.
+    ///
+    /// Positions are only probed right after a capture or a pawn move, where the 50-move
+    /// counter is the one the tables assume.
+    #[cfg(feature = "syzygy")]
+    fn probe_tablebases(&mut self, game_state: &mut GameState, ply: usize) -> Option<i32> {
+        if ply == 0 || game_state.half_move_clock != 0 {
+            return None;
+        }
+        let wdl = self.tablebases.as_ref()?.probe_wdl(game_state)?;
+        // Cursed wins and blessed losses are draws with the 50-move rule
+        Some(match wdl {
+            Wdl::Win => TB_WIN_SCORE - ply as i32,
+            Wdl::Loss => -TB_WIN_SCORE + ply as i32,
+            _ => 0,
+        })
+    }
+
+    /// Plays a pseudo-legal move if it is legal, returning whether it was played.
+    fn make_move(&mut self, game_state: &mut GameState, chess_move: &Move) -> bool {
+        if !game_state.make_legal_move(chess_move) {
+            return false;
+        }
+        #[cfg(feature = "nnue")]
+        if let Some(nnue) = &mut self.nnue {
+            nnue.make_move(game_state);
+        }
+        true
+    }
+
+    /// Takes back the last move played with `make_move`.
+    fn unmake_move(&mut self, game_state: &mut GameState) {
+        game_state.unmake_move();
+        #[cfg(feature = "nnue")]
+        if let Some(nnue) = &mut self.nnue {
+            nnue.unmake_move();
+        }
+    }
+
+    /// Scores the position from the point of view of the side to move.
+    fn evaluate(&mut self, game_state: &GameState) -> i32 {
+        #[cfg(feature = "nnue")]
+        if let Some(nnue) = &self.nnue {
+            return nnue.evaluate(game_state.current_player);
+        }
+        self.evaluator
+            .evaluate(&game_state.board, game_state.current_player)
+    }
+
+    /// Searches the captures and promotions of the position until it is quiet, returning its
+    /// score from the point of view of the side to move. `qply` is the number of plies already
+    /// played in the quiescence search.
+    ///
+    /// The side to move can always stand pat, i.e. take the static evaluation instead of
+    /// capturing, except when in check, where every evasion is searched instead.
+    fn quiescence(
+        &mut self,
+        game_state: &mut GameState,
+        ply: usize,
+        qply: usize,
+        mut alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        self.nodes += 1;
+        self.check_limits();
+        if self.stopped {
+            return 0;
+        }
+
+        let in_check = game_state.is_in_check();
+        if ply >= MAX_PLY {
+            return self.evaluate(game_state);
+        }
+
+        let mut best_score = -MATE_SCORE;
+        let mut moves = if in_check {
+            game_state.generate_moves()
+        } else {
+            let stand_pat = self.evaluate(game_state);
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            alpha = alpha.max(stand_pat);
+            best_score = stand_pat;
+
+            let board = &game_state.board;
+            let mut moves = board.generate_captures(game_state.current_player);
+            // Underpromotions hardly ever change the outcome of an exchange, and captures
+            // losing material cannot improve on standing pat
+            moves.retain(|chess_move| {
+                chess_move
+                    .promotion
+                    .is_none_or(|piece_type| piece_type == PieceType::Queen)
+                    && board.see_ge(chess_move, 0)
+            });
+            moves
+        };
+        moves.sort_by_key(|chess_move| -mvv_lva(&game_state.board, chess_move));
+
+        let search_checks = self.options.quiescence_checks && !in_check && qply == 0;
+        let quiet_checks = if search_checks {
+            quiet_checks(game_state)
+        } else {
+            Vec::new()
+        };
+
+        let mut legal_moves = 0;
+        for chess_move in moves.into_iter().chain(quiet_checks) {
+            if !self.make_move(game_state, &chess_move) {
+                continue;
+            }
+            legal_moves += 1;
+            let score = -self.quiescence(game_state, ply + 1, qply + 1, -beta, -alpha);
+            self.unmake_move(game_state);
+
+            if self.stopped {
+                return 0;
+            }
+            if score > best_score {
+                best_score = score;
+                alpha = alpha.max(score);
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+
+        if in_check && legal_moves == 0 {
+            return -MATE_SCORE + ply as i32;
+        }
+        best_score
+    }
+}
+
+/// The depth reduction of a late quiet move, growing with the depth and the number of moves
+/// already searched.
+fn late_move_reduction(depth: u32, moves: u32, pv_node: bool) -> u32 {
+    let reduction = 0.75 + (depth as f64).ln() * (moves as f64).ln() / 2.25;
+    (reduction as u32).saturating_sub(pv_node as u32).max(1)
+}
+
+/// Checks whether a color has any piece besides its king and pawns. Without them, zugzwang is
+/// common and passing is not a good guess of the worst move anymore.
+fn has_pieces(board: &Board, color: Color) -> bool {
+    board.squares.iter().flatten().any(|piece| {
+        piece.color == color && !matches!(piece.piece_type, PieceType::Pawn | PieceType::King)
+    })
+}
+
+/// Returns the quiet moves of the side to move that give check.
+fn quiet_checks(game_state: &mut GameState) -> Vec<Move> {
+    let mut checks = Vec::new();
+    for chess_move in game_state.generate_moves() {
+        let quiet = is_quiet(&game_state.board, &chess_move);
+        if quiet && game_state.make_legal_move(&chess_move) {
+            if game_state.is_in_check() {
+                checks.push(chess_move.clone());
+            }
+            game_state.unmake_move();
+        }
+    }
+    checks
+}
+
+/// Orders captures by Most Valuable Victim first, then Least Valuable Attacker.
+fn mvv_lva(board: &Board, chess_move: &Move) -> i32 {
+    // An en passant capture has no piece on its target square, and takes a pawn
+    let victim = board
+        .get_piece(chess_move.target_square)
+        .map_or(0, |piece| piece_value(piece.piece_type));
+    let victim = match board.get_piece(chess_move.initial_square) {
+        Some(piece) if piece.piece_type == PieceType::Pawn && victim == 0 => {
+            if board.en_passant_target == Some(chess_move.target_square) {
+                piece_value(PieceType::Pawn)
+            } else {
+                0
+            }
+        }
+        _ => victim,
+    };
+    let attacker = board
+        .get_piece(chess_move.initial_square)
+        .map_or(0, |piece| piece_value(piece.piece_type));
+    let promotion = chess_move.promotion.map_or(0, piece_value);
+    victim * 10 + promotion - attacker
+}
+
+/// Checks whether the position was already reached since the last capture or pawn move, in the
+/// game or in the search. A single repetition is scored as a draw, as whatever makes it good
+/// enough to repeat once makes it good enough to repeat again.
+///
+/// The keys of the history include the side to move, so only every other position is compared.
+fn is_repetition(game_state: &GameState) -> bool {
+    let reversible = (game_state.half_move_clock as usize).min(game_state.history.len());
+    game_state.history[game_state.history.len() - reversible..]
+        .iter()
+        .rev()
+        .skip(1)
+        .step_by(2)
+        .any(|played| played.key == game_state.key)
+}
+
+/// Searches a position with the given limits and returns the best move found.
+///
+/// # Examples
+///
+/// ```
+/// use ruce::game_state::GameState;
+/// use ruce::search::{search, SearchLimits};
+/// use std::str::FromStr;
+///
+/// // White mates with Rd8
+/// let mut game_state = GameState::from_str("6k1/5ppp/8/8/8/8/8/3R2K1 w - - 0 1").unwrap();
+/// let limits = SearchLimits {
+///     depth: Some(3),
+///     ..SearchLimits::default()
+/// };
+/// let result = search(&mut game_state, &limits);
+/// assert_eq!(result.pv[0].to_string(), "d1d8");
+/// assert_eq!(result.uci_score(), "mate 1");
+/// ```
+pub fn search(game_state: &mut GameState, limits: &SearchLimits) -> SearchResult {
+    Searcher::new(limits.clone()).search(game_state, |_| {})
+}
+
+/// Searches the `lines` best moves of a position with the given limits, and returns them
+/// best first with their score and principal variation.
+///
+/// # Examples
+///
+/// ```
+/// use ruce::game_state::GameState;
+/// use ruce::search::{search_multi_pv, SearchLimits};
+/// use std::str::FromStr;
+///
+/// // Rd8 mates, the other moves do not
+/// let mut game_state = GameState::from_str("6k1/5ppp/8/8/8/8/8/3R2K1 w - - 0 1").unwrap();
+/// let limits = SearchLimits {
+///     depth: Some(3),
+///     ..SearchLimits::default()
+/// };
+/// let lines = search_multi_pv(&mut game_state, &limits, 3);
+/// assert_eq!(lines.len(), 3);
+/// assert_eq!(lines[0].0.to_string(), "d1d8");
+/// assert!(lines[0].1 > lines[1].1 && lines[1].1 >= lines[2].1);
+/// ```
+pub fn search_multi_pv(
+    game_state: &mut GameState,
+    limits: &SearchLimits,
+    lines: usize,
+) -> Vec<(Move, i32, Vec<Move>)> {
+    let mut searcher = Searcher::new(limits.clone());
+    searcher.set_multi_pv(lines);
+    searcher
+        .search_lines(game_state, |_| {})
+        .into_iter()
+        .filter_map(|line| Some((line.best_move?, line.score, line.pv)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn depth(depth: u32) -> SearchLimits {
+        SearchLimits {
+            depth: Some(depth),
+            ..SearchLimits::default()
+        }
+    }
+
+    #[test]
+    fn test_finds_mate_in_two() {
+        let fen = "2r3k1/5ppp/8/8/8/8/3R1PPP/3R2K1 w - - 0 1";
+        let mut game_state = GameState::from_str(fen).unwrap();
+        let result = search(&mut game_state, &depth(4));
+        assert_eq!(result.score, MATE_SCORE - 3);
+        assert_eq!(result.uci_score(), "mate 2");
+        let pv: Vec<String> = result.pv.iter().map(|m| m.to_string()).collect();
+        assert_eq!(pv, vec!["d2d8", "c8d8", "d1d8"]);
+        assert_eq!(game_state.to_fen(), fen);
+    }
+
+    #[test]
+    fn test_wins_material() {
+        // The knight on e5 is hanging
+        let mut game_state =
+            GameState::from_str("rnbqkb1r/pppp1ppp/5n2/4N3/8/8/PPPP1PPP/RNBQKB1R b KQkq - 0 3")
+                .unwrap();
+        let result = search(&mut game_state, &depth(3));
+        assert!(result.score >= 0);
+        assert_eq!(result.pv.first(), result.best_move.as_ref());
+        assert_eq!(result.depth, 3);
+    }
+
+    #[test]
+    fn test_mated_and_stalemated_positions() {
+        let mut mated = GameState::from_str("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        let result = search(&mut mated, &depth(2));
+        assert_eq!(result.best_move, None);
+        assert_eq!(result.score, -MATE_SCORE);
+
+        let mut stalemate = GameState::from_str("7k/8/6QK/8/8/8/8/8 b - - 0 1").unwrap();
+        let result = search(&mut stalemate, &depth(2));
+        assert_eq!(result.best_move, None);
+        assert_eq!(result.score, 0);
+    }
+
+    #[test]
+    fn test_perpetual_check_draws_by_repetition() {
+        // Far behind, white can only save the game by checking on g5 and f6 forever
+        let fen = "rq3rk1/5p1p/8/8/8/8/3Q2PP/7K w - - 0 1";
+        let mut game_state = GameState::from_str(fen).unwrap();
+        let result = search(&mut game_state, &depth(6));
+        assert_eq!(result.score, 0);
+        assert_eq!(result.best_move, Some(Move::new(11, 38)));
+        assert_eq!(game_state.to_fen(), fen);
+
+        // Once the checks have been played in the game, repeating them is a draw at once
+        for chess_move in ["d2g5", "g8h8", "g5f6", "h8g8"] {
+            game_state.make_move(&Move::from_uci(chess_move).unwrap());
+        }
+        let result = search(&mut game_state, &depth(1));
+        assert_eq!(result.score, 0);
+        assert_eq!(result.best_move, Some(Move::new(45, 38)));
+    }
+
+    #[test]
+    fn test_repetitions_do_not_cross_null_moves() {
+        let mut game_state = GameState::new();
+        for chess_move in ["g1f3", "b8c6", "f3g1", "c6b8"] {
+            game_state.make_move(&Move::from_uci(chess_move).unwrap());
+        }
+        assert!(is_repetition(&game_state));
+        // The same pieces, but black to move
+        game_state.make_null_move();
+        assert!(!is_repetition(&game_state));
+    }
+
+    #[test]
+    fn test_node_limit_and_stop_flag() {
+        let mut game_state = GameState::new();
+        let limits = SearchLimits {
+            nodes: Some(5000),
+            ..SearchLimits::default()
+        };
+        let result = search(&mut game_state, &limits);
+        assert!(result.best_move.is_some());
+        assert!(result.nodes <= 5001);
+
+        let stop = Arc::new(AtomicBool::new(true));
+        let infinite = SearchLimits {
+            infinite: true,
+            ..SearchLimits::default()
+        };
+        let result = Searcher::with_stop_flag(infinite, stop).search(&mut game_state, |_| {});
+        assert_eq!(result.depth, 1);
+        assert!(result.best_move.is_some());
+    }
+
+    #[test]
+    fn test_clock_limits() {
+        let mut game_state = GameState::new();
+        let limits = SearchLimits {
+            wtime: Some(Duration::from_millis(3000)),
+            btime: Some(Duration::from_millis(3000)),
+            ..SearchLimits::default()
+        };
+        let result = search(&mut game_state, &limits);
+        assert!(result.best_move.is_some());
+        assert!(result.time < Duration::from_millis(2400));
+
+        // Kg1 is the only legal move, it is played after the first iteration
+        let mut game_state = GameState::from_str("8/8/8/8/8/5k2/r7/7K w - - 0 1").unwrap();
+        let result = search(&mut game_state, &limits);
+        assert_eq!(result.best_move, Some(Move::new(7, 6)));
+        assert_eq!(result.depth, 1);
+    }
+
+    #[test]
+    fn test_parallel_search() {
+        let fen = "2r3k1/5ppp/8/8/8/8/3R1PPP/3R2K1 w - - 0 1";
+        let mut game_state = GameState::from_str(fen).unwrap();
+        let mut searcher = Searcher::new(depth(4));
+        searcher.set_threads(4);
+        let mut depths = Vec::new();
+        let result = searcher.search(&mut game_state, |result| depths.push(result.depth));
+        assert_eq!(result.score, MATE_SCORE - 3);
+        assert_eq!(result.best_move, Some(Move::new(11, 59)));
+        assert_eq!(depths, vec![1, 2, 3, 4]);
+        assert_eq!(game_state.to_fen(), fen);
+
+        // A single thread gives the same result every time
+        let single =
+            |game_state: &mut GameState| Searcher::new(depth(5)).search(game_state, |_| {});
+        let first = single(&mut game_state);
+        let second = single(&mut game_state);
+        assert_eq!((first.pv, first.nodes), (second.pv, second.nodes));
+    }
+
+    #[test]
+    fn test_multi_pv() {
+        // Qxd5 wins a rook, Rxa7 a pawn, and the other moves nothing
+        let fen = "4k3/p7/8/3r4/8/1Q6/8/R3K3 w - - 0 1";
+        let mut game_state = GameState::from_str(fen).unwrap();
+        let mut searcher = Searcher::new(depth(3));
+        searcher.set_multi_pv(3);
+        let mut reported = Vec::new();
+        let lines = searcher.search_lines(&mut game_state, |line| {
+            reported.push((line.depth, line.multipv))
+        });
+        assert_eq!(reported.len(), 9);
+        assert_eq!(reported[3..6], [(2, 1), (2, 2), (2, 3)]);
+        let moves: Vec<String> = lines
+            .iter()
+            .map(|line| line.best_move.as_ref().unwrap().to_string())
+            .collect();
+        assert_eq!(moves[..2], ["b3d5", "a1a7"]);
+        assert!(lines.windows(2).all(|pair| pair[0].score >= pair[1].score));
+        assert!(lines
+            .iter()
+            .all(|line| line.pv[0] == *line.best_move.as_ref().unwrap()));
+        assert_eq!(game_state.to_fen(), fen);
+
+        // There are no more lines than legal moves
+        let mut game_state = GameState::from_str("8/8/8/8/8/5k2/r7/7K w - - 0 1").unwrap();
+        let lines = search_multi_pv(&mut game_state, &depth(3), 4);
+        assert_eq!(lines.len(), 1);
+        let mut game_state = GameState::from_str("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        let lines = Searcher::new(depth(2)).search_lines(&mut game_state, |_| {});
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].best_move, None);
+    }
+
+    #[test]
+    fn test_quiescence_sees_recaptures() {
+        // Qxd6 wins a pawn at depth 1 without quiescence, but cxd6 takes the queen back
+        let mut game_state = GameState::from_str("4k3/2p5/3p4/8/8/8/8/3QK3 w - - 0 1").unwrap();
+        let result = search(&mut game_state, &depth(1));
+        assert_ne!(result.best_move, Some(Move::new(3, 43)));
+        assert!(result.score < 900);
+    }
+
+    #[test]
+    fn test_quiescence_checks() {
+        // Rxa5 wins a knight, but leaves the back rank to the mating Rd1+
+        let fen = "3r2k1/5ppp/8/n7/8/8/5PPP/R5K1 w - - 0 1";
+        let mut game_state = GameState::from_str(fen).unwrap();
+        let mut searcher = Searcher::new(depth(1));
+        let without_checks = searcher.search(&mut game_state, |_| {});
+        assert_eq!(without_checks.best_move, Some(Move::new(0, 32)));
+        searcher.options.quiescence_checks = true;
+        let with_checks = searcher.search(&mut game_state, |_| {});
+        assert_ne!(with_checks.best_move, Some(Move::new(0, 32)));
+        assert_eq!(game_state.to_fen(), fen);
+    }
+
+    #[test]
+    fn test_transposition_table_is_shared_between_searches() {
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+        let mut game_state = GameState::from_str(fen).unwrap();
+        let tt = Arc::new(TranspositionTable::new(1));
+        let mut searcher = Searcher::new(depth(4));
+        searcher.set_transposition_table(tt.clone());
+        let first = searcher.search(&mut game_state, |_| {});
+
+        let mut other = Searcher::new(depth(4));
+        other.set_transposition_table(tt.clone());
+        let second = other.search(&mut game_state, |_| {});
+        assert!(second.nodes < first.nodes);
+        assert_eq!(second.score, first.score);
+    }
+
+    /// The search options with everything switched off.
+    fn plain_search() -> SearchOptions {
+        SearchOptions {
+            quiescence_checks: false,
+            principal_variation_search: false,
+            null_move_pruning: false,
+            late_move_reductions: false,
+            futility_pruning: false,
+            reverse_futility_pruning: false,
+            check_extensions: false,
+            aspiration_windows: false,
+        }
+    }
+
+    #[test]
+    fn test_every_switch_finds_the_mate() {
+        let fen = "2r3k1/5ppp/8/8/8/8/3R1PPP/3R2K1 w - - 0 1";
+        let switches: [fn(&mut SearchOptions) -> &mut bool; 7] = [
+            |options| &mut options.principal_variation_search,
+            |options| &mut options.null_move_pruning,
+            |options| &mut options.late_move_reductions,
+            |options| &mut options.futility_pruning,
+            |options| &mut options.reverse_futility_pruning,
+            |options| &mut options.check_extensions,
+            |options| &mut options.aspiration_windows,
+        ];
+        let mut options = vec![SearchOptions::default(), plain_search()];
+        for switch in switches {
+            let mut switched = SearchOptions::default();
+            *switch(&mut switched) = false;
+            options.push(switched);
+        }
+        for options in options {
+            let mut game_state = GameState::from_str(fen).unwrap();
+            let mut searcher = Searcher::new(depth(5));
+            searcher.options = options.clone();
+            let result = searcher.search(&mut game_state, |_| {});
+            assert_eq!(result.uci_score(), "mate 2", "{:?}", options);
+            assert_eq!(result.pv[0], Move::new(11, 59), "{:?}", options);
+        }
+    }
+
+    #[test]
+    fn test_pruning_reduces_the_tree() {
+        let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4";
+        let mut game_state = GameState::from_str(fen).unwrap();
+        let pruned = search(&mut game_state, &depth(4));
+        let mut searcher = Searcher::new(depth(4));
+        searcher.options = plain_search();
+        let plain = searcher.search(&mut game_state, |_| {});
+        assert!(pruned.nodes * 2 < plain.nodes);
+        assert_eq!(game_state.to_fen(), fen);
+    }
+
+    #[test]
+    fn test_no_null_move_with_pawns_only() {
+        let board = Board::from_str("4k3/pppp4/8/8/8/8/4PPPP/3NK3").unwrap();
+        assert!(has_pieces(&board, Color::White));
+        assert!(!has_pieces(&board, Color::Black));
+    }
+
+    #[test]
+    fn test_iterations_are_reported() {
+        let mut game_state = GameState::new();
+        let mut depths = Vec::new();
+        Searcher::new(depth(3)).search(&mut game_state, |result| depths.push(result.depth));
+        assert_eq!(depths, vec![1, 2, 3]);
+    }
+
+    #[cfg(feature = "syzygy")]
+    #[test]
+    fn test_tablebases() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/syzygy");
+        let tablebases = Arc::new(Tablebases::open(path).unwrap());
+
+        // Only the moves reaching mate the fastest are searched
+        let mut game_state = GameState::from_str("8/8/8/4k3/8/8/8/R3K3 w - - 0 1").unwrap();
+        let root_moves = tablebases.root_moves(&mut game_state).unwrap();
+        assert!(root_moves.len() < game_state.generate_legal_moves().len());
+        let mut searcher = Searcher::new(depth(3));
+        searcher.set_tablebases(Some(tablebases.clone()));
+        let result = searcher.search(&mut game_state, |_| {});
+        assert!(root_moves.contains(&result.best_move.unwrap()));
+
+        // Taking the rook, with the king or the queen, reaches a won position of the tablebases
+        let mut game_state = GameState::from_str("8/8/8/3k4/8/8/1r6/KQ6 w - - 0 1").unwrap();
+        let mut searcher = Searcher::new(depth(2));
+        searcher.set_tablebases(Some(tablebases));
+        let result = searcher.search(&mut game_state, |_| {});
+        assert_eq!(result.best_move.unwrap().target_square, 9);
+        assert_eq!(result.score, TB_WIN_SCORE - 1);
+    }
+}
@@ -0,0 +1,200 @@
+//! Module containing the mate prover, proving or refuting a forced mate in a number of moves.
+//!
+//! Unlike the search, the prover does not evaluate positions: it tries every legal move of the
+//! attacker, checks first, and every legal reply of the defender, until each line ends in
+//! checkmate. It is exhaustive, so a mate it does not find does not exist, and it finds the
+//! shortest mates first.
+
+use std::fmt;
+
+use crate::board::moves::Move;
+use crate::game_state::GameState;
+
+/// A forced mate: a move of the attacker and, for every legal reply of the defender, the
+/// forced mate following it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MateTree {
+    /// The move of the attacker.
+    pub attack: Move,
+    /// Every legal reply to `attack` with the mate following it, empty when `attack` mates.
+    pub defences: Vec<(Move, MateTree)>,
+}
+
+impl MateTree {
+    /// The number of moves of the attacker until mate against the best defence.
+    pub fn moves(&self) -> u32 {
+        1 + self
+            .defences
+            .iter()
+            .map(|(_, tree)| tree.moves())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns the main line of the mate, the defender always choosing the reply delaying
+    /// mate the most.
+    pub fn main_line(&self) -> Vec<Move> {
+        let mut line = vec![self.attack.clone()];
+        let longest = self.defences.iter().max_by_key(|(_, tree)| tree.moves());
+        if let Some((defence, tree)) = longest {
+            line.push(defence.clone());
+            line.extend(tree.main_line());
+        }
+        line
+    }
+
+    fn fmt_indented(&self, f: &mut fmt::Formatter, indent: usize) -> fmt::Result {
+        writeln!(f, "{:indent$}{}", "", self.attack)?;
+        for (defence, tree) in &self.defences {
+            writeln!(f, "{:indent$}  {}", "", defence)?;
+            tree.fmt_indented(f, indent + 4)?;
+        }
+        Ok(())
+    }
+}
+
+/// Displays the tree one move per line, each reply indented under the move it answers.
+impl fmt::Display for MateTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+/// Looks for a forced mate in at most `moves` moves of the side to move, returning the
+/// shortest one found, or `None` when there is none.
+///
+/// The game state is used to play the moves and is left unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use ruce::game_state::GameState;
+/// use ruce::search::mate::find_mate;
+/// use std::str::FromStr;
+///
+/// // Rd8+ Rxd8 Rxd8#
+/// let fen = "2r3k1/5ppp/8/8/8/8/3R1PPP/3R2K1 w - - 0 1";
+/// let mut game_state = GameState::from_str(fen).unwrap();
+/// assert_eq!(find_mate(&mut game_state, 1), None);
+/// let mate = find_mate(&mut game_state, 2).unwrap();
+/// assert_eq!(mate.moves(), 2);
+/// assert_eq!(mate.attack.to_string(), "d2d8");
+/// ```
+pub fn find_mate(game_state: &mut GameState, moves: u32) -> Option<MateTree> {
+    (1..=moves).find_map(|moves| prove(game_state, moves))
+}
+
+/// Proves a mate in at most `moves` moves, trying every move of the attacker, checks first.
+fn prove(game_state: &mut GameState, moves: u32) -> Option<MateTree> {
+    for (attack, check) in ordered_moves(game_state) {
+        // Only a check can mate on the last move
+        if moves == 1 && !check {
+            break;
+        }
+        game_state.make_move(&attack);
+        let defences = refute(game_state, moves);
+        game_state.unmake_move();
+        if let Some(defences) = defences {
+            return Some(MateTree { attack, defences });
+        }
+    }
+    None
+}
+
+/// Checks that every reply of the defender, right after a move of the attacker with `moves`
+/// moves to mate, is mated in time, returning the mate after each of them.
+fn refute(game_state: &mut GameState, moves: u32) -> Option<Vec<(Move, MateTree)>> {
+    let replies = game_state.generate_legal_moves();
+    if replies.is_empty() {
+        // Checkmate, or stalemate which fails the attack
+        return game_state.is_in_check().then(Vec::new);
+    }
+    if moves == 1 {
+        return None;
+    }
+    let mut defences = Vec::with_capacity(replies.len());
+    for defence in replies {
+        game_state.make_move(&defence);
+        let mate = find_mate(game_state, moves - 1);
+        game_state.unmake_move();
+        defences.push((defence, mate?));
+    }
+    Some(defences)
+}
+
+/// Returns the legal moves of the side to move with whether they give check, checks first.
+fn ordered_moves(game_state: &mut GameState) -> Vec<(Move, bool)> {
+    let mut moves: Vec<(Move, bool)> = game_state
+        .generate_legal_moves()
+        .into_iter()
+        .map(|chess_move| {
+            game_state.make_move(&chess_move);
+            let check = game_state.is_in_check();
+            game_state.unmake_move();
+            (chess_move, check)
+        })
+        .collect();
+    moves.sort_by_key(|&(_, check)| !check);
+    moves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn uci(moves: &[Move]) -> Vec<String> {
+        moves.iter().map(Move::to_string).collect()
+    }
+
+    #[test]
+    fn test_mate_in_one_and_two() {
+        let mut game_state = GameState::from_str("6k1/5ppp/8/8/8/8/8/3R2K1 w - - 0 1").unwrap();
+        let mate = find_mate(&mut game_state, 3).unwrap();
+        assert_eq!(mate.moves(), 1);
+        assert_eq!(uci(&mate.main_line()), ["d1d8"]);
+
+        let fen = "2r3k1/5ppp/8/8/8/8/3R1PPP/3R2K1 w - - 0 1";
+        let mut game_state = GameState::from_str(fen).unwrap();
+        let mate = find_mate(&mut game_state, 2).unwrap();
+        assert_eq!(uci(&mate.main_line()), ["d2d8", "c8d8", "d1d8"]);
+        // The tree answers every legal reply, here the only one
+        assert_eq!(mate.defences.len(), 1);
+        assert_eq!(mate.to_string(), "d2d8\n  c8d8\n    d1d8\n");
+        assert_eq!(game_state.to_fen(), fen);
+    }
+
+    #[test]
+    fn test_every_defence_is_answered() {
+        // Rb7 leaves the black king Kd8 and Kf8, both met by Ra8
+        let mut game_state = GameState::from_str("4k3/8/8/8/8/8/R7/1R2K3 w - - 0 1").unwrap();
+        assert_eq!(find_mate(&mut game_state, 1), None);
+        let mate = find_mate(&mut game_state, 2).unwrap();
+        assert_eq!(mate.moves(), 2);
+        assert_eq!(mate.defences.len(), 2);
+        for (defence, tree) in &mate.defences {
+            game_state.make_move(&mate.attack);
+            game_state.make_move(defence);
+            game_state.make_move(&tree.attack);
+            assert!(game_state.is_checkmate());
+            for _ in 0..3 {
+                game_state.unmake_move();
+            }
+        }
+    }
+
+    #[test]
+    fn test_no_mate() {
+        let mut game_state = GameState::from_str("7k/8/5K2/8/8/8/8/8 w - - 0 1").unwrap();
+        assert_eq!(find_mate(&mut game_state, 3), None);
+        // The shortest mate is found first
+        let mut game_state = GameState::from_str("7k/8/5K2/8/8/8/8/6Q1 w - - 0 1").unwrap();
+        assert_eq!(
+            uci(&find_mate(&mut game_state, 2).unwrap().main_line()),
+            ["g1g7"]
+        );
+        // The side to move is mated: no mate to find
+        let mut game_state = GameState::from_str("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(find_mate(&mut game_state, 2), None);
+    }
+}
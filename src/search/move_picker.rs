@@ -0,0 +1,328 @@
+//! Module containing the move picker, returning the moves of a position best first.
+//!
+//! Alpha-beta cuts the search of a position as soon as a move reaches beta, so the moves most
+//! likely to do so are tried first: the hash move, then the captures ordered by Most Valuable
+//! Victim / Least Valuable Attacker, then the killer moves and the counter-move, then the
+//! other quiet moves ordered by their history score, and finally the captures losing material
+//! according to their static exchange evaluation. The moves are generated in stages, so the
+//! quiet moves are never generated when a capture already cut the search.
+//!
+//! The killers, counter-moves and history scores are learned during the search and kept in
+//! `Heuristics`.
+
+use crate::board::moves::Move;
+use crate::board::piece::{Color, PieceType};
+use crate::board::Board;
+use crate::eval::color_index;
+use crate::search::{mvv_lva, MAX_PLY};
+
+/// History scores stay between `-MAX_HISTORY` and `MAX_HISTORY`.
+const MAX_HISTORY: i32 = 16_384;
+
+/// The move ordering knowledge gathered by the search: the killer moves of each ply, the
+/// counter-move of each move and the history score of each quiet move.
+pub struct Heuristics {
+    /// The last two quiet moves that caused a cutoff at each ply, most recent first.
+    killers: Vec<[Option<Move>; 2]>,
+    /// The quiet move that refuted each move, indexed by its initial and target squares.
+    counter_moves: Vec<[Option<Move>; 64]>,
+    /// How often each quiet move caused a cutoff, indexed by color, initial and target square.
+    history: Vec<[[i32; 64]; 64]>,
+}
+
+impl Heuristics {
+    /// Creates empty heuristics.
+    pub fn new() -> Self {
+        Heuristics {
+            killers: vec![[None, None]; MAX_PLY + 1],
+            counter_moves: vec![[const { None }; 64]; 64],
+            history: vec![[[0; 64]; 64]; 2],
+        }
+    }
+
+    /// Prepares for a new search: the killers are forgotten, since the plies do not match
+    /// anymore, and the history scores are halved.
+    pub fn new_search(&mut self) {
+        self.killers.fill([None, None]);
+        for scores in self.history.iter_mut().flatten().flatten() {
+            *scores /= 2;
+        }
+    }
+
+    /// Returns the killer moves of a ply.
+    pub fn killers(&self, ply: usize) -> [Option<Move>; 2] {
+        self.killers[ply].clone()
+    }
+
+    /// Returns the counter-move of the previous move, if any.
+    pub fn counter_move(&self, previous_move: Option<&Move>) -> Option<Move> {
+        let previous_move = previous_move?;
+        self.counter_moves[previous_move.initial_square as usize]
+            [previous_move.target_square as usize]
+            .clone()
+    }
+
+    /// Returns the history score of a quiet move of `color`.
+    pub fn history(&self, color: Color, chess_move: &Move) -> i32 {
+        self.history[color_index(color)][chess_move.initial_square as usize]
+            [chess_move.target_square as usize]
+    }
+
+    /// Records that the quiet move `best_move` of `color` caused a cutoff at a depth and a ply,
+    /// after the quiet moves `tried` failed to, in reply to `previous_move`.
+    pub fn update(
+        &mut self,
+        color: Color,
+        best_move: &Move,
+        tried: &[Move],
+        previous_move: Option<&Move>,
+        depth: u32,
+        ply: usize,
+    ) {
+        let killers = &mut self.killers[ply];
+        if killers[0].as_ref() != Some(best_move) {
+            killers[1] = killers[0].take();
+            killers[0] = Some(best_move.clone());
+        }
+        if let Some(previous_move) = previous_move {
+            self.counter_moves[previous_move.initial_square as usize]
+                [previous_move.target_square as usize] = Some(best_move.clone());
+        }
+
+        let bonus = (depth * depth).min(400) as i32;
+        let history = &mut self.history[color_index(color)];
+        let mut add_bonus = |chess_move: &Move, bonus: i32| {
+            let score =
+                &mut history[chess_move.initial_square as usize][chess_move.target_square as usize];
+            // The more extreme the score already is, the less it moves
+            *score += bonus - *score * bonus.abs() / MAX_HISTORY;
+        };
+        add_bonus(best_move, bonus);
+        for chess_move in tried.iter().filter(|&chess_move| chess_move != best_move) {
+            add_bonus(chess_move, -bonus);
+        }
+    }
+}
+
+impl Default for Heuristics {
+    fn default() -> Self {
+        Heuristics::new()
+    }
+}
+
+/// The stages of a `MovePicker`, in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    HashMove,
+    GenerateCaptures,
+    Captures,
+    /// The killer moves, then the counter-move.
+    Refutations,
+    GenerateQuiets,
+    Quiets,
+    /// The captures losing material, put aside during the captures stage.
+    BadCaptures,
+    Done,
+}
+
+/// Returns the pseudo-legal moves of a position one by one, best first.
+///
+/// The position must not change between two calls to `next`, except for moves played and
+/// taken back.
+pub struct MovePicker {
+    stage: Stage,
+    hash_move: Option<Move>,
+    /// The killers and the counter-move, in the order they are tried.
+    refutations: Vec<Move>,
+    refutation_index: usize,
+    /// The moves generated by the current stage, best last so they can be popped.
+    moves: Vec<Move>,
+    bad_captures: Vec<Move>,
+}
+
+impl MovePicker {
+    /// Creates a picker trying `hash_move` first, then the captures, the killer moves, the
+    /// counter-move and the quiet moves.
+    pub fn new(
+        hash_move: Option<Move>,
+        killers: [Option<Move>; 2],
+        counter_move: Option<Move>,
+    ) -> Self {
+        MovePicker {
+            stage: Stage::HashMove,
+            hash_move,
+            refutations: killers
+                .into_iter()
+                .chain([counter_move])
+                .flatten()
+                .collect(),
+            refutation_index: 0,
+            moves: Vec::new(),
+            bad_captures: Vec::new(),
+        }
+    }
+
+    /// Returns the next move of the position, `None` once every move has been returned.
+    pub fn next(&mut self, board: &Board, color: Color, heuristics: &Heuristics) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::HashMove => {
+                    self.stage = Stage::GenerateCaptures;
+                    match &self.hash_move {
+                        Some(hash_move) if board.is_pseudo_legal(hash_move, color) => {
+                            return Some(hash_move.clone());
+                        }
+                        _ => self.hash_move = None,
+                    }
+                }
+                Stage::GenerateCaptures => {
+                    self.moves = board.generate_captures(color);
+                    self.moves
+                        .retain(|chess_move| Some(chess_move) != self.hash_move.as_ref());
+                    self.moves
+                        .sort_by_key(|chess_move| mvv_lva(board, chess_move));
+                    self.stage = Stage::Captures;
+                }
+                Stage::Captures => match self.moves.pop() {
+                    Some(chess_move) if board.see_ge(&chess_move, 0) => return Some(chess_move),
+                    Some(chess_move) => self.bad_captures.push(chess_move),
+                    None => self.stage = Stage::Refutations,
+                },
+                Stage::Refutations => {
+                    let Some(chess_move) = self.refutations.get(self.refutation_index) else {
+                        self.stage = Stage::GenerateQuiets;
+                        continue;
+                    };
+                    self.refutation_index += 1;
+                    // A refutation of another position may not be a move of this one
+                    let duplicate = Some(chess_move) == self.hash_move.as_ref()
+                        || self.refutations[..self.refutation_index - 1].contains(chess_move);
+                    if !duplicate
+                        && is_quiet(board, chess_move)
+                        && board.is_pseudo_legal(chess_move, color)
+                    {
+                        return Some(chess_move.clone());
+                    }
+                }
+                Stage::GenerateQuiets => {
+                    self.moves = board
+                        .generate_moves(color)
+                        .into_iter()
+                        .filter(|chess_move| {
+                            is_quiet(board, chess_move)
+                                && Some(chess_move) != self.hash_move.as_ref()
+                                && !self.refutations.contains(chess_move)
+                        })
+                        .collect();
+                    self.moves
+                        .sort_by_key(|chess_move| heuristics.history(color, chess_move));
+                    self.stage = Stage::Quiets;
+                }
+                Stage::Quiets => match self.moves.pop() {
+                    Some(chess_move) => return Some(chess_move),
+                    None => {
+                        self.bad_captures.reverse();
+                        self.stage = Stage::BadCaptures;
+                    }
+                },
+                Stage::BadCaptures => match self.bad_captures.pop() {
+                    Some(chess_move) => return Some(chess_move),
+                    None => self.stage = Stage::Done,
+                },
+                Stage::Done => return None,
+            }
+        }
+    }
+}
+
+/// Checks whether a move neither captures nor promotes. Castling is quiet, even in Chess960
+/// where the king takes its own rook.
+pub fn is_quiet(board: &Board, chess_move: &Move) -> bool {
+    let color = board
+        .get_piece(chess_move.initial_square)
+        .map(|piece| piece.color);
+    let captures = board
+        .get_piece(chess_move.target_square)
+        .is_some_and(|piece| Some(piece.color) != color);
+    if chess_move.promotion.is_some() || captures {
+        return false;
+    }
+    // En passant captures land on an empty square
+    !(board.en_passant_target == Some(chess_move.target_square)
+        && board
+            .get_piece(chess_move.initial_square)
+            .is_some_and(|piece| piece.piece_type == PieceType::Pawn))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn picked(picker: &mut MovePicker, board: &Board, heuristics: &Heuristics) -> Vec<String> {
+        std::iter::from_fn(|| picker.next(board, Color::White, heuristics))
+            .map(|chess_move| chess_move.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_moves_are_picked_in_stages() {
+        // The queen can take the rook, the pawn can take the knight
+        let board = Board::from_str("4k3/8/2r5/3n4/4P3/8/2Q5/4K3").unwrap();
+        let mut heuristics = Heuristics::new();
+        heuristics.update(Color::White, &Move::new(4, 5), &[], None, 3, 0);
+        heuristics.update(
+            Color::White,
+            &Move::new(10, 2),
+            &[Move::new(4, 5)],
+            None,
+            3,
+            1,
+        );
+
+        let hash_move = Move::new(10, 18);
+        let killers = heuristics.killers(0);
+        let counter_move = Some(Move::new(28, 36));
+        let mut picker = MovePicker::new(Some(hash_move.clone()), killers, counter_move);
+        let moves = picked(&mut picker, &board, &heuristics);
+        assert_eq!(moves[..5], ["c2c3", "c2c6", "e4d5", "e1f1", "e4e5"]);
+        // The history of c2c1 is higher than the one of every other quiet move
+        assert_eq!(moves[5], "c2c1");
+
+        let mut expected: Vec<String> = board
+            .generate_moves(Color::White)
+            .iter()
+            .map(Move::to_string)
+            .collect();
+        let mut moves = moves;
+        expected.sort();
+        moves.sort();
+        assert_eq!(moves, expected);
+    }
+
+    #[test]
+    fn test_losing_captures_come_last() {
+        // Qxd5 loses the queen to the pawn on e6
+        let board = Board::from_str("4k3/8/4p3/3p4/8/8/8/3QK3").unwrap();
+        let heuristics = Heuristics::new();
+        let mut picker = MovePicker::new(None, [None, None], None);
+        let moves = picked(&mut picker, &board, &heuristics);
+        assert_eq!(moves.last().unwrap(), "d1d5");
+        assert_eq!(moves.len(), board.generate_moves(Color::White).len());
+    }
+
+    #[test]
+    fn test_invalid_hash_move_and_killers_are_skipped() {
+        let board = Board::from_str("4k3/8/8/8/8/8/8/4K3").unwrap();
+        let heuristics = Heuristics::new();
+        // e1e2 is a capture-free king move, a1a2 has no piece and e1e3 is not a king move
+        let mut picker = MovePicker::new(
+            Some(Move::new(0, 8)),
+            [Some(Move::new(4, 20)), Some(Move::new(4, 12))],
+            Some(Move::new(4, 12)),
+        );
+        let moves = picked(&mut picker, &board, &heuristics);
+        assert_eq!(moves[0], "e1e2");
+        assert_eq!(moves.len(), 5);
+    }
+}
@@ -0,0 +1,165 @@
+//! Module containing the time manager, budgeting the time of a move from the clock.
+//!
+//! Every move gets two limits. The soft limit is checked between two iterations of the
+//! search: no new iteration is started once it has passed. It is stretched when the search is
+//! unsure, i.e. when the best move keeps changing or the score drops. The hard limit is
+//! checked during the iterations, and stops the search wherever it is.
+
+use std::time::Duration;
+
+use crate::board::moves::Move;
+use crate::board::piece::Color;
+use crate::search::SearchLimits;
+
+/// The number of moves the remaining time is shared between, when `movestogo` is not given.
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+/// The hard limit is this many times the soft limit...
+const HARD_LIMIT_FACTOR: u32 = 4;
+/// ...and never more than this share of the remaining time, in percent.
+const MAX_TIME_PERCENT: u32 = 80;
+/// The score drop, in centipawns, at which the soft limit is stretched the most.
+const MAX_SCORE_DROP: i32 = 100;
+
+/// Budgets the time of one move and decides when to stop searching it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeManager {
+    soft: Duration,
+    hard: Duration,
+    /// How often the best move changed in the last iterations, older changes counting less.
+    best_move_changes: f64,
+    best_move: Option<Move>,
+    score: Option<i32>,
+    /// The score drop between the last two iterations, in centipawns.
+    score_drop: i32,
+}
+
+impl TimeManager {
+    /// Creates the time manager of a move of `side_to_move`, or `None` when the limits do not
+    /// give its clock or the search is infinite.
+    ///
+    /// The remaining time, less the move overhead, is shared between the moves to go, and
+    /// most of the increment is added.
+    pub fn new(limits: &SearchLimits, side_to_move: Color) -> Option<TimeManager> {
+        let (time, increment) = match side_to_move {
+            Color::White => (limits.wtime?, limits.winc),
+            Color::Black => (limits.btime?, limits.binc),
+        };
+        if limits.infinite {
+            return None;
+        }
+        let available = time.saturating_sub(limits.move_overhead);
+        let moves_to_go = limits.movestogo.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+        let increment = increment.unwrap_or_default() * 3 / 4;
+        let max_time = available * MAX_TIME_PERCENT / 100;
+
+        let soft = (available / moves_to_go + increment).min(max_time);
+        let hard = (soft * HARD_LIMIT_FACTOR).min(max_time);
+        Some(TimeManager {
+            soft,
+            hard,
+            best_move_changes: 0.0,
+            best_move: None,
+            score: None,
+            score_drop: 0,
+        })
+    }
+
+    /// The time after which no new iteration is started, before any stretching.
+    pub fn soft_limit(&self) -> Duration {
+        self.soft
+    }
+
+    /// The time after which the search stops in the middle of an iteration.
+    pub fn hard_limit(&self) -> Duration {
+        self.hard
+    }
+
+    /// Records the result of an iteration.
+    pub fn update(&mut self, best_move: Option<&Move>, score: i32) {
+        self.best_move_changes /= 2.0;
+        if self.best_move.is_some() && self.best_move.as_ref() != best_move {
+            self.best_move_changes += 1.0;
+        }
+        self.score_drop = self.score.map_or(0, |previous| previous - score);
+        self.best_move = best_move.cloned();
+        self.score = Some(score);
+    }
+
+    /// The soft limit, stretched when the best move is unstable or the score drops.
+    pub fn scaled_soft_limit(&self) -> Duration {
+        let instability = 1.0 + self.best_move_changes.min(2.0) / 2.0;
+        let drop = self.score_drop.clamp(0, MAX_SCORE_DROP) as f64 / MAX_SCORE_DROP as f64;
+        let scale = instability * (1.0 + drop / 2.0);
+        self.soft.mul_f64(scale).min(self.hard)
+    }
+
+    /// Checks whether a new iteration should be started after `elapsed`.
+    pub fn should_continue(&self, elapsed: Duration) -> bool {
+        elapsed < self.scaled_soft_limit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clock(wtime: u64, winc: u64, movestogo: Option<u32>) -> SearchLimits {
+        SearchLimits {
+            wtime: Some(Duration::from_millis(wtime)),
+            btime: Some(Duration::from_millis(1000)),
+            winc: Some(Duration::from_millis(winc)),
+            movestogo,
+            ..SearchLimits::default()
+        }
+    }
+
+    #[test]
+    fn test_time_allocation() {
+        let time = TimeManager::new(&clock(60_000, 0, Some(20)), Color::White).unwrap();
+        assert_eq!(time.soft_limit(), Duration::from_millis(3000));
+        assert_eq!(time.hard_limit(), Duration::from_millis(12_000));
+
+        let time = TimeManager::new(&clock(60_000, 1000, None), Color::White).unwrap();
+        assert_eq!(time.soft_limit(), Duration::from_millis(2000 + 750));
+
+        // Little time left: the limits stay within the clock
+        let mut limits = clock(100, 1000, Some(1));
+        limits.move_overhead = Duration::from_millis(50);
+        let time = TimeManager::new(&limits, Color::White).unwrap();
+        assert_eq!(time.soft_limit(), Duration::from_millis(40));
+        assert_eq!(time.hard_limit(), Duration::from_millis(40));
+
+        let time = TimeManager::new(&clock(60_000, 0, None), Color::Black).unwrap();
+        assert!(time.hard_limit() < Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn test_no_time_manager_without_clock() {
+        assert_eq!(
+            TimeManager::new(&SearchLimits::default(), Color::White),
+            None
+        );
+        let mut limits = clock(60_000, 0, None);
+        limits.infinite = true;
+        assert_eq!(TimeManager::new(&limits, Color::White), None);
+    }
+
+    #[test]
+    fn test_unstable_searches_get_more_time() {
+        let mut time = TimeManager::new(&clock(60_000, 0, Some(20)), Color::White).unwrap();
+        let (e2e4, d2d4) = (Move::new(12, 28), Move::new(11, 27));
+        time.update(Some(&e2e4), 30);
+        time.update(Some(&e2e4), 30);
+        assert_eq!(time.scaled_soft_limit(), time.soft_limit());
+        assert!(time.should_continue(Duration::from_millis(2999)));
+        assert!(!time.should_continue(Duration::from_millis(3000)));
+
+        time.update(Some(&d2d4), 30);
+        assert_eq!(time.scaled_soft_limit(), Duration::from_millis(4500));
+
+        time.update(Some(&d2d4), 30);
+        time.update(Some(&d2d4), -70);
+        let stable = time.soft_limit().mul_f64(1.0 + 0.25 / 2.0);
+        assert_eq!(time.scaled_soft_limit(), stable.mul_f64(1.5));
+    }
+}
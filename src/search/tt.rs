@@ -0,0 +1,384 @@
+//! Module containing the transposition table, remembering the results of the positions already
+//! searched.
+//!
+//! The table is made of buckets of two entries indexed by the Zobrist key of the position. The
+//! first entry of a bucket is depth-preferred: it is only replaced by a deeper search of
+//! another position, or by an entry of a newer search. The second one is always replaced by
+//! what the first entry did not take, so that recent results are kept too.
+//!
+//! Every entry is two atomic words, the key xored with the data and the data itself, so the
+//! table can be shared between threads without locks: an entry torn by two concurrent writes
+//! does not match its key anymore and is ignored.
+
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use crate::board::moves::Move;
+use crate::board::piece::PieceType;
+use crate::search::{MAX_PLY, TB_WIN_SCORE};
+
+/// The size of the table when none is given, in megabytes.
+pub const DEFAULT_HASH_MB: usize = 16;
+
+/// The age is stored on 6 bits.
+const AGE_MASK: u8 = 0x3f;
+
+/// How the score of an entry relates to the real score of the position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    /// The real score is at most the score of the entry, no move raised alpha.
+    Upper,
+    /// The real score is at least the score of the entry, a move reached beta.
+    Lower,
+    /// The score of the entry is the real score.
+    Exact,
+}
+
+/// The result of the search of a position, as stored in the table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TtEntry {
+    /// The best move found, if any move was searched.
+    pub best_move: Option<Move>,
+    /// The score of the position, from the point of view of the side to move. Mate scores
+    /// are relative to the ply given to `probe`.
+    pub score: i32,
+    /// The depth of the search.
+    pub depth: u32,
+    /// How `score` relates to the real score.
+    pub bound: Bound,
+}
+
+#[derive(Debug, Default)]
+struct Slot {
+    /// The key of the position xored with `data`.
+    check: AtomicU64,
+    data: AtomicU64,
+}
+
+impl Slot {
+    /// Returns the data of the slot if it holds the position with the given key.
+    fn load(&self, key: u64) -> Option<u64> {
+        let data = self.data.load(Ordering::Relaxed);
+        let check = self.check.load(Ordering::Relaxed);
+        (data != 0 && check ^ data == key).then_some(data)
+    }
+
+    fn store(&self, key: u64, data: u64) {
+        self.check.store(key ^ data, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
+
+    /// Returns the raw data of the slot, whatever position it holds.
+    fn data(&self) -> u64 {
+        self.data.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Bucket {
+    depth_preferred: Slot,
+    always_replace: Slot,
+}
+
+/// A hash table of search results, shared by every search of a game.
+#[derive(Debug)]
+pub struct TranspositionTable {
+    buckets: Vec<Bucket>,
+    /// The age of the current search, entries of older searches are replaced first.
+    age: AtomicU8,
+}
+
+impl TranspositionTable {
+    /// Creates an empty table taking about `size_mb` megabytes, at least one bucket.
+    pub fn new(size_mb: usize) -> Self {
+        let buckets = size_mb * 1024 * 1024 / std::mem::size_of::<Bucket>();
+        TranspositionTable::with_buckets(buckets.max(1))
+    }
+
+    fn with_buckets(buckets: usize) -> Self {
+        TranspositionTable {
+            buckets: (0..buckets).map(|_| Bucket::default()).collect(),
+            age: AtomicU8::new(0),
+        }
+    }
+
+    /// The number of entries the table can hold.
+    pub fn capacity(&self) -> usize {
+        self.buckets.len() * 2
+    }
+
+    /// Removes every entry.
+    pub fn clear(&self) {
+        for bucket in &self.buckets {
+            bucket.depth_preferred.store(0, 0);
+            bucket.always_replace.store(0, 0);
+        }
+        self.age.store(0, Ordering::Relaxed);
+    }
+
+    /// Starts a new search, making the entries of the previous ones older.
+    pub fn new_search(&self) {
+        let age = self.age.load(Ordering::Relaxed);
+        self.age.store((age + 1) & AGE_MASK, Ordering::Relaxed);
+    }
+
+    /// Looks the position up, `ply` being its distance to the root of the search.
+    pub fn probe(&self, key: u64, ply: usize) -> Option<TtEntry> {
+        let bucket = self.bucket(key);
+        let data = bucket
+            .depth_preferred
+            .load(key)
+            .or_else(|| bucket.always_replace.load(key))?;
+        let (best_move, score, depth, bound, _) = unpack(data);
+        Some(TtEntry {
+            best_move,
+            score: score_from_tt(score, ply),
+            depth,
+            bound,
+        })
+    }
+
+    /// Stores the result of the search of a position, `ply` being its distance to the root.
+    ///
+    /// When the search found no best move, the best move already stored for the position is
+    /// kept.
+    pub fn store(
+        &self,
+        key: u64,
+        depth: u32,
+        bound: Bound,
+        score: i32,
+        best_move: Option<&Move>,
+        ply: usize,
+    ) {
+        let bucket = self.bucket(key);
+        let age = self.age.load(Ordering::Relaxed);
+        let previous_move = || {
+            let data = bucket
+                .depth_preferred
+                .load(key)
+                .or_else(|| bucket.always_replace.load(key))?;
+            unpack(data).0
+        };
+        let best_move = best_move.cloned().or_else(previous_move);
+        let data = pack(
+            best_move.as_ref(),
+            score_to_tt(score, ply),
+            depth,
+            bound,
+            age,
+        );
+
+        let (_, _, old_depth, _, old_age) = unpack(bucket.depth_preferred.data());
+        let replace = bucket.depth_preferred.data() == 0
+            || bucket.depth_preferred.load(key).is_some()
+            || old_age != age
+            || depth >= old_depth;
+        if replace {
+            bucket.depth_preferred.store(key, data);
+        } else {
+            bucket.always_replace.store(key, data);
+        }
+    }
+
+    /// Returns how full the table is, in permille, counting the entries of the current search
+    /// in the first buckets.
+    pub fn hashfull(&self) -> u32 {
+        let age = self.age.load(Ordering::Relaxed);
+        let sample = &self.buckets[..self.buckets.len().min(500)];
+        let used = sample
+            .iter()
+            .flat_map(|bucket| [&bucket.depth_preferred, &bucket.always_replace])
+            .filter(|slot| slot.data() != 0 && unpack(slot.data()).4 == age)
+            .count();
+        (used * 1000 / (sample.len() * 2)) as u32
+    }
+
+    fn bucket(&self, key: u64) -> &Bucket {
+        let index = ((key as u128 * self.buckets.len() as u128) >> 64) as usize;
+        &self.buckets[index]
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        TranspositionTable::new(DEFAULT_HASH_MB)
+    }
+}
+
+/// Scores above this value (or below its negation) are mate or tablebase scores, which count
+/// the plies from the root.
+const DISTANCE_THRESHOLD: i32 = TB_WIN_SCORE - MAX_PLY as i32;
+
+/// Mate and tablebase scores are stored relative to the position rather than to the root, so
+/// that they stay right when the position is reached at another ply.
+fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score > DISTANCE_THRESHOLD {
+        score + ply as i32
+    } else if score < -DISTANCE_THRESHOLD {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score > DISTANCE_THRESHOLD {
+        score - ply as i32
+    } else if score < -DISTANCE_THRESHOLD {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+/// Packs an entry in a word: 16 bits of move, 16 of score, 8 of depth, 2 of bound and 6 of
+/// age. The bound is never 0, so a 0 word is an empty slot.
+fn pack(best_move: Option<&Move>, score: i32, depth: u32, bound: Bound, age: u8) -> u64 {
+    let bound = match bound {
+        Bound::Upper => 1,
+        Bound::Lower => 2,
+        Bound::Exact => 3,
+    };
+    encode_move(best_move) as u64
+        | (score as i16 as u16 as u64) << 16
+        | (depth.min(u8::MAX as u32) as u64) << 32
+        | bound << 40
+        | (age as u64) << 42
+}
+
+fn unpack(data: u64) -> (Option<Move>, i32, u32, Bound, u8) {
+    let bound = match (data >> 40) & 3 {
+        1 => Bound::Upper,
+        2 => Bound::Lower,
+        _ => Bound::Exact,
+    };
+    (
+        decode_move(data as u16),
+        (data >> 16) as u16 as i16 as i32,
+        (data >> 32) as u8 as u32,
+        bound,
+        (data >> 42) as u8 & AGE_MASK,
+    )
+}
+
+/// Encodes a move on 16 bits: 6 for each square and 3 for the promotion. No move is 0, which
+/// is not a valid move since it goes from a1 to a1.
+fn encode_move(chess_move: Option<&Move>) -> u16 {
+    let Some(chess_move) = chess_move else {
+        return 0;
+    };
+    let promotion = match chess_move.promotion {
+        None => 0,
+        Some(PieceType::Knight) => 1,
+        Some(PieceType::Bishop) => 2,
+        Some(PieceType::Rook) => 3,
+        Some(_) => 4,
+    };
+    chess_move.initial_square as u16 | (chess_move.target_square as u16) << 6 | promotion << 12
+}
+
+fn decode_move(data: u16) -> Option<Move> {
+    if data == 0 {
+        return None;
+    }
+    let promotion = match data >> 12 {
+        1 => Some(PieceType::Knight),
+        2 => Some(PieceType::Bishop),
+        3 => Some(PieceType::Rook),
+        4 => Some(PieceType::Queen),
+        _ => None,
+    };
+    Some(Move {
+        initial_square: (data & 0x3f) as u8,
+        target_square: ((data >> 6) & 0x3f) as u8,
+        promotion,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::MATE_SCORE;
+
+    #[test]
+    fn test_store_and_probe() {
+        let tt = TranspositionTable::new(1);
+        let promotion = Move {
+            initial_square: 52,
+            target_square: 61,
+            promotion: Some(PieceType::Knight),
+        };
+        tt.store(42, 5, Bound::Lower, -120, Some(&promotion), 3);
+        assert_eq!(
+            tt.probe(42, 7),
+            Some(TtEntry {
+                best_move: Some(promotion.clone()),
+                score: -120,
+                depth: 5,
+                bound: Bound::Lower,
+            })
+        );
+        assert_eq!(tt.probe(43, 7), None);
+
+        // Storing without a best move keeps the previous one
+        tt.store(42, 6, Bound::Upper, -150, None, 3);
+        assert_eq!(tt.probe(42, 3).unwrap().best_move, Some(promotion));
+
+        tt.clear();
+        assert_eq!(tt.probe(42, 3), None);
+    }
+
+    #[test]
+    fn test_mate_scores_are_relative_to_the_position() {
+        let tt = TranspositionTable::new(1);
+        // Mate in 3 plies from a position found at ply 4
+        tt.store(7, 3, Bound::Exact, MATE_SCORE - 7, None, 4);
+        assert_eq!(tt.probe(7, 4).unwrap().score, MATE_SCORE - 7);
+        assert_eq!(tt.probe(7, 2).unwrap().score, MATE_SCORE - 5);
+        tt.store(8, 3, Bound::Exact, -MATE_SCORE + 7, None, 4);
+        assert_eq!(tt.probe(8, 10).unwrap().score, -MATE_SCORE + 13);
+        // Tablebase wins count the plies from the root too
+        tt.store(9, 3, Bound::Exact, TB_WIN_SCORE - 6, None, 6);
+        assert_eq!(tt.probe(9, 2).unwrap().score, TB_WIN_SCORE - 2);
+        tt.store(10, 3, Bound::Exact, -TB_WIN_SCORE + 6, None, 6);
+        assert_eq!(tt.probe(10, 8).unwrap().score, -TB_WIN_SCORE + 8);
+    }
+
+    #[test]
+    fn test_replacement_policy() {
+        let tt = TranspositionTable::with_buckets(1);
+        tt.store(1, 8, Bound::Exact, 10, None, 0);
+        // A shallower entry goes to the always-replace slot
+        tt.store(2, 3, Bound::Exact, 20, None, 0);
+        assert!(tt.probe(1, 0).is_some());
+        assert!(tt.probe(2, 0).is_some());
+        tt.store(3, 2, Bound::Exact, 30, None, 0);
+        assert!(tt.probe(1, 0).is_some());
+        assert!(tt.probe(2, 0).is_none());
+        assert!(tt.probe(3, 0).is_some());
+
+        // An entry of a previous search is replaced whatever its depth
+        tt.new_search();
+        tt.store(4, 1, Bound::Exact, 40, None, 0);
+        assert!(tt.probe(1, 0).is_none());
+        assert!(tt.probe(4, 0).is_some());
+    }
+
+    #[test]
+    fn test_size_and_hashfull() {
+        let tt = TranspositionTable::new(1);
+        assert_eq!(tt.capacity(), 1024 * 1024 / 16);
+        assert_eq!(tt.hashfull(), 0);
+        for key in 0..1000u64 {
+            tt.store(
+                key.wrapping_mul(0x9e37_79b9_7f4a_7c15),
+                1,
+                Bound::Exact,
+                0,
+                None,
+                0,
+            );
+        }
+        assert!(tt.hashfull() > 0);
+    }
+}
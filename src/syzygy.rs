@@ -0,0 +1,696 @@
+//! Module containing the probing of Syzygy endgame tablebases, which give the exact result of
+//! the positions with few pieces.
+//!
+//! Each set of pieces has two tables: the WDL table (`.rtbw`) tells whether the side to move
+//! wins, draws or loses, and the DTZ table (`.rtbz`) tells how many plies it takes to the next
+//! capture or pawn move, the one zeroing the 50-move counter, when both sides play their best.
+//! Both take the 50-move rule into account: a win that takes too long is a cursed win, and
+//! counts as a draw.
+//!
+//! The tables leave out some positions where a capture is the best move, so a probe first
+//! searches the captures, and the tables of fewer pieces they lead to. Positions with castling
+//! rights are never in the tables.
+//!
+//! Tables are found when the tablebases are opened, but only read when first probed.
+
+mod encoding;
+mod table;
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::ops::Neg;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use thiserror::Error;
+
+use crate::board::moves::Move;
+use crate::board::piece::{Color, Piece, PieceType};
+use crate::board::Board;
+use crate::game_state::GameState;
+
+use self::table::{Table, TableKind};
+
+/// The rank of the best root moves, above the rank of any move given by its distance to zeroing.
+const MAX_DTZ: i32 = 1 << 18;
+
+/// The result of a position for the side to move, with the 50-move rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Wdl {
+    /// Lost.
+    Loss = -2,
+    /// Lost without the 50-move rule, but drawn with it.
+    BlessedLoss = -1,
+    /// Drawn.
+    Draw = 0,
+    /// Won without the 50-move rule, but drawn with it.
+    CursedWin = 1,
+    /// Won.
+    Win = 2,
+}
+
+impl Wdl {
+    /// Converts a value between -2 and 2 to a result.
+    fn from_value(value: i32) -> Wdl {
+        match value {
+            i32::MIN..=-2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            _ => Wdl::Win,
+        }
+    }
+
+    /// The distance to zeroing of a position whose best move zeroes the 50-move counter, cursed
+    /// results being 100 plies further.
+    fn dtz_before_zeroing(self) -> i32 {
+        match self {
+            Wdl::Loss => -1,
+            Wdl::BlessedLoss => -101,
+            Wdl::Draw => 0,
+            Wdl::CursedWin => 101,
+            Wdl::Win => 1,
+        }
+    }
+
+    fn signum(self) -> i32 {
+        (self as i32).signum()
+    }
+}
+
+/// The result of the other side.
+impl Neg for Wdl {
+    type Output = Wdl;
+
+    fn neg(self) -> Wdl {
+        Wdl::from_value(-(self as i32))
+    }
+}
+
+/// An error opening tablebases.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum SyzygyError {
+    #[error("Cannot read the tablebases: {0}")]
+    Io(String),
+    #[error("Not a Syzygy table: {0}")]
+    InvalidMagic(String),
+    #[error("Invalid Syzygy table: {0}")]
+    InvalidTable(String),
+}
+
+/// A table file, read on first use.
+#[derive(Debug)]
+struct LazyTable {
+    name: String,
+    kind: TableKind,
+    path: PathBuf,
+    /// The table, or `None` when the file cannot be read.
+    table: OnceLock<Option<Table>>,
+}
+
+impl LazyTable {
+    fn get(&self) -> Option<&Table> {
+        self.table
+            .get_or_init(|| Table::open(&self.path, &self.name, self.kind).ok())
+            .as_ref()
+    }
+}
+
+/// The Syzygy tables found in a set of directories.
+#[derive(Debug)]
+pub struct Tablebases {
+    tables: HashMap<(String, TableKind), LazyTable>,
+    /// The number of pieces of the largest WDL table.
+    max_pieces: usize,
+}
+
+impl Tablebases {
+    /// Finds the tables in `paths`, a list of directories separated like the `PATH` variable
+    /// (by ':' on Unix, ';' on Windows). Files which are not tables are ignored.
+    pub fn open(paths: &str) -> Result<Tablebases, SyzygyError> {
+        let mut tables = HashMap::new();
+        let mut max_pieces = 0;
+        for directory in env::split_paths(paths) {
+            let entries = fs::read_dir(&directory)
+                .map_err(|e| SyzygyError::Io(format!("{}: {}", directory.display(), e)))?;
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let (Some(name), Some(extension)) = (
+                    path.file_stem().and_then(|name| name.to_str()),
+                    path.extension().and_then(|extension| extension.to_str()),
+                ) else {
+                    continue;
+                };
+                let kind = match extension {
+                    "rtbw" => TableKind::Wdl,
+                    "rtbz" => TableKind::Dtz,
+                    _ => continue,
+                };
+                let Some(pieces) = table::piece_count(name) else {
+                    continue;
+                };
+                if kind == TableKind::Wdl {
+                    max_pieces = max_pieces.max(pieces);
+                }
+                let table = LazyTable {
+                    name: name.to_string(),
+                    kind,
+                    path: path.clone(),
+                    table: OnceLock::new(),
+                };
+                tables.entry((name.to_string(), kind)).or_insert(table);
+            }
+        }
+        Ok(Tablebases { tables, max_pieces })
+    }
+
+    /// The number of pieces of the largest positions in the tablebases, kings included.
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    /// Checks whether the position can be in the tablebases: it has few enough pieces, and no
+    /// castling rights.
+    pub fn can_probe(&self, game_state: &GameState) -> bool {
+        let (white_kingside, white_queenside, black_kingside, black_queenside) =
+            game_state.board.castling_availability;
+        !(white_kingside || white_queenside || black_kingside || black_queenside)
+            && board_pieces(&game_state.board).len() <= self.max_pieces
+    }
+
+    /// Probes the result of the position for the side to move, or `None` when it is not in the
+    /// tablebases.
+    ///
+    /// The game state is used to play the captures of the position and is left unchanged.
+    pub fn probe_wdl(&self, game_state: &mut GameState) -> Option<Wdl> {
+        if !self.can_probe(game_state) {
+            return None;
+        }
+        self.search(game_state, false).map(|(wdl, _)| wdl)
+    }
+
+    /// Probes the distance to zeroing of the position in plies, positive when the side to move
+    /// wins and negative when it loses, 0 for a draw, or `None` when the position is not in
+    /// the tablebases.
+    ///
+    /// The distance can be one ply off when the best move is the one zeroing the counter, so a
+    /// won position with a distance below 100 plies is a win, but one of 100 can still be a
+    /// cursed win. Cursed wins and blessed losses are 100 plies further than the table gives.
+    ///
+    /// The game state is used to play moves and is left unchanged.
+    pub fn probe_dtz(&self, game_state: &mut GameState) -> Option<i32> {
+        if !self.can_probe(game_state) {
+            return None;
+        }
+        self.dtz(game_state)
+    }
+
+    /// Returns the legal moves of the position keeping its best result, or `None` when it is
+    /// not in the tablebases.
+    ///
+    /// With the DTZ tables, the winning moves kept are the ones reaching the next zeroing move
+    /// the fastest, and the losing moves the ones delaying it the most, so that playing them
+    /// wins, or holds, whatever the search thinks. Without them, every move keeping the result
+    /// of the WDL tables is kept.
+    ///
+    /// The game state is used to play moves and is left unchanged.
+    pub fn root_moves(&self, game_state: &mut GameState) -> Option<Vec<Move>> {
+        if !self.can_probe(game_state) {
+            return None;
+        }
+        let moves = game_state.generate_legal_moves();
+        if moves.is_empty() {
+            return None;
+        }
+        let ranks = self
+            .rank_by_dtz(game_state, &moves)
+            .or_else(|| self.rank_by_wdl(game_state, &moves))?;
+        let best = ranks.iter().copied().max()?;
+        Some(
+            moves
+                .into_iter()
+                .zip(ranks)
+                .filter(|&(_, rank)| rank == best)
+                .map(|(chess_move, _)| chess_move)
+                .collect(),
+        )
+    }
+
+    /// Ranks the moves by their distance to zeroing from the root, higher ranks being better.
+    fn rank_by_dtz(&self, game_state: &mut GameState, moves: &[Move]) -> Option<Vec<i32>> {
+        let mut ranks = Vec::with_capacity(moves.len());
+        for chess_move in moves {
+            game_state.make_move(chess_move);
+            let dtz = if game_state.half_move_clock == 0 {
+                self.search(game_state, false)
+                    .map(|(wdl, _)| (-wdl).dtz_before_zeroing())
+            } else {
+                // One ply further from the root
+                self.dtz(game_state).map(|dtz| -dtz - dtz.signum())
+            };
+            let mate = dtz == Some(2) && game_state.is_checkmate();
+            game_state.unmake_move();
+            let dtz = if mate { 1 } else { dtz? };
+            ranks.push(match dtz.signum() {
+                1 => MAX_DTZ - dtz,
+                -1 => -MAX_DTZ - dtz,
+                _ => 0,
+            });
+        }
+        Some(ranks)
+    }
+
+    /// Ranks the moves by their result.
+    fn rank_by_wdl(&self, game_state: &mut GameState, moves: &[Move]) -> Option<Vec<i32>> {
+        let mut ranks = Vec::with_capacity(moves.len());
+        for chess_move in moves {
+            game_state.make_move(chess_move);
+            let wdl = self.search(game_state, false);
+            game_state.unmake_move();
+            ranks.push(-(wdl?.0 as i32));
+        }
+        Some(ranks)
+    }
+
+    /// Returns the table of the pieces of the board, and whether black is the side the table
+    /// calls white.
+    fn table(&self, board: &Board, kind: TableKind) -> Option<(&Table, bool)> {
+        let white = material_name(board, Color::White);
+        let black = material_name(board, Color::Black);
+        if let Some(table) = self.tables.get(&(format!("{}v{}", white, black), kind)) {
+            return table.get().map(|table| (table, false));
+        }
+        let table = self.tables.get(&(format!("{}v{}", black, white), kind))?;
+        table.get().map(|table| (table, true))
+    }
+
+    /// Looks up the result of the position in its WDL table.
+    fn probe_wdl_table(&self, game_state: &GameState) -> Option<Wdl> {
+        let pieces = board_pieces(&game_state.board);
+        if pieces.len() == 2 {
+            return Some(Wdl::Draw);
+        }
+        let (table, black_stronger) = self.table(&game_state.board, TableKind::Wdl)?;
+        let white_to_move = game_state.current_player == Color::White;
+        Some(table.probe_wdl(&pieces, white_to_move, black_stronger))
+    }
+
+    /// Finds the result of the position from its captures, and from its pawn moves when
+    /// `zeroing_moves` is set, and from its table. Also returns whether the best move is one
+    /// of the moves searched, i.e. a move zeroing the 50-move counter.
+    fn search(&self, game_state: &mut GameState, zeroing_moves: bool) -> Option<(Wdl, bool)> {
+        let moves = game_state.generate_legal_moves();
+        let mut best = Wdl::Loss;
+        let mut searched = 0;
+        for chess_move in &moves {
+            let zeroing = is_capture(&game_state.board, chess_move)
+                || (zeroing_moves && is_pawn_move(&game_state.board, chess_move));
+            if !zeroing {
+                continue;
+            }
+            searched += 1;
+            game_state.make_move(chess_move);
+            let wdl = self.search(game_state, false);
+            game_state.unmake_move();
+            let wdl = -wdl?.0;
+            if wdl > best {
+                best = wdl;
+                if wdl == Wdl::Win {
+                    return Some((wdl, true));
+                }
+            }
+        }
+
+        // When every move has been searched, the table is not needed
+        let every_move = searched > 0 && searched == moves.len();
+        let wdl = if every_move {
+            best
+        } else {
+            self.probe_wdl_table(game_state)?
+        };
+        if best >= wdl {
+            Some((best, best > Wdl::Draw || every_move))
+        } else {
+            Some((wdl, false))
+        }
+    }
+
+    /// Finds the distance to zeroing of the position, see `probe_dtz`.
+    fn dtz(&self, game_state: &mut GameState) -> Option<i32> {
+        let (wdl, zeroing) = self.search(game_state, true)?;
+        if wdl == Wdl::Draw {
+            return Some(0);
+        }
+        if zeroing {
+            return Some(wdl.dtz_before_zeroing());
+        }
+
+        let pieces = board_pieces(&game_state.board);
+        let (table, black_stronger) = self.table(&game_state.board, TableKind::Dtz)?;
+        let white_to_move = game_state.current_player == Color::White;
+        if let Some(dtz) = table.probe_dtz(&pieces, white_to_move, black_stronger, wdl) {
+            let cursed = matches!(wdl, Wdl::CursedWin | Wdl::BlessedLoss);
+            return Some((dtz + 100 * cursed as i32) * wdl.signum());
+        }
+
+        // The table only has the other side to move: the distance is the one of the best reply
+        let mut min_dtz = i32::MAX;
+        for chess_move in game_state.generate_legal_moves() {
+            let zeroing = is_capture(&game_state.board, &chess_move)
+                || is_pawn_move(&game_state.board, &chess_move);
+            game_state.make_move(&chess_move);
+            let dtz = if zeroing {
+                self.search(game_state, false)
+                    .map(|(wdl, _)| -wdl.dtz_before_zeroing())
+            } else {
+                self.dtz(game_state).map(|dtz| -dtz)
+            };
+            let mate = dtz == Some(1) && game_state.is_checkmate();
+            game_state.unmake_move();
+            let mut dtz = dtz?;
+            if mate {
+                min_dtz = 1;
+            }
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+            if dtz < min_dtz && dtz.signum() == wdl.signum() {
+                min_dtz = dtz;
+            }
+        }
+        Some(if min_dtz == i32::MAX { -1 } else { min_dtz })
+    }
+}
+
+/// The pieces of one side in the order of table names, e.g. `KRP`.
+fn material_name(board: &Board, color: Color) -> String {
+    let mut name = String::new();
+    for piece_type in [
+        PieceType::King,
+        PieceType::Queen,
+        PieceType::Rook,
+        PieceType::Bishop,
+        PieceType::Knight,
+        PieceType::Pawn,
+    ] {
+        for square in 0..64 {
+            let piece = board.get_piece(square);
+            if piece.is_some_and(|piece| piece.piece_type == piece_type && piece.color == color) {
+                name.push(piece_type.as_char());
+            }
+        }
+    }
+    name
+}
+
+/// The pieces of the board with their codes in the tables, by increasing square.
+fn board_pieces(board: &Board) -> Vec<(u8, u8)> {
+    (0..64)
+        .filter_map(|square| {
+            board
+                .get_piece(square)
+                .map(|piece| (square, piece_code(piece)))
+        })
+        .collect()
+}
+
+/// The code of a piece in the tables: 1 to 6 from pawn to king, plus 8 for black.
+fn piece_code(piece: &Piece) -> u8 {
+    let code = match piece.piece_type {
+        PieceType::Pawn => 1,
+        PieceType::Knight => 2,
+        PieceType::Bishop => 3,
+        PieceType::Rook => 4,
+        PieceType::Queen => 5,
+        PieceType::King => 6,
+    };
+    match piece.color {
+        Color::White => code,
+        Color::Black => code + 8,
+    }
+}
+
+fn is_capture(board: &Board, chess_move: &Move) -> bool {
+    board.get_piece(chess_move.target_square).is_some()
+        || (board.en_passant_target == Some(chess_move.target_square)
+            && is_pawn_move(board, chess_move))
+}
+
+fn is_pawn_move(board: &Board, chess_move: &Move) -> bool {
+    board
+        .get_piece(chess_move.initial_square)
+        .is_some_and(|piece| piece.piece_type == PieceType::Pawn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/syzygy");
+
+    fn tablebases() -> Tablebases {
+        Tablebases::open(FIXTURES).unwrap()
+    }
+
+    fn probe_wdl(tablebases: &Tablebases, fen: &str) -> Option<Wdl> {
+        let mut game_state = GameState::from_str(fen).unwrap();
+        let wdl = tablebases.probe_wdl(&mut game_state);
+        assert_eq!(game_state.to_fen(), fen);
+        wdl
+    }
+
+    fn probe_dtz(tablebases: &Tablebases, fen: &str) -> Option<i32> {
+        tablebases.probe_dtz(&mut GameState::from_str(fen).unwrap())
+    }
+
+    fn root_moves(tablebases: &Tablebases, fen: &str) -> Option<Vec<String>> {
+        let mut game_state = GameState::from_str(fen).unwrap();
+        let moves = tablebases.root_moves(&mut game_state)?;
+        let mut moves: Vec<String> = moves.iter().map(Move::to_string).collect();
+        moves.sort();
+        Some(moves)
+    }
+
+    #[test]
+    fn test_open() {
+        assert_eq!(tablebases().max_pieces(), 3);
+        assert!(matches!(
+            Tablebases::open("does/not/exist"),
+            Err(SyzygyError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_probe_wdl() {
+        let tablebases = tablebases();
+        assert_eq!(
+            probe_wdl(&tablebases, "4k3/8/8/8/8/8/8/3QK3 w - - 0 1"),
+            Some(Wdl::Win)
+        );
+        assert_eq!(
+            probe_wdl(&tablebases, "4k3/8/8/8/8/8/8/3QK3 b - - 0 1"),
+            Some(Wdl::Loss)
+        );
+        // The colors of the table are swapped
+        assert_eq!(
+            probe_wdl(&tablebases, "3rk3/8/8/8/8/8/8/4K3 w - - 0 1"),
+            Some(Wdl::Loss)
+        );
+        // The queen is lost
+        assert_eq!(
+            probe_wdl(&tablebases, "8/8/8/8/8/8/1k6/Q2K4 b - - 0 1"),
+            Some(Wdl::Draw)
+        );
+        assert_eq!(
+            probe_wdl(&tablebases, "8/8/8/4k3/8/8/8/2B1K3 w - - 0 1"),
+            Some(Wdl::Draw)
+        );
+        assert_eq!(
+            probe_wdl(&tablebases, "4k3/8/8/8/8/8/8/3NK3 b - - 0 1"),
+            Some(Wdl::Draw)
+        );
+        assert_eq!(
+            probe_wdl(&tablebases, "8/8/8/4k3/8/8/8/4K3 b - - 0 1"),
+            Some(Wdl::Draw)
+        );
+
+        // The pawn promotes, or the king stops a rook pawn
+        assert_eq!(
+            probe_wdl(&tablebases, "8/4P3/8/8/8/8/k7/4K3 w - - 0 1"),
+            Some(Wdl::Win)
+        );
+        assert_eq!(
+            probe_wdl(&tablebases, "7k/8/8/8/8/8/7P/7K w - - 0 1"),
+            Some(Wdl::Draw)
+        );
+        assert_eq!(
+            probe_wdl(&tablebases, "4k3/8/8/8/8/8/4p3/K7 b - - 0 1"),
+            Some(Wdl::Win)
+        );
+
+        // Too many pieces, or castling rights
+        assert_eq!(
+            probe_wdl(&tablebases, "4k3/8/8/8/8/8/8/3QK2R w - - 0 1"),
+            None
+        );
+        assert_eq!(
+            probe_wdl(&tablebases, "4k3/8/8/8/8/8/8/4K2R w K - 0 1"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_probe_dtz() {
+        let tablebases = tablebases();
+        // Qg8 mate, or Kb8 Qg8 mate
+        assert_eq!(
+            probe_dtz(&tablebases, "k7/8/1K6/8/8/8/8/6Q1 w - - 0 1"),
+            Some(1)
+        );
+        assert_eq!(
+            probe_dtz(&tablebases, "k7/8/1K6/8/8/8/8/6Q1 b - - 0 1"),
+            Some(-2)
+        );
+        assert_eq!(
+            probe_dtz(&tablebases, "6q1/8/8/8/8/1k6/8/K7 b - - 0 1"),
+            Some(1)
+        );
+        assert_eq!(
+            probe_dtz(&tablebases, "8/8/8/8/8/8/1k6/Q2K4 b - - 0 1"),
+            Some(0)
+        );
+        // The best move is a promotion, which zeroes the counter
+        assert_eq!(
+            probe_dtz(&tablebases, "8/4P3/8/8/8/8/k7/4K3 w - - 0 1"),
+            Some(1)
+        );
+
+        // Without the DTZ table of the position
+        assert_eq!(
+            probe_dtz(&tablebases, "4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"),
+            None
+        );
+    }
+
+    /// The test tables are not written by the official generator, this checks the reader
+    /// against the official tables of KQvK, KRvK, KPvK and KRvKP, found in `SYZYGY_PATH`.
+    #[test]
+    #[ignore = "needs the official tables in SYZYGY_PATH"]
+    fn test_official_tables() {
+        let path = std::env::var("SYZYGY_PATH").expect("SYZYGY_PATH is not set");
+        let tablebases = Tablebases::open(&path).unwrap();
+        assert!(tablebases.max_pieces() >= 4);
+
+        assert_eq!(
+            probe_wdl(&tablebases, "4k3/8/8/8/8/8/8/3QK3 b - - 0 1"),
+            Some(Wdl::Loss)
+        );
+        assert_eq!(
+            probe_wdl(&tablebases, "3rk3/8/8/8/8/8/8/4K3 w - - 0 1"),
+            Some(Wdl::Loss)
+        );
+        assert_eq!(
+            probe_wdl(&tablebases, "8/4P3/8/8/8/8/k7/4K3 w - - 0 1"),
+            Some(Wdl::Win)
+        );
+        assert_eq!(
+            probe_wdl(&tablebases, "7k/8/8/8/8/8/7P/7K w - - 0 1"),
+            Some(Wdl::Draw)
+        );
+        // Qg8 mate, or Kb8 Qg8 mate
+        assert_eq!(
+            probe_dtz(&tablebases, "k7/8/1K6/8/8/8/8/6Q1 w - - 0 1"),
+            Some(1)
+        );
+        assert_eq!(
+            probe_dtz(&tablebases, "k7/8/1K6/8/8/8/8/6Q1 b - - 0 1"),
+            Some(-2)
+        );
+        assert_eq!(
+            probe_dtz(&tablebases, "8/8/8/8/8/8/1k6/Q2K4 b - - 0 1"),
+            Some(0)
+        );
+
+        // Rh8 mate
+        let fen = "k7/8/1K6/8/8/8/p7/7R w - - 0 1";
+        assert_eq!(probe_wdl(&tablebases, fen), Some(Wdl::Win));
+        assert_eq!(probe_dtz(&tablebases, fen), Some(1));
+        // The king and the pawn on the second rank hold the rook
+        let fen = "8/8/8/8/8/8/1kp4K/7R w - - 0 1";
+        assert_eq!(probe_wdl(&tablebases, fen), Some(Wdl::Draw));
+        assert_eq!(probe_dtz(&tablebases, fen), Some(0));
+        // The pawn promotes and wins against the rook, with the colors swapped too
+        assert_eq!(
+            probe_wdl(&tablebases, "8/8/8/8/2R5/4kp2/2K5/8 b - - 0 1"),
+            Some(Wdl::Win)
+        );
+        assert_eq!(
+            probe_wdl(&tablebases, "8/2k5/4KP2/2r5/8/8/8/8 w - - 0 1"),
+            Some(Wdl::Win)
+        );
+        // Ka2 is the only move, and b1=Q zeroes the counter
+        let fen = "8/8/8/8/8/3R4/1p6/K1k5 w - - 0 1";
+        assert_eq!(probe_wdl(&tablebases, fen), Some(Wdl::Loss));
+        assert_eq!(probe_dtz(&tablebases, fen), Some(-2));
+        // Taking the pawn wins and zeroes the counter
+        let fen = "4k3/8/8/8/8/8/4p3/R3K3 w - - 0 1";
+        assert_eq!(probe_dtz(&tablebases, fen), Some(1));
+        assert_eq!(root_moves(&tablebases, fen), Some(vec!["e1e2".to_string()]));
+    }
+
+    #[test]
+    fn test_dtz_matches_the_mate_distance() {
+        let tablebases = tablebases();
+        for fen in [
+            "k7/8/2K5/8/8/8/8/7Q w - - 0 1",
+            "7k/8/5K2/8/8/8/8/R7 w - - 0 1",
+        ] {
+            let mut game_state = GameState::from_str(fen).unwrap();
+            let dtz = tablebases.probe_dtz(&mut game_state).unwrap();
+            let mate = crate::search::mate::find_mate(&mut game_state, 4).unwrap();
+            assert_eq!(dtz, 2 * mate.moves() as i32 - 1, "{}", fen);
+
+            // The losing side is one ply further from the end
+            game_state.make_move(&mate.attack);
+            let dtz = tablebases.probe_dtz(&mut game_state).unwrap();
+            assert_eq!(dtz, -2 * (mate.moves() as i32 - 1), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_root_moves() {
+        let tablebases = tablebases();
+        assert_eq!(
+            root_moves(&tablebases, "k7/8/1K6/8/8/8/8/6Q1 w - - 0 1"),
+            Some(vec!["g1g8".to_string()])
+        );
+        // Taking the queen is the only move not losing
+        assert_eq!(
+            root_moves(&tablebases, "8/8/8/8/8/8/1k6/Q2K4 b - - 0 1"),
+            Some(vec!["b2a1".to_string()])
+        );
+
+        // Without DTZ table, every move keeping the win is kept
+        let fen = "8/4P3/8/8/8/8/k7/4K3 w - - 0 1";
+        let moves = root_moves(&tablebases, fen).unwrap();
+        assert!(moves.contains(&"e7e8q".to_string()));
+        assert!(!moves.contains(&"e7e8b".to_string()));
+        let mut game_state = GameState::from_str(fen).unwrap();
+        for chess_move in game_state.generate_legal_moves() {
+            game_state.make_move(&chess_move);
+            let wdl = tablebases.probe_wdl(&mut game_state);
+            game_state.unmake_move();
+            assert_eq!(
+                wdl == Some(Wdl::Loss),
+                moves.contains(&chess_move.to_string())
+            );
+        }
+
+        assert_eq!(
+            root_moves(&tablebases, "4k3/8/8/8/8/8/8/3QK2R w - - 0 1"),
+            None
+        );
+    }
+}
@@ -0,0 +1,174 @@
+//! Module containing the tables mapping the squares of the pieces of a position to its index
+//! in a Syzygy table.
+//!
+//! Positions are first mirrored so that the leading piece, or the leading pawn, lies in a
+//! corner of the board, then the pieces are encoded group by group, each group being a
+//! combination of squares among the ones left by the previous groups.
+
+use std::sync::OnceLock;
+
+/// The number of ways to place two kings, the first one in the a1-d1-d4 triangle.
+pub const KING_PAIRS: u64 = 462;
+/// The number of ways to place three unique pieces, the first one in the a1-d1-d4 triangle.
+pub const UNIQUE_TRIPLES: u64 = 31_332;
+
+pub struct Encoding {
+    /// Encodes the squares below the a1-h8 diagonal to 0..28.
+    pub map_b1h1h7: [u64; 64],
+    /// Encodes the squares of the a1-d1-d4 triangle to 0..10, the diagonal ones last.
+    pub map_a1d1d4: [u64; 64],
+    /// Encodes the legal squares of a king, the other one being in the a1-d1-d4 triangle.
+    pub map_kk: [[u64; 64]; 10],
+    /// `binomial[k][n]` is the number of ways to choose `k` squares among `n`.
+    pub binomial: [[u64; 64]; 7],
+    /// Encodes the squares a2-h7 to 0..48, the leading pawn being the highest one.
+    pub map_pawns: [u64; 64],
+    /// The index of the leading pawn on each square, by number of leading pawns.
+    pub lead_pawn_idx: [[u64; 64]; 6],
+    /// The number of leading pawn combinations of each file, by number of leading pawns.
+    pub lead_pawns_size: [[u64; 4]; 6],
+}
+
+/// The rank of a square minus its file: negative below the a1-h8 diagonal.
+pub fn off_diagonal(square: u8) -> i32 {
+    (square / 8) as i32 - (square % 8) as i32
+}
+
+/// Returns the encoding tables, computed on first use.
+pub fn encoding() -> &'static Encoding {
+    static ENCODING: OnceLock<Encoding> = OnceLock::new();
+    ENCODING.get_or_init(Encoding::new)
+}
+
+impl Encoding {
+    fn new() -> Self {
+        let mut map_b1h1h7 = [0; 64];
+        let mut code = 0;
+        for square in 0..64 {
+            if off_diagonal(square) < 0 {
+                map_b1h1h7[square as usize] = code;
+                code += 1;
+            }
+        }
+
+        let mut map_a1d1d4 = [0; 64];
+        let mut diagonal = Vec::new();
+        code = 0;
+        for square in 0..28u8 {
+            if square % 8 > 3 {
+                continue;
+            }
+            if off_diagonal(square) < 0 {
+                map_a1d1d4[square as usize] = code;
+                code += 1;
+            } else if off_diagonal(square) == 0 {
+                diagonal.push(square);
+            }
+        }
+        for square in diagonal {
+            map_a1d1d4[square as usize] = code;
+            code += 1;
+        }
+
+        let mut map_kk = [[0; 64]; 10];
+        let mut both_on_diagonal = Vec::new();
+        code = 0;
+        for index in 0..10 {
+            for first in 0..28u8 {
+                // b1 is mapped to 0, as are the squares outside of the triangle
+                if map_a1d1d4[first as usize] != index || (index == 0 && first != 1) {
+                    continue;
+                }
+                for second in 0..64u8 {
+                    let adjacent = (first / 8).abs_diff(second / 8) <= 1
+                        && (first % 8).abs_diff(second % 8) <= 1;
+                    if adjacent {
+                        continue;
+                    }
+                    if off_diagonal(first) == 0 && off_diagonal(second) > 0 {
+                        continue;
+                    }
+                    if off_diagonal(first) == 0 && off_diagonal(second) == 0 {
+                        both_on_diagonal.push((index, second));
+                    } else {
+                        map_kk[index as usize][second as usize] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (index, second) in both_on_diagonal {
+            map_kk[index as usize][second as usize] = code;
+            code += 1;
+        }
+
+        let mut binomial = [[0; 64]; 7];
+        binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..7.min(n + 1) {
+                let with = if k > 0 { binomial[k - 1][n - 1] } else { 0 };
+                let without = if k < n { binomial[k][n - 1] } else { 0 };
+                binomial[k][n] = with + without;
+            }
+        }
+
+        let mut map_pawns = [0; 64];
+        let mut lead_pawn_idx = [[0; 64]; 6];
+        let mut lead_pawns_size = [[0; 4]; 6];
+        let mut available: u64 = 47;
+        for lead_pawns in 1..6 {
+            for file in 0..4u8 {
+                let mut index = 0;
+                for rank in 1..7u8 {
+                    let square = (rank * 8 + file) as usize;
+                    if lead_pawns == 1 {
+                        map_pawns[square] = available;
+                        map_pawns[square ^ 7] = available - 1;
+                        available = available.saturating_sub(2);
+                    }
+                    lead_pawn_idx[lead_pawns][square] = index;
+                    index += binomial[lead_pawns - 1][map_pawns[square] as usize];
+                }
+                lead_pawns_size[lead_pawns][file as usize] = index;
+            }
+        }
+
+        Encoding {
+            map_b1h1h7,
+            map_a1d1d4,
+            map_kk,
+            binomial,
+            map_pawns,
+            lead_pawn_idx,
+            lead_pawns_size,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encoding_tables() {
+        let encoding = encoding();
+        // Every legal pair of kings has a code below 462
+        let codes: Vec<u64> = encoding
+            .map_kk
+            .iter()
+            .flatten()
+            .copied()
+            .filter(|&code| code > 0)
+            .collect();
+        assert_eq!(codes.len() as u64, KING_PAIRS - 1);
+        assert_eq!(codes.iter().max(), Some(&(KING_PAIRS - 1)));
+
+        assert_eq!(encoding.map_pawns[8], 47);
+        assert_eq!(encoding.map_pawns[15], 46);
+        assert_eq!(encoding.map_pawns[16], 45);
+        assert_eq!(encoding.map_pawns[51], 1);
+        assert_eq!(encoding.map_pawns[52], 0);
+        assert_eq!(encoding.binomial[2][5], 10);
+        assert_eq!(encoding.lead_pawns_size[1], [6, 6, 6, 6]);
+    }
+}
@@ -0,0 +1,726 @@
+//! Module containing the reading of a Syzygy table file and the lookup of positions in it.
+//!
+//! A table is split in subtables: one per side to move for WDL tables of unequal material, and
+//! one per file of the leading pawn for tables with pawns. Each subtable stores the values of
+//! its positions in blocks of canonical Huffman codes, each code standing for a symbol which
+//! is either a value or a pair of symbols, as built by Recursive Pairing. A sparse index gives
+//! the block of every `span` positions, so that a value is decoded without reading the blocks
+//! before it.
+
+use std::fs;
+use std::path::Path;
+
+use super::encoding::{encoding, off_diagonal, KING_PAIRS, UNIQUE_TRIPLES};
+use super::{SyzygyError, Wdl};
+
+/// The magic numbers starting WDL and DTZ files.
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+/// The largest number of pieces of a table.
+pub const MAX_PIECES: usize = 7;
+
+/// The flags of a subtable.
+const FLAG_STM: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+/// The symbol marking the leaves of the symbol tree.
+const LEAF: u16 = 0xfff;
+
+/// The kind of a table file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TableKind {
+    /// Win/draw/loss tables, `.rtbw` files.
+    Wdl,
+    /// Distance to zeroing tables, `.rtbz` files.
+    Dtz,
+}
+
+/// The number of pieces of the table `name`, or `None` if it is not a table name.
+pub fn piece_count(name: &str) -> Option<usize> {
+    Material::from_name(name).map(|material| material.piece_count)
+}
+
+/// The pieces of a table, as given by its name, e.g. `KRvKP`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Material {
+    piece_count: usize,
+    has_pawns: bool,
+    /// Whether a side has a piece other than its king that it has only one of.
+    has_unique_pieces: bool,
+    /// The pawns of the leading color, the one with fewer pawns but some, then of the other.
+    pawn_count: [usize; 2],
+    /// Whether both sides have the same pieces.
+    symmetric: bool,
+}
+
+impl Material {
+    fn from_name(name: &str) -> Option<Material> {
+        let (white, black) = name.split_once('v')?;
+        let valid = |side: &str| {
+            side.starts_with('K')
+                && side.chars().filter(|&c| c == 'K').count() == 1
+                && side.chars().all(|c| "KQRBNP".contains(c))
+        };
+        if !valid(white) || !valid(black) || white.len() + black.len() > MAX_PIECES {
+            return None;
+        }
+        let count = |side: &str, piece: char| side.chars().filter(|&c| c == piece).count();
+        let has_unique_pieces = [white, black]
+            .iter()
+            .any(|side| "QRBNP".chars().any(|piece| count(side, piece) == 1));
+        let (white_pawns, black_pawns) = (count(white, 'P'), count(black, 'P'));
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        let pawn_count = if white_leads {
+            [white_pawns, black_pawns]
+        } else {
+            [black_pawns, white_pawns]
+        };
+        Some(Material {
+            piece_count: white.len() + black.len(),
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces,
+            pawn_count,
+            symmetric: white == black,
+        })
+    }
+}
+
+/// The decoding information of a subtable. Offsets are from the start of the file.
+#[derive(Debug, Clone, Default)]
+struct PairsData {
+    flags: u8,
+    max_sym_len: u8,
+    /// The shortest code length, or the value of every position of a single value subtable.
+    min_sym_len: u8,
+    num_blocks: u32,
+    block_size: usize,
+    span: u64,
+    /// The lowest symbol of each code length, from the shortest.
+    lowest_sym: usize,
+    /// The two symbols each symbol stands for, 12 bits each.
+    btree: usize,
+    /// The number of values of each block, minus one.
+    block_length: usize,
+    block_length_size: usize,
+    /// The block and offset in the block of every `span` values.
+    sparse_index: usize,
+    sparse_index_size: usize,
+    data: usize,
+    /// The lowest code of each length, left-aligned on 64 bits.
+    base64: Vec<u64>,
+    /// The number of values each symbol stands for, minus one.
+    symlen: Vec<u8>,
+    /// The pieces of the table, in the order they are encoded.
+    pieces: [u8; MAX_PIECES],
+    /// The factor of the index of each group of pieces, the last one being the table size.
+    group_idx: [u64; MAX_PIECES + 1],
+    /// The number of pieces of each group, ended by 0.
+    group_len: [usize; MAX_PIECES + 1],
+    /// Where the values of each result start in the DTZ map.
+    map_idx: [u16; 4],
+}
+
+/// A Syzygy table read from a file.
+#[derive(Debug)]
+pub struct Table {
+    bytes: Vec<u8>,
+    kind: TableKind,
+    material: Material,
+    /// The subtables, by side to move and by file of the leading pawn.
+    subtables: Vec<Vec<PairsData>>,
+    /// Where the DTZ map starts.
+    map: usize,
+}
+
+impl Table {
+    /// Reads the table `name`, e.g. `KQvK`, from a file.
+    pub fn open(path: &Path, name: &str, kind: TableKind) -> Result<Table, SyzygyError> {
+        let bytes = fs::read(path).map_err(|e| SyzygyError::Io(e.to_string()))?;
+        Table::from_bytes(bytes, name, kind)
+    }
+
+    /// Reads the table `name` from the content of its file.
+    pub fn from_bytes(bytes: Vec<u8>, name: &str, kind: TableKind) -> Result<Table, SyzygyError> {
+        let invalid = || SyzygyError::InvalidTable(name.to_string());
+        let material = Material::from_name(name).ok_or_else(invalid)?;
+        let magic = match kind {
+            TableKind::Wdl => WDL_MAGIC,
+            TableKind::Dtz => DTZ_MAGIC,
+        };
+        if bytes.len() < 6 || bytes[..4] != magic {
+            return Err(SyzygyError::InvalidMagic(name.to_string()));
+        }
+
+        let mut table = Table {
+            bytes,
+            kind,
+            material,
+            subtables: Vec::new(),
+            map: 0,
+        };
+        table.read_layout().ok_or_else(invalid)?;
+        Ok(table)
+    }
+
+    /// Reads the headers of the subtables, returning `None` if the file is invalid.
+    fn read_layout(&mut self) -> Option<()> {
+        let material = self.material.clone();
+        let flags = *self.bytes.get(4)?;
+        let split = flags & 1 != 0;
+        if (flags & 2 != 0) != material.has_pawns
+            || (self.kind == TableKind::Wdl && split == material.symmetric)
+        {
+            return None;
+        }
+        let sides = if self.kind == TableKind::Wdl && !material.symmetric {
+            2
+        } else {
+            1
+        };
+        let files = if material.has_pawns { 4 } else { 1 };
+        self.subtables = vec![vec![PairsData::default(); files]; sides];
+
+        let mut pos = 5;
+        let both_pawns = material.has_pawns && material.pawn_count[1] > 0;
+        for file in 0..files {
+            let first = *self.bytes.get(pos)?;
+            let second = if both_pawns {
+                *self.bytes.get(pos + 1)?
+            } else {
+                0xff
+            };
+            let order = [[first & 0xf, second & 0xf], [first >> 4, second >> 4]];
+            pos += 1 + both_pawns as usize;
+            for k in 0..material.piece_count {
+                let byte = *self.bytes.get(pos)?;
+                for side in 0..sides {
+                    self.subtables[side][file].pieces[k] =
+                        if side == 1 { byte >> 4 } else { byte & 0xf };
+                }
+                pos += 1;
+            }
+            for (subtables, order) in self.subtables.iter_mut().zip(order) {
+                set_groups(&material, &mut subtables[file], order, file)?;
+            }
+        }
+        pos += pos & 1;
+
+        for file in 0..files {
+            for side in 0..sides {
+                pos = self.read_sizes(side, file, pos)?;
+            }
+        }
+        if self.kind == TableKind::Dtz {
+            pos = self.read_dtz_map(pos, files)?;
+        }
+        for file in 0..files {
+            for side in 0..sides {
+                let subtable = &mut self.subtables[side][file];
+                subtable.sparse_index = pos;
+                pos += subtable.sparse_index_size * 6;
+            }
+        }
+        for file in 0..files {
+            for side in 0..sides {
+                let subtable = &mut self.subtables[side][file];
+                subtable.block_length = pos;
+                pos += subtable.block_length_size * 2;
+            }
+        }
+        for file in 0..files {
+            for side in 0..sides {
+                let subtable = &mut self.subtables[side][file];
+                pos = (pos + 0x3f) & !0x3f;
+                subtable.data = pos;
+                pos += subtable.num_blocks as usize * subtable.block_size;
+            }
+        }
+        (pos <= self.bytes.len()).then_some(())
+    }
+
+    /// Reads the sizes and the symbols of a subtable, returning the position after them.
+    fn read_sizes(&mut self, side: usize, file: usize, mut pos: usize) -> Option<usize> {
+        let bytes = &self.bytes;
+        let d = &mut self.subtables[side][file];
+        d.flags = *bytes.get(pos)?;
+        pos += 1;
+        if d.flags & FLAG_SINGLE_VALUE != 0 {
+            d.min_sym_len = *bytes.get(pos)?;
+            return Some(pos + 1);
+        }
+
+        let groups = d.group_len.iter().position(|&len| len == 0)?;
+        let table_size = d.group_idx[groups];
+        d.block_size = 1usize.checked_shl(*bytes.get(pos)? as u32)?;
+        d.span = 1u64.checked_shl(*bytes.get(pos + 1)? as u32)?;
+        d.sparse_index_size = table_size.div_ceil(d.span) as usize;
+        let padding = *bytes.get(pos + 2)? as usize;
+        d.num_blocks = read_u32_le(bytes, pos + 3)?;
+        d.block_length_size = d.num_blocks as usize + padding;
+        d.max_sym_len = *bytes.get(pos + 7)?;
+        d.min_sym_len = *bytes.get(pos + 8)?;
+        pos += 9;
+        if d.min_sym_len == 0 || d.max_sym_len < d.min_sym_len || d.max_sym_len > 32 {
+            return None;
+        }
+        d.lowest_sym = pos;
+
+        // Codes of the same length are consecutive, longer codes having lower values
+        let lengths = (d.max_sym_len - d.min_sym_len + 1) as usize;
+        d.base64 = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            let lowest = read_u16_le(bytes, pos + 2 * i)? as u64;
+            let next_lowest = read_u16_le(bytes, pos + 2 * i + 2)? as u64;
+            d.base64[i] = (d.base64[i + 1] + lowest).wrapping_sub(next_lowest) / 2;
+        }
+        for (i, base) in d.base64.iter_mut().enumerate() {
+            *base <<= 64 - i as u32 - d.min_sym_len as u32;
+        }
+        pos += lengths * 2;
+
+        let symbols = read_u16_le(bytes, pos)? as usize;
+        pos += 2;
+        d.btree = pos;
+        if pos + symbols * 3 > bytes.len() {
+            return None;
+        }
+        d.symlen = vec![0; symbols];
+        let mut visited = vec![false; symbols];
+        for symbol in 0..symbols {
+            if !visited[symbol] {
+                let len = symbol_length(bytes, d.btree, symbol, &mut d.symlen, &mut visited)?;
+                d.symlen[symbol] = len;
+            }
+        }
+        Some(pos + symbols * 3 + (symbols & 1))
+    }
+
+    /// Reads the maps from stored values to distances of a DTZ table.
+    fn read_dtz_map(&mut self, mut pos: usize, files: usize) -> Option<usize> {
+        self.map = pos;
+        for file in 0..files {
+            let d = &mut self.subtables[0][file];
+            if d.flags & FLAG_MAPPED == 0 {
+                continue;
+            }
+            if d.flags & FLAG_WIDE != 0 {
+                pos += pos & 1;
+                for i in 0..4 {
+                    d.map_idx[i] = ((pos - self.map) / 2 + 1) as u16;
+                    pos += 2 * read_u16_le(&self.bytes, pos)? as usize + 2;
+                }
+            } else {
+                for i in 0..4 {
+                    d.map_idx[i] = (pos - self.map + 1) as u16;
+                    pos += *self.bytes.get(pos)? as usize + 1;
+                }
+            }
+        }
+        Some(pos + (pos & 1))
+    }
+
+    /// Looks up the WDL result of a position, given by its pieces as `(square, code)` pairs in
+    /// increasing square order, the codes being the ones of `piece_code`.
+    pub fn probe_wdl(&self, pieces: &[(u8, u8)], white_to_move: bool, black_stronger: bool) -> Wdl {
+        let (subtable, _, index) = self
+            .index(pieces, white_to_move, black_stronger)
+            .expect("WDL tables have both sides to move");
+        Wdl::from_value(self.decompress(subtable, index) as i32 - 2)
+    }
+
+    /// Looks up the distance to zeroing of a position whose WDL result is `wdl`, in plies, or
+    /// `None` when the table only stores the other side to move.
+    pub fn probe_dtz(
+        &self,
+        pieces: &[(u8, u8)],
+        white_to_move: bool,
+        black_stronger: bool,
+        wdl: Wdl,
+    ) -> Option<i32> {
+        let (subtable, file, index) = self.index(pieces, white_to_move, black_stronger)?;
+        let value = self.decompress(subtable, index) as usize;
+        let d = &self.subtables[0][file];
+
+        let map_index = match wdl {
+            Wdl::Win => 0,
+            Wdl::Loss => 1,
+            Wdl::CursedWin => 2,
+            Wdl::BlessedLoss | Wdl::Draw => 3,
+        };
+        let mut value = value as i32;
+        if d.flags & FLAG_MAPPED != 0 {
+            let start = d.map_idx[map_index] as usize + value as usize;
+            value = if d.flags & FLAG_WIDE != 0 {
+                read_u16_le(&self.bytes, self.map + 2 * start).unwrap_or(0) as i32
+            } else {
+                self.bytes.get(self.map + start).copied().unwrap_or(0) as i32
+            };
+        }
+        let in_moves = match wdl {
+            Wdl::Win => d.flags & FLAG_WIN_PLIES == 0,
+            Wdl::Loss => d.flags & FLAG_LOSS_PLIES == 0,
+            _ => true,
+        };
+        if in_moves {
+            value *= 2;
+        }
+        Some(value + 1)
+    }
+
+    /// Returns the subtable, the file of the leading pawn and the index of a position, or
+    /// `None` for a DTZ table storing the other side to move.
+    fn index(
+        &self,
+        board_pieces: &[(u8, u8)],
+        white_to_move: bool,
+        black_stronger: bool,
+    ) -> Option<(&PairsData, usize, u64)> {
+        let encoding = encoding();
+        let material = &self.material;
+        // Tables are stored with white being the stronger side, and symmetric ones with white
+        // to move: the other positions are looked up with the colors swapped
+        let flip = black_stronger || (material.symmetric && !white_to_move);
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let stm = (flip == white_to_move) as usize;
+
+        let mut squares = Vec::with_capacity(board_pieces.len());
+        let mut pieces = Vec::with_capacity(board_pieces.len());
+        let mut lead_pawns = 0;
+        let mut file = 0;
+        if material.has_pawns {
+            let lead_pawn = self.subtables[0][0].pieces[0] ^ flip_color;
+            for &(square, _) in board_pieces.iter().filter(|&&(_, code)| code == lead_pawn) {
+                squares.push(square ^ flip_squares);
+                pieces.push(lead_pawn ^ flip_color);
+            }
+            lead_pawns = squares.len();
+            let lead = (0..lead_pawns)
+                .max_by_key(|&i| encoding.map_pawns[squares[i] as usize])
+                .unwrap_or(0);
+            squares.swap(0, lead);
+            file = ((squares[0] % 8) as usize).min(7 - (squares[0] % 8) as usize);
+        }
+
+        let d_stm = &self.subtables[stm % self.subtables.len()][file];
+        let stored_side = (d_stm.flags & FLAG_STM) as usize;
+        if self.kind == TableKind::Dtz
+            && stored_side != stm
+            && (material.has_pawns || !material.symmetric)
+        {
+            return None;
+        }
+
+        for &(square, code) in board_pieces {
+            if !(material.has_pawns && code == self.subtables[0][0].pieces[0] ^ flip_color) {
+                squares.push(square ^ flip_squares);
+                pieces.push(code ^ flip_color);
+            }
+        }
+        let d = d_stm;
+
+        // Orders the pieces as the table encodes them
+        for i in lead_pawns..pieces.len().saturating_sub(1) {
+            if let Some(j) = (i + 1..pieces.len()).find(|&j| pieces[j] == d.pieces[i]) {
+                pieces.swap(i, j);
+                squares.swap(i, j);
+            }
+        }
+
+        if squares[0] % 8 > 3 {
+            squares.iter_mut().for_each(|square| *square ^= 7);
+        }
+
+        let mut index;
+        if material.has_pawns {
+            index = encoding.lead_pawn_idx[lead_pawns][squares[0] as usize];
+            squares[1..lead_pawns].sort_by_key(|&square| encoding.map_pawns[square as usize]);
+            for (i, &square) in squares[1..lead_pawns].iter().enumerate() {
+                index += encoding.binomial[i + 1][encoding.map_pawns[square as usize] as usize];
+            }
+        } else {
+            if squares[0] / 8 > 3 {
+                squares.iter_mut().for_each(|square| *square ^= 56);
+            }
+            // The first piece of the leading group off the diagonal is mapped below it
+            if let Some(i) = (0..d.group_len[0]).find(|&i| off_diagonal(squares[i]) != 0) {
+                if off_diagonal(squares[i]) > 0 {
+                    for square in &mut squares[i..] {
+                        *square = ((*square >> 3) | (*square << 3)) & 63;
+                    }
+                }
+            }
+
+            let s = |i: usize| squares[i] as u64;
+            let rank = |i: usize| (squares[i] / 8) as u64;
+            index = if material.has_unique_pieces {
+                let adjust1 = (s(1) > s(0)) as u64;
+                let adjust2 = (s(2) > s(0)) as u64 + (s(2) > s(1)) as u64;
+                if off_diagonal(squares[0]) != 0 {
+                    (encoding.map_a1d1d4[s(0) as usize] * 63 + (s(1) - adjust1)) * 62 + s(2)
+                        - adjust2
+                } else if off_diagonal(squares[1]) != 0 {
+                    (6 * 63 + rank(0) * 28 + encoding.map_b1h1h7[s(1) as usize]) * 62 + s(2)
+                        - adjust2
+                } else if off_diagonal(squares[2]) != 0 {
+                    6 * 63 * 62
+                        + 4 * 28 * 62
+                        + rank(0) * 7 * 28
+                        + (rank(1) - adjust1) * 28
+                        + encoding.map_b1h1h7[s(2) as usize]
+                } else {
+                    6 * 63 * 62
+                        + 4 * 28 * 62
+                        + 4 * 7 * 28
+                        + rank(0) * 7 * 6
+                        + (rank(1) - adjust1) * 6
+                        + (rank(2) - adjust2)
+                }
+            } else {
+                encoding.map_kk[encoding.map_a1d1d4[s(0) as usize] as usize][s(1) as usize]
+            };
+        }
+
+        // The other groups are combinations of the squares left by the previous ones
+        index *= d.group_idx[0];
+        let mut start = d.group_len[0];
+        let mut remaining_pawns = material.has_pawns && material.pawn_count[1] > 0;
+        let mut group = 1;
+        while d.group_len[group] != 0 {
+            let end = start + d.group_len[group];
+            squares[start..end].sort_unstable();
+            let mut combination = 0;
+            for i in 0..end - start {
+                let square = squares[start + i];
+                let adjust = squares[..start].iter().filter(|&&s| square > s).count() as u64;
+                let free = square as u64 - adjust - 8 * remaining_pawns as u64;
+                combination += encoding.binomial[i + 1][free as usize];
+            }
+            remaining_pawns = false;
+            index += combination * d.group_idx[group];
+            start = end;
+            group += 1;
+        }
+        Some((d, file, index))
+    }
+
+    /// Decodes the value of the position with the given index in a subtable.
+    fn decompress(&self, d: &PairsData, index: u64) -> u8 {
+        if d.flags & FLAG_SINGLE_VALUE != 0 {
+            return d.min_sym_len;
+        }
+        let bytes = &self.bytes;
+        let block_length = |block: i64| -> i64 {
+            read_u16_le(bytes, d.block_length + 2 * block.max(0) as usize).unwrap_or(0) as i64
+        };
+
+        // The sparse index locates the value in the middle of each span
+        let k = (index / d.span) as usize;
+        let entry = d.sparse_index + 6 * k;
+        let mut block = read_u32_le(bytes, entry).unwrap_or(0) as i64;
+        let mut offset = read_u16_le(bytes, entry + 4).unwrap_or(0) as i64;
+        offset += (index % d.span) as i64 - (d.span / 2) as i64;
+        while offset < 0 && block > 0 {
+            block -= 1;
+            offset += block_length(block) + 1;
+        }
+        while offset > block_length(block) && block + 1 < d.num_blocks as i64 {
+            offset -= block_length(block) + 1;
+            block += 1;
+        }
+
+        let mut ptr = d.data + block as usize * d.block_size;
+        let mut buffer = read_u64_be(bytes, ptr);
+        ptr += 8;
+        let mut buffer_size = 64;
+        let mut symbol;
+        loop {
+            let mut len = 0;
+            while len + 1 < d.base64.len() && buffer < d.base64[len] {
+                len += 1;
+            }
+            let shift = 64 - len as u32 - d.min_sym_len as u32;
+            symbol = ((buffer - d.base64[len]) >> shift) as usize;
+            symbol += read_u16_le(bytes, d.lowest_sym + 2 * len).unwrap_or(0) as usize;
+            let values = *d.symlen.get(symbol).unwrap_or(&0) as i64 + 1;
+            if offset < values {
+                break;
+            }
+            offset -= values;
+            let len = len as u32 + d.min_sym_len as u32;
+            buffer = buffer.checked_shl(len).unwrap_or(0);
+            buffer_size -= len as i32;
+            if buffer_size <= 32 {
+                buffer_size += 32;
+                buffer |= (read_u32_be(bytes, ptr) as u64) << (64 - buffer_size);
+                ptr += 4;
+            }
+        }
+
+        // Goes down the pairs of the symbol to the one holding the value
+        while d.symlen.get(symbol).is_some_and(|&len| len > 0) {
+            let (left, right) = read_symbol_pair(bytes, d.btree, symbol);
+            let left_values = d.symlen[left as usize] as i64 + 1;
+            if offset < left_values {
+                symbol = left as usize;
+            } else {
+                offset -= left_values;
+                symbol = right as usize;
+            }
+        }
+        read_symbol_pair(bytes, d.btree, symbol).0 as u8
+    }
+}
+
+/// Sets the groups of pieces of a subtable and the factors of their indices. The first group
+/// holds the leading pieces or pawns; `order` gives the rank of the leading group and of the
+/// other pawns among the groups.
+fn set_groups(material: &Material, d: &mut PairsData, order: [u8; 2], file: usize) -> Option<()> {
+    let encoding = encoding();
+    let mut first_len: i32 = if material.has_pawns {
+        0
+    } else if material.has_unique_pieces {
+        3
+    } else {
+        2
+    };
+    let mut n = 0;
+    d.group_len[0] = 1;
+    for i in 1..material.piece_count {
+        first_len -= 1;
+        if first_len > 0 || d.pieces[i] == d.pieces[i - 1] {
+            d.group_len[n] += 1;
+        } else {
+            n += 1;
+            d.group_len[n] = 1;
+        }
+    }
+    n += 1;
+    d.group_len[n] = 0;
+
+    let both_pawns = material.has_pawns && material.pawn_count[1] > 0;
+    let mut next = if both_pawns { 2 } else { 1 };
+    let mut free_squares = 64 - d.group_len[0] - if both_pawns { d.group_len[1] } else { 0 };
+    let mut index: u64 = 1;
+    let mut k = 0;
+    while next < n || k == order[0] as usize || k == order[1] as usize {
+        if k == order[0] as usize {
+            d.group_idx[0] = index;
+            index *= if material.has_pawns {
+                encoding.lead_pawns_size[d.group_len[0]][file]
+            } else if material.has_unique_pieces {
+                UNIQUE_TRIPLES
+            } else {
+                KING_PAIRS
+            };
+        } else if k == order[1] as usize {
+            d.group_idx[1] = index;
+            index *= encoding.binomial[d.group_len[1]][48 - d.group_len[0]];
+        } else {
+            d.group_idx[next] = index;
+            index *= encoding.binomial[*d.group_len.get(next)?][free_squares];
+            free_squares = free_squares.checked_sub(d.group_len[next])?;
+            next += 1;
+        }
+        k += 1;
+        if k > MAX_PIECES {
+            return None;
+        }
+    }
+    d.group_idx[n] = index;
+    Some(())
+}
+
+/// Computes the number of values a symbol stands for, minus one.
+fn symbol_length(
+    bytes: &[u8],
+    btree: usize,
+    symbol: usize,
+    symlen: &mut [u8],
+    visited: &mut [bool],
+) -> Option<u8> {
+    visited[symbol] = true;
+    let (left, right) = read_symbol_pair(bytes, btree, symbol);
+    if right == LEAF {
+        return Some(0);
+    }
+    let (left, right) = (left as usize, right as usize);
+    for child in [left, right] {
+        if !*visited.get(child)? {
+            symlen[child] = symbol_length(bytes, btree, child, symlen, visited)?;
+        }
+    }
+    symlen[left].checked_add(symlen[right])?.checked_add(1)
+}
+
+/// Reads the two 12 bits symbols a symbol stands for. A leaf has its value on the left and
+/// `LEAF` on the right.
+fn read_symbol_pair(bytes: &[u8], btree: usize, symbol: usize) -> (u16, u16) {
+    let at = |i: usize| bytes.get(btree + 3 * symbol + i).copied().unwrap_or(0) as u16;
+    let left = ((at(1) & 0xf) << 8) | at(0);
+    let right = (at(2) << 4) | (at(1) >> 4);
+    (left, right)
+}
+
+fn read_u16_le(bytes: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        bytes.get(pos..pos + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32_le(bytes: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(pos..pos + 4)?.try_into().ok()?,
+    ))
+}
+
+/// Reads a big endian word of the compressed data, padded with zeros past the end.
+fn read_u32_be(bytes: &[u8], pos: usize) -> u32 {
+    let mut word = [0; 4];
+    for (i, byte) in word.iter_mut().enumerate() {
+        *byte = bytes.get(pos + i).copied().unwrap_or(0);
+    }
+    u32::from_be_bytes(word)
+}
+
+fn read_u64_be(bytes: &[u8], pos: usize) -> u64 {
+    (read_u32_be(bytes, pos) as u64) << 32 | read_u32_be(bytes, pos + 4) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_material_from_name() {
+        let material = Material::from_name("KRPvKR").unwrap();
+        assert_eq!(material.piece_count, 5);
+        assert!(material.has_pawns && material.has_unique_pieces);
+        assert_eq!(material.pawn_count, [1, 0]);
+        assert!(!material.symmetric);
+
+        let material = Material::from_name("KPvKPP").unwrap();
+        assert_eq!(material.pawn_count, [1, 2]);
+        assert!(Material::from_name("KNNvKNN").unwrap().symmetric);
+        assert!(!Material::from_name("KNNvKNN").unwrap().has_unique_pieces);
+
+        assert_eq!(Material::from_name("KQK"), None);
+        assert_eq!(Material::from_name("KQvKX"), None);
+        assert_eq!(Material::from_name("QvKK"), None);
+    }
+
+    #[test]
+    fn test_invalid_files() {
+        let result = Table::from_bytes(vec![0; 64], "KQvK", TableKind::Wdl);
+        assert!(matches!(result, Err(SyzygyError::InvalidMagic(_))));
+        let mut bytes = WDL_MAGIC.to_vec();
+        bytes.extend([1, 0, 0x10, 0x20, 0x60]);
+        let result = Table::from_bytes(bytes, "KQvK", TableKind::Wdl);
+        assert!(matches!(result, Err(SyzygyError::InvalidTable(_))));
+    }
+}
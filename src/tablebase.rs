@@ -0,0 +1,674 @@
+//! Module containing distance-to-mate tables of the endings with 3 and 4 pieces, generated by
+//! retrograde analysis.
+//!
+//! A table gives, for every position of a set of pieces, how many moves it takes to checkmate
+//! with the best play of both sides, or that the position is a draw. It is generated backwards
+//! from the checkmates: a position with a move to a lost position is won, one mate further,
+//! and a position whose moves all lead to won positions is lost. The moves, checks and mates
+//! all come from `GameState`, so generating the endings of known results also checks the move
+//! generator and the mate detection. Captures and promotions lead to other tables, which are
+//! generated first.
+//!
+//! Tables know nothing of castling, en passant or the 50-move rule: positions with castling
+//! rights or an en passant capture are not probed, and a mate in more than 50 moves may be a
+//! draw in a game.
+//!
+//! A table file is the magic `RUCE_DTM`, the format version as a little endian `u32`, the
+//! length of the material name as a byte and the name, like `KQvK`, then one byte per index:
+//! 0 for a draw, 255 for an index of no position, and otherwise the distance to mate in plies
+//! plus one.
+
+mod generate;
+mod index;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::board::piece::{Color, PieceType};
+use crate::board::Board;
+use crate::game_state::GameState;
+
+use self::index::Indexer;
+
+/// The largest number of pieces of a table, kings included.
+pub const MAX_PIECES: usize = 4;
+
+const MAGIC: &[u8; 8] = b"RUCE_DTM";
+const VERSION: u32 = 1;
+
+/// The byte of the draws in a table.
+const DRAW: u8 = 0;
+/// The byte of the indexes of no position.
+const NO_POSITION: u8 = u8::MAX;
+
+/// The pieces other than the kings, by decreasing value.
+const PIECE_TYPES: [PieceType; 5] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+    PieceType::Pawn,
+];
+
+/// An error generating or reading a table.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum TablebaseError {
+    #[error("Invalid material: {0}")]
+    InvalidMaterial(String),
+    #[error("Too many pieces: {0}, tables have up to {MAX_PIECES}")]
+    TooManyPieces(usize),
+    #[error("Cannot read the table: {0}")]
+    Io(String),
+    #[error("Not a table file")]
+    InvalidMagic,
+    #[error("Unsupported table version: {0}")]
+    UnsupportedVersion(u32),
+    #[error("Invalid table size: expected {expected} bytes, found {found}")]
+    InvalidSize { expected: usize, found: usize },
+}
+
+/// The result of a position for the side to move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Dtm {
+    /// The side to move mates in this number of moves.
+    Win(u32),
+    /// Neither side can force a mate.
+    Draw,
+    /// The side to move is mated in this number of moves, 0 when it already is.
+    Loss(u32),
+}
+
+impl Dtm {
+    /// The result of a position mated in `plies`: won when the number is odd, lost when even.
+    fn from_plies(plies: u32) -> Dtm {
+        if plies % 2 == 1 {
+            Dtm::Win(plies.div_ceil(2))
+        } else {
+            Dtm::Loss(plies / 2)
+        }
+    }
+
+    /// The number of plies to mate, or `None` for a draw.
+    fn plies(self) -> Option<u32> {
+        match self {
+            Dtm::Win(moves) => Some(2 * moves - 1),
+            Dtm::Draw => None,
+            Dtm::Loss(moves) => Some(2 * moves),
+        }
+    }
+
+    /// The result of a position in a table byte, or `None` when there is no position.
+    fn from_byte(byte: u8) -> Option<Dtm> {
+        match byte {
+            DRAW => Some(Dtm::Draw),
+            NO_POSITION => None,
+            _ => Some(Dtm::from_plies(byte as u32 - 1)),
+        }
+    }
+}
+
+impl fmt::Display for Dtm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Dtm::Win(moves) => write!(f, "mate in {}", moves),
+            Dtm::Draw => write!(f, "draw"),
+            Dtm::Loss(moves) => write!(f, "mated in {}", moves),
+        }
+    }
+}
+
+/// The pieces of a table, written like `KQvKR`: the white king and pieces, `v`, then the black
+/// king and pieces.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Material {
+    /// The white pieces other than the king, by decreasing value.
+    white: Vec<PieceType>,
+    /// The black pieces other than the king, by decreasing value.
+    black: Vec<PieceType>,
+}
+
+impl Material {
+    /// The material of a board, which must have one king of each color.
+    pub fn of(board: &Board) -> Material {
+        let mut material = Material {
+            white: Vec::new(),
+            black: Vec::new(),
+        };
+        for piece in board.squares.iter().flatten() {
+            if piece.piece_type != PieceType::King {
+                match piece.color {
+                    Color::White => material.white.push(piece.piece_type),
+                    Color::Black => material.black.push(piece.piece_type),
+                }
+            }
+        }
+        material.sort();
+        material
+    }
+
+    /// The number of pieces, kings included.
+    pub fn pieces(&self) -> usize {
+        2 + self.white.len() + self.black.len()
+    }
+
+    /// The material with the colors swapped.
+    pub fn flipped(&self) -> Material {
+        Material {
+            white: self.black.clone(),
+            black: self.white.clone(),
+        }
+    }
+
+    /// The material with the stronger side as white, the one tables of other endings are
+    /// generated for.
+    pub fn normalized(&self) -> Material {
+        if strength(&self.white) < strength(&self.black) {
+            self.flipped()
+        } else {
+            self.clone()
+        }
+    }
+
+    /// The materials a capture or a promotion leads to, normalized, other than the bare kings.
+    fn successors(&self) -> Vec<Material> {
+        let mut successors = Vec::new();
+        for material in [self.clone(), self.flipped()] {
+            for (i, &piece_type) in material.white.iter().enumerate() {
+                let mut taken = material.clone();
+                taken.white.remove(i);
+                let mut changed = vec![taken.clone()];
+                if piece_type == PieceType::Pawn {
+                    for &promotion in &PIECE_TYPES[..4] {
+                        let mut promoted = taken.clone();
+                        promoted.white.push(promotion);
+                        promoted.sort();
+                        changed.push(promoted);
+                    }
+                }
+                for successor in changed {
+                    let successor = successor.normalized();
+                    if successor.pieces() > 2 && !successors.contains(&successor) {
+                        successors.push(successor);
+                    }
+                }
+            }
+        }
+        successors
+    }
+
+    fn sort(&mut self) {
+        let rank = |piece_type: &PieceType| PIECE_TYPES.iter().position(|p| p == piece_type);
+        self.white.sort_by_key(rank);
+        self.black.sort_by_key(rank);
+    }
+}
+
+/// Compares the pieces of the sides: the most valuable side first, then the one with more
+/// pieces, then the one with the most valuable pieces.
+fn strength(pieces: &[PieceType]) -> (u32, usize, std::cmp::Reverse<Vec<usize>>) {
+    let value = pieces
+        .iter()
+        .map(|piece_type| match piece_type {
+            PieceType::Queen => 9,
+            PieceType::Rook => 5,
+            PieceType::Bishop | PieceType::Knight => 3,
+            _ => 1,
+        })
+        .sum();
+    let ranks = pieces
+        .iter()
+        .map(|piece_type| {
+            PIECE_TYPES
+                .iter()
+                .position(|p| p == piece_type)
+                .unwrap_or(0)
+        })
+        .collect();
+    (value, pieces.len(), std::cmp::Reverse(ranks))
+}
+
+impl fmt::Display for Material {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let side = |pieces: &[PieceType]| -> String {
+            std::iter::once('K')
+                .chain(pieces.iter().map(PieceType::as_char))
+                .collect()
+        };
+        write!(f, "{}v{}", side(&self.white), side(&self.black))
+    }
+}
+
+impl FromStr for Material {
+    type Err = TablebaseError;
+
+    /// Parses a material like `KQvKR`, with the pieces of each side in any order.
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let invalid = || TablebaseError::InvalidMaterial(name.to_string());
+        let (white, black) = name.split_once('v').ok_or_else(invalid)?;
+        let side = |pieces: &str| -> Result<Vec<PieceType>, TablebaseError> {
+            let pieces = pieces.strip_prefix('K').ok_or_else(invalid)?;
+            pieces
+                .chars()
+                .map(|c| {
+                    PieceType::from_char(c)
+                        .filter(|piece_type| {
+                            c.is_ascii_uppercase() && *piece_type != PieceType::King
+                        })
+                        .ok_or_else(invalid)
+                })
+                .collect()
+        };
+        let mut material = Material {
+            white: side(white)?,
+            black: side(black)?,
+        };
+        material.sort();
+        Ok(material)
+    }
+}
+
+/// Counts of the positions of a table, up to the symmetries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Summary {
+    pub material: Material,
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+    /// The position of the longest mate, as a FEN, and its number of moves.
+    pub longest_mate: Option<(String, u32)>,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{}: {} positions, {} wins, {} draws, {} losses",
+            self.material,
+            self.wins + self.draws + self.losses,
+            self.wins,
+            self.draws,
+            self.losses
+        )?;
+        if let Some((fen, moves)) = &self.longest_mate {
+            writeln!(f, "Longest mate in {}: {}", moves, fen)?;
+        }
+        Ok(())
+    }
+}
+
+/// The distance-to-mate table of a material.
+#[derive(Debug, Clone)]
+pub struct Tablebase {
+    material: Material,
+    indexer: Indexer,
+    /// A byte per index, see the module documentation.
+    values: Vec<u8>,
+}
+
+impl Tablebase {
+    /// Generates the table of a material, and the tables of the endings its captures and
+    /// promotions lead to. `progress` is called with each material before its table is
+    /// generated.
+    pub fn generate(
+        material: &Material,
+        mut progress: impl FnMut(&Material),
+    ) -> Result<Tablebase, TablebaseError> {
+        if material.pieces() > MAX_PIECES {
+            return Err(TablebaseError::TooManyPieces(material.pieces()));
+        }
+        Ok(generate_with(material, &mut HashMap::new(), &mut progress))
+    }
+
+    /// Reads a table from a file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Tablebase, TablebaseError> {
+        let bytes = std::fs::read(path).map_err(|e| TablebaseError::Io(e.to_string()))?;
+        Tablebase::from_bytes(&bytes)
+    }
+
+    /// Reads a table from the content of a table file.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Tablebase, TablebaseError> {
+        if bytes.len() < 13 || &bytes[..8] != MAGIC {
+            return Err(TablebaseError::InvalidMagic);
+        }
+        let version = u32::from_le_bytes(bytes[8..12].try_into().unwrap());
+        if version != VERSION {
+            return Err(TablebaseError::UnsupportedVersion(version));
+        }
+        let header = 13 + bytes[12] as usize;
+        let name = bytes
+            .get(13..header)
+            .and_then(|name| std::str::from_utf8(name).ok())
+            .ok_or(TablebaseError::InvalidMagic)?;
+        let material = Material::from_str(name)?;
+        if material.pieces() > MAX_PIECES {
+            return Err(TablebaseError::TooManyPieces(material.pieces()));
+        }
+        let indexer = Indexer::new(&material);
+        let expected = header + indexer.size();
+        if bytes.len() != expected {
+            return Err(TablebaseError::InvalidSize {
+                expected,
+                found: bytes.len(),
+            });
+        }
+        Ok(Tablebase {
+            material,
+            indexer,
+            values: bytes[header..].to_vec(),
+        })
+    }
+
+    /// Writes the table to a file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), TablebaseError> {
+        std::fs::write(path, self.to_bytes()).map_err(|e| TablebaseError::Io(e.to_string()))
+    }
+
+    /// The content of the table file.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let name = self.material.to_string();
+        let mut bytes = Vec::with_capacity(13 + name.len() + self.values.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.push(name.len() as u8);
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.extend_from_slice(&self.values);
+        bytes
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    /// Probes the result of the position for the side to move, or `None` when it is not in the
+    /// table: it has other pieces, with the colors swapped or not, castling rights, or an en
+    /// passant capture.
+    pub fn probe(&self, game_state: &GameState) -> Option<Dtm> {
+        let board = &game_state.board;
+        let (white_kingside, white_queenside, black_kingside, black_queenside) =
+            board.castling_availability;
+        if white_kingside || white_queenside || black_kingside || black_queenside {
+            return None;
+        }
+        if let Some(target) = board.en_passant_target {
+            let en_passant = game_state.generate_legal_moves().iter().any(|chess_move| {
+                chess_move.target_square == target
+                    && board
+                        .get_piece(chess_move.initial_square)
+                        .is_some_and(|piece| piece.piece_type == PieceType::Pawn)
+            });
+            if en_passant {
+                return None;
+            }
+        }
+
+        let material = Material::of(board);
+        let white_to_move = game_state.current_player == Color::White;
+        let index = if material == self.material {
+            self.indexer.board_index(board, white_to_move, false)
+        } else if material.flipped() == self.material {
+            self.indexer.board_index(board, !white_to_move, true)
+        } else {
+            None
+        }?;
+        Dtm::from_byte(self.values[index])
+    }
+
+    /// Counts the results of the positions of the table and finds its longest mate.
+    pub fn summary(&self) -> Summary {
+        let mut summary = Summary {
+            material: self.material.clone(),
+            wins: 0,
+            draws: 0,
+            losses: 0,
+            longest_mate: None,
+        };
+        let mut longest = None;
+        for (index, &byte) in self.values.iter().enumerate() {
+            match Dtm::from_byte(byte) {
+                Some(Dtm::Win(moves)) => {
+                    summary.wins += 1;
+                    if longest.is_none_or(|(_, longest)| moves > longest) {
+                        longest = Some((index, moves));
+                    }
+                }
+                Some(Dtm::Draw) => summary.draws += 1,
+                Some(Dtm::Loss(_)) => summary.losses += 1,
+                None => {}
+            }
+        }
+        summary.longest_mate = longest.and_then(|(index, moves)| {
+            let (squares, white_to_move) = self.indexer.position(index)?;
+            let mut game_state = generate::empty_game_state();
+            generate::place(
+                &mut game_state,
+                self.indexer.pieces(),
+                &squares,
+                white_to_move,
+            );
+            Some((game_state.to_fen(), moves))
+        });
+        summary
+    }
+}
+
+/// Generates the table of a material after the tables of its successors missing from `tables`,
+/// which are added to it.
+fn generate_with(
+    material: &Material,
+    tables: &mut HashMap<Material, Tablebase>,
+    progress: &mut dyn FnMut(&Material),
+) -> Tablebase {
+    for successor in material.successors() {
+        if !tables.contains_key(&successor) {
+            let table = generate_with(&successor, tables, progress);
+            tables.insert(successor, table);
+        }
+    }
+    progress(material);
+    let indexer = Indexer::new(material);
+    let values = generate::solve(&indexer, tables);
+    Tablebase {
+        material: material.clone(),
+        indexer,
+        values,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::mate::find_mate;
+    use std::sync::{Mutex, OnceLock};
+
+    /// Generates a table, sharing the tables already generated by the other tests.
+    fn table(name: &str) -> Tablebase {
+        static TABLES: OnceLock<Mutex<HashMap<Material, Tablebase>>> = OnceLock::new();
+        let mut tables = TABLES.get_or_init(Default::default).lock().unwrap();
+        let material = Material::from_str(name).unwrap();
+        if !tables.contains_key(&material) {
+            let table = generate_with(&material, &mut tables, &mut |_| {});
+            tables.insert(material.clone(), table);
+        }
+        tables[&material].clone()
+    }
+
+    fn probe(table: &Tablebase, fen: &str) -> Option<Dtm> {
+        table.probe(&GameState::from_str(fen).unwrap())
+    }
+
+    #[test]
+    fn test_material() {
+        let material = Material::from_str("KPQvKRN").unwrap();
+        assert_eq!(material.to_string(), "KQPvKRN");
+        assert_eq!(material.pieces(), 6);
+        assert_eq!(material.flipped().to_string(), "KRNvKQP");
+        assert_eq!(material.flipped().normalized(), material);
+        assert_eq!(
+            Material::from_str("KBvKN")
+                .unwrap()
+                .normalized()
+                .to_string(),
+            "KBvKN"
+        );
+        assert_eq!(
+            Material::from_str("KvKP").unwrap().normalized().to_string(),
+            "KPvK"
+        );
+        let successors: Vec<String> = Material::from_str("KPvKR")
+            .unwrap()
+            .successors()
+            .iter()
+            .map(Material::to_string)
+            .collect();
+        assert_eq!(
+            successors,
+            ["KRvK", "KQvKR", "KRvKR", "KRvKB", "KRvKN", "KPvK"]
+        );
+        for name in ["KQK", "QvK", "KQvKK", "Kqvk", "KXvK"] {
+            assert_eq!(
+                Material::from_str(name),
+                Err(TablebaseError::InvalidMaterial(name.to_string()))
+            );
+        }
+        assert_eq!(
+            Tablebase::generate(&Material::from_str("KQRvKR").unwrap(), |_| {}).err(),
+            Some(TablebaseError::TooManyPieces(5))
+        );
+    }
+
+    #[test]
+    fn test_known_endings() {
+        // The longest mates of these endings are well known
+        for (name, longest) in [("KQvK", 10), ("KRvK", 16), ("KPvK", 28)] {
+            let table = table(name);
+            let (fen, moves) = table.summary().longest_mate.unwrap();
+            assert_eq!(moves, longest, "{}", name);
+            assert_eq!(probe(&table, &fen), Some(Dtm::Win(longest)), "{}", fen);
+        }
+        for name in ["KBvK", "KNvK"] {
+            let summary = table(name).summary();
+            assert_eq!((summary.wins, summary.losses), (0, 0), "{}", name);
+            assert_eq!(summary.longest_mate, None);
+        }
+    }
+
+    #[test]
+    fn test_mates_match_the_mate_prover() {
+        let table = table("KQvK");
+        for moves in 1..=3 {
+            let index = table
+                .values
+                .iter()
+                .position(|&byte| Dtm::from_byte(byte) == Some(Dtm::Win(moves)))
+                .unwrap();
+            let (squares, white_to_move) = table.indexer.position(index).unwrap();
+            let mut game_state = generate::empty_game_state();
+            generate::place(
+                &mut game_state,
+                table.indexer.pieces(),
+                &squares,
+                white_to_move,
+            );
+            let mate = find_mate(&mut game_state, moves).unwrap();
+            assert_eq!(mate.moves(), moves, "{}", game_state.to_fen());
+        }
+    }
+
+    #[test]
+    fn test_probe() {
+        let queens = table("KQvK");
+        assert_eq!(
+            probe(&queens, "k7/8/1K6/8/8/8/8/6Q1 w - - 0 1"),
+            Some(Dtm::Win(1))
+        );
+        assert_eq!(
+            probe(&queens, "k7/8/1K6/8/8/8/8/6Q1 b - - 0 1"),
+            Some(Dtm::Loss(1))
+        );
+        assert_eq!(
+            probe(&queens, "k5Q1/8/1K6/8/8/8/8/8 b - - 0 1"),
+            Some(Dtm::Loss(0))
+        );
+        // Stalemate
+        assert_eq!(
+            probe(&queens, "k7/2Q5/1K6/8/8/8/8/8 b - - 0 1"),
+            Some(Dtm::Draw)
+        );
+        // The queen is taken
+        assert_eq!(
+            probe(&queens, "8/8/8/8/8/8/2k5/1Q2K3 b - - 0 1"),
+            Some(Dtm::Draw)
+        );
+        // With the colors swapped
+        assert_eq!(
+            probe(&queens, "K7/8/1k6/8/8/8/8/6q1 b - - 0 1"),
+            Some(Dtm::Win(1))
+        );
+        // Other pieces or castling rights
+        assert_eq!(probe(&queens, "k7/8/1K6/8/8/8/8/6R1 w - - 0 1"), None);
+        assert_eq!(probe(&queens, "4k3/8/8/8/8/8/8/Q3K2R w K - 0 1"), None);
+
+        // Pawns promote, with the tables of the promoted pieces
+        let pawns = table("KPvK");
+        assert_eq!(
+            probe(&pawns, "k7/2P5/1K6/8/8/8/8/8 w - - 0 1"),
+            Some(Dtm::Win(1))
+        );
+        assert_eq!(
+            probe(&pawns, "8/8/8/8/8/1k6/2p5/K7 b - - 0 1"),
+            Some(Dtm::Win(1))
+        );
+        // The king in front of the pawn on the sixth rank wins, whoever moves
+        assert_eq!(
+            probe(&pawns, "4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"),
+            Some(Dtm::Win(11))
+        );
+        assert_eq!(
+            probe(&pawns, "4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"),
+            Some(Dtm::Loss(12))
+        );
+        // Stalemate, and the defending king in front of the pawn
+        assert_eq!(
+            probe(&pawns, "4k3/4P3/4K3/8/8/8/8/8 b - - 0 1"),
+            Some(Dtm::Draw)
+        );
+        assert_eq!(
+            probe(&pawns, "8/8/8/4k3/8/8/4P3/4K3 w - - 0 1"),
+            Some(Dtm::Draw)
+        );
+        // An en passant target without an en passant capture
+        assert_eq!(
+            probe(&pawns, "8/8/8/8/4P2k/8/8/4K3 b - e3 0 1"),
+            Some(Dtm::Draw)
+        );
+    }
+
+    #[test]
+    fn test_table_file_round_trip() {
+        let table = table("KNvK");
+        let bytes = table.to_bytes();
+        assert_eq!(&bytes[..8], MAGIC);
+        let read = Tablebase::from_bytes(&bytes).unwrap();
+        assert_eq!(read.material(), table.material());
+        assert_eq!(read.values, table.values);
+
+        assert_eq!(
+            Tablebase::from_bytes(b"NOTATABLE\0\0\0\0").err(),
+            Some(TablebaseError::InvalidMagic)
+        );
+        let mut old = bytes.clone();
+        old[8] = 0;
+        assert_eq!(
+            Tablebase::from_bytes(&old).err(),
+            Some(TablebaseError::UnsupportedVersion(0))
+        );
+        assert!(matches!(
+            Tablebase::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(TablebaseError::InvalidSize { .. })
+        ));
+    }
+}
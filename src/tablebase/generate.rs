@@ -0,0 +1,280 @@
+//! Generation of a table by retrograde analysis.
+//!
+//! A first pass sets up every position of the table and plays its legal moves. Mates are lost
+//! in 0 plies. Captures and promotions are looked up in the tables they lead to, and the other
+//! moves are counted. Then the positions are solved by increasing distance to mate: the
+//! positions moving into a position lost in `n` plies are won in `n + 1`, and each position
+//! won in `n` takes one from the count of the positions moving into it, which are lost once
+//! the count reaches 0. The positions moving into a given one are found by taking back the
+//! moves of the side that just moved. Whatever is left once no position is solved is a draw.
+
+use std::collections::HashMap;
+
+use crate::board::moves::Move;
+use crate::board::piece::{Color, Piece, PieceType};
+use crate::board::Board;
+use crate::game_state::{GameResult, GameState};
+use crate::zobrist::position_key;
+
+use super::index::Indexer;
+use super::{Dtm, Material, Tablebase, DRAW, NO_POSITION};
+
+/// The distance of the illegal positions and unused indexes.
+const ILLEGAL: u16 = u16::MAX;
+/// The distance of the positions not solved yet.
+const UNKNOWN: u16 = u16::MAX - 1;
+/// The longest loss of the positions with a move that does not lose.
+const CANNOT_LOSE: u16 = u16::MAX;
+
+/// A game state with an empty board.
+pub fn empty_game_state() -> GameState {
+    GameState {
+        board: Board::new_empty_board(),
+        current_player: Color::White,
+        turn: 1,
+        game_result: GameResult::Undecided,
+        half_move_clock: 0,
+        history: Vec::new(),
+        key: 0,
+    }
+}
+
+/// Sets up the position of pieces on squares.
+pub fn place(game_state: &mut GameState, pieces: &[Piece], squares: &[u8], white_to_move: bool) {
+    game_state.board.squares.fill(None);
+    for (&piece, &square) in pieces.iter().zip(squares) {
+        game_state.board.set_piece(square, piece);
+    }
+    game_state.current_player = if white_to_move {
+        Color::White
+    } else {
+        Color::Black
+    };
+    game_state.key = position_key(&game_state.board, game_state.current_player);
+}
+
+/// The positions to solve, by distance to mate in plies.
+#[derive(Default)]
+struct Queue {
+    positions: Vec<Vec<u32>>,
+}
+
+impl Queue {
+    fn push(&mut self, plies: u16, index: usize) {
+        let plies = plies as usize;
+        if self.positions.len() <= plies {
+            self.positions.resize_with(plies + 1, Vec::new);
+        }
+        self.positions[plies].push(index as u32);
+    }
+}
+
+/// Solves the positions of a table, given the tables of the materials its captures and
+/// promotions lead to, and returns its bytes.
+pub fn solve(indexer: &Indexer, tables: &HashMap<Material, Tablebase>) -> Vec<u8> {
+    let size = indexer.size();
+    let mut distances = vec![ILLEGAL; size];
+    // The number of moves to other positions of the table not known to be lost
+    let mut moves_left = vec![0u8; size];
+    // The longest loss of the moves out of the table or to positions already won
+    let mut longest_loss = vec![0u16; size];
+    let mut queue = Queue::default();
+
+    let mut game_state = empty_game_state();
+    let mut successors = Vec::new();
+    for index in 0..size {
+        let Some((squares, white_to_move)) = indexer.position(index) else {
+            continue;
+        };
+        if indexer.index(&squares, white_to_move) != Some(index) {
+            continue;
+        }
+        place(&mut game_state, indexer.pieces(), &squares, white_to_move);
+        if game_state
+            .board
+            .is_in_check(game_state.current_player.opposite())
+        {
+            continue;
+        }
+        distances[index] = UNKNOWN;
+
+        let mut legal_moves = 0;
+        let mut shortest_win = None;
+        let mut loss = 0;
+        let mut can_lose = true;
+        successors.clear();
+        for chess_move in game_state.generate_moves() {
+            let leaves_table = chess_move.promotion.is_some()
+                || game_state
+                    .board
+                    .get_piece(chess_move.target_square)
+                    .is_some();
+            if !game_state.make_legal_move(&chess_move) {
+                continue;
+            }
+            legal_moves += 1;
+            if leaves_table {
+                match probe(tables, &game_state).plies() {
+                    Some(plies) if plies % 2 == 0 => {
+                        let plies = plies as u16 + 1;
+                        shortest_win = Some(shortest_win.map_or(plies, |win: u16| win.min(plies)));
+                    }
+                    Some(plies) => loss = loss.max(plies as u16 + 1),
+                    None => can_lose = false,
+                }
+            } else {
+                let moved = moved_squares(&squares, &chess_move);
+                successors.push(indexer.index(&moved, !white_to_move).expect("a legal move"));
+            }
+            game_state.unmake_move();
+        }
+        successors.sort_unstable();
+        successors.dedup();
+        moves_left[index] = successors.len() as u8;
+
+        if legal_moves == 0 {
+            if game_state.is_checkmate() {
+                queue.push(0, index);
+            }
+            longest_loss[index] = CANNOT_LOSE;
+            continue;
+        }
+        if let Some(win) = shortest_win {
+            queue.push(win, index);
+            can_lose = false;
+        }
+        if !can_lose {
+            longest_loss[index] = CANNOT_LOSE;
+        } else if successors.is_empty() {
+            queue.push(loss, index);
+        } else {
+            longest_loss[index] = loss;
+        }
+    }
+
+    let mut predecessors = Vec::new();
+    let mut plies = 0;
+    while plies < queue.positions.len() {
+        for index in std::mem::take(&mut queue.positions[plies]) {
+            let index = index as usize;
+            if distances[index] != UNKNOWN {
+                continue;
+            }
+            distances[index] = plies as u16;
+
+            let (squares, white_to_move) = indexer.position(index).expect("a legal position");
+            place(&mut game_state, indexer.pieces(), &squares, white_to_move);
+            find_predecessors(indexer, &game_state, &squares, &mut predecessors);
+            for &predecessor in &predecessors {
+                if distances[predecessor] != UNKNOWN {
+                    continue;
+                }
+                let plies = plies as u16 + 1;
+                if plies % 2 == 1 {
+                    // A move into a lost position wins
+                    queue.push(plies, predecessor);
+                } else if longest_loss[predecessor] != CANNOT_LOSE {
+                    moves_left[predecessor] -= 1;
+                    longest_loss[predecessor] = longest_loss[predecessor].max(plies);
+                    if moves_left[predecessor] == 0 {
+                        queue.push(longest_loss[predecessor], predecessor);
+                    }
+                }
+            }
+        }
+        plies += 1;
+    }
+
+    distances
+        .into_iter()
+        .map(|distance| match distance {
+            ILLEGAL => NO_POSITION,
+            UNKNOWN => DRAW,
+            // The longest mates with 4 pieces are far below 254 plies
+            _ => u8::try_from(distance + 1).expect("a mate shorter than 254 plies"),
+        })
+        .collect()
+}
+
+/// Probes a position of another table, the bare kings being a draw.
+fn probe(tables: &HashMap<Material, Tablebase>, game_state: &GameState) -> Dtm {
+    let material = Material::of(&game_state.board);
+    if material.pieces() == 2 {
+        return Dtm::Draw;
+    }
+    tables
+        .get(&material.normalized())
+        .and_then(|table| table.probe(game_state))
+        .expect("the tables of the successors are generated first")
+}
+
+/// The squares of the pieces after a move that is not a capture.
+fn moved_squares(squares: &[u8], chess_move: &Move) -> Vec<u8> {
+    squares
+        .iter()
+        .map(|&square| {
+            if square == chess_move.initial_square {
+                chess_move.target_square
+            } else {
+                square
+            }
+        })
+        .collect()
+}
+
+/// Finds the indexes of the legal positions moving into the position set up in the game state,
+/// without a capture or a promotion.
+fn find_predecessors(
+    indexer: &Indexer,
+    game_state: &GameState,
+    squares: &[u8],
+    predecessors: &mut Vec<usize>,
+) {
+    let board = &game_state.board;
+    let color = game_state.current_player.opposite();
+    let white_to_move = color == Color::White;
+    predecessors.clear();
+
+    // Pieces other than pawns move back the way they move forward
+    let mut moves_back = Vec::new();
+    for chess_move in board.generate_moves(color) {
+        let piece = board.get_piece(chess_move.initial_square);
+        if piece.is_some_and(|piece| piece.piece_type != PieceType::Pawn)
+            && board.get_piece(chess_move.target_square).is_none()
+        {
+            moves_back.push(chess_move);
+        }
+    }
+    // Pawns move back one square, or two to their starting rank
+    let (back, double_step_rank): (i8, u8) = match color {
+        Color::White => (-8, 3),
+        Color::Black => (8, 4),
+    };
+    let pawn = Piece {
+        piece_type: PieceType::Pawn,
+        color,
+    };
+    for &square in squares {
+        if board.get_piece(square) != Some(&pawn) {
+            continue;
+        }
+        let one_back = (square as i8 + back) as u8;
+        if !(8..56).contains(&one_back) || board.get_piece(one_back).is_some() {
+            continue;
+        }
+        moves_back.push(Move::new(square, one_back));
+        let two_back = (one_back as i8 + back) as u8;
+        if square / 8 == double_step_rank && board.get_piece(two_back).is_none() {
+            moves_back.push(Move::new(square, two_back));
+        }
+    }
+
+    for chess_move in moves_back {
+        let moved = moved_squares(squares, &chess_move);
+        if let Some(index) = indexer.index(&moved, white_to_move) {
+            predecessors.push(index);
+        }
+    }
+    predecessors.sort_unstable();
+    predecessors.dedup();
+}
@@ -0,0 +1,264 @@
+//! Indexing of the positions of a table.
+//!
+//! A position is indexed by the squares of its pieces, kings first, and the side to move. The
+//! board symmetries keeping the moves the same shrink the table: without pawns, the position
+//! is turned and mirrored so that the white king is in the triangle a1-d1-d4, and with pawns,
+//! which only move up or down, it is mirrored so that the white king is on the queenside.
+//! Positions that are equal up to a symmetry, or to swapping two identical pieces, all get the
+//! smallest of their indexes, so some indexes are never used.
+
+use std::ops::Range;
+use std::sync::OnceLock;
+
+use crate::board::piece::{Color, Piece, PieceType};
+use crate::board::Board;
+
+use super::{Material, MAX_PIECES};
+
+/// The number of squares of the white king without pawns: a1, b1, c1, d1, b2, c2, d2, c3, d3
+/// and d4.
+const TRIANGLE_SQUARES: usize = 10;
+/// The number of squares of the white king with pawns: the files a to d.
+const QUEENSIDE_SQUARES: usize = 32;
+/// Marks the squares the white king is never indexed on.
+const NONE: u8 = u8::MAX;
+
+/// The index of each square in the triangle, or `NONE`.
+fn triangle() -> &'static [u8; 64] {
+    static TRIANGLE: OnceLock<[u8; 64]> = OnceLock::new();
+    TRIANGLE.get_or_init(|| {
+        let mut triangle = [NONE; 64];
+        let mut index = 0;
+        for (square, triangle) in triangle.iter_mut().enumerate() {
+            let (file, rank) = (square % 8, square / 8);
+            if rank <= file && file < 4 {
+                *triangle = index;
+                index += 1;
+            }
+        }
+        triangle
+    })
+}
+
+/// Maps a square through one of the 8 symmetries of the board: bit 0 mirrors the files, bit
+/// 1 the ranks and bit 2 swaps files and ranks.
+fn transform(square: u8, symmetry: usize) -> u8 {
+    let (mut file, mut rank) = (square % 8, square / 8);
+    if symmetry & 1 != 0 {
+        file = 7 - file;
+    }
+    if symmetry & 2 != 0 {
+        rank = 7 - rank;
+    }
+    if symmetry & 4 != 0 {
+        (file, rank) = (rank, file);
+    }
+    rank * 8 + file
+}
+
+/// Maps positions of a material to the indexes of a table and back.
+#[derive(Debug, Clone)]
+pub struct Indexer {
+    /// The pieces in index order: the white king, the black king, then the other white and
+    /// black pieces, identical ones next to each other.
+    pieces: Vec<Piece>,
+    /// The ranges of identical pieces, which are indexed by increasing square.
+    identical: Vec<Range<usize>>,
+    has_pawns: bool,
+}
+
+impl Indexer {
+    pub fn new(material: &Material) -> Indexer {
+        let king = |color| Piece {
+            piece_type: PieceType::King,
+            color,
+        };
+        let mut pieces = vec![king(Color::White), king(Color::Black)];
+        for (color, piece_types) in [
+            (Color::White, &material.white),
+            (Color::Black, &material.black),
+        ] {
+            pieces.extend(
+                piece_types
+                    .iter()
+                    .map(|&piece_type| Piece { piece_type, color }),
+            );
+        }
+        let has_pawns = pieces
+            .iter()
+            .any(|piece| piece.piece_type == PieceType::Pawn);
+        let mut identical = Vec::new();
+        let mut start = 2;
+        while start < pieces.len() {
+            let end = (start..pieces.len())
+                .find(|&i| pieces[i] != pieces[start])
+                .unwrap_or(pieces.len());
+            if end - start > 1 {
+                identical.push(start..end);
+            }
+            start = end;
+        }
+        Indexer {
+            pieces,
+            identical,
+            has_pawns,
+        }
+    }
+
+    /// The number of indexes of the table.
+    pub fn size(&self) -> usize {
+        let king_squares = if self.has_pawns {
+            QUEENSIDE_SQUARES
+        } else {
+            TRIANGLE_SQUARES
+        };
+        king_squares * 64usize.pow(self.pieces.len() as u32 - 1) * 2
+    }
+
+    /// The symmetries the positions are reduced by.
+    fn symmetries(&self) -> usize {
+        if self.has_pawns {
+            2
+        } else {
+            8
+        }
+    }
+
+    /// The index of the white king on a square, or `NONE` when it is not indexed there.
+    fn king_index(&self, square: u8) -> u8 {
+        if self.has_pawns {
+            if square % 8 < 4 {
+                square / 8 * 4 + square % 8
+            } else {
+                NONE
+            }
+        } else {
+            triangle()[square as usize]
+        }
+    }
+
+    /// The index of a position, given the squares of its pieces in index order, or `None` when
+    /// two pieces are on the same square.
+    pub fn index(&self, squares: &[u8], white_to_move: bool) -> Option<usize> {
+        for (i, square) in squares.iter().enumerate() {
+            if squares[..i].contains(square) {
+                return None;
+            }
+        }
+        let mut best = usize::MAX;
+        let mut transformed = [0; MAX_PIECES];
+        let transformed = &mut transformed[..squares.len()];
+        for symmetry in 0..self.symmetries() {
+            for (transformed, &square) in transformed.iter_mut().zip(squares) {
+                *transformed = transform(square, symmetry);
+            }
+            let king = self.king_index(transformed[0]);
+            if king == NONE {
+                continue;
+            }
+            for group in &self.identical {
+                transformed[group.clone()].sort_unstable();
+            }
+            let index = transformed[1..]
+                .iter()
+                .fold(king as usize, |index, &square| index * 64 + square as usize);
+            best = best.min(index * 2 + !white_to_move as usize);
+        }
+        Some(best)
+    }
+
+    /// The index of the position of a board, which must have the pieces of the table, or the
+    /// pieces with the colors swapped when `flip` is set.
+    pub fn board_index(&self, board: &Board, white_to_move: bool, flip: bool) -> Option<usize> {
+        let mut squares = [0; MAX_PIECES];
+        let mut used = 0u64;
+        for (i, piece) in self.pieces.iter().enumerate() {
+            let piece = if flip {
+                Piece {
+                    color: piece.color.opposite(),
+                    ..*piece
+                }
+            } else {
+                *piece
+            };
+            let square = (0..64u8).find(|&square| {
+                used & (1 << square) == 0 && board.get_piece(square) == Some(&piece)
+            })?;
+            used |= 1 << square;
+            // Swapping the colors also mirrors the ranks, so that pawns keep their direction
+            squares[i] = if flip { square ^ 56 } else { square };
+        }
+        self.index(&squares[..self.pieces.len()], white_to_move)
+    }
+
+    /// The squares of the pieces in index order and whether white is to move at an index, or
+    /// `None` when the pieces overlap or a pawn is on the first or last rank.
+    pub fn position(&self, index: usize) -> Option<(Vec<u8>, bool)> {
+        let white_to_move = index & 1 == 0;
+        let mut rest = index >> 1;
+        let mut squares = vec![0; self.pieces.len()];
+        for square in squares[1..].iter_mut().rev() {
+            *square = (rest % 64) as u8;
+            rest /= 64;
+        }
+        squares[0] = (0..64u8).find(|&square| self.king_index(square) as usize == rest)?;
+
+        for (i, &square) in squares.iter().enumerate() {
+            if squares[..i].contains(&square)
+                || (self.pieces[i].piece_type == PieceType::Pawn && !(8..56).contains(&square))
+            {
+                return None;
+            }
+        }
+        Some((squares, white_to_move))
+    }
+
+    /// The pieces in index order.
+    pub fn pieces(&self) -> &[Piece] {
+        &self.pieces
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_symmetric_positions_share_an_index() {
+        let indexer = Indexer::new(&Material::from_str("KQvK").unwrap());
+        assert_eq!(indexer.size(), 10 * 64 * 64 * 2);
+        // Ke1, kd8, Qa1 turned and mirrored the 8 ways, with the same index
+        let squares = [4, 59, 0];
+        let index = indexer.index(&squares, true).unwrap();
+        for symmetry in 0..8 {
+            let transformed: Vec<u8> = squares
+                .iter()
+                .map(|&square| transform(square, symmetry))
+                .collect();
+            assert_eq!(indexer.index(&transformed, true), Some(index));
+            assert_ne!(indexer.index(&transformed, false), Some(index));
+        }
+        let (position, white_to_move) = indexer.position(index).unwrap();
+        assert!(white_to_move);
+        assert_eq!(indexer.index(&position, true), Some(index));
+        assert_eq!(indexer.index(&[4, 4, 0], true), None);
+
+        // Identical pieces can be swapped, but pawns cannot turn
+        let indexer = Indexer::new(&Material::from_str("KPPvK").unwrap());
+        assert_eq!(indexer.size(), 32 * 64 * 64 * 64 * 2);
+        assert_eq!(
+            indexer.index(&[4, 60, 8, 9], true),
+            indexer.index(&[4, 60, 9, 8], true)
+        );
+        assert_eq!(
+            indexer.index(&[4, 60, 8, 9], true),
+            indexer.index(&[3, 59, 15, 14], true)
+        );
+        assert_ne!(
+            indexer.index(&[4, 60, 8, 9], true),
+            indexer.index(&[60, 4, 48, 49], true)
+        );
+        assert_eq!(indexer.position(0), None);
+    }
+}
@@ -0,0 +1,377 @@
+//! Module containing the Texel tuner, fitting the evaluation parameters to game results.
+//!
+//! Every position of the training set is labeled with the result of the game it comes from.
+//! A position's evaluation `e` predicts the result `1 / (1 + 10^(-k * e / 400))`, and the
+//! tuner searches the parameters minimizing the mean squared error between predictions and
+//! results, with the local search of the original Texel method: every parameter in turn is
+//! moved up or down by one, and the change is kept when the error decreases.
+//!
+//! Positions are resolved with a quiescence search when loaded: the tuner evaluates the
+//! position at the end of the capture sequence rather than the position itself, so that
+//! hanging pieces do not blur the error.
+
+use std::io::BufRead;
+use std::str::FromStr;
+use std::thread;
+
+use thiserror::Error;
+
+use crate::board::moves::Move;
+use crate::board::piece::{Color, Piece};
+use crate::board::Board;
+use crate::epd::Epd;
+use crate::eval::params::EvalParams;
+use crate::eval::{evaluate_trace_with, piece_value};
+use crate::game_state::{GameResult, GameState};
+use crate::pgn::parse_result;
+
+/// Small sets of positions are not worth spawning threads for.
+const MIN_POSITIONS_PER_THREAD: usize = 1024;
+
+/// An error raised while loading the training positions.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum TuneError {
+    #[error("Line {0}: invalid position: {1}")]
+    InvalidPosition(usize, String),
+    #[error("Line {0}: missing or invalid result")]
+    MissingResult(usize),
+    #[error("Error reading the positions: {0}")]
+    Io(String),
+}
+
+/// A quiet position of the training set, with the result of its game.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TuningPosition {
+    /// The pieces of the position, in the layout of `Board::squares`.
+    pub squares: Vec<Option<Piece>>,
+    pub side_to_move: Color,
+    /// The result of the game from the point of view of white: 1 for a win, 0.5 for a draw
+    /// and 0 for a loss.
+    pub result: f64,
+}
+
+/// Parses a labeled position, either a FEN string followed by the result, or an EPD record
+/// with the result as its `c9` comment.
+///
+/// The result after a FEN string may be written `1-0`, `0-1` or `1/2-1/2`, possibly quoted,
+/// or as the score of white `1.0`, `0.5` or `0.0`, possibly in brackets.
+///
+/// # Examples
+///
+/// ```
+/// use ruce::tune::parse_labeled_position;
+///
+/// let (_, result) = parse_labeled_position("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1 [1.0]").unwrap();
+/// assert_eq!(result, 1.0);
+/// let (_, result) = parse_labeled_position("4k3/8/8/8/8/8/8/4K3 b - - c9 \"1/2-1/2\";").unwrap();
+/// assert_eq!(result, 0.5);
+/// ```
+pub fn parse_labeled_position(line: &str) -> Option<(GameState, f64)> {
+    let line = line.trim();
+    if let Some((fen, result)) = line.rsplit_once(char::is_whitespace) {
+        let game_state = GameState::from_str(fen.trim());
+        if let (Some(result), Ok(game_state)) = (parse_score(result), game_state) {
+            return Some((game_state, result));
+        }
+    }
+    let epd = Epd::from_str(line).ok()?;
+    let result = parse_score(epd.comment(9)?)?;
+    Some((epd.game_state, result))
+}
+
+/// Parses a game result as the score of white.
+fn parse_score(result: &str) -> Option<f64> {
+    let result = result
+        .trim_end_matches(';')
+        .trim_matches(|c| c == '"' || c == '[' || c == ']');
+    match parse_result(result) {
+        Some(GameResult::WhiteWon) => Some(1.0),
+        Some(GameResult::BlackWon) => Some(0.0),
+        Some(GameResult::Draw) => Some(0.5),
+        Some(GameResult::Undecided) => None,
+        // Without a decimal point, the number could be the move number of the FEN string
+        None if !result.contains('.') => None,
+        None => result
+            .parse::<f64>()
+            .ok()
+            .filter(|score| (0.0..=1.0).contains(score)),
+    }
+}
+
+/// Reads labeled positions, one per line, and resolves them with a quiescence search using
+/// `params`. Empty lines and lines starting with `#` are skipped.
+pub fn load_positions(
+    input: impl BufRead,
+    params: &EvalParams,
+) -> Result<Vec<TuningPosition>, TuneError> {
+    let mut positions = Vec::new();
+    for (number, line) in input.lines().enumerate() {
+        let line = line.map_err(|e| TuneError::Io(e.to_string()))?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (mut game_state, result) = parse_labeled_position(line).ok_or_else(|| {
+            let label = line.rsplit(char::is_whitespace).next().unwrap_or(line);
+            match parse_score(label) {
+                Some(_) => TuneError::InvalidPosition(number + 1, line.to_string()),
+                None => TuneError::MissingResult(number + 1),
+            }
+        })?;
+        let mut pv = Vec::new();
+        quiescence(&mut game_state, -i32::MAX, i32::MAX, params, &mut pv);
+        for chess_move in &pv {
+            game_state.make_move(chess_move);
+        }
+        positions.push(TuningPosition {
+            squares: game_state.board.squares,
+            side_to_move: game_state.current_player,
+            result,
+        });
+    }
+    Ok(positions)
+}
+
+/// A captures-only search returning the score of the position for the side to move, and the
+/// sequence of captures leading to the quiet position it comes from in `pv`.
+fn quiescence(
+    game_state: &mut GameState,
+    mut alpha: i32,
+    beta: i32,
+    params: &EvalParams,
+    pv: &mut Vec<Move>,
+) -> i32 {
+    pv.clear();
+    let stand_pat =
+        evaluate_trace_with(&game_state.board, game_state.current_player, params).relative_score();
+    if stand_pat >= beta {
+        return stand_pat;
+    }
+    alpha = alpha.max(stand_pat);
+
+    let board = &game_state.board;
+    let mut captures = board.generate_captures(game_state.current_player);
+    captures.sort_by_key(|chess_move| {
+        let victim = board.get_piece(chess_move.target_square);
+        -victim.map_or(0, |piece| piece_value(piece.piece_type))
+    });
+
+    let mut child_pv = Vec::new();
+    for chess_move in captures {
+        if !game_state.make_legal_move(&chess_move) {
+            continue;
+        }
+        let score = -quiescence(game_state, -beta, -alpha, params, &mut child_pv);
+        game_state.unmake_move();
+        if score > alpha {
+            alpha = score;
+            pv.clear();
+            pv.push(chess_move);
+            pv.append(&mut child_pv);
+            if alpha >= beta {
+                break;
+            }
+        }
+    }
+    alpha
+}
+
+/// The result predicted for a score in centipawns, from 0 to 1.
+pub fn sigmoid(score: f64, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * score / 400.0))
+}
+
+/// The mean squared error between the results of the positions and the results predicted
+/// by their evaluation with `params`.
+pub fn mean_squared_error(positions: &[TuningPosition], params: &EvalParams, k: f64) -> f64 {
+    if positions.is_empty() {
+        return 0.0;
+    }
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = positions
+        .len()
+        .div_ceil(threads)
+        .max(MIN_POSITIONS_PER_THREAD);
+    let total: f64 = thread::scope(|scope| {
+        let handles: Vec<_> = positions
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || squared_errors(chunk, params, k)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("tuning thread panicked"))
+            .sum()
+    });
+    total / positions.len() as f64
+}
+
+fn squared_errors(positions: &[TuningPosition], params: &EvalParams, k: f64) -> f64 {
+    let mut board = Board::new_empty_board();
+    positions
+        .iter()
+        .map(|position| {
+            board.squares.clone_from(&position.squares);
+            let score = evaluate_trace_with(&board, position.side_to_move, params).score;
+            (position.result - sigmoid(score as f64, k)).powi(2)
+        })
+        .sum()
+}
+
+/// Finds the scaling constant `k` of the sigmoid best fitting the positions with the given
+/// parameters, by narrowing down the interval from 0 to 4 ten times.
+pub fn find_k(positions: &[TuningPosition], params: &EvalParams) -> f64 {
+    let (mut low, mut high) = (0.0, 4.0);
+    for _ in 0..10 {
+        let step = (high - low) / 10.0;
+        let (best, _) = (0..=10)
+            .map(|i| low + step * i as f64)
+            .map(|k| (k, mean_squared_error(positions, params, k)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .unwrap_or((1.0, 0.0));
+        low = (best - step).max(0.0);
+        high = best + step;
+    }
+    (low + high) / 2.0
+}
+
+/// Tunes evaluation parameters on a set of positions.
+pub struct Tuner {
+    positions: Vec<TuningPosition>,
+    params: EvalParams,
+    k: f64,
+}
+
+impl Tuner {
+    /// Creates a tuner starting from `params`, fitting the sigmoid constant to them.
+    pub fn new(positions: Vec<TuningPosition>, params: EvalParams) -> Self {
+        let k = find_k(&positions, &params);
+        Tuner {
+            positions,
+            params,
+            k,
+        }
+    }
+
+    /// Returns the scaling constant of the sigmoid.
+    pub fn k(&self) -> f64 {
+        self.k
+    }
+
+    /// Returns the current parameters.
+    pub fn params(&self) -> &EvalParams {
+        &self.params
+    }
+
+    /// Returns the error of the current parameters.
+    pub fn error(&self) -> f64 {
+        mean_squared_error(&self.positions, &self.params, self.k)
+    }
+
+    /// Runs local search passes over every parameter until none of them improves the error,
+    /// or `max_iterations` passes are done. `report` is called after each pass with its
+    /// number and the error reached. Returns the error of the tuned parameters.
+    pub fn tune(&mut self, max_iterations: u32, mut report: impl FnMut(u32, f64)) -> f64 {
+        let mut values = self.params.values();
+        let mut best_error = self.error();
+        let mut params = self.params.clone();
+
+        for iteration in 1..=max_iterations {
+            let mut improved = false;
+            for index in 0..values.len() {
+                for delta in [1, -2] {
+                    values[index] += delta;
+                    params.set_values(&values);
+                    let error = mean_squared_error(&self.positions, &params, self.k);
+                    if error < best_error {
+                        best_error = error;
+                        improved = true;
+                        break;
+                    }
+                    if delta == -2 {
+                        values[index] += 1;
+                    }
+                }
+            }
+            self.params.set_values(&values);
+            report(iteration, best_error);
+            if !improved {
+                break;
+            }
+        }
+        best_error
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_labeled_positions() {
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+        for (label, expected) in [
+            ("1-0", 1.0),
+            ("\"0-1\";", 0.0),
+            ("[0.5]", 0.5),
+            ("0.0", 0.0),
+        ] {
+            let (game_state, result) =
+                parse_labeled_position(&format!("{} {}", fen, label)).unwrap();
+            assert_eq!(game_state.to_fen(), fen);
+            assert_eq!(result, expected);
+        }
+
+        let epd = "4k3/8/8/8/8/8/4P3/4K3 w - - id \"pawn\"; c9 \"1-0\";";
+        assert_eq!(parse_labeled_position(epd).unwrap().1, 1.0);
+        assert!(parse_labeled_position(&format!("{} 2.0", fen)).is_none());
+        assert!(parse_labeled_position(fen).is_none());
+    }
+
+    #[test]
+    fn test_positions_are_resolved() {
+        // The rook takes the hanging queen before the position is stored
+        let input = "# comment\n\n4k3/8/8/8/3q4/8/8/3RK3 w - - 0 1 1-0\n";
+        let positions = load_positions(input.as_bytes(), &EvalParams::DEFAULT).unwrap();
+        assert_eq!(positions.len(), 1);
+        let board = Board::from_str("4k3/8/8/8/3R4/8/8/4K3").unwrap();
+        assert_eq!(positions[0].squares, board.squares);
+        assert_eq!(positions[0].side_to_move, Color::Black);
+
+        let load = |input: &str| load_positions(input.as_bytes(), &EvalParams::DEFAULT);
+        assert_eq!(
+            load("4k3/8/8/8/8/8/8/4K3 w - - 0 1 draw").unwrap_err(),
+            TuneError::MissingResult(1)
+        );
+        assert_eq!(
+            load("\n4k3/8/8/8/8/8/8/4X3 w - - 0 1 1-0").unwrap_err(),
+            TuneError::InvalidPosition(2, "4k3/8/8/8/8/8/8/4X3 w - - 0 1 1-0".to_string())
+        );
+    }
+
+    #[test]
+    fn test_tuning_reduces_the_error() {
+        // White wins every game a pawn up, but the pawn is worth almost nothing
+        let input = "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1 1-0\n\
+                     4k3/8/8/8/8/8/4P3/4K3 b - - 0 1 1-0\n\
+                     4k3/4p3/8/8/8/8/8/4K3 w - - 0 1 0-1\n\
+                     4k3/8/8/8/8/8/8/4K3 w - - 0 1 1/2-1/2\n";
+        let mut params = EvalParams::DEFAULT;
+        params.material[0] = crate::eval::Score::new(1, 1);
+        let positions = load_positions(input.as_bytes(), &params).unwrap();
+
+        let mut tuner = Tuner::new(positions, params);
+        let initial_error = tuner.error();
+        let mut reports = Vec::new();
+        let error = tuner.tune(2, |iteration, error| reports.push((iteration, error)));
+        assert!(error < initial_error);
+        assert_eq!(reports.len(), 2);
+        assert_eq!(tuner.error(), error);
+        assert!(tuner.params().material[0].eg > 1);
+    }
+
+    #[test]
+    fn test_sigmoid() {
+        assert_eq!(sigmoid(0.0, 1.0), 0.5);
+        assert!((sigmoid(400.0, 1.0) - 10.0 / 11.0).abs() < 1e-12);
+    }
+}
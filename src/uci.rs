@@ -0,0 +1,610 @@
+//! Module containing the UCI (Universal Chess Interface) protocol, used by chess GUIs to talk
+//! to the engine through its standard input and output.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::BufRead;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::board::moves::Move;
+use crate::book::{Book, Selection};
+#[cfg(feature = "nnue")]
+use crate::eval::nnue::Network;
+use crate::game_state::GameState;
+use crate::search::tt::{TranspositionTable, DEFAULT_HASH_MB};
+use crate::search::{
+    SearchLimits, SearchOptions, SearchResult, Searcher, MAX_MULTI_PV, MAX_THREADS,
+};
+#[cfg(feature = "syzygy")]
+use crate::syzygy::Tablebases;
+
+/// The largest transposition table accepted by the `Hash` option, in megabytes.
+const MAX_HASH_MB: usize = 65_536;
+
+/// The default and largest values of the `Move Overhead` option, in milliseconds.
+const DEFAULT_MOVE_OVERHEAD_MS: u64 = 10;
+const MAX_MOVE_OVERHEAD_MS: u64 = 5000;
+
+/// Returns the field of a search switch.
+type Switch = fn(&mut SearchOptions) -> &mut bool;
+
+/// The check options switching the parts of the search on and off, for testing what each of
+/// them brings.
+const SEARCH_SWITCHES: [(&str, Switch); 8] = [
+    ("QuiescenceChecks", |options| &mut options.quiescence_checks),
+    ("PVS", |options| &mut options.principal_variation_search),
+    ("NullMove", |options| &mut options.null_move_pruning),
+    ("LMR", |options| &mut options.late_move_reductions),
+    ("Futility", |options| &mut options.futility_pruning),
+    ("ReverseFutility", |options| {
+        &mut options.reverse_futility_pruning
+    }),
+    ("CheckExtensions", |options| &mut options.check_extensions),
+    ("AspirationWindows", |options| {
+        &mut options.aspiration_windows
+    }),
+];
+
+/// The engine side of a UCI session.
+pub struct UciEngine {
+    game_state: GameState,
+    /// The switches of the search, set with the check options of `SEARCH_SWITCHES`.
+    options: SearchOptions,
+    /// The transposition table, kept from one search to the next until `ucinewgame`.
+    tt: Arc<TranspositionTable>,
+    /// The number of search threads, set with the `Threads` option.
+    threads: usize,
+    /// The number of lines searched, set with the `MultiPV` option.
+    multi_pv: usize,
+    /// The time kept aside from the clock on each move, set with the `Move Overhead` option.
+    move_overhead: Duration,
+    /// Whether moves are played from the book, set with the `OwnBook` option.
+    own_book: bool,
+    /// The book loaded with the `BookFile` option.
+    book: Option<Book>,
+    /// How book moves are chosen, set with the `BookMode` option.
+    book_selection: Selection,
+    /// Whether castling moves are written as the king taking its own rook, set with the
+    /// `UCI_Chess960` option.
+    chess960: bool,
+    /// The network loaded with the `EvalFile` option.
+    #[cfg(feature = "nnue")]
+    network: Option<Arc<Network>>,
+    /// The tablebases found in the directories of the `SyzygyPath` option.
+    #[cfg(feature = "syzygy")]
+    tablebases: Option<Arc<Tablebases>>,
+    /// The stop flag and thread of the running search, if any.
+    search: Option<(Arc<AtomicBool>, JoinHandle<()>)>,
+}
+
+impl UciEngine {
+    /// Creates an engine set up on the starting position.
+    pub fn new() -> Self {
+        UciEngine {
+            game_state: GameState::new(),
+            options: SearchOptions::default(),
+            tt: Arc::new(TranspositionTable::new(DEFAULT_HASH_MB)),
+            threads: 1,
+            multi_pv: 1,
+            move_overhead: Duration::from_millis(DEFAULT_MOVE_OVERHEAD_MS),
+            own_book: false,
+            book: None,
+            book_selection: Selection::default(),
+            chess960: false,
+            #[cfg(feature = "nnue")]
+            network: None,
+            #[cfg(feature = "syzygy")]
+            tablebases: None,
+            search: None,
+        }
+    }
+
+    /// Reads and handles commands until `quit` or the end of the input.
+    pub fn run(&mut self, input: impl BufRead) {
+        for line in input.lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if !self.handle_command(&line) {
+                break;
+            }
+        }
+        self.stop_search();
+    }
+
+    /// Handles one command, returning `false` when the engine should quit.
+    pub fn handle_command(&mut self, line: &str) -> bool {
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("uci") => {
+                println!("id name ruce {}", env!("CARGO_PKG_VERSION"));
+                println!("id author the ruce developers");
+                println!(
+                    "option name Hash type spin default {} min 1 max {}",
+                    DEFAULT_HASH_MB, MAX_HASH_MB
+                );
+                println!("option name Clear Hash type button");
+                println!(
+                    "option name MultiPV type spin default 1 min 1 max {}",
+                    MAX_MULTI_PV
+                );
+                println!(
+                    "option name Threads type spin default 1 min 1 max {}",
+                    MAX_THREADS
+                );
+                println!(
+                    "option name Move Overhead type spin default {} min 0 max {}",
+                    DEFAULT_MOVE_OVERHEAD_MS, MAX_MOVE_OVERHEAD_MS
+                );
+                println!("option name UCI_Chess960 type check default false");
+                println!("option name OwnBook type check default false");
+                println!("option name BookFile type string default <empty>");
+                let selections: Vec<String> = Selection::ALL
+                    .iter()
+                    .map(|selection| format!("var {}", selection))
+                    .collect();
+                println!(
+                    "option name BookMode type combo default {} {}",
+                    Selection::default(),
+                    selections.join(" ")
+                );
+                let mut defaults = SearchOptions::default();
+                for (name, switch) in SEARCH_SWITCHES {
+                    println!(
+                        "option name {} type check default {}",
+                        name,
+                        switch(&mut defaults)
+                    );
+                }
+                #[cfg(feature = "nnue")]
+                println!("option name EvalFile type string default <empty>");
+                #[cfg(feature = "syzygy")]
+                println!("option name SyzygyPath type string default <empty>");
+                println!("uciok");
+            }
+            Some("isready") => println!("readyok"),
+            Some("setoption") => {
+                self.stop_search();
+                let args: Vec<&str> = tokens.collect();
+                match parse_setoption(&args) {
+                    Some((name, value)) => self.set_option(&name, &value),
+                    None => println!("info string Invalid option"),
+                }
+            }
+            Some("ucinewgame") => {
+                self.stop_search();
+                self.game_state = GameState::new();
+                self.tt.clear();
+            }
+            Some("position") => {
+                self.stop_search();
+                let args: Vec<&str> = tokens.collect();
+                match parse_position(&args, self.chess960) {
+                    Ok(game_state) => self.game_state = game_state,
+                    Err(e) => println!("info string {}", e),
+                }
+            }
+            Some("go") => {
+                self.stop_search();
+                let args: Vec<&str> = tokens.collect();
+                self.start_search(parse_go(&args));
+            }
+            Some("stop") => self.stop_search(),
+            Some("quit") => return false,
+            Some(command) => println!("info string Unknown command: {}", command),
+            None => {}
+        }
+        true
+    }
+
+    /// Sets an option of the engine, option names are case insensitive.
+    fn set_option(&mut self, name: &str, value: &str) {
+        match name.to_lowercase().as_str() {
+            "hash" => match value.parse::<usize>() {
+                Ok(size_mb) => {
+                    let size_mb = size_mb.clamp(1, MAX_HASH_MB);
+                    self.tt = Arc::new(TranspositionTable::new(size_mb));
+                }
+                Err(_) => println!("info string Invalid Hash value: {}", value),
+            },
+            "clear hash" => self.tt.clear(),
+            "threads" => match value.parse::<usize>() {
+                Ok(threads) => self.threads = threads.clamp(1, MAX_THREADS),
+                Err(_) => println!("info string Invalid Threads value: {}", value),
+            },
+            "multipv" => match value.parse::<usize>() {
+                Ok(lines) => self.multi_pv = lines.clamp(1, MAX_MULTI_PV),
+                Err(_) => println!("info string Invalid MultiPV value: {}", value),
+            },
+            "move overhead" => match value.parse::<u64>() {
+                Ok(millis) => {
+                    let millis = millis.min(MAX_MOVE_OVERHEAD_MS);
+                    self.move_overhead = Duration::from_millis(millis);
+                }
+                Err(_) => println!("info string Invalid Move Overhead value: {}", value),
+            },
+            "uci_chess960" => match value.parse::<bool>() {
+                Ok(chess960) => self.chess960 = chess960,
+                Err(_) => println!("info string Invalid UCI_Chess960 value: {}", value),
+            },
+            "ownbook" => match value.parse::<bool>() {
+                Ok(own_book) => self.own_book = own_book,
+                Err(_) => println!("info string Invalid OwnBook value: {}", value),
+            },
+            "bookfile" => {
+                if value.is_empty() || value == "<empty>" {
+                    self.book = None;
+                    return;
+                }
+                match Book::open(value) {
+                    Ok(book) => {
+                        println!("info string Loaded book {} of {} moves", value, book.len());
+                        self.book = Some(book);
+                    }
+                    Err(e) => {
+                        println!("info string {}", e);
+                        self.book = None;
+                    }
+                }
+            }
+            "bookmode" => match value.parse::<Selection>() {
+                Ok(selection) => self.book_selection = selection,
+                Err(_) => println!("info string Invalid BookMode value: {}", value),
+            },
+            #[cfg(feature = "nnue")]
+            "evalfile" => {
+                if value.is_empty() || value == "<empty>" {
+                    self.network = None;
+                    return;
+                }
+                match Network::load(value) {
+                    Ok(network) => {
+                        println!("info string Loaded network {}", value);
+                        self.network = Some(Arc::new(network));
+                    }
+                    Err(e) => {
+                        println!("info string {}, using the handcrafted evaluation", e);
+                        self.network = None;
+                    }
+                }
+            }
+            #[cfg(feature = "syzygy")]
+            "syzygypath" => {
+                if value.is_empty() || value == "<empty>" {
+                    self.tablebases = None;
+                    return;
+                }
+                match Tablebases::open(value) {
+                    Ok(tablebases) => {
+                        println!(
+                            "info string Found tablebases of up to {} pieces",
+                            tablebases.max_pieces()
+                        );
+                        self.tablebases = Some(Arc::new(tablebases));
+                    }
+                    Err(e) => {
+                        println!("info string {}", e);
+                        self.tablebases = None;
+                    }
+                }
+            }
+            lowercase => {
+                let switch = SEARCH_SWITCHES
+                    .iter()
+                    .find(|(switch_name, _)| switch_name.to_lowercase() == lowercase);
+                match (switch, value.parse::<bool>()) {
+                    (Some((_, switch)), Ok(value)) => *switch(&mut self.options) = value,
+                    (Some(_), Err(_)) => println!("info string Invalid {} value: {}", name, value),
+                    (None, _) => println!("info string Unknown option: {}", name),
+                }
+            }
+        }
+    }
+
+    fn start_search(&mut self, mut limits: SearchLimits) {
+        if let Some(book_move) = self.book_move(&limits) {
+            println!("bestmove {}", book_move);
+            return;
+        }
+        limits.move_overhead = self.move_overhead;
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut game_state = self.game_state.clone();
+        let mut searcher = Searcher::with_stop_flag(limits, stop.clone());
+        searcher.set_transposition_table(self.tt.clone());
+        searcher.options = self.options.clone();
+        searcher.set_threads(self.threads);
+        searcher.set_multi_pv(self.multi_pv);
+        #[cfg(feature = "nnue")]
+        searcher.set_network(self.network.clone());
+        #[cfg(feature = "syzygy")]
+        searcher.set_tablebases(self.tablebases.clone());
+        let handle = thread::spawn(move || {
+            let result =
+                searcher.search(&mut game_state, |result| println!("{}", info_line(result)));
+            match result.best_move {
+                Some(best_move) => println!("bestmove {}", best_move),
+                None => println!("bestmove 0000"),
+            }
+        });
+        self.search = Some((stop, handle));
+    }
+
+    /// The move to play from the book instead of searching, if any. Infinite searches are
+    /// always run, as they analyse the position rather than play it.
+    fn book_move(&self, limits: &SearchLimits) -> Option<Move> {
+        if !self.own_book || limits.infinite {
+            return None;
+        }
+        let random = RandomState::new().build_hasher().finish();
+        self.book
+            .as_ref()?
+            .choose(&self.game_state, self.book_selection, random)
+    }
+
+    /// Stops the running search, if any, and waits for it to print its best move.
+    fn stop_search(&mut self) {
+        if let Some((stop, handle)) = self.search.take() {
+            stop.store(true, Ordering::Relaxed);
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Default for UciEngine {
+    fn default() -> Self {
+        UciEngine::new()
+    }
+}
+
+/// Formats the `info` line reporting a search iteration.
+pub fn info_line(result: &SearchResult) -> String {
+    let millis = result.time.as_millis();
+    let nps = (result.nodes as u128 * 1000) / millis.max(1);
+    let pv: Vec<String> = result.pv.iter().map(Move::to_string).collect();
+    format!(
+        "info depth {} multipv {} score {} nodes {} time {} nps {} pv {}",
+        result.depth,
+        result.multipv,
+        result.uci_score(),
+        result.nodes,
+        millis,
+        nps,
+        pv.join(" ")
+    )
+}
+
+/// Parses the arguments of the `position` command:
+/// `[startpos | fen <fen>] [moves <move> ...]`.
+///
+/// In Chess960 mode, castling moves are read, and later written, as the king taking its own
+/// rook, e.g. `e1h1`, whatever the position.
+pub fn parse_position(args: &[&str], chess960: bool) -> Result<GameState, String> {
+    let moves_index = args.iter().position(|&arg| arg == "moves");
+    let (setup, moves) = match moves_index {
+        Some(index) => (&args[..index], &args[index + 1..]),
+        None => (args, &[][..]),
+    };
+
+    let mut game_state = match setup.first() {
+        Some(&"startpos") => GameState::new(),
+        Some(&"fen") => GameState::from_str(&setup[1..].join(" ")).map_err(|e| e.to_string())?,
+        _ => return Err("position must be startpos or fen".to_string()),
+    };
+    game_state.board.chess960 |= chess960;
+
+    for uci_move in moves {
+        let chess_move = Move::from_uci(uci_move)
+            .filter(|chess_move| game_state.generate_legal_moves().contains(chess_move))
+            .ok_or_else(|| format!("Illegal move: {}", uci_move))?;
+        game_state.make_move(&chess_move);
+    }
+    Ok(game_state)
+}
+
+/// Parses the arguments of the `setoption` command, `name <name> [value <value>]`, returning
+/// the name and the value of the option. Both can contain spaces, the value is empty when
+/// missing.
+pub fn parse_setoption(args: &[&str]) -> Option<(String, String)> {
+    if args.first() != Some(&"name") {
+        return None;
+    }
+    let value_index = args.iter().position(|&arg| arg == "value");
+    let (name, value) = match value_index {
+        Some(index) => (&args[1..index], &args[index + 1..]),
+        None => (&args[1..], &[][..]),
+    };
+    if name.is_empty() {
+        return None;
+    }
+    Some((name.join(" "), value.join(" ")))
+}
+
+/// Parses the arguments of the `go` command.
+pub fn parse_go(args: &[&str]) -> SearchLimits {
+    let mut limits = SearchLimits::default();
+    let mut iter = args.iter();
+    while let Some(&arg) = iter.next() {
+        let mut value = || iter.next().and_then(|value| value.parse::<u64>().ok());
+        match arg {
+            "depth" => limits.depth = value().map(|depth| depth as u32),
+            "nodes" => limits.nodes = value(),
+            "movetime" => limits.movetime = value().map(Duration::from_millis),
+            "wtime" => limits.wtime = value().map(Duration::from_millis),
+            "btime" => limits.btime = value().map(Duration::from_millis),
+            "winc" => limits.winc = value().map(Duration::from_millis),
+            "binc" => limits.binc = value().map(Duration::from_millis),
+            "movestogo" => limits.movestogo = value().map(|moves| moves as u32),
+            "infinite" => limits.infinite = true,
+            _ => {}
+        }
+    }
+    limits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_position() {
+        let game_state =
+            parse_position(&["startpos", "moves", "e2e4", "e7e5", "g1f3"], false).unwrap();
+        assert_eq!(
+            game_state.to_fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+        );
+
+        let fen = ["fen", "4k3/1P6/8/8/8/8/8/4K3", "w", "-", "-", "0", "1"];
+        let mut args = fen.to_vec();
+        args.extend(["moves", "b7b8n"]);
+        let game_state = parse_position(&args, false).unwrap();
+        assert_eq!(game_state.to_fen(), "1N2k3/8/8/8/8/8/8/4K3 b - - 0 1");
+
+        assert_eq!(
+            parse_position(&["startpos", "moves", "e2e5"], false).unwrap_err(),
+            "Illegal move: e2e5"
+        );
+    }
+
+    #[test]
+    fn test_chess960_castling_moves() {
+        let args = [
+            "fen",
+            "4k3/8/8/8/8/8/8/R3K2R",
+            "w",
+            "KQ",
+            "-",
+            "0",
+            "1",
+            "moves",
+            "e1h1",
+        ];
+        assert_eq!(
+            parse_position(&args, false).unwrap_err(),
+            "Illegal move: e1h1"
+        );
+        let game_state = parse_position(&args, true).unwrap();
+        assert_eq!(game_state.to_fen(), "4k3/8/8/8/8/8/8/R4RK1 b - - 1 1");
+
+        let mut engine = UciEngine::new();
+        engine.handle_command("setoption name UCI_Chess960 value true");
+        assert!(engine.chess960);
+        engine.handle_command("position startpos moves g1f3 g8f6 g2g3 g7g6 f1g2 f8g7 e1h1");
+        assert_eq!(engine.game_state.to_fen().split(' ').nth(2), Some("kq"));
+    }
+
+    #[test]
+    fn test_parse_setoption() {
+        assert_eq!(
+            parse_setoption(&["name", "EvalFile", "value", "nets/my", "net.bin"]),
+            Some(("EvalFile".to_string(), "nets/my net.bin".to_string()))
+        );
+        assert_eq!(
+            parse_setoption(&["name", "Clear", "Hash"]),
+            Some(("Clear Hash".to_string(), String::new()))
+        );
+        assert_eq!(parse_setoption(&["value", "1"]), None);
+    }
+
+    #[test]
+    fn test_search_switches() {
+        let mut engine = UciEngine::new();
+        engine.handle_command("setoption name NullMove value false");
+        engine.handle_command("setoption name lmr value false");
+        assert!(!engine.options.null_move_pruning);
+        assert!(!engine.options.late_move_reductions);
+        assert!(engine.options.futility_pruning);
+        engine.handle_command("setoption name NullMove value maybe");
+        assert!(!engine.options.null_move_pruning);
+
+        engine.handle_command("setoption name Threads value 8");
+        assert_eq!(engine.threads, 8);
+        engine.handle_command("setoption name Threads value 0");
+        assert_eq!(engine.threads, 1);
+        engine.handle_command("setoption name MultiPV value 3");
+        assert_eq!(engine.multi_pv, 3);
+        engine.handle_command("setoption name Move Overhead value 50");
+        assert_eq!(engine.move_overhead, Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_book_options() {
+        let mut engine = UciEngine::new();
+        engine.handle_command("setoption name BookMode value best");
+        assert_eq!(engine.book_selection, Selection::Best);
+        engine.handle_command("setoption name BookFile value does/not/exist.bin");
+        assert!(engine.book.is_none());
+
+        // A book playing 1. d4 in the starting position
+        let mut bytes = crate::book::polyglot_key(&GameState::new())
+            .to_be_bytes()
+            .to_vec();
+        bytes.extend([0x02, 0xdb, 0x00, 0x01, 0, 0, 0, 0]);
+        engine.book = Some(Book::from_bytes(&bytes).unwrap());
+        let limits = parse_go(&["depth", "1"]);
+        assert_eq!(engine.book_move(&limits), None);
+        engine.handle_command("setoption name OwnBook value true");
+        assert_eq!(engine.book_move(&limits), Move::from_uci("d2d4"));
+        assert_eq!(engine.book_move(&parse_go(&["infinite"])), None);
+        engine.handle_command("position startpos moves d2d4");
+        assert_eq!(engine.book_move(&limits), None);
+    }
+
+    #[cfg(feature = "syzygy")]
+    #[test]
+    fn test_syzygy_path() {
+        let mut engine = UciEngine::new();
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/syzygy");
+        engine.handle_command(&format!("setoption name SyzygyPath value {}", path));
+        assert_eq!(
+            engine.tablebases.as_ref().map(|tb| tb.max_pieces()),
+            Some(3)
+        );
+        engine.handle_command("setoption name SyzygyPath value <empty>");
+        assert!(engine.tablebases.is_none());
+        engine.handle_command("setoption name SyzygyPath value does/not/exist");
+        assert!(engine.tablebases.is_none());
+    }
+
+    #[test]
+    fn test_info_line() {
+        let result = SearchResult {
+            best_move: Some(Move::new(12, 28)),
+            score: 35,
+            pv: vec![Move::new(12, 28), Move::new(52, 36)],
+            depth: 7,
+            nodes: 5000,
+            time: Duration::from_millis(500),
+            multipv: 2,
+        };
+        assert_eq!(
+            info_line(&result),
+            "info depth 7 multipv 2 score cp 35 nodes 5000 time 500 nps 10000 pv e2e4 e7e5"
+        );
+    }
+
+    #[test]
+    fn test_parse_go() {
+        assert_eq!(
+            parse_go(&["depth", "6", "nodes", "1000", "movetime", "250"]),
+            SearchLimits {
+                depth: Some(6),
+                nodes: Some(1000),
+                movetime: Some(Duration::from_millis(250)),
+                ..SearchLimits::default()
+            }
+        );
+        assert!(parse_go(&["infinite"]).infinite);
+
+        let limits = parse_go(&["wtime", "60000", "btime", "55000", "winc", "1000"]);
+        assert_eq!(limits.wtime, Some(Duration::from_secs(60)));
+        assert_eq!(limits.btime, Some(Duration::from_secs(55)));
+        assert_eq!(limits.winc, Some(Duration::from_secs(1)));
+        assert_eq!(limits.binc, None);
+        assert_eq!(parse_go(&["movestogo", "12"]).movestogo, Some(12));
+    }
+}
@@ -0,0 +1,188 @@
+//! Module containing the Zobrist keys used to hash positions.
+//!
+//! A position is hashed by xoring one random key per piece on its square, plus keys for the
+//! side to move, the castling rights and the en passant file. The keys are generated at
+//! compile time from a fixed seed, so hashes are identical from one run to the next.
+
+use crate::board::piece::{Color, Piece, PieceType};
+use crate::board::Board;
+
+const PIECE_KEYS: [[u64; 64]; 12] = generate_piece_keys();
+const CASTLING_KEYS: [u64; 4] = generate_keys::<4>(0x00c4_a571_1e5d_b00f);
+const EN_PASSANT_KEYS: [u64; 8] = generate_keys::<8>(0x5eed_0fe9_a551_4a7e);
+const SIDE_KEY: u64 = splitmix64(0x0b1a_c4a5_1de7_0111).1;
+
+/// The SplitMix64 generator, returning the next state and the random number it produced.
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (state, z ^ (z >> 31))
+}
+
+const fn generate_keys<const N: usize>(seed: u64) -> [u64; N] {
+    let mut keys = [0; N];
+    let mut state = seed;
+    let mut i = 0;
+    while i < N {
+        let (next, key) = splitmix64(state);
+        keys[i] = key;
+        state = next;
+        i += 1;
+    }
+    keys
+}
+
+const fn generate_piece_keys() -> [[u64; 64]; 12] {
+    let mut keys = [[0; 64]; 12];
+    let mut state = 0x7275_6365_7a6f_6272;
+    let mut piece = 0;
+    while piece < 12 {
+        let mut square = 0;
+        while square < 64 {
+            let (next, key) = splitmix64(state);
+            keys[piece][square] = key;
+            state = next;
+            square += 1;
+        }
+        piece += 1;
+    }
+    keys
+}
+
+/// Returns the key of a piece standing on a square.
+pub fn piece_key(piece: &Piece, square: u8) -> u64 {
+    let piece_type = match piece.piece_type {
+        PieceType::Pawn => 0,
+        PieceType::Knight => 1,
+        PieceType::Bishop => 2,
+        PieceType::Rook => 3,
+        PieceType::Queen => 4,
+        PieceType::King => 5,
+    };
+    let color = match piece.color {
+        Color::White => 0,
+        Color::Black => 6,
+    };
+    PIECE_KEYS[piece_type + color][square as usize]
+}
+
+/// Returns the key xored in when black is to move.
+pub fn side_key() -> u64 {
+    SIDE_KEY
+}
+
+/// Returns the key of a set of castling rights, in the order of
+/// `Board::castling_availability`.
+pub fn castling_key(castling_availability: (bool, bool, bool, bool)) -> u64 {
+    let (white_king, white_queen, black_king, black_queen) = castling_availability;
+    [white_king, white_queen, black_king, black_queen]
+        .iter()
+        .zip(CASTLING_KEYS)
+        .filter(|(&available, _)| available)
+        .fold(0, |key, (_, castling_key)| key ^ castling_key)
+}
+
+/// Returns the key of an en passant target square, which only depends on its file.
+pub fn en_passant_key(en_passant_target: Option<u8>) -> u64 {
+    en_passant_target.map_or(0, |square| EN_PASSANT_KEYS[(square % 8) as usize])
+}
+
+/// Returns the key of a position: its pieces, the side to move, the castling rights and the
+/// en passant file.
+///
+/// # Examples
+///
+/// ```
+/// use ruce::board::piece::Color;
+/// use ruce::game_state::GameState;
+/// use ruce::zobrist::position_key;
+///
+/// let game_state = GameState::new();
+/// let key = position_key(&game_state.board, game_state.current_player);
+/// assert_ne!(key, position_key(&game_state.board, Color::Black));
+/// ```
+pub fn position_key(board: &Board, side_to_move: Color) -> u64 {
+    let pieces = board
+        .squares
+        .iter()
+        .enumerate()
+        .filter_map(|(square, piece)| piece.map(|piece| piece_key(&piece, square as u8)))
+        .fold(0, |key, piece_key| key ^ piece_key);
+    let side = match side_to_move {
+        Color::White => 0,
+        Color::Black => SIDE_KEY,
+    };
+    pieces
+        ^ side
+        ^ castling_key(board.castling_availability)
+        ^ en_passant_key(board.en_passant_target)
+}
+
+/// Returns the key of the pawns of both colors, ignoring every other piece.
+///
+/// Positions with the same pawn structure share this key, which makes it the key of the
+/// pawn hash table of the evaluation.
+pub fn pawn_key(board: &Board) -> u64 {
+    board
+        .squares
+        .iter()
+        .enumerate()
+        .filter_map(|(square, piece)| piece.map(|piece| (square as u8, piece)))
+        .filter(|(_, piece)| piece.piece_type == PieceType::Pawn)
+        .fold(0, |key, (square, piece)| key ^ piece_key(&piece, square))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_keys_are_distinct() {
+        let mut keys: HashSet<u64> = PIECE_KEYS.iter().flatten().copied().collect();
+        keys.extend(CASTLING_KEYS);
+        keys.extend(EN_PASSANT_KEYS);
+        keys.insert(SIDE_KEY);
+        assert_eq!(keys.len(), 12 * 64 + 4 + 8 + 1);
+        assert!(!keys.contains(&0));
+    }
+
+    #[test]
+    fn test_position_key_of_transpositions() {
+        use crate::board::moves::Move;
+        use crate::game_state::GameState;
+
+        let key = |moves: &[(u8, u8)]| {
+            let mut game_state = GameState::new();
+            for &(from, to) in moves {
+                game_state.make_move(&Move::new(from, to));
+            }
+            position_key(&game_state.board, game_state.current_player)
+        };
+        // 1. Nf3 Nf6 2. Nc3 and 1. Nc3 Nf6 2. Nf3 reach the same position
+        assert_eq!(
+            key(&[(6, 21), (62, 45), (1, 18)]),
+            key(&[(1, 18), (62, 45), (6, 21)])
+        );
+
+        // The en passant target is part of the position
+        let mut game_state = GameState::new();
+        game_state.make_move(&Move::new(12, 28));
+        let with_target = position_key(&game_state.board, Color::Black);
+        game_state.board.en_passant_target = None;
+        assert_ne!(with_target, position_key(&game_state.board, Color::Black));
+    }
+
+    #[test]
+    fn test_pawn_key_only_depends_on_pawns() {
+        let with_pieces =
+            Board::from_str("rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R").unwrap();
+        let pawns_only = Board::from_str("4k3/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/4K3").unwrap();
+        let other_pawns = Board::from_str("4k3/pp1ppppp/8/2p5/3P4/8/PPP2PPP/4K3").unwrap();
+        assert_eq!(pawn_key(&with_pieces), pawn_key(&pawns_only));
+        assert_ne!(pawn_key(&pawns_only), pawn_key(&other_pawns));
+    }
+}